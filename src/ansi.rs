@@ -0,0 +1,301 @@
+//! ANSIエスケープシーケンス（SGR）の解析モジュール
+//!
+//! `cargo build` や `ls --color`、`grep --color` の出力に含まれる
+//! 色・装飾の指定を解釈し、スタイル付きの断片（Span）に分割する。
+//! 描画方法には依存しないため、GUI側で任意の形式に変換して使う。
+
+/// 色の指定。0〜15 は基本16色、16〜255 は 256色パレット
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+impl Color {
+    /// 24bit の RGB 値に変換する（基本16色は xterm 風の配色）
+    pub fn to_rgb(self) -> (u8, u8, u8) {
+        match self {
+            Color::Rgb(r, g, b) => (r, g, b),
+            Color::Indexed(n) => indexed_to_rgb(n),
+        }
+    }
+}
+
+/// 基本16色のパレット（ダークテーマで読みやすい明るめの配色）
+const BASIC_COLORS: [(u8, u8, u8); 16] = [
+    (0x2e, 0x34, 0x36), // black
+    (0xe0, 0x4b, 0x4b), // red
+    (0x73, 0xc9, 0x36), // green
+    (0xd7, 0xb3, 0x3c), // yellow
+    (0x4f, 0x8f, 0xe6), // blue
+    (0xb5, 0x6c, 0xd4), // magenta
+    (0x3c, 0xb8, 0xc4), // cyan
+    (0xd3, 0xd7, 0xcf), // white
+    (0x77, 0x7d, 0x80), // bright black
+    (0xff, 0x6e, 0x6e), // bright red
+    (0x9c, 0xe8, 0x5a), // bright green
+    (0xfc, 0xe9, 0x4f), // bright yellow
+    (0x7f, 0xb2, 0xff), // bright blue
+    (0xd8, 0x98, 0xf0), // bright magenta
+    (0x6f, 0xe4, 0xec), // bright cyan
+    (0xff, 0xff, 0xff), // bright white
+];
+
+/// 256色パレットの番号を RGB に変換する
+fn indexed_to_rgb(n: u8) -> (u8, u8, u8) {
+    match n {
+        0..=15 => BASIC_COLORS[n as usize],
+        // 16〜231: 6x6x6 のカラーキューブ
+        16..=231 => {
+            let i = n - 16;
+            let level = |v: u8| if v == 0 { 0 } else { 55 + v * 40 };
+            (level(i / 36), level((i / 6) % 6), level(i % 6))
+        }
+        // 232〜255: 24段階のグレースケール
+        _ => {
+            let v = 8 + (n - 232) * 10;
+            (v, v, v)
+        }
+    }
+}
+
+/// 文字の装飾状態。SGR を受け取るたびに更新される
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Style {
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    pub bold: bool,
+    pub dim: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub inverse: bool,
+    pub strikethrough: bool,
+}
+
+/// 同じスタイルが続く文字列の断片
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub text: String,
+    pub style: Style,
+}
+
+/// 1行を解析してスタイル付きの断片に分割する。
+///
+/// `style` は行をまたいで引き継がれる現在の状態で、解析後は行末時点の
+/// 状態に更新される。SGR 以外の制御シーケンス（カーソル移動、行消去、
+/// OSC のハイパーリンクなど）は表示できないため読み捨てる。
+/// 行の途中の復帰（CR）は行頭からの上書き（進捗表示など）として扱い、
+/// 行末の CR（CRLF の改行）は無視する。
+pub fn parse_line(line: &str, style: &mut Style) -> Vec<Span> {
+    // 表示する文字とそのスタイルを1桁ずつ置いていく。CR の後は `column` から上書きする
+    let mut cells: Vec<(char, Style)> = Vec::new();
+    let mut column = 0;
+    let mut chars = line.trim_end_matches(['\r', '\n']).chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\x1b' => match chars.next() {
+                // CSI: ESC [ パラメータ 中間バイト 終端バイト
+                Some('[') => {
+                    let mut params = String::new();
+                    let mut final_byte = None;
+                    for c in chars.by_ref() {
+                        if ('\x40'..='\x7e').contains(&c) {
+                            final_byte = Some(c);
+                            break;
+                        }
+                        params.push(c);
+                    }
+                    if final_byte == Some('m') {
+                        apply_sgr(&params, style);
+                    }
+                }
+                // OSC: BEL または ESC \ で終わる
+                Some(']') => {
+                    while let Some(c) = chars.next() {
+                        if c == '\x07' {
+                            break;
+                        }
+                        if c == '\x1b' && chars.peek() == Some(&'\\') {
+                            chars.next();
+                            break;
+                        }
+                    }
+                }
+                // その他の2バイトシーケンスは無視する
+                _ => {}
+            },
+            // 復帰の後は行頭から上書きする（後の文字が届かない部分は残る）
+            '\r' => column = 0,
+            '\t' => put(&mut cells, &mut column, ch, *style),
+            c if c.is_control() => {}
+            c => put(&mut cells, &mut column, c, *style),
+        }
+    }
+
+    let mut spans: Vec<Span> = Vec::new();
+    let mut text = String::new();
+    let mut current = cells.first().map_or(*style, |&(_, style)| style);
+    for (ch, cell_style) in cells {
+        if cell_style != current {
+            push_span(&mut spans, &mut text, current);
+            current = cell_style;
+        }
+        text.push(ch);
+    }
+    push_span(&mut spans, &mut text, current);
+
+    spans
+}
+
+/// エスケープシーケンスを取り除いたプレーンテキストを返す
+pub fn strip(line: &str) -> String {
+    let mut style = Style::default();
+    parse_line(line, &mut style)
+        .into_iter()
+        .map(|span| span.text)
+        .collect()
+}
+
+/// `column` 桁目に文字を置き（既にあれば上書きし）、次の桁へ進む
+fn put(cells: &mut Vec<(char, Style)>, column: &mut usize, ch: char, style: Style) {
+    match cells.get_mut(*column) {
+        Some(cell) => *cell = (ch, style),
+        None => cells.push((ch, style)),
+    }
+    *column += 1;
+}
+
+/// 溜まっている文字列を断片として確定する
+fn push_span(spans: &mut Vec<Span>, text: &mut String, style: Style) {
+    if text.is_empty() {
+        return;
+    }
+    // 直前と同じスタイルなら結合する
+    if let Some(last) = spans.last_mut() {
+        if last.style == style {
+            last.text.push_str(text);
+            text.clear();
+            return;
+        }
+    }
+    spans.push(Span {
+        text: std::mem::take(text),
+        style,
+    });
+}
+
+/// SGR のパラメータ列（"1;31" など）を解釈して状態を更新する
+fn apply_sgr(params: &str, style: &mut Style) {
+    // パラメータ省略 (ESC [ m) はリセット扱い
+    if params.is_empty() {
+        *style = Style::default();
+        return;
+    }
+
+    // "38:2::255:0:0" のようなコロン区切りのサブパラメータ形式は
+    // 1つのパラメータ内で完結しているため個別に扱う
+    let mut codes = params.split(';');
+    while let Some(code) = codes.next() {
+        if code.contains(':') {
+            let sub: Vec<u32> = code.split(':').map(|s| s.parse().unwrap_or(0)).collect();
+            match sub.first() {
+                Some(38) => style.fg = extended_color_colon(&sub[1..]),
+                Some(48) => style.bg = extended_color_colon(&sub[1..]),
+                // "4:3"（波線など）も下線として扱う
+                Some(4) => style.underline = sub.get(1).copied().unwrap_or(1) != 0,
+                _ => {}
+            }
+            continue;
+        }
+
+        let code: u32 = if code.is_empty() { 0 } else { code.parse().unwrap_or(u32::MAX) };
+        match code {
+            0 => *style = Style::default(),
+            1 => style.bold = true,
+            2 => style.dim = true,
+            3 => style.italic = true,
+            4 => style.underline = true,
+            7 => style.inverse = true,
+            9 => style.strikethrough = true,
+            21 | 22 => {
+                style.bold = false;
+                style.dim = false;
+            }
+            23 => style.italic = false,
+            24 => style.underline = false,
+            27 => style.inverse = false,
+            29 => style.strikethrough = false,
+            30..=37 => style.fg = Some(Color::Indexed((code - 30) as u8)),
+            38 => style.fg = extended_color(&mut codes),
+            39 => style.fg = None,
+            40..=47 => style.bg = Some(Color::Indexed((code - 40) as u8)),
+            48 => style.bg = extended_color(&mut codes),
+            49 => style.bg = None,
+            90..=97 => style.fg = Some(Color::Indexed((code - 90 + 8) as u8)),
+            100..=107 => style.bg = Some(Color::Indexed((code - 100 + 8) as u8)),
+            _ => {}
+        }
+    }
+}
+
+/// セミコロン区切りの拡張色指定（"5;n" または "2;r;g;b"）を読む
+fn extended_color<'a>(codes: &mut impl Iterator<Item = &'a str>) -> Option<Color> {
+    let mut next = || codes.next().and_then(|s| s.parse::<u8>().ok());
+    match next()? {
+        5 => next().map(Color::Indexed),
+        2 => Some(Color::Rgb(next()?, next()?, next()?)),
+        _ => None,
+    }
+}
+
+/// コロン区切りの拡張色指定を読む。
+/// "2:r:g:b" と色空間IDを含む "2::r:g:b" の両方を受け付ける
+fn extended_color_colon(sub: &[u32]) -> Option<Color> {
+    let byte = |i: usize| sub.get(i).map(|&v| v.min(255) as u8);
+    match sub.first()? {
+        5 => byte(1).map(Color::Indexed),
+        2 if sub.len() >= 5 => Some(Color::Rgb(byte(2)?, byte(3)?, byte(4)?)),
+        2 => Some(Color::Rgb(byte(1)?, byte(2)?, byte(3)?)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(line: &str) -> Vec<String> {
+        let mut style = Style::default();
+        parse_line(line, &mut style).into_iter().map(|span| span.text).collect()
+    }
+
+    #[test]
+    fn crlf_line_keeps_its_text() {
+        assert_eq!(texts("abc\r\n"), ["abc"]);
+        assert_eq!(texts("abc\r"), ["abc"]);
+        assert_eq!(strip("\x1b[31mred\x1b[0m\r\n"), "red");
+    }
+
+    #[test]
+    fn mid_line_cr_overwrites_from_column_zero() {
+        assert_eq!(texts("50%\r100%"), ["100%"]);
+        // 後の文字が届かない部分は残る
+        assert_eq!(texts("downloading\rDONE"), ["DONEloading"]);
+    }
+
+    #[test]
+    fn overwrite_keeps_styles_of_uncovered_cells() {
+        let mut style = Style::default();
+        let spans = parse_line("ab\x1b[1mcd\x1b[0m\rX", &mut style);
+        let bold = Style { bold: true, ..Style::default() };
+        assert_eq!(
+            spans,
+            [
+                Span { text: "Xb".to_string(), style: Style::default() },
+                Span { text: "cd".to_string(), style: bold },
+            ]
+        );
+        assert_eq!(style, Style::default());
+    }
+}
@@ -0,0 +1,115 @@
+//! 端末幅に合わせた複数列レイアウト
+//!
+//! `ls` の一覧や補完候補の一覧を、GNU `ls -C` と同じく「縦方向に並べ、
+//! 画面幅に収まる最大の列数」で表示するための計算を行う。
+//! 幅は表示上の桁数で数える（全角文字・絵文字は2桁、ANSIエスケープは0桁）。
+
+use crate::ansi;
+use std::io::IsTerminal;
+use unicode_width::UnicodeWidthStr;
+
+/// 端末幅が取得できない場合の既定値
+pub const DEFAULT_WIDTH: usize = 80;
+
+/// 列と列の間の空白の数
+const COLUMN_GAP: usize = 2;
+
+/// 文字列の表示幅（桁数）。ANSIエスケープは幅に含めない
+pub fn display_width(text: &str) -> usize {
+    if text.contains('\x1b') {
+        ansi::strip(text).width()
+    } else {
+        text.width()
+    }
+}
+
+/// 標準出力につながった端末の幅を返す。
+/// 端末でなければ環境変数 COLUMNS、それもなければ既定値
+pub fn terminal_width() -> usize {
+    if std::io::stdout().is_terminal() {
+        let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+        let ret = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+        if ret == 0 && size.ws_col > 0 {
+            return size.ws_col as usize;
+        }
+    }
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|&w: &usize| w > 0)
+        .unwrap_or(DEFAULT_WIDTH)
+}
+
+/// 項目を `width` 桁に収まる複数列に並べ、各行の文字列を返す。
+///
+/// 項目は上から下、左から右の順（縦優先）に並ぶ。
+/// 1項目でも幅を超える場合は1列になる。
+pub fn format_columns(items: &[String], width: usize) -> Vec<String> {
+    let items: Vec<&[u8]> = items.iter().map(|item| item.as_bytes()).collect();
+    format_columns_bytes(&items, width)
+        .into_iter()
+        .map(|line| String::from_utf8_lossy(&line).into_owned())
+        .collect()
+}
+
+/// `format_columns` のバイト列版。UTF-8 でない項目（ls が端末以外へそのまま書き出す名前）も並べられる。
+/// 幅は不正なバイトを1桁として数える
+pub fn format_columns_bytes(items: &[&[u8]], width: usize) -> Vec<Vec<u8>> {
+    if items.is_empty() {
+        return Vec::new();
+    }
+
+    let widths: Vec<usize> = items.iter().map(|item| display_width(&String::from_utf8_lossy(item))).collect();
+    let (rows, col_widths) = fit_columns(&widths, width);
+
+    let mut lines = Vec::with_capacity(rows);
+    for row in 0..rows {
+        let mut line = Vec::new();
+        for (col, col_width) in col_widths.iter().enumerate() {
+            let index = col * rows + row;
+            let Some(item) = items.get(index) else {
+                break;
+            };
+            line.extend_from_slice(item);
+            // 行末の項目には空白を付けない
+            let is_last = col + 1 == col_widths.len() || (col + 1) * rows + row >= items.len();
+            if !is_last {
+                line.resize(line.len() + col_width - widths[index], b' ');
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// 収まる最大の列数を探し、(行数, 各列の幅（間隔込み）) を返す
+fn fit_columns(widths: &[usize], line_width: usize) -> (usize, Vec<usize>) {
+    let count = widths.len();
+    // 1項目あたり最低でも「1文字 + 間隔」は必要
+    let max_cols = (line_width / (1 + COLUMN_GAP)).clamp(1, count);
+
+    for cols in (1..=max_cols).rev() {
+        let rows = count.div_ceil(cols);
+        // 行数が同じになる列数は実際には列が余るので、必要な列数に詰める
+        let used_cols = count.div_ceil(rows);
+        if used_cols != cols {
+            continue;
+        }
+
+        let col_widths: Vec<usize> = (0..cols)
+            .map(|col| {
+                let start = col * rows;
+                let end = (start + rows).min(count);
+                let max = widths[start..end].iter().copied().max().unwrap_or(0);
+                // 最終列は間隔不要
+                if col + 1 == cols { max } else { max + COLUMN_GAP }
+            })
+            .collect();
+
+        if cols == 1 || col_widths.iter().sum::<usize>() <= line_width {
+            return (rows, col_widths);
+        }
+    }
+
+    (count, vec![widths.iter().copied().max().unwrap_or(0)])
+}
@@ -0,0 +1,274 @@
+//! catコマンド。ファイルの内容を順に標準出力へ書き出す
+//!
+//! 対応オプション:
+//! - `-n` すべての行に行番号を付ける / `-b` 空行以外に行番号を付ける
+//! - `-s` 連続する空行を1行にまとめる
+//! - `-E` 行末に `$` を表示する / `-T` タブを `^I` と表示する
+//! - `-v` 制御文字などを `^X` や `M-X` の形で表示する（タブと改行を除く）
+//! - `-A` `-vET` と同じ
+//!
+//! ファイルを指定しないか `-` を指定すると標準入力を読む。
+//! 行番号は複数のファイルにまたがって通しで付ける。
+
+use super::error_message;
+use crate::context::Context;
+use crate::quoting;
+use crate::errln;
+use std::ffi::{OsStr, OsString};
+use std::io::{self, Read, Write};
+use std::mem;
+
+/// コマンド名
+pub const NAME: &str = "cat";
+
+/// 一度に読むバイト数
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// 解析済みのオプション
+#[derive(Debug, Default)]
+struct Options {
+    number: bool,
+    number_nonblank: bool,
+    squeeze_blank: bool,
+    show_ends: bool,
+    show_tabs: bool,
+    show_nonprinting: bool,
+}
+
+impl Options {
+    /// 内容をそのまま書き出せばよいか
+    fn is_plain(&self) -> bool {
+        !(self.number || self.number_nonblank || self.squeeze_blank
+            || self.show_ends || self.show_tabs || self.show_nonprinting)
+    }
+}
+
+/// 行番号と空行の状態（ファイルをまたいで引き継ぐ）
+struct Formatter {
+    opts: Options,
+    /// 次に付ける行番号
+    line: u64,
+    at_line_start: bool,
+    /// 直前の行が空行だったか（-s 用）
+    prev_blank: bool,
+}
+
+impl Formatter {
+    /// `data` をオプションに従って変換し `out` に追加する
+    fn format(&mut self, data: &[u8], out: &mut Vec<u8>) {
+        for &byte in data {
+            if self.at_line_start {
+                let blank = byte == b'\n';
+                if blank && self.opts.squeeze_blank && self.prev_blank {
+                    continue;
+                }
+                self.prev_blank = blank;
+                if self.opts.number_nonblank && !blank || self.opts.number && !self.opts.number_nonblank {
+                    let _ = write!(out, "{:6}\t", self.line);
+                    self.line += 1;
+                }
+                self.at_line_start = false;
+            }
+            match byte {
+                b'\n' => {
+                    if self.opts.show_ends {
+                        out.push(b'$');
+                    }
+                    out.push(b'\n');
+                    self.at_line_start = true;
+                }
+                b'\t' if self.opts.show_tabs => out.extend_from_slice(b"^I"),
+                b'\t' => out.push(b'\t'),
+                _ if self.opts.show_nonprinting => push_visible(out, byte),
+                _ => out.push(byte),
+            }
+        }
+    }
+}
+
+/// 表示できないバイトを `^X`（制御文字）・`^?`（DEL）・`M-X`（8ビット目が立ったもの）で追加する
+fn push_visible(out: &mut Vec<u8>, byte: u8) {
+    let byte = if byte >= 0x80 {
+        out.extend_from_slice(b"M-");
+        byte - 0x80
+    } else {
+        byte
+    };
+    match byte {
+        0x00..=0x1f => out.extend_from_slice(&[b'^', byte + 0x40]),
+        0x7f => out.extend_from_slice(b"^?"),
+        _ => out.push(byte),
+    }
+}
+
+/// 中断を確かめながら読む。中断されたら `ErrorKind::Interrupted` のエラーを返す
+pub(crate) fn read_some(ctx: &Context, reader: &mut dyn Read, buf: &mut [u8]) -> io::Result<usize> {
+    loop {
+        if ctx.interrupted() {
+            return Err(io::ErrorKind::Interrupted.into());
+        }
+        match reader.read(buf) {
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            result => return result,
+        }
+    }
+}
+
+/// 中断を確かめながら1行ずつ読む
+pub(crate) struct LineReader {
+    buf: Vec<u8>,
+    pos: usize,
+    len: usize,
+    /// これまでに読んだ内容に NUL が含まれていたか（バイナリファイルの判定に使う）
+    pub(crate) saw_nul: bool,
+}
+
+impl LineReader {
+    pub(crate) fn new() -> Self {
+        LineReader { buf: vec![0u8; BUFFER_SIZE], pos: 0, len: 0, saw_nul: false }
+    }
+
+    /// 次の1行（改行を含む）を `line` に読む。入力の終わりなら false を返す
+    pub(crate) fn read_line(&mut self, ctx: &Context, reader: &mut dyn Read, line: &mut Vec<u8>) -> io::Result<bool> {
+        self.read_record(ctx, reader, b'\n', line)
+    }
+
+    /// `delim` で終わる次の1件（`delim` を含む）を `line` に読む（`-z` なら NUL 区切り）。
+    /// 入力の終わりなら false を返す
+    pub(crate) fn read_record(
+        &mut self,
+        ctx: &Context,
+        reader: &mut dyn Read,
+        delim: u8,
+        line: &mut Vec<u8>,
+    ) -> io::Result<bool> {
+        line.clear();
+        loop {
+            if self.pos == self.len {
+                self.len = read_some(ctx, reader, &mut self.buf)?;
+                self.pos = 0;
+                if self.len == 0 {
+                    return Ok(!line.is_empty());
+                }
+                self.saw_nul |= self.buf[..self.len].contains(&0);
+            }
+            let chunk = &self.buf[self.pos..self.len];
+            match chunk.iter().position(|&b| b == delim) {
+                Some(i) => {
+                    line.extend_from_slice(&chunk[..=i]);
+                    self.pos += i + 1;
+                    return Ok(true);
+                }
+                None => {
+                    line.extend_from_slice(chunk);
+                    self.pos = self.len;
+                }
+            }
+        }
+    }
+}
+
+/// `name` のファイル（`-` なら標準入力）を開いて `f` に渡す。
+/// 外側のエラーは開けなかったとき、内側は `f` の結果
+pub(crate) fn with_input<T>(
+    ctx: &mut Context,
+    name: &OsStr,
+    f: impl FnOnce(&mut Context, &mut dyn Read) -> io::Result<T>,
+) -> io::Result<io::Result<T>> {
+    if name == "-" {
+        let mut stdin = mem::replace(&mut ctx.stdin, Box::new(io::empty()));
+        let result = f(ctx, &mut stdin);
+        ctx.stdin = stdin;
+        return Ok(result);
+    }
+    let mut file = ctx.fs.open(&ctx.resolve(name))?;
+    Ok(f(ctx, &mut file))
+}
+
+/// 見出しなどに使う入力の名前（`-` は "standard input"）
+pub(crate) fn input_name(name: &OsStr) -> String {
+    if name == "-" {
+        "standard input".to_string()
+    } else {
+        quoting::display(name)
+    }
+}
+
+pub fn run(ctx: &mut Context, args: &[OsString]) {
+    let mut opts = Options::default();
+    let mut files: Vec<&OsStr> = Vec::new();
+    let mut only_files = false;
+    for arg in args {
+        let Some(s) = arg.to_str().filter(|s| !only_files && s.starts_with('-') && s.len() > 1) else {
+            files.push(arg);
+            continue;
+        };
+        match s {
+            "--" => only_files = true,
+            "--number" => opts.number = true,
+            "--number-nonblank" => opts.number_nonblank = true,
+            "--squeeze-blank" => opts.squeeze_blank = true,
+            "--show-ends" => opts.show_ends = true,
+            "--show-tabs" => opts.show_tabs = true,
+            "--show-nonprinting" => opts.show_nonprinting = true,
+            "--show-all" => (opts.show_nonprinting, opts.show_ends, opts.show_tabs) = (true, true, true),
+            _ if s.starts_with("--") => {
+                errln!(ctx, "cat: unrecognized option '{}'", s);
+                return;
+            }
+            _ => {
+                for ch in s[1..].chars() {
+                    match ch {
+                        'n' => opts.number = true,
+                        'b' => opts.number_nonblank = true,
+                        's' => opts.squeeze_blank = true,
+                        'E' => opts.show_ends = true,
+                        'T' => opts.show_tabs = true,
+                        'v' => opts.show_nonprinting = true,
+                        'A' => (opts.show_nonprinting, opts.show_ends, opts.show_tabs) = (true, true, true),
+                        'e' => (opts.show_nonprinting, opts.show_ends) = (true, true),
+                        't' => (opts.show_nonprinting, opts.show_tabs) = (true, true),
+                        _ => {
+                            errln!(ctx, "cat: invalid option -- '{}'", ch);
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    if files.is_empty() {
+        files.push(OsStr::new("-"));
+    }
+
+    let mut formatter = Formatter { opts, line: 1, at_line_start: true, prev_blank: false };
+    for file in files {
+        let result = with_input(ctx, file, |ctx, reader| copy(ctx, reader, &mut formatter));
+        match result.and_then(|copied| copied) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => break,
+            Err(e) => errln!(ctx, "cat: {}: {}", quoting::display(file), error_message(&e)),
+        }
+    }
+    let _ = ctx.stdout.flush();
+}
+
+/// `reader` の内容を最後まで書き出す
+fn copy(ctx: &mut Context, reader: &mut dyn Read, formatter: &mut Formatter) -> io::Result<()> {
+    let plain = formatter.opts.is_plain();
+    let mut buf = vec![0u8; BUFFER_SIZE];
+    let mut out = Vec::new();
+    loop {
+        let n = read_some(ctx, reader, &mut buf)?;
+        if n == 0 {
+            return Ok(());
+        }
+        if plain {
+            let _ = ctx.stdout.write_all(&buf[..n]);
+        } else {
+            out.clear();
+            formatter.format(&buf[..n], &mut out);
+            let _ = ctx.stdout.write_all(&out);
+        }
+    }
+}
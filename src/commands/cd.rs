@@ -1,80 +1,91 @@
 //! cdコマンド。ディレクトリ移動（Tab補完の簡易実装付き）
+//!
+//! `cd -` は直前のディレクトリ（`$OLDPWD`）へ戻り、移動先を表示する。
 
-use std::env;
-use std::fs;
+use crate::columns;
+use crate::context::Context;
+use crate::quoting::{self, QuotingStyle};
+use crate::{errln, outln};
+use std::ffi::{OsStr, OsString};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
 use std::path::Path;
 
 /// コマンド名
 pub const NAME: &str = "cd";
 
-/// cdコマンドを処理する。引数なしならホームディレクトリへ移動する。
+/// cdコマンドを処理する。引数なしならホームディレクトリへ、`-` なら直前のディレクトリへ移動する。
 ///
 /// 末尾に `\t` を含む場合は補完候補を表示する簡易実装。
 /// 実際のTabキー入力は行単位読み取りのため検知できない。
-pub fn run(args: &[&str]) {
+pub fn run(ctx: &mut Context, args: &[OsString]) {
     if args.is_empty() {
-        change_directory(&[]);
+        change_directory(ctx, NAME, None);
         return;
     }
 
-    let path = args[0];
+    if args[0] == "-" {
+        let Some(oldpwd) = ctx.oldpwd.clone() else {
+            errln!(ctx, "cd: OLDPWD not set");
+            return;
+        };
+        if change_directory(ctx, NAME, Some(oldpwd.as_os_str())) {
+            outln!(ctx, "{}", ctx.cwd.display());
+        }
+        return;
+    }
+
+    let path = args[0].as_bytes();
 
-    if path.ends_with('\t') {
-        let path_without_tab = path.trim_end_matches('\t');
-        if let Some(completions) = get_path_completions(path_without_tab) {
+    if let Some(path_without_tab) = path.strip_suffix(b"\t") {
+        let path_without_tab = OsStr::from_bytes(path_without_tab);
+        if let Some(completions) = get_path_completions(ctx, path_without_tab) {
             if completions.len() == 1 {
                 // 候補が1つなら自動補完して移動
-                change_directory(&[&completions[0]]);
+                change_directory(ctx, NAME, Some(&completions[0]));
             } else if !completions.is_empty() {
-                println!("Possible completions:");
-                for comp in &completions {
-                    println!("  {}", comp);
+                // 候補はそのまま入力し直せる形（shell-escape）で表示する
+                let shown: Vec<String> = completions
+                    .iter()
+                    .map(|c| quoting::quote(c, QuotingStyle::ShellEscape))
+                    .collect();
+                outln!(ctx, "Possible completions:");
+                for line in columns::format_columns(&shown, ctx.width) {
+                    outln!(ctx, "{}", line);
                 }
             }
         }
     } else {
-        change_directory(args);
+        change_directory(ctx, NAME, Some(&args[0]));
     }
 }
 
-/// 部分パス文字列にマッチする補完候補を返す。
+/// 部分パスにマッチする補完候補を返す。名前は OsStr のまま比較するため
+/// UTF-8 でないファイル名も候補に含まれる。
 /// 例: "src/ma" -> ["src/main.rs", ...]
-fn get_path_completions(partial: &str) -> Option<Vec<String>> {
-    let path = Path::new(partial);
+fn get_path_completions(ctx: &Context, partial: &OsStr) -> Option<Vec<OsString>> {
+    let bytes = partial.as_bytes();
 
-    // ディレクトリ部分とファイル名プレフィックスに分割する
-    let (dir_path, file_prefix) = if partial.ends_with('/') {
-        (partial, "")
-    } else if partial.is_empty() {
-        (".", "")
-    } else {
-        let parent = path.parent().unwrap_or(Path::new("."));
-        let file_name = path.file_name()
-            .and_then(|s| s.to_str())
-            .unwrap_or("");
-        (parent.to_str().unwrap_or("."), file_name)
+    // ディレクトリ部分（末尾の / を含む）とファイル名プレフィックスに分割する
+    let (dir_part, file_prefix) = match bytes.iter().rposition(|&b| b == b'/') {
+        Some(i) => (&bytes[..=i], &bytes[i + 1..]),
+        None => (&b""[..], bytes),
     };
 
-    let entries = fs::read_dir(dir_path).ok()?;
+    let dir_path = if dir_part.is_empty() { Path::new(".") } else { Path::new(OsStr::from_bytes(dir_part)) };
+    let dir = ctx.resolve(dir_path);
+    let names = ctx.fs.read_dir(&dir).ok()?;
 
-    let mut matches: Vec<String> = Vec::new();
-    for entry in entries.flatten() {
-        if let Some(name) = entry.file_name().to_str() {
-            if file_prefix.is_empty() || name.starts_with(file_prefix) {
-                let mut completion = if dir_path == "." {
-                    name.to_string()
-                } else if partial.ends_with('/') {
-                    format!("{}{}", partial, name)
-                } else {
-                    format!("{}/{}", dir_path, name)
-                };
+    let mut matches: Vec<OsString> = Vec::new();
+    for name in names {
+        if name.as_bytes().starts_with(file_prefix) {
+            let mut completion = dir_part.to_vec();
+            completion.extend_from_slice(name.as_bytes());
 
-                // ディレクトリは末尾に / を付ける
-                if entry.path().is_dir() {
-                    completion.push('/');
-                }
-                matches.push(completion);
+            // ディレクトリは末尾に / を付ける
+            if ctx.fs.is_dir(&dir.join(&name)) {
+                completion.push(b'/');
             }
+            matches.push(OsString::from_vec(completion));
         }
     }
 
@@ -86,16 +97,40 @@ fn get_path_completions(partial: &str) -> Option<Vec<String>> {
     }
 }
 
-/// カレントディレクトリを変更する。引数が空ならホーム（未設定なら /）へ。
-fn change_directory(args: &[&str]) {
-    let new_dir = if args.is_empty() {
-        env::var("HOME").unwrap_or_else(|_| String::from("/"))
-    } else {
-        args[0].to_string()
+/// カレントディレクトリを変更し、移動前のディレクトリを `ctx.oldpwd` に覚える。
+/// 移動先は z 用の記録（`crate::frecency`）にも加える。
+/// 引数が空ならホーム（未設定なら /、サンドボックスの中ではそのルート）へ。
+/// 移動できなければ `name`（pushd・popd からも呼ぶ）を付けたエラーを表示して false を返す
+pub fn change_directory(ctx: &mut Context, name: &str, target: Option<&OsStr>) -> bool {
+    let new_dir = match target {
+        Some(dir) => dir.to_os_string(),
+        None => ctx.home().into_os_string(),
     };
 
-    let path = Path::new(&new_dir);
-    if let Err(e) = env::set_current_dir(&path) {
-        eprintln!("cd: {}: {}", new_dir, e);
+    let path = ctx.resolve(&new_dir);
+    match ctx.fs.metadata(&path) {
+        Ok(meta) if meta.is_dir() => {}
+        Ok(_) => {
+            errln!(ctx, "{}: {}: Not a directory", name, quoting::display(&new_dir));
+            return false;
+        }
+        Err(e) => {
+            errln!(ctx, "{}: {}: {}", name, quoting::display(&new_dir), super::error_message(&e));
+            return false;
+        }
+    }
+
+    match ctx.fs.canonicalize(&path) {
+        Ok(canonical) => {
+            if let Some(frecency) = &ctx.frecency {
+                let _ = frecency.add(&canonical);
+            }
+            ctx.oldpwd = Some(std::mem::replace(&mut ctx.cwd, canonical));
+            true
+        }
+        Err(e) => {
+            errln!(ctx, "{}: {}: {}", name, quoting::display(&new_dir), super::error_message(&e));
+            false
+        }
     }
 }
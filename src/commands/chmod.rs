@@ -0,0 +1,218 @@
+//! chmodコマンド。ファイルのパーミッションを変える
+//!
+//! 対応オプション:
+//! - `-R` ディレクトリの中身も再帰的に変える（途中のシンボリックリンクはたどらない）
+//! - `-v` 処理したファイルをすべて表示する（`-c` は変更したものだけ）
+//! - `-f` ほとんどのエラーを表示しない
+//! - `--reference=FILE` FILE と同じパーミッションにする
+//!
+//! モードは 8進数（`755`）か記号形式（`u+x,go-w` `a=rX` `g=u`）で指定する。
+//! 記号形式で対象（u/g/o/a）を省略すると umask で隠されるビットは変えない。
+//! `-w` や `-x` のようにオプションと紛らわしいモードも、モードとして解釈できればそう扱う。
+
+use super::error_message;
+use crate::context::Context;
+use crate::file_info;
+use crate::fs_action::FsAction;
+use crate::fs_util;
+use crate::mode::Mode;
+use crate::quoting;
+use crate::vfs::Stat;
+use crate::{errln, outln};
+use std::ffi::{OsStr, OsString};
+use std::path::Path;
+
+/// コマンド名
+pub const NAME: &str = "chmod";
+
+/// 処理したファイルの表示（-v / -c）
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Verbosity {
+    Quiet,
+    Changes,
+    All,
+}
+
+/// 解析済みのオプション
+struct Options {
+    recursive: bool,
+    silent: bool,
+    verbosity: Verbosity,
+    umask: u32,
+}
+
+/// 変更後のモードの決め方
+enum Target {
+    Mode(Mode),
+    /// --reference で指定したファイルのモード
+    Reference(u32),
+}
+
+pub fn run(ctx: &mut Context, args: &[OsString]) {
+    let mut opts = Options {
+        recursive: false,
+        silent: false,
+        verbosity: Verbosity::Quiet,
+        umask: fs_util::umask(),
+    };
+    let mut reference: Option<&OsStr> = None;
+    let mut operands: Vec<&OsStr> = Vec::new();
+    let mut only_operands = false;
+
+    for os_arg in args {
+        let arg = match os_arg.to_str() {
+            Some(arg) if !only_operands && arg.starts_with('-') && arg != "-" => arg,
+            _ => {
+                operands.push(os_arg);
+                continue;
+            }
+        };
+        match arg {
+            "--" => only_operands = true,
+            "--recursive" => opts.recursive = true,
+            "--silent" | "--quiet" => opts.silent = true,
+            "--verbose" => opts.verbosity = Verbosity::All,
+            "--changes" => opts.verbosity = Verbosity::Changes,
+            _ if arg.starts_with("--reference=") => reference = Some(OsStr::new(&arg["--reference=".len()..])),
+            _ if arg.starts_with("--") => {
+                errln!(ctx, "chmod: unrecognized option '{}'", arg);
+                errln!(ctx, "Try 'chmod --help' for more information.");
+                return;
+            }
+            // `-w` `-rx` などはモードとして扱う
+            _ if !arg[1..].chars().all(|c| "Rvcf".contains(c)) && Mode::parse(arg).is_some() => operands.push(os_arg),
+            _ => {
+                for ch in arg[1..].chars() {
+                    match ch {
+                        'R' => opts.recursive = true,
+                        'f' => opts.silent = true,
+                        'v' => opts.verbosity = Verbosity::All,
+                        'c' => opts.verbosity = Verbosity::Changes,
+                        _ => {
+                            errln!(ctx, "chmod: invalid option -- '{}'", ch);
+                            errln!(ctx, "Try 'chmod --help' for more information.");
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // ドライランでは予定の操作だけを表示する
+    if ctx.dry_run {
+        opts.verbosity = Verbosity::Quiet;
+    }
+
+    // モードの引数（--reference なら参照ファイル）。オペランド不足のメッセージに使う
+    let mut last_spec = reference;
+    let target = match reference {
+        Some(file) => match ctx.fs.metadata(&ctx.resolve(file)) {
+            Ok(meta) => Target::Reference(meta.mode() & 0o7777),
+            Err(e) => {
+                errln!(ctx, "chmod: failed to get attributes of '{}': {}", quoting::display(file), error_message(&e));
+                return;
+            }
+        },
+        None => {
+            if operands.is_empty() {
+                errln!(ctx, "chmod: missing operand");
+                errln!(ctx, "Try 'chmod --help' for more information.");
+                return;
+            }
+            let spec = operands.remove(0);
+            last_spec = Some(spec);
+            match spec.to_str().and_then(Mode::parse) {
+                Some(mode) => Target::Mode(mode),
+                None => {
+                    errln!(ctx, "chmod: invalid mode: '{}'", quoting::display(spec));
+                    errln!(ctx, "Try 'chmod --help' for more information.");
+                    return;
+                }
+            }
+        }
+    };
+
+    if operands.is_empty() {
+        errln!(ctx, "chmod: missing operand after '{}'", last_spec.map(quoting::display).unwrap_or_default());
+        errln!(ctx, "Try 'chmod --help' for more information.");
+        return;
+    }
+
+    for file in operands {
+        if ctx.interrupted() {
+            break;
+        }
+        let path = ctx.resolve(file);
+        // 引数で指定したシンボリックリンクは参照先を変える
+        match ctx.fs.metadata(&path) {
+            Ok(meta) => change(ctx, &path, Path::new(file), &meta, &target, &opts),
+            Err(e) => {
+                let message = format!("cannot access '{}': {}", quoting::display(file), error_message(&e));
+                report(ctx, &opts, &message);
+            }
+        }
+    }
+}
+
+/// 1つのファイル（-R ならその中身も）のモードを変える
+fn change(ctx: &mut Context, path: &Path, shown: &Path, meta: &Stat, target: &Target, opts: &Options) {
+    let old = meta.mode() & 0o7777;
+    let new = match target {
+        Target::Mode(mode) => mode.apply(old, meta.is_dir(), opts.umask),
+        Target::Reference(mode) => *mode,
+    };
+
+    let name = quoting::display(shown.as_os_str());
+    if old != new {
+        match ctx.perform(FsAction::SetMode { path, mode: new }) {
+            Ok(()) => {
+                if opts.verbosity != Verbosity::Quiet {
+                    outln!(ctx, "mode of '{}' changed from {:04o} ({}) to {:04o} ({})", name, old, file_info::permission_string(old), new, file_info::permission_string(new));
+                }
+            }
+            Err(e) => {
+                report(ctx, opts, &format!("changing permissions of '{}': {}", name, error_message(&e)));
+            }
+        }
+    } else if opts.verbosity == Verbosity::All {
+        outln!(ctx, "mode of '{}' retained as {:04o} ({})", name, old, file_info::permission_string(old));
+    }
+
+    if !opts.recursive || !meta.is_dir() {
+        return;
+    }
+    let mut names = match ctx.fs.read_dir(path) {
+        Ok(names) => names,
+        Err(e) => {
+            report(ctx, opts, &format!("cannot read directory '{}': {}", name, error_message(&e)));
+            return;
+        }
+    };
+    names.sort();
+    for child in names {
+        if ctx.interrupted() {
+            return;
+        }
+        let child_path = path.join(&child);
+        let child_shown = shown.join(&child);
+        match ctx.fs.symlink_metadata(&child_path) {
+            // 再帰中に出会ったシンボリックリンクは変えない
+            Ok(meta) if meta.is_symlink() => {}
+            Ok(meta) => change(ctx, &child_path, &child_shown, &meta, target, opts),
+            Err(e) => {
+                let message = format!("cannot access '{}': {}", quoting::display(child_shown.as_os_str()), error_message(&e));
+                report(ctx, opts, &message);
+            }
+        }
+    }
+}
+
+/// エラーを表示する。-f なら表示せず終了ステータスだけを失敗にする
+fn report(ctx: &mut Context, opts: &Options, message: &str) {
+    if opts.silent {
+        ctx.status = 1;
+    } else {
+        errln!(ctx, "chmod: {}", message);
+    }
+}
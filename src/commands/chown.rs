@@ -0,0 +1,300 @@
+//! chown / chgrp コマンド。ファイルの所有者・グループを変える
+//!
+//! 対応オプション:
+//! - `-R` ディレクトリの中身も再帰的に変える（シンボリックリンクはたどらず、リンク自体を変える）
+//! - `-h` シンボリックリンク自体を変える（`--no-dereference`）
+//! - `-v` 処理したファイルをすべて表示する（`-c` は変更したものだけ）
+//! - `-f` ほとんどのエラーを表示しない
+//! - `--reference=FILE` FILE と同じ所有者・グループにする
+//!
+//! chown の指定は `USER`、`USER:GROUP`、`USER:`（ユーザーのログイングループ）、`:GROUP`。
+//! chgrp は `GROUP` だけを取る。名前は passwd / group データベースから引き、
+//! 見つからない数字はそのまま ID として扱う。
+
+use super::error_message;
+use crate::context::Context;
+use crate::file_info::{self, NameCache};
+use crate::fs_action::FsAction;
+use crate::quoting;
+use crate::vfs::Stat;
+use crate::{errln, outln};
+use std::ffi::{OsStr, OsString};
+use std::path::Path;
+
+/// コマンド名
+pub const NAME: &str = "chown";
+/// グループだけを変える別名
+pub const CHGRP: &str = "chgrp";
+
+/// 処理したファイルの表示（-v / -c）
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Verbosity {
+    Quiet,
+    Changes,
+    All,
+}
+
+/// 解析済みのオプション
+struct Options {
+    /// エラーメッセージに使うコマンド名
+    name: &'static str,
+    recursive: bool,
+    no_dereference: bool,
+    silent: bool,
+    verbosity: Verbosity,
+}
+
+/// 変更後の所有者。`None` の項目は変えない
+#[derive(Clone, Copy)]
+struct Owner {
+    uid: Option<u32>,
+    gid: Option<u32>,
+}
+
+pub fn run(ctx: &mut Context, args: &[OsString]) {
+    run_with(ctx, args, NAME);
+}
+
+/// chgrp として実行する
+pub fn run_chgrp(ctx: &mut Context, args: &[OsString]) {
+    run_with(ctx, args, CHGRP);
+}
+
+fn run_with(ctx: &mut Context, args: &[OsString], name: &'static str) {
+    let mut opts = Options {
+        name,
+        recursive: false,
+        no_dereference: false,
+        silent: false,
+        verbosity: Verbosity::Quiet,
+    };
+    let mut reference: Option<&OsStr> = None;
+    let mut operands: Vec<&OsStr> = Vec::new();
+    let mut only_operands = false;
+
+    for os_arg in args {
+        let arg = match os_arg.to_str() {
+            Some(arg) if !only_operands && arg.starts_with('-') && arg != "-" => arg,
+            _ => {
+                operands.push(os_arg);
+                continue;
+            }
+        };
+        match arg {
+            "--" => only_operands = true,
+            "--recursive" => opts.recursive = true,
+            "--no-dereference" => opts.no_dereference = true,
+            "--dereference" => opts.no_dereference = false,
+            "--silent" | "--quiet" => opts.silent = true,
+            "--verbose" => opts.verbosity = Verbosity::All,
+            "--changes" => opts.verbosity = Verbosity::Changes,
+            _ if arg.starts_with("--reference=") => reference = Some(OsStr::new(&arg["--reference=".len()..])),
+            _ if arg.starts_with("--") => {
+                errln!(ctx, "{}: unrecognized option '{}'", name, arg);
+                errln!(ctx, "Try '{} --help' for more information.", name);
+                return;
+            }
+            _ => {
+                for ch in arg[1..].chars() {
+                    match ch {
+                        'R' => opts.recursive = true,
+                        'h' => opts.no_dereference = true,
+                        'f' => opts.silent = true,
+                        'v' => opts.verbosity = Verbosity::All,
+                        'c' => opts.verbosity = Verbosity::Changes,
+                        _ => {
+                            errln!(ctx, "{}: invalid option -- '{}'", name, ch);
+                            errln!(ctx, "Try '{} --help' for more information.", name);
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // ドライランでは予定の操作だけを表示する
+    if ctx.dry_run {
+        opts.verbosity = Verbosity::Quiet;
+    }
+
+    let mut last_spec = reference;
+    let owner = match reference {
+        Some(file) => match ctx.fs.metadata(&ctx.resolve(file)) {
+            Ok(meta) if name == CHGRP => Owner { uid: None, gid: Some(meta.gid()) },
+            Ok(meta) => Owner { uid: Some(meta.uid()), gid: Some(meta.gid()) },
+            Err(e) => {
+                errln!(ctx, "{}: failed to get attributes of '{}': {}", name, quoting::display(file), error_message(&e));
+                return;
+            }
+        },
+        None => {
+            if operands.is_empty() {
+                errln!(ctx, "{}: missing operand", name);
+                errln!(ctx, "Try '{} --help' for more information.", name);
+                return;
+            }
+            let spec = operands.remove(0);
+            last_spec = Some(spec);
+            let text = spec.to_string_lossy();
+            let parsed = if name == CHGRP {
+                parse_id(&text, file_info::group_by_name).map(|gid| Owner { uid: None, gid: Some(gid) }).ok_or("group")
+            } else {
+                parse_owner(&text)
+            };
+            match parsed {
+                Ok(owner) => owner,
+                Err(what) => {
+                    errln!(ctx, "{}: invalid {}: '{}'", name, what, text);
+                    return;
+                }
+            }
+        }
+    };
+
+    if operands.is_empty() {
+        errln!(ctx, "{}: missing operand after '{}'", name, last_spec.map(quoting::display).unwrap_or_default());
+        errln!(ctx, "Try '{} --help' for more information.", name);
+        return;
+    }
+
+    // -R では途中のシンボリックリンクをたどらないので、引数のリンクもリンク自体を変える
+    let follow = !opts.no_dereference && !opts.recursive;
+    let mut names = NameCache::default();
+    for file in operands {
+        if ctx.interrupted() {
+            break;
+        }
+        let path = ctx.resolve(file);
+        let result = if follow { ctx.fs.metadata(&path) } else { ctx.fs.symlink_metadata(&path) };
+        match result {
+            Ok(meta) => change(ctx, &path, Path::new(file), &meta, owner, follow, &opts, &mut names),
+            Err(e) => {
+                let message = format!("cannot access '{}': {}", quoting::display(file), error_message(&e));
+                report(ctx, &opts, &message);
+            }
+        }
+    }
+}
+
+/// `USER[:GROUP]` の指定を解析する。失敗したら "user" "group" "spec" のどれが不正かを返す
+fn parse_owner(spec: &str) -> Result<Owner, &'static str> {
+    let (user, group) = match spec.split_once(':') {
+        Some((user, group)) => (user, Some(group)),
+        None => (spec, None),
+    };
+    let mut owner = Owner { uid: None, gid: None };
+    if !user.is_empty() {
+        // 数字の ID でも、登録されたユーザーならログイングループがわかる
+        let entry = file_info::user_by_name(user)
+            .or_else(|| user.parse().ok().and_then(file_info::user_name).and_then(|name| file_info::user_by_name(&name)));
+        match entry {
+            Some((uid, login_group)) => {
+                owner.uid = Some(uid);
+                // "USER:" ならログイングループにする
+                if group == Some("") {
+                    owner.gid = Some(login_group);
+                }
+            }
+            None => owner.uid = Some(user.parse().map_err(|_| "user")?),
+        }
+    }
+    match group {
+        // ":" だけなら何も変えない。"USER:" でログイングループがわからなければ不正
+        Some("") if !user.is_empty() && owner.gid.is_none() => return Err("spec"),
+        Some("") => {}
+        Some(group) => owner.gid = Some(parse_id(group, file_info::group_by_name).ok_or("group")?),
+        None => {}
+    }
+    Ok(owner)
+}
+
+/// 名前を引き、見つからなければ数値として解釈する
+fn parse_id(text: &str, lookup: fn(&str) -> Option<u32>) -> Option<u32> {
+    if text.is_empty() {
+        return None;
+    }
+    lookup(text).or_else(|| text.parse().ok())
+}
+
+/// 1つのファイル（-R ならその中身も）の所有者を変える
+#[allow(clippy::too_many_arguments)]
+fn change(
+    ctx: &mut Context,
+    path: &Path,
+    shown: &Path,
+    meta: &Stat,
+    owner: Owner,
+    follow: bool,
+    opts: &Options,
+    names: &mut NameCache,
+) {
+    let (old_uid, old_gid) = (meta.uid(), meta.gid());
+    let (new_uid, new_gid) = (owner.uid.unwrap_or(old_uid), owner.gid.unwrap_or(old_gid));
+    let describe = |names: &mut NameCache, uid: u32, gid: u32| {
+        if opts.name == CHGRP {
+            names.group(gid).to_string()
+        } else {
+            let user = names.user(uid).to_string();
+            format!("{}:{}", user, names.group(gid))
+        }
+    };
+    let what = if opts.name == CHGRP { "group" } else { "ownership" };
+
+    let name = quoting::display(shown.as_os_str());
+    if (old_uid, old_gid) != (new_uid, new_gid) {
+        let action = FsAction::SetOwner {
+            path,
+            uid: owner.uid,
+            gid: owner.gid,
+            follow_symlinks: follow,
+        };
+        match ctx.perform(action) {
+            Ok(()) => {
+                if opts.verbosity != Verbosity::Quiet {
+                    let (old, new) = (describe(names, old_uid, old_gid), describe(names, new_uid, new_gid));
+                    outln!(ctx, "changed {} of '{}' from {} to {}", what, name, old, new);
+                }
+            }
+            Err(e) => report(ctx, opts, &format!("changing {} of '{}': {}", what, name, error_message(&e))),
+        }
+    } else if opts.verbosity == Verbosity::All {
+        let old = describe(names, old_uid, old_gid);
+        outln!(ctx, "{} of '{}' retained as {}", what, name, old);
+    }
+
+    if !opts.recursive || !meta.is_dir() {
+        return;
+    }
+    let mut children = match ctx.fs.read_dir(path) {
+        Ok(children) => children,
+        Err(e) => {
+            report(ctx, opts, &format!("cannot read directory '{}': {}", name, error_message(&e)));
+            return;
+        }
+    };
+    children.sort();
+    for child in children {
+        if ctx.interrupted() {
+            return;
+        }
+        let child_path = path.join(&child);
+        let child_shown = shown.join(&child);
+        match ctx.fs.symlink_metadata(&child_path) {
+            Ok(meta) => change(ctx, &child_path, &child_shown, &meta, owner, false, opts, names),
+            Err(e) => {
+                let message = format!("cannot access '{}': {}", quoting::display(child_shown.as_os_str()), error_message(&e));
+                report(ctx, opts, &message);
+            }
+        }
+    }
+}
+
+/// エラーを表示する。-f なら表示せず終了ステータスだけを失敗にする
+fn report(ctx: &mut Context, opts: &Options, message: &str) {
+    if opts.silent {
+        ctx.status = 1;
+    } else {
+        errln!(ctx, "{}: {}", opts.name, message);
+    }
+}
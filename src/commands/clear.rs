@@ -1,12 +1,15 @@
 //! clearコマンド。ANSIエスケープで画面をクリアする
 
-use std::io::{self, Write};
+use crate::context::Context;
+use crate::out;
+use std::ffi::OsString;
+use std::io::Write;
 
 /// コマンド名
 pub const NAME: &str = "clear";
 
-pub fn run(_args: &[&str]) {
+pub fn run(ctx: &mut Context, _args: &[OsString]) {
     // \x1B[2J: 画面クリア / \x1B[1;1H: カーソルを左上へ
-    print!("\x1B[2J\x1B[1;1H");
-    let _ = io::stdout().flush();
+    out!(ctx, "\x1B[2J\x1B[1;1H");
+    let _ = ctx.stdout.flush();
 }
@@ -0,0 +1,823 @@
+//! cpコマンド。ファイル・ディレクトリをコピーする
+//!
+//! 対応オプション:
+//! - `-r` / `-R` ディレクトリを再帰的にコピーする
+//! - `-p` パーミッション・タイムスタンプ・所有者（権限がある場合のみ）を保持する
+//! - `-a` `-r -P -p` と同じ
+//! - `-f` / `-i` / `-n` 既存ファイルを強制上書き・確認してから上書き・上書きしない（最後の指定が有効）
+//! - `-L` / `-P` シンボリックリンクをたどる・リンク自体をコピーする
+//!   （既定は `-r` なしならたどり、`-r` ありならリンク自体をコピー）
+//! - `-v` コピーしたファイルを表示する
+//! - `--reflink[=WHEN]` COW クローンの使い方（auto: 可能なら使う, always, never）
+//!
+//! 通常ファイルはまず reflink（FICLONE）を試し、使えなければ疎なファイルは
+//! 穴（SEEK_HOLE）を保ったまま、それ以外は通常の読み書きでコピーする。
+//! 実際のファイルシステム以外（`Context::fs`）では、中身を読み書きするだけの単純なコピーになる。
+//! 大きなコピーの進捗は `Context::interaction` に通知する。
+//! コピー処理（`Copier`）は mv のファイルシステムをまたぐ移動でも使う。
+//!
+//! undo が有効なら、上書きするファイルの元の内容を退避してから上書きする。
+
+use super::error_message;
+use crate::context::{Context, Progress};
+use crate::fs_action::FsAction;
+use crate::fs_util;
+use crate::journal::Recorder;
+use crate::quoting;
+use crate::vfs::{Fs, Stat};
+use crate::{errln, outln};
+use std::ffi::{OsStr, OsString};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::{FileExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// コマンド名
+pub const NAME: &str = "cp";
+
+/// 読み書きの単位
+const BUFFER_SIZE: usize = 128 * 1024;
+
+/// 進捗を通知する間隔
+const REPORT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// ioctl(FICLONE)。ファイルの中身をコピーせずに共有する（btrfs, XFS など）
+const FICLONE: libc::c_ulong = 0x4004_9409;
+
+/// 既存のコピー先の扱い
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Overwrite {
+    /// 上書きする（書き込めなければ失敗）
+    Default,
+    /// 書き込めなければ削除してから作り直す（-f）
+    Force,
+    /// 上書きしない（-n）
+    NoClobber,
+    /// 上書きする前に確認する（-i）
+    Interactive,
+}
+
+/// reflink（COW クローン）の使い方
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Reflink {
+    /// 可能なら使う（既定）
+    Auto,
+    /// 必ず使う（使えなければ失敗）
+    Always,
+    /// 使わない
+    Never,
+}
+
+/// コピーの方法
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct CopyOptions {
+    pub recursive: bool,
+    pub preserve: bool,
+    pub overwrite: Overwrite,
+    /// シンボリックリンクをたどるか
+    pub dereference: bool,
+    pub reflink: Reflink,
+    pub verbose: bool,
+}
+
+/// ファイル・ディレクトリのコピーを行い、進捗を通知する
+pub(crate) struct Copier {
+    opts: CopyOptions,
+    /// メッセージの先頭に付けるコマンド名
+    command: &'static str,
+    /// 進捗の全体量（バイト数）
+    total: u64,
+    /// コピー済みのバイト数
+    done: u64,
+    last_report: Option<Instant>,
+    /// 1つでもエラーがあったか
+    pub failed: bool,
+    /// 上書きを記録する undo 用の記録（cp のみ。mv は自身で記録する）
+    pub recorder: Option<Recorder>,
+}
+
+impl Copier {
+    pub fn new(command: &'static str, opts: CopyOptions) -> Self {
+        Copier {
+            opts,
+            command,
+            total: 0,
+            done: 0,
+            last_report: None,
+            failed: false,
+            recorder: None,
+        }
+    }
+
+    /// `path` 以下の合計サイズを進捗の全体量に加える
+    pub fn add_total(&mut self, ctx: &Context, path: &Path) {
+        self.total += tree_size(ctx.fs.as_ref(), path, self.opts.dereference, self.opts.recursive);
+    }
+
+    /// 進捗表示を消す
+    pub fn finish(&mut self, ctx: &mut Context) {
+        ctx.interaction.progress(None);
+    }
+
+    /// `src` を `dst` にコピーする。`src_shown` と `dst_shown` はメッセージ用の名前。
+    /// すべてコピーできたら true（-n や -i で上書きを見送った場合は false）
+    pub fn copy(
+        &mut self,
+        ctx: &mut Context,
+        src: &Path,
+        dst: &Path,
+        src_shown: &Path,
+        dst_shown: &Path,
+    ) -> bool {
+        let src_is_dir = if self.opts.dereference {
+            ctx.fs.is_dir(src)
+        } else {
+            ctx.fs.symlink_metadata(src).is_ok_and(|m| m.is_dir())
+        };
+        if src_is_dir && self.opts.recursive && is_inside(ctx.fs.as_ref(), src, dst) {
+            errln!(
+                ctx,
+                "{}: cannot copy a directory, '{}', into itself, '{}'",
+                self.command,
+                shown(src_shown),
+                shown(dst_shown)
+            );
+            self.failed = true;
+            return false;
+        }
+        self.copy_entry(ctx, src, dst, src_shown, dst_shown)
+    }
+
+    /// 1項目をコピーする（ディレクトリなら中身も）
+    fn copy_entry(
+        &mut self,
+        ctx: &mut Context,
+        src: &Path,
+        dst: &Path,
+        src_shown: &Path,
+        dst_shown: &Path,
+    ) -> bool {
+        let meta = if self.opts.dereference {
+            ctx.fs.metadata(src)
+        } else {
+            ctx.fs.symlink_metadata(src)
+        };
+        let meta = match meta {
+            Ok(meta) => meta,
+            Err(e) => {
+                return self.error(
+                    ctx,
+                    format!("cannot stat '{}': {}", shown(src_shown), error_message(&e)),
+                )
+            }
+        };
+
+        let dst_meta = ctx.fs.symlink_metadata(dst).ok();
+        // 上書き前の内容の退避先（undo 用）
+        let mut stash = None;
+        if dst_meta.is_some() {
+            let dst_cmp = if self.opts.dereference {
+                ctx.fs.metadata(dst).ok()
+            } else {
+                dst_meta.clone()
+            };
+            if dst_cmp.is_some_and(|d| d.dev() == meta.dev() && d.ino() == meta.ino()) {
+                return self.error(
+                    ctx,
+                    format!(
+                        "'{}' and '{}' are the same file",
+                        shown(src_shown),
+                        shown(dst_shown)
+                    ),
+                );
+            }
+        }
+
+        let file_type = meta.file_type();
+        if file_type.is_dir() {
+            return self.copy_dir(ctx, src, dst, &meta, src_shown, dst_shown);
+        }
+
+        // ディレクトリ以外は、既存のコピー先を上書きしてよいか先に確かめる
+        if let Some(dst_meta) = &dst_meta {
+            if ctx.fs.is_dir(dst) {
+                return self.error(
+                    ctx,
+                    format!(
+                        "cannot overwrite directory '{}' with non-directory",
+                        shown(dst_shown)
+                    ),
+                );
+            }
+            if !self.may_overwrite(ctx, dst_shown) {
+                return false;
+            }
+            stash = self.recorder.as_mut().map(|recorder| recorder.stash_existing(dst, true));
+            // リンクや特殊ファイルは作り直すので、既存のものを消しておく
+            let recreate = file_type.is_symlink() || (self.opts.recursive && !file_type.is_file());
+            if recreate || dst_meta.file_type().is_symlink() && !self.opts.dereference {
+                if let Err(e) = ctx.perform(FsAction::RemoveFile(dst)) {
+                    return self.error(
+                        ctx,
+                        format!(
+                            "cannot remove '{}': {}",
+                            shown(dst_shown),
+                            error_message(&e)
+                        ),
+                    );
+                }
+            }
+        }
+
+        if ctx.preview(&FsAction::Copy { from: src, to: dst }) {
+            return true;
+        }
+        let result = if file_type.is_symlink() {
+            ctx.fs.read_link(src).and_then(|target| ctx.fs.symlink(&target, dst))
+        } else if file_type.is_file() || !self.opts.recursive {
+            // -r なしの特殊ファイル（/dev/null など）は中身を読んでコピーする
+            self.copy_file(ctx, src, dst, &meta, src_shown)
+        } else if ctx.fs.is_native() {
+            fs_util::mknod(dst, meta.mode(), meta.rdev())
+        } else {
+            Err(io::Error::from_raw_os_error(libc::EOPNOTSUPP))
+        };
+        if let Err(e) = result {
+            return self.error(
+                ctx,
+                format!(
+                    "cannot create '{}': {}",
+                    shown(dst_shown),
+                    error_message(&e)
+                ),
+            );
+        }
+
+        if self.opts.verbose {
+            outln!(ctx, "'{}' -> '{}'", shown(src_shown), shown(dst_shown));
+        }
+        if self.opts.preserve {
+            self.preserve_attributes(ctx, dst, &meta, dst_shown);
+        }
+        if let (Some(recorder), Some(stash)) = (self.recorder.as_mut(), stash) {
+            recorder.overwritten(dst, stash);
+        }
+        true
+    }
+
+    /// ディレクトリを中身ごとコピーする
+    fn copy_dir(
+        &mut self,
+        ctx: &mut Context,
+        src: &Path,
+        dst: &Path,
+        meta: &Stat,
+        src_shown: &Path,
+        dst_shown: &Path,
+    ) -> bool {
+        if !self.opts.recursive {
+            return self.error(
+                ctx,
+                format!(
+                    "-r not specified; omitting directory '{}'",
+                    shown(src_shown)
+                ),
+            );
+        }
+
+        let mode = meta.mode() & 0o7777;
+        let created = match ctx.fs.metadata(dst) {
+            Ok(existing) if existing.is_dir() => false,
+            Ok(_) => {
+                return self.error(
+                    ctx,
+                    format!(
+                        "cannot overwrite non-directory '{}' with directory '{}'",
+                        shown(dst_shown),
+                        shown(src_shown)
+                    ),
+                );
+            }
+            Err(_) if ctx.preview(&FsAction::CreateDir { path: dst, mode: Some(mode) }) => false,
+            Err(_) => {
+                // 中身を書き込めるよう、所有者の rwx は一時的に付けておく
+                if let Err(e) = ctx.fs.create_dir(dst, (mode & 0o777) | 0o700) {
+                    return self.error(
+                        ctx,
+                        format!(
+                            "cannot create directory '{}': {}",
+                            shown(dst_shown),
+                            error_message(&e)
+                        ),
+                    );
+                }
+                if self.opts.verbose {
+                    outln!(ctx, "'{}' -> '{}'", shown(src_shown), shown(dst_shown));
+                }
+                true
+            }
+        };
+
+        let mut names = match ctx.fs.read_dir(src) {
+            Ok(names) => names,
+            Err(e) => {
+                return self.error(
+                    ctx,
+                    format!(
+                        "cannot access '{}': {}",
+                        shown(src_shown),
+                        error_message(&e)
+                    ),
+                );
+            }
+        };
+        names.sort();
+
+        let mut ok = true;
+        for name in &names {
+            ok &= self.copy_entry(
+                ctx,
+                &src.join(name),
+                &dst.join(name),
+                &src_shown.join(name),
+                &dst_shown.join(name),
+            );
+        }
+
+        if ctx.dry_run {
+            return ok;
+        }
+        // 日時は中身を書き込んだ後でないと更新されてしまう
+        if self.opts.preserve {
+            self.preserve_attributes(ctx, dst, meta, dst_shown);
+        } else if created && mode & 0o700 != 0o700 {
+            let _ = ctx.fs.metadata(dst).and_then(|current| {
+                let restored = (current.mode() & 0o7777 & !0o700) | (mode & 0o700);
+                ctx.fs.set_permissions(dst, restored)
+            });
+        }
+        ok
+    }
+
+    /// 通常ファイル（または -r なしの特殊ファイル）の中身をコピーする
+    fn copy_file(
+        &mut self,
+        ctx: &mut Context,
+        src: &Path,
+        dst: &Path,
+        meta: &Stat,
+        shown: &Path,
+    ) -> io::Result<()> {
+        let force = self.opts.overwrite == Overwrite::Force;
+        // 実際のファイルシステム以外では中身を順に読み書きするだけ
+        if !ctx.fs.is_native() {
+            let src_file = ctx.fs.open(src)?;
+            let dst_file = match ctx.fs.create(dst, meta.mode() & 0o777) {
+                Err(e) if force && e.kind() == io::ErrorKind::PermissionDenied => {
+                    ctx.fs.remove_file(dst)?;
+                    ctx.fs.create(dst, meta.mode() & 0o777)?
+                }
+                result => result?,
+            };
+            return self.copy_stream(ctx, src_file, dst_file, shown);
+        }
+
+        let src_file = File::open(src)?;
+        let open = || {
+            OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .mode(meta.mode() & 0o777)
+                .open(dst)
+        };
+        let dst_file = match open() {
+            Err(e) if force && e.kind() == io::ErrorKind::PermissionDenied => {
+                ctx.fs.remove_file(dst)?;
+                open()?
+            }
+            result => result?,
+        };
+
+        if meta.is_file() {
+            self.copy_data(ctx, &src_file, &dst_file, meta, shown)
+        } else {
+            self.copy_stream(ctx, src_file, dst_file, shown)
+        }
+    }
+
+    /// 通常ファイルの中身をコピーする（reflink → 疎なコピー → 通常のコピーの順に試す）
+    fn copy_data(
+        &mut self,
+        ctx: &mut Context,
+        src: &File,
+        dst: &File,
+        meta: &Stat,
+        shown: &Path,
+    ) -> io::Result<()> {
+        let len = meta.len();
+        if self.opts.reflink != Reflink::Never {
+            let ret = unsafe { libc::ioctl(dst.as_raw_fd(), FICLONE as _, src.as_raw_fd()) };
+            if ret == 0 {
+                self.advance(ctx, len, shown);
+                return Ok(());
+            }
+            if self.opts.reflink == Reflink::Always {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        // 割り当て済みブロックがサイズより少なければ穴がある
+        if meta.blocks() * 512 < len {
+            self.copy_sparse(ctx, src, dst, len, shown)
+        } else {
+            self.copy_range(ctx, src, dst, 0, None, shown)
+        }
+    }
+
+    /// 穴を保ったままコピーする。データのある範囲だけを書き込み、最後にサイズを合わせる
+    fn copy_sparse(
+        &mut self,
+        ctx: &mut Context,
+        src: &File,
+        dst: &File,
+        len: u64,
+        shown: &Path,
+    ) -> io::Result<()> {
+        let fd = src.as_raw_fd();
+        let mut pos = 0u64;
+        while pos < len {
+            let data = unsafe { libc::lseek(fd, pos as libc::off_t, libc::SEEK_DATA) };
+            if data < 0 {
+                let e = io::Error::last_os_error();
+                match e.raw_os_error() {
+                    // これ以降にデータはない
+                    Some(libc::ENXIO) => break,
+                    // SEEK_DATA に対応していないファイルシステム
+                    Some(libc::EINVAL) => return self.copy_range(ctx, src, dst, pos, None, shown),
+                    _ => return Err(e),
+                }
+            }
+            let hole = unsafe { libc::lseek(fd, data, libc::SEEK_HOLE) };
+            if hole < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let (data, hole) = (data as u64, hole as u64);
+            self.advance(ctx, data - pos, shown);
+            self.copy_range(ctx, src, dst, data, Some(hole), shown)?;
+            pos = hole;
+        }
+        self.advance(ctx, len.saturating_sub(pos), shown);
+        dst.set_len(len)
+    }
+
+    /// `start` から `end`（None なら末尾）までを同じ位置にコピーする
+    fn copy_range(
+        &mut self,
+        ctx: &mut Context,
+        src: &File,
+        dst: &File,
+        start: u64,
+        end: Option<u64>,
+        shown: &Path,
+    ) -> io::Result<()> {
+        let mut buf = vec![0u8; BUFFER_SIZE];
+        let mut offset = start;
+        loop {
+            let want = match end {
+                Some(end) => (end - offset).min(BUFFER_SIZE as u64) as usize,
+                None => BUFFER_SIZE,
+            };
+            if want == 0 {
+                break;
+            }
+            let n = match src.read_at(&mut buf[..want], offset) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            dst.write_all_at(&buf[..n], offset)?;
+            offset += n as u64;
+            self.advance(ctx, n as u64, shown);
+        }
+        Ok(())
+    }
+
+    /// 位置指定できない入力（デバイスなど）を最後まで読んでコピーする
+    fn copy_stream(
+        &mut self,
+        ctx: &mut Context,
+        mut src: impl Read,
+        mut dst: impl Write,
+        shown: &Path,
+    ) -> io::Result<()> {
+        let mut buf = vec![0u8; BUFFER_SIZE];
+        loop {
+            let n = match src.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            dst.write_all(&buf[..n])?;
+            self.advance(ctx, n as u64, shown);
+        }
+        Ok(())
+    }
+
+    /// 所有者・パーミッション・日時をコピー元に合わせる
+    fn preserve_attributes(
+        &mut self,
+        ctx: &mut Context,
+        path: &Path,
+        meta: &Stat,
+        path_shown: &Path,
+    ) {
+        let is_symlink = meta.file_type().is_symlink();
+        let mut mode = meta.mode() & 0o7777;
+
+        // 所有者は権限がある場合だけ変えられる。変えられなければ setuid/setgid は落とす
+        // （実際のファイルシステム以外では所有者を変えない）
+        let chowned = if ctx.fs.is_native() {
+            std::os::unix::fs::lchown(path, Some(meta.uid()), Some(meta.gid()))
+        } else {
+            Ok(())
+        };
+        if let Err(e) = chowned {
+            if e.raw_os_error() != Some(libc::EPERM) {
+                self.error(
+                    ctx,
+                    format!(
+                        "failed to preserve ownership for '{}': {}",
+                        shown(path_shown),
+                        error_message(&e)
+                    ),
+                );
+            }
+            mode &= !0o6000;
+        }
+
+        // シンボリックリンク自体のパーミッションは変えられない（常に 777）
+        if !is_symlink {
+            if let Err(e) = ctx.fs.set_permissions(path, mode) {
+                self.error(
+                    ctx,
+                    format!(
+                        "preserving permissions for '{}': {}",
+                        shown(path_shown),
+                        error_message(&e)
+                    ),
+                );
+            }
+        }
+
+        if let Err(e) = ctx.fs.set_times(path, Some(meta.atime()), Some(meta.mtime()), false) {
+            self.error(
+                ctx,
+                format!(
+                    "preserving times for '{}': {}",
+                    shown(path_shown),
+                    error_message(&e)
+                ),
+            );
+        }
+    }
+
+    /// 既存のコピー先を上書きしてよいか
+    fn may_overwrite(&mut self, ctx: &mut Context, dst_shown: &Path) -> bool {
+        match self.opts.overwrite {
+            Overwrite::NoClobber => false,
+            Overwrite::Interactive => {
+                ctx.interaction.progress(None);
+                ctx.confirm(&format!(
+                    "{}: overwrite '{}'? ",
+                    self.command,
+                    shown(dst_shown)
+                ))
+            }
+            Overwrite::Default | Overwrite::Force => true,
+        }
+    }
+
+    /// コピー済みの量を加え、一定間隔で進捗を通知する
+    fn advance(&mut self, ctx: &mut Context, bytes: u64, current: &Path) {
+        self.done += bytes;
+        if self.total == 0 {
+            return;
+        }
+        let now = Instant::now();
+        if self
+            .last_report
+            .is_some_and(|last| now.duration_since(last) < REPORT_INTERVAL)
+        {
+            return;
+        }
+        self.last_report = Some(now);
+        let progress = Progress {
+            message: shown(current),
+            done: self.done.min(self.total),
+            total: self.total,
+        };
+        ctx.interaction.progress(Some(&progress));
+    }
+
+    /// エラーを表示して記録する。呼び出し元でそのまま返せるよう常に false を返す
+    fn error(&mut self, ctx: &mut Context, message: String) -> bool {
+        ctx.interaction.progress(None);
+        errln!(ctx, "{}: {}", self.command, message);
+        self.failed = true;
+        false
+    }
+}
+
+/// メッセージ用の名前
+fn shown(path: &Path) -> String {
+    quoting::display(path.as_os_str())
+}
+
+/// `path` 以下の通常ファイルの合計サイズ（進捗の全体量）
+fn tree_size(fs: &dyn Fs, path: &Path, dereference: bool, recursive: bool) -> u64 {
+    let meta = if dereference {
+        fs.metadata(path)
+    } else {
+        fs.symlink_metadata(path)
+    };
+    let Ok(meta) = meta else {
+        return 0;
+    };
+    if meta.is_dir() {
+        if !recursive {
+            return 0;
+        }
+        fs.read_dir(path)
+            .map(|names| {
+                names
+                    .iter()
+                    .map(|name| tree_size(fs, &path.join(name), dereference, recursive))
+                    .sum()
+            })
+            .unwrap_or(0)
+    } else if meta.is_file() {
+        meta.len()
+    } else {
+        0
+    }
+}
+
+/// `dst` が `src` ディレクトリの中（または `src` 自身）を指すか
+pub(crate) fn is_inside(fs: &dyn Fs, src: &Path, dst: &Path) -> bool {
+    let Ok(src) = fs.canonicalize(src) else {
+        return false;
+    };
+    // コピー先はまだ存在しないことが多いので、親ディレクトリで判定する
+    let dst = match fs.canonicalize(dst) {
+        Ok(dst) => dst,
+        Err(_) => match (
+            dst.parent().and_then(|p| fs.canonicalize(p).ok()),
+            dst.file_name(),
+        ) {
+            (Some(parent), Some(name)) => parent.join(name),
+            _ => return false,
+        },
+    };
+    dst.starts_with(&src)
+}
+
+pub fn run(ctx: &mut Context, args: &[OsString]) {
+    let mut opts = CopyOptions {
+        recursive: false,
+        preserve: false,
+        overwrite: Overwrite::Default,
+        dereference: false,
+        reflink: Reflink::Auto,
+        verbose: false,
+    };
+    // -L / -P の明示指定。なければ -r の有無で決める
+    let mut dereference = None;
+    let mut operands: Vec<&OsStr> = Vec::new();
+    let mut only_operands = false;
+
+    for os_arg in args {
+        let arg = match os_arg.to_str() {
+            Some(arg) if !only_operands && arg.starts_with('-') && arg != "-" => arg,
+            _ => {
+                operands.push(os_arg);
+                continue;
+            }
+        };
+        match arg {
+            "--" => only_operands = true,
+            "--recursive" => opts.recursive = true,
+            "--preserve" => opts.preserve = true,
+            "--archive" => {
+                opts.recursive = true;
+                opts.preserve = true;
+                dereference = Some(false);
+            }
+            "--force" => opts.overwrite = Overwrite::Force,
+            "--interactive" => opts.overwrite = Overwrite::Interactive,
+            "--no-clobber" => opts.overwrite = Overwrite::NoClobber,
+            "--dereference" => dereference = Some(true),
+            "--no-dereference" => dereference = Some(false),
+            "--verbose" => opts.verbose = true,
+            "--reflink" | "--reflink=auto" => opts.reflink = Reflink::Auto,
+            "--reflink=always" => opts.reflink = Reflink::Always,
+            "--reflink=never" => opts.reflink = Reflink::Never,
+            _ if arg.starts_with("--reflink=") => {
+                errln!(
+                    ctx,
+                    "cp: invalid argument '{}' for '--reflink'",
+                    &arg["--reflink=".len()..]
+                );
+                errln!(ctx, "Valid arguments are: 'auto', 'always', 'never'");
+                return;
+            }
+            _ if arg.starts_with("--") => {
+                errln!(ctx, "cp: unrecognized option '{}'", arg);
+                return;
+            }
+            _ => {
+                for ch in arg[1..].chars() {
+                    match ch {
+                        'r' | 'R' => opts.recursive = true,
+                        'p' => opts.preserve = true,
+                        'a' => {
+                            opts.recursive = true;
+                            opts.preserve = true;
+                            dereference = Some(false);
+                        }
+                        'f' => opts.overwrite = Overwrite::Force,
+                        'i' => opts.overwrite = Overwrite::Interactive,
+                        'n' => opts.overwrite = Overwrite::NoClobber,
+                        'L' => dereference = Some(true),
+                        'P' => dereference = Some(false),
+                        'v' => opts.verbose = true,
+                        _ => {
+                            errln!(ctx, "cp: invalid option -- '{}'", ch);
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    opts.dereference = dereference.unwrap_or(!opts.recursive);
+    // ドライランでは予定の操作だけを表示する
+    opts.verbose &= !ctx.dry_run;
+
+    let (target, sources) = match operands.split_last() {
+        None => {
+            errln!(ctx, "cp: missing file operand");
+            errln!(ctx, "Try 'cp --help' for more information.");
+            return;
+        }
+        Some((target, [])) => {
+            errln!(
+                ctx,
+                "cp: missing destination file operand after '{}'",
+                quoting::display(target)
+            );
+            return;
+        }
+        Some((target, sources)) => (Path::new(*target), sources),
+    };
+
+    let target_path = ctx.resolve(target);
+    let into_dir = ctx.fs.is_dir(&target_path);
+    if sources.len() > 1 && !into_dir {
+        errln!(
+            ctx,
+            "cp: target '{}' is not a directory",
+            quoting::display(target.as_os_str())
+        );
+        return;
+    }
+
+    let mut copier = Copier::new(NAME, opts);
+    copier.recorder = ctx.recorder(NAME, args);
+    for source in sources {
+        copier.add_total(ctx, &ctx.resolve(source));
+    }
+
+    for source in sources {
+        let src_shown = Path::new(source);
+        // ディレクトリへのコピーは同じ名前で中に作る（"." などは中身をそのままコピーする）
+        let dst_shown: PathBuf = match src_shown.file_name() {
+            Some(name) if into_dir => target.join(name),
+            _ => target.to_path_buf(),
+        };
+        let src = ctx.resolve(src_shown);
+        let dst = ctx.resolve(&dst_shown);
+        copier.copy(ctx, &src, &dst, src_shown, &dst_shown);
+    }
+    copier.finish(ctx);
+
+    if let Some(recorder) = copier.recorder.take() {
+        if let Err(e) = recorder.commit() {
+            errln!(ctx, "cp: cannot record for undo: {}", error_message(&e));
+        }
+    }
+}
@@ -0,0 +1,352 @@
+//! cutコマンド。各行から指定した部分だけを取り出す
+//!
+//! 対応オプション:
+//! - `-f LIST` フィールド（`-d` の区切り文字で分けたもの。既定はタブ）
+//! - `-c LIST` 文字（UTF-8 の1文字単位。不正なバイトは1バイトを1文字とする）
+//! - `-b LIST` バイト
+//! - `-d DELIM` フィールドの区切り文字（1文字。全角文字も使える）
+//! - `-s` 区切り文字を含まない行は出力しない（既定はそのまま出力する）
+//! - `--complement` 指定した部分以外を出力する
+//! - `--output-delimiter=STR` 出力の区切り（既定は `-d` の文字。`-c` `-b` では離れた範囲の間に入れる）
+//! - `-z` 行の区切りを改行ではなく NUL にする
+//!
+//! LIST は `N`、`N-M`、`N-`（最後まで）、`-M`（先頭から）をカンマで区切って並べたもの。
+//! 番号は1から数え、指定の順序や重なりによらず入力での順に出力する。
+
+use super::cat::{self, LineReader};
+use super::error_message;
+use crate::context::Context;
+use crate::quoting;
+use crate::errln;
+use std::ffi::{OsStr, OsString};
+use std::io::{self, Read, Write};
+
+/// コマンド名
+pub const NAME: &str = "cut";
+
+/// 取り出す単位
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Unit {
+    Bytes,
+    Chars,
+    Fields,
+}
+
+/// 取り出す番号の範囲（1から、両端を含む）。重なりをまとめて昇順に並べたもの
+struct List {
+    ranges: Vec<(usize, usize)>,
+}
+
+impl List {
+    /// `1,3-5,7-` の形を解析する
+    fn parse(spec: &str) -> Result<List, String> {
+        let mut ranges = Vec::new();
+        for part in spec.split([',', ' ']) {
+            let (low, high) = match part.split_once('-') {
+                Some(("", "")) => return Err("invalid range with no endpoint: -".to_string()),
+                Some((low, high)) => (low, high),
+                None => (part, part),
+            };
+            let number = |s: &str, default: usize| -> Result<usize, String> {
+                if s.is_empty() {
+                    return Ok(default);
+                }
+                match s.parse::<usize>() {
+                    Ok(0) => Err("fields and positions are numbered from 1".to_string()),
+                    Ok(n) => Ok(n),
+                    Err(_) => Err(format!("invalid field value '{}'", s)),
+                }
+            };
+            let low = number(low, 1)?;
+            let high = number(high, usize::MAX)?;
+            if low > high {
+                return Err("invalid decreasing range".to_string());
+            }
+            ranges.push((low, high));
+        }
+        ranges.sort();
+        let mut merged: Vec<(usize, usize)> = Vec::new();
+        for (low, high) in ranges {
+            match merged.last_mut() {
+                Some(last) if low <= last.1.saturating_add(1) => last.1 = last.1.max(high),
+                _ => merged.push((low, high)),
+            }
+        }
+        Ok(List { ranges: merged })
+    }
+
+    /// `n` 番目（1から）を取り出すか
+    fn contains(&self, n: usize) -> bool {
+        self.ranges.iter().any(|&(low, high)| low <= n && n <= high)
+    }
+}
+
+/// 解析済みのオプション
+struct Options {
+    unit: Unit,
+    list: List,
+    complement: bool,
+    delimiter: Vec<u8>,
+    output_delimiter: Option<Vec<u8>>,
+    only_delimited: bool,
+    line_delim: u8,
+}
+
+impl Options {
+    fn selected(&self, n: usize) -> bool {
+        self.list.contains(n) != self.complement
+    }
+}
+
+pub fn run(ctx: &mut Context, args: &[OsString]) {
+    let mut unit_list: Option<(Unit, String)> = None;
+    let mut delimiter: Option<Vec<u8>> = None;
+    let mut output_delimiter = None;
+    let mut only_delimited = false;
+    let mut complement = false;
+    let mut line_delim = b'\n';
+    let mut files: Vec<&OsStr> = Vec::new();
+    let mut only_files = false;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let Some(s) = arg.to_str().filter(|s| !only_files && s.starts_with('-') && s.len() > 1) else {
+            files.push(arg);
+            continue;
+        };
+        // 値を取るオプション（-f 1 / -f1 / --fields=1 / --fields 1）
+        let (option, value): (char, &OsStr) = match s {
+            "--" => {
+                only_files = true;
+                continue;
+            }
+            "--only-delimited" => {
+                only_delimited = true;
+                continue;
+            }
+            "--complement" => {
+                complement = true;
+                continue;
+            }
+            "--zero-terminated" => {
+                line_delim = b'\0';
+                continue;
+            }
+            _ if s.starts_with("--output-delimiter=") => {
+                output_delimiter = Some(s.as_bytes()["--output-delimiter=".len()..].to_vec());
+                continue;
+            }
+            _ if s.starts_with("--") => {
+                let (name, value) = match s.split_once('=') {
+                    Some((name, value)) => (name, Some(OsStr::new(value))),
+                    None => (s, None),
+                };
+                let option = match name {
+                    "--bytes" => 'b',
+                    "--characters" => 'c',
+                    "--fields" => 'f',
+                    "--delimiter" => 'd',
+                    _ => {
+                        errln!(ctx, "cut: unrecognized option '{}'", s);
+                        return;
+                    }
+                };
+                match value.or_else(|| args.next().map(|v| v.as_os_str())) {
+                    Some(value) => (option, value),
+                    None => {
+                        errln!(ctx, "cut: option '{}' requires an argument", name);
+                        return;
+                    }
+                }
+            }
+            _ => {
+                let mut value = None;
+                for (i, ch) in s[1..].char_indices() {
+                    match ch {
+                        's' => only_delimited = true,
+                        'z' => line_delim = b'\0',
+                        // -n は GNU cut と同じく無視する
+                        'n' => {}
+                        'b' | 'c' | 'f' | 'd' => {
+                            let rest = &s[i + 2..];
+                            value = match (rest.is_empty(), if rest.is_empty() { args.next() } else { None }) {
+                                (false, _) => Some((ch, OsStr::new(rest))),
+                                (true, Some(next)) => Some((ch, next.as_os_str())),
+                                (true, None) => {
+                                    errln!(ctx, "cut: option requires an argument -- '{}'", ch);
+                                    return;
+                                }
+                            };
+                            break;
+                        }
+                        _ => {
+                            errln!(ctx, "cut: invalid option -- '{}'", ch);
+                            return;
+                        }
+                    }
+                }
+                match value {
+                    Some(value) => value,
+                    None => continue,
+                }
+            }
+        };
+        let unit = match option {
+            'b' => Unit::Bytes,
+            'c' => Unit::Chars,
+            'f' => Unit::Fields,
+            _ => {
+                let bytes = value.as_encoded_bytes();
+                if bytes.len() <= 1 || std::str::from_utf8(bytes).is_ok_and(|d| d.chars().count() == 1) {
+                    // 空の区切り（-d ''）は NUL とみなす
+                    delimiter = Some(if bytes.is_empty() { vec![b'\0'] } else { bytes.to_vec() });
+                } else {
+                    errln!(ctx, "cut: the delimiter must be a single character");
+                    return;
+                }
+                continue;
+            }
+        };
+        if unit_list.is_some() {
+            errln!(ctx, "cut: only one type of list may be specified");
+            return;
+        }
+        unit_list = Some((unit, value.to_string_lossy().into_owned()));
+    }
+
+    let Some((unit, spec)) = unit_list else {
+        errln!(ctx, "cut: you must specify a list of bytes, characters, or fields");
+        return;
+    };
+    let list = match List::parse(&spec) {
+        Ok(list) => list,
+        Err(message) => {
+            errln!(ctx, "cut: {}", message);
+            return;
+        }
+    };
+    if unit != Unit::Fields && (delimiter.is_some() || only_delimited) {
+        let message = if only_delimited {
+            "suppressing non-delimited lines makes sense\n\tonly when operating on fields"
+        } else {
+            "an input delimiter may be specified only when operating on fields"
+        };
+        errln!(ctx, "cut: {}", message);
+        return;
+    }
+    let opts = Options {
+        unit,
+        list,
+        complement,
+        delimiter: delimiter.unwrap_or_else(|| b"\t".to_vec()),
+        output_delimiter,
+        only_delimited,
+        line_delim,
+    };
+    if files.is_empty() {
+        files.push(OsStr::new("-"));
+    }
+
+    for file in files {
+        let result = cat::with_input(ctx, file, |ctx, reader| cut(ctx, reader, &opts));
+        match result {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) if e.kind() == io::ErrorKind::Interrupted => break,
+            Ok(Err(e)) | Err(e) => errln!(ctx, "cut: {}: {}", quoting::display(file), error_message(&e)),
+        }
+    }
+    let _ = ctx.stdout.flush();
+}
+
+/// 入力の各行から取り出して書き出す
+fn cut(ctx: &mut Context, reader: &mut dyn Read, opts: &Options) -> io::Result<()> {
+    let mut lines = LineReader::new();
+    let mut line = Vec::new();
+    let mut out = Vec::new();
+    while lines.read_record(ctx, reader, opts.line_delim, &mut line)? {
+        if line.last() == Some(&opts.line_delim) {
+            line.pop();
+        }
+        out.clear();
+        let keep = match opts.unit {
+            Unit::Fields => cut_fields(&line, opts, &mut out),
+            Unit::Bytes => {
+                let pieces = line.iter().map(std::slice::from_ref);
+                cut_pieces(pieces, opts, &mut out);
+                true
+            }
+            Unit::Chars => {
+                cut_pieces(chars(&line), opts, &mut out);
+                true
+            }
+        };
+        if keep {
+            out.push(opts.line_delim);
+            let _ = ctx.stdout.write_all(&out);
+        }
+    }
+    Ok(())
+}
+
+/// UTF-8 の1文字ずつ（不正なバイトは1バイトずつ）に分ける
+fn chars(line: &[u8]) -> impl Iterator<Item = &[u8]> {
+    line.utf8_chunks().flat_map(|chunk| {
+        let valid = chunk.valid();
+        valid
+            .char_indices()
+            .map(move |(i, c)| &valid.as_bytes()[i..i + c.len_utf8()])
+            .chain(chunk.invalid().iter().map(std::slice::from_ref))
+    })
+}
+
+/// バイトか文字の単位で取り出す。`--output-delimiter` があれば離れた範囲の間に入れる
+fn cut_pieces<'a>(pieces: impl Iterator<Item = &'a [u8]>, opts: &Options, out: &mut Vec<u8>) {
+    let mut prev_selected = true;
+    let mut any = false;
+    for (i, piece) in pieces.enumerate() {
+        let selected = opts.selected(i + 1);
+        if selected {
+            if let Some(delimiter) = opts.output_delimiter.as_ref().filter(|_| any && !prev_selected) {
+                out.extend_from_slice(delimiter);
+            }
+            out.extend_from_slice(piece);
+            any = true;
+        }
+        prev_selected = selected;
+    }
+}
+
+/// フィールドを取り出す。出力しない行なら false を返す
+fn cut_fields(line: &[u8], opts: &Options, out: &mut Vec<u8>) -> bool {
+    let delimiter = &opts.delimiter;
+    let mut fields = Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+    while i + delimiter.len() <= line.len() {
+        if line[i..].starts_with(delimiter) {
+            fields.push(&line[start..i]);
+            i += delimiter.len();
+            start = i;
+        } else {
+            i += 1;
+        }
+    }
+    // 区切り文字を含まない行はそのまま（-s なら出力しない）
+    if fields.is_empty() {
+        out.extend_from_slice(line);
+        return !opts.only_delimited;
+    }
+    fields.push(&line[start..]);
+    let output_delimiter = opts.output_delimiter.as_ref().unwrap_or(delimiter);
+    let mut first = true;
+    for (n, field) in fields.iter().enumerate() {
+        if opts.selected(n + 1) {
+            if !first {
+                out.extend_from_slice(output_delimiter);
+            }
+            out.extend_from_slice(field);
+            first = false;
+        }
+    }
+    true
+}
@@ -0,0 +1,415 @@
+//! dfコマンド。マウントされたファイルシステムごとの使用量を表示する
+//!
+//! 対応オプション:
+//! - `-h` `1.5G` のように表示する（`--human-readable`）
+//! - `-k` `-m` 1K・1M 単位で表示する（既定は 1K）
+//! - `-i` ブロックの代わりに inode の数を表示する（`--inodes`）
+//! - `-T` ファイルシステムの種類も表示する（`--print-type`）
+//! - `-a` 大きさが 0 の疑似ファイルシステムや重複したマウントも表示する（`--all`）
+//! - `-l` ローカルのファイルシステムだけを表示する（`--local`）
+//! - `-t TYPE` / `-x TYPE` 種類が TYPE のものだけ・以外を表示する（複数指定可）
+//! - `--total` 最後に合計を表示する
+//!
+//! FILE を指定すると、そのファイルがあるファイルシステムだけを表示する。
+//! マウントの一覧は `/proc/self/mountinfo` から、使用量は `statvfs` で調べる。
+//! サンドボックスの中では、ルートを含むファイルシステムだけを `/` にマウントされたものとして表示する
+//! （ほかのマウントは見せない）。
+//! GUI版では結果を並べ替えられる表で表示する。
+
+use super::error_message;
+use crate::context::Context;
+use crate::file_info;
+use crate::fs_util;
+use crate::quoting;
+use crate::table::{Cell, Table};
+use crate::{errln, outln};
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::{Path, PathBuf};
+
+/// コマンド名
+pub const NAME: &str = "df";
+
+/// マウント情報の場所
+const MOUNTINFO: &str = "/proc/self/mountinfo";
+
+/// ネットワーク越しのファイルシステムの種類（-l で除く）
+const REMOTE_TYPES: &[&str] = &["nfs", "nfs4", "cifs", "smb3", "smbfs", "sshfs", "fuse.sshfs", "afs", "ceph", "9p"];
+
+/// 解析済みのオプション
+struct Options {
+    human: bool,
+    block_size: u64,
+    inodes: bool,
+    print_type: bool,
+    all: bool,
+    local: bool,
+    total: bool,
+    only_types: Vec<String>,
+    exclude_types: Vec<String>,
+}
+
+/// `/proc/self/mountinfo` の1行
+#[derive(Clone)]
+struct Mount {
+    /// デバイス番号（major:minor）
+    device: String,
+    mount_point: PathBuf,
+    fs_type: String,
+    source: String,
+}
+
+/// `statvfs` で調べた使用量
+struct Usage {
+    size: u64,
+    used: u64,
+    available: u64,
+    inodes: u64,
+    inodes_used: u64,
+    inodes_free: u64,
+}
+
+pub fn run(ctx: &mut Context, args: &[OsString]) {
+    let mut opts = Options {
+        human: false,
+        block_size: 1024,
+        inodes: false,
+        print_type: false,
+        all: false,
+        local: false,
+        total: false,
+        only_types: Vec::new(),
+        exclude_types: Vec::new(),
+    };
+    let mut files: Vec<&OsStr> = Vec::new();
+    let mut only_operands = false;
+    let mut args = args.iter();
+
+    while let Some(os_arg) = args.next() {
+        let arg = match os_arg.to_str() {
+            Some(arg) if !only_operands && arg.starts_with('-') && arg != "-" => arg,
+            _ => {
+                files.push(os_arg);
+                continue;
+            }
+        };
+        // -t / -x の引数（短い形式の文字, 値）
+        let mut type_filter: Option<(char, String)> = None;
+        match arg {
+            "--" => only_operands = true,
+            "--human-readable" => opts.human = true,
+            "--inodes" => opts.inodes = true,
+            "--print-type" => opts.print_type = true,
+            "--all" => opts.all = true,
+            "--local" => opts.local = true,
+            "--total" => opts.total = true,
+            "--type" | "--exclude-type" => match args.next() {
+                Some(value) => type_filter = Some((if arg == "--type" { 't' } else { 'x' }, value.to_string_lossy().into_owned())),
+                None => {
+                    errln!(ctx, "df: option '{}' requires an argument", arg);
+                    return;
+                }
+            },
+            _ if arg.starts_with("--type=") => type_filter = Some(('t', arg["--type=".len()..].to_string())),
+            _ if arg.starts_with("--exclude-type=") => type_filter = Some(('x', arg["--exclude-type=".len()..].to_string())),
+            _ if arg.starts_with("--") => {
+                errln!(ctx, "df: unrecognized option '{}'", arg);
+                errln!(ctx, "Try 'df --help' for more information.");
+                return;
+            }
+            _ => {
+                for (i, ch) in arg[1..].char_indices() {
+                    match ch {
+                        'h' => opts.human = true,
+                        'k' => opts.block_size = 1024,
+                        'm' => opts.block_size = 1024 * 1024,
+                        'i' => opts.inodes = true,
+                        'T' => opts.print_type = true,
+                        'a' => opts.all = true,
+                        'l' => opts.local = true,
+                        't' | 'x' => {
+                            let rest = &arg[1 + i + 1..];
+                            let value = if !rest.is_empty() {
+                                Some(rest.to_string())
+                            } else {
+                                args.next().map(|value| value.to_string_lossy().into_owned())
+                            };
+                            match value {
+                                Some(value) => type_filter = Some((ch, value)),
+                                None => {
+                                    errln!(ctx, "df: option requires an argument -- '{}'", ch);
+                                    return;
+                                }
+                            }
+                            break;
+                        }
+                        _ => {
+                            errln!(ctx, "df: invalid option -- '{}'", ch);
+                            errln!(ctx, "Try 'df --help' for more information.");
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+        match type_filter {
+            Some(('t', fs_type)) => opts.only_types.push(fs_type),
+            Some((_, fs_type)) => opts.exclude_types.push(fs_type),
+            None => {}
+        }
+    }
+
+    let mut mounts = match read_mounts() {
+        Ok(mounts) => mounts,
+        Err(e) => {
+            errln!(ctx, "df: cannot read {}: {}", MOUNTINFO, error_message(&e));
+            return;
+        }
+    };
+    // サンドボックスの中ではルート自体を唯一のマウントポイントにする
+    let sandbox = ctx.sandbox.clone();
+    if let Some(root) = &sandbox {
+        let Some(mount) = containing_mount(&mounts, root) else {
+            errln!(ctx, "df: no file systems processed");
+            return;
+        };
+        mounts = vec![Mount { mount_point: root.clone(), ..mount.clone() }];
+    }
+
+    // (マウント, 使用量) の一覧
+    let mut selected: Vec<(&Mount, Usage)> = Vec::new();
+    if files.is_empty() {
+        // 同じデバイスが複数の場所にマウントされていれば、いちばん短いマウントポイントだけを表示する
+        let mut by_device: HashMap<&str, usize> = HashMap::new();
+        for (i, mount) in mounts.iter().enumerate() {
+            if !type_matches(&mount.fs_type, &opts) {
+                continue;
+            }
+            // 後から同じ場所にマウントされて隠れたものは表示しない
+            if !opts.all && mounts[i + 1..].iter().any(|later| later.mount_point == mount.mount_point) {
+                continue;
+            }
+            let Ok(usage) = statvfs(&mount.mount_point) else {
+                continue;
+            };
+            if !opts.all {
+                if usage.size == 0 {
+                    continue;
+                }
+                if let Some(&index) = by_device.get(mount.device.as_str()) {
+                    let shown: &Mount = selected[index].0;
+                    if mount.mount_point.as_os_str().len() < shown.mount_point.as_os_str().len() {
+                        selected[index] = (mount, usage);
+                    }
+                    continue;
+                }
+                by_device.insert(&mount.device, selected.len());
+            }
+            selected.push((mount, usage));
+        }
+    } else {
+        for file in files {
+            let path = ctx.resolve(file);
+            let result = ctx.fs.canonicalize(&path).and_then(|path| {
+                let mount = containing_mount(&mounts, &path).ok_or_else(|| io::Error::from_raw_os_error(libc::ENOENT))?;
+                Ok((mount, statvfs(&mount.mount_point)?))
+            });
+            match result {
+                Ok((mount, usage)) if type_matches(&mount.fs_type, &opts) => selected.push((mount, usage)),
+                Ok(_) => {}
+                Err(e) => errln!(ctx, "df: {}: {}", quoting::display(file), error_message(&e)),
+            }
+        }
+    }
+
+    if selected.is_empty() {
+        errln!(ctx, "df: no file systems processed");
+        return;
+    }
+    let table = build_table(&selected, &opts, sandbox.as_deref());
+    if ctx.show_table(&table) {
+        return;
+    }
+    for line in table.to_lines() {
+        outln!(ctx, "{}", line);
+    }
+}
+
+/// 表示する表を作る。`sandbox` があればマウントポイントはその中のパス（ルートは `/`）で表示する
+fn build_table(selected: &[(&Mount, Usage)], opts: &Options, sandbox: Option<&Path>) -> Table {
+    let size_title = match (opts.human, opts.block_size) {
+        (true, _) => "Size",
+        (false, 1024) => "1K-blocks",
+        _ => "1M-blocks",
+    };
+    let mut titles = vec![("Filesystem", false)];
+    if opts.print_type {
+        titles.push(("Type", false));
+    }
+    if opts.inodes {
+        titles.extend([("Inodes", true), ("IUsed", true), ("IFree", true), ("IUse%", true)]);
+    } else {
+        titles.extend([(size_title, true), ("Used", true), (if opts.human { "Avail" } else { "Available" }, true), ("Use%", true)]);
+    }
+    titles.push(("Mounted on", false));
+    let mut table = Table::new(&titles);
+
+    let mut sums = [0u64; 3];
+    for (mount, usage) in selected {
+        let values = if opts.inodes {
+            [usage.inodes, usage.inodes_used, usage.inodes_free]
+        } else {
+            [usage.size, usage.used, usage.available]
+        };
+        for (sum, value) in sums.iter_mut().zip(values) {
+            *sum += value;
+        }
+        let mount_point = match sandbox.and_then(|root| mount.mount_point.strip_prefix(root).ok()) {
+            Some(inner) => quoting::display(Path::new("/").join(inner).as_os_str()),
+            None => quoting::display(mount.mount_point.as_os_str()),
+        };
+        table.push(row(&mount.source, &mount.fs_type, values, &mount_point, opts));
+    }
+    if opts.total {
+        table.push(row("total", "-", sums, "-", opts));
+    }
+    table
+}
+
+/// 1行分のセル。`values` は (全体, 使用, 空き)
+fn row(source: &str, fs_type: &str, values: [u64; 3], mount_point: &str, opts: &Options) -> Vec<Cell> {
+    let [total, used, free] = values;
+    let amount = |value: u64| {
+        let text = if opts.inodes {
+            if opts.human {
+                human_count(value)
+            } else {
+                value.to_string()
+            }
+        } else if opts.human {
+            file_info::human_size(value)
+        } else {
+            value.div_ceil(opts.block_size).to_string()
+        };
+        Cell::number(text, value as f64)
+    };
+    // 使用率は一般ユーザーが使える分（used + free）に対して切り上げる（GNU と同じ）
+    let percent = match used + free {
+        0 => Cell::number("-", -1.0),
+        base => {
+            let percent = (used * 100).div_ceil(base);
+            Cell::number(format!("{}%", percent), percent as f64)
+        }
+    };
+
+    let mut cells = vec![Cell::text(source)];
+    if opts.print_type {
+        cells.push(Cell::text(fs_type));
+    }
+    cells.extend([amount(total), amount(used), amount(free), percent, Cell::text(mount_point)]);
+    cells
+}
+
+/// inode 数の -h 表示（1000 単位）
+fn human_count(value: u64) -> String {
+    const UNITS: [&str; 5] = ["", "k", "M", "G", "T"];
+    let mut value = value as f64;
+    let mut unit = 0;
+    while value >= 1000.0 && unit < UNITS.len() - 1 {
+        value /= 1000.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{}", value as u64)
+    } else if value < 10.0 {
+        format!("{:.1}{}", (value * 10.0).ceil() / 10.0, UNITS[unit])
+    } else {
+        format!("{}{}", value.ceil() as u64, UNITS[unit])
+    }
+}
+
+/// -t / -x / -l の指定に合う種類か
+fn type_matches(fs_type: &str, opts: &Options) -> bool {
+    if !opts.only_types.is_empty() && !opts.only_types.iter().any(|t| t == fs_type) {
+        return false;
+    }
+    if opts.local && REMOTE_TYPES.contains(&fs_type) {
+        return false;
+    }
+    !opts.exclude_types.iter().any(|t| t == fs_type)
+}
+
+/// マウントの一覧を読む
+fn read_mounts() -> io::Result<Vec<Mount>> {
+    let text = std::fs::read(MOUNTINFO)?;
+    Ok(text.split(|&b| b == b'\n').filter_map(parse_mount).collect())
+}
+
+/// mountinfo の1行を解析する。
+/// 形式: `ID 親ID major:minor ルート マウントポイント オプション [任意の項目...] - 種類 ソース スーパーブロックのオプション`
+fn parse_mount(line: &[u8]) -> Option<Mount> {
+    let fields: Vec<&[u8]> = line.split(|&b| b == b' ').collect();
+    let separator = fields.iter().position(|&field| field == b"-")?;
+    if separator < 6 || fields.len() < separator + 3 {
+        return None;
+    }
+    let text = |field: &[u8]| String::from_utf8_lossy(&unescape(field)).into_owned();
+    Some(Mount {
+        device: text(fields[2]),
+        mount_point: PathBuf::from(OsString::from_vec(unescape(fields[4]))),
+        fs_type: text(fields[separator + 1]),
+        source: text(fields[separator + 2]),
+    })
+}
+
+/// mountinfo の `\040`（空白）のような8進エスケープを戻す
+fn unescape(field: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(field.len());
+    let mut i = 0;
+    while i < field.len() {
+        let digits = field.get(i + 1..i + 4);
+        match digits {
+            Some(digits) if field[i] == b'\\' && digits.iter().all(|b| (b'0'..=b'7').contains(b)) => {
+                out.push(digits.iter().fold(0u8, |value, b| value.wrapping_mul(8) + (b - b'0')));
+                i += 4;
+            }
+            _ => {
+                out.push(field[i]);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+/// `path` を含むマウント（マウントポイントがいちばん長く一致するもの。同じなら後からマウントしたもの）
+fn containing_mount<'a>(mounts: &'a [Mount], path: &Path) -> Option<&'a Mount> {
+    mounts
+        .iter()
+        .filter(|mount| path.starts_with(&mount.mount_point))
+        .max_by_key(|mount| mount.mount_point.as_os_str().as_bytes().len())
+}
+
+/// ファイルシステムの使用量を調べる
+fn statvfs(path: &Path) -> io::Result<Usage> {
+    let c_path = fs_util::c_path(path)?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let unit = stat.f_frsize as u64;
+    let blocks = stat.f_blocks as u64;
+    let free = stat.f_bfree as u64;
+    let inodes = stat.f_files as u64;
+    let inodes_free = stat.f_ffree as u64;
+    Ok(Usage {
+        size: blocks * unit,
+        used: blocks.saturating_sub(free) * unit,
+        available: stat.f_bavail as u64 * unit,
+        inodes,
+        inodes_used: inodes.saturating_sub(inodes_free),
+        inodes_free,
+    })
+}
@@ -0,0 +1,81 @@
+//! dirsコマンド。ディレクトリスタック（`crate::dir_stack`）を表示する
+//!
+//! 対応オプション:
+//! - `-v` 1行に1つ、位置の番号を付けて表示する、`-p` 番号なしで1行に1つ表示する
+//! - `-l` ホームディレクトリを `~` に縮めない
+//! - `-c` スタックを空にする
+//! - `+N` `-N` 先頭・末尾から N 番目の項目だけを表示する
+
+use crate::context::Context;
+use crate::dir_stack;
+use crate::{errln, outln};
+use std::ffi::OsString;
+
+/// コマンド名
+pub const NAME: &str = "dirs";
+
+pub fn run(ctx: &mut Context, args: &[OsString]) {
+    let (mut verbose, mut per_line, mut long, mut clear) = (false, false, false, false);
+    let mut index = None;
+
+    for os_arg in args {
+        let arg = os_arg.to_string_lossy();
+        if dir_stack::is_index(&arg) {
+            match ctx.dir_stack.index(&arg) {
+                Some(i) => index = Some(i),
+                None => {
+                    errln!(ctx, "dirs: {}: directory stack index out of range", arg);
+                    return;
+                }
+            }
+            continue;
+        }
+        if !arg.starts_with('-') || arg == "-" {
+            errln!(ctx, "dirs: {}: invalid argument", arg);
+            errln!(ctx, "Try 'dirs --help' for more information.");
+            return;
+        }
+        for ch in arg[1..].chars() {
+            match ch {
+                'v' => verbose = true,
+                'p' => per_line = true,
+                'l' => long = true,
+                'c' => clear = true,
+                _ => {
+                    errln!(ctx, "dirs: invalid option -- '{}'", ch);
+                    errln!(ctx, "Try 'dirs --help' for more information.");
+                    return;
+                }
+            }
+        }
+    }
+    if clear {
+        ctx.dir_stack.dirs.clear();
+        return;
+    }
+
+    let home = ctx.home();
+    let shown: Vec<String> = ctx
+        .dir_stack
+        .entries(&ctx.cwd)
+        .map(|dir| if long { dir.display().to_string() } else { dir_stack::tilde_path(dir, &home) })
+        .collect();
+    if let Some(i) = index {
+        outln!(ctx, "{}", shown[i]);
+    } else if verbose {
+        for (i, dir) in shown.iter().enumerate() {
+            outln!(ctx, "{:>2}  {}", i, dir);
+        }
+    } else if per_line {
+        for dir in &shown {
+            outln!(ctx, "{}", dir);
+        }
+    } else {
+        outln!(ctx, "{}", shown.join(" "));
+    }
+}
+
+/// pushd・popd の後にスタックを `dirs` と同じ形で表示する
+pub fn print_stack(ctx: &mut Context) {
+    run(ctx, &[]);
+}
@@ -0,0 +1,482 @@
+//! duコマンド。ファイルとディレクトリのディスク使用量を表示する
+//!
+//! 対応オプション:
+//! - `-s` 引数ごとの合計だけを表示する（`--summarize`）
+//! - `-h` `1.5K` `23M` のように表示する（`--human-readable`）
+//! - `-d N` 深さ N までのディレクトリを表示する（`--max-depth=N`）
+//! - `-a` ディレクトリだけでなくファイルも表示する（`--all`）
+//! - `-c` 最後に総計を表示する（`--total`）
+//! - `--apparent-size` 使用ブロック数でなくファイルの大きさを数える（`-b` はバイト単位で表示）
+//! - `-k` `-m` 1K・1M 単位で表示する（既定は 1K）
+//! - `-l` ハードリンクを重複して数える（`--count-links`。既定は同じ inode を1度だけ数える）
+//! - `-x` 引数と異なるファイルシステム上のディレクトリには入らない（`--one-file-system`）
+//! - `-L` シンボリックリンクをたどる（`--dereference`）
+//!
+//! ディレクトリは複数のスレッドで並行してたどる。表示順はスレッド数に関係なく名前順（中身が先）。
+//! GUI版では結果を並べ替えられる表で表示する。
+
+use super::error_message;
+use crate::context::Context;
+use crate::file_info;
+use crate::quoting;
+use crate::table::{Cell, Table};
+use crate::vfs::{Fs, Stat};
+use crate::{errln, outln};
+use std::collections::{HashSet, VecDeque};
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// コマンド名
+pub const NAME: &str = "du";
+
+/// 並行してディレクトリを読むスレッドの最大数
+const MAX_WORKERS: usize = 8;
+
+/// 解析済みのオプション
+struct Options {
+    all: bool,
+    human: bool,
+    total: bool,
+    apparent: bool,
+    /// 表示の単位（バイト）
+    block_size: u64,
+    max_depth: Option<usize>,
+    count_links: bool,
+    one_file_system: bool,
+    dereference: bool,
+}
+
+pub fn run(ctx: &mut Context, args: &[OsString]) {
+    let mut opts = Options {
+        all: false,
+        human: false,
+        total: false,
+        apparent: false,
+        block_size: 1024,
+        max_depth: None,
+        count_links: false,
+        one_file_system: false,
+        dereference: false,
+    };
+    let mut summarize = false;
+    let mut files: Vec<&OsStr> = Vec::new();
+    let mut only_operands = false;
+    let mut args = args.iter();
+
+    while let Some(os_arg) = args.next() {
+        let arg = match os_arg.to_str() {
+            Some(arg) if !only_operands && arg.starts_with('-') && arg != "-" => arg,
+            _ => {
+                files.push(os_arg);
+                continue;
+            }
+        };
+        // -d の引数
+        let mut depth: Option<&OsStr> = None;
+        match arg {
+            "--" => only_operands = true,
+            "--all" => opts.all = true,
+            "--summarize" => summarize = true,
+            "--human-readable" => opts.human = true,
+            "--total" => opts.total = true,
+            "--apparent-size" => opts.apparent = true,
+            "--bytes" => {
+                opts.apparent = true;
+                opts.block_size = 1;
+            }
+            "--count-links" => opts.count_links = true,
+            "--one-file-system" => opts.one_file_system = true,
+            "--dereference" => opts.dereference = true,
+            "--max-depth" => match args.next() {
+                Some(value) => depth = Some(value),
+                None => {
+                    errln!(ctx, "du: option '--max-depth' requires an argument");
+                    return;
+                }
+            },
+            _ if arg.starts_with("--max-depth=") => depth = Some(OsStr::new(&arg["--max-depth=".len()..])),
+            _ if arg.starts_with("--") => {
+                errln!(ctx, "du: unrecognized option '{}'", arg);
+                errln!(ctx, "Try 'du --help' for more information.");
+                return;
+            }
+            _ => {
+                for (i, ch) in arg[1..].char_indices() {
+                    match ch {
+                        'a' => opts.all = true,
+                        's' => summarize = true,
+                        'h' => opts.human = true,
+                        'c' => opts.total = true,
+                        'b' => {
+                            opts.apparent = true;
+                            opts.block_size = 1;
+                        }
+                        'k' => opts.block_size = 1024,
+                        'm' => opts.block_size = 1024 * 1024,
+                        'l' => opts.count_links = true,
+                        'x' => opts.one_file_system = true,
+                        'L' => opts.dereference = true,
+                        'd' => {
+                            let rest = &arg[1 + i + 1..];
+                            if !rest.is_empty() {
+                                depth = Some(OsStr::new(rest));
+                            } else if let Some(value) = args.next() {
+                                depth = Some(value);
+                            } else {
+                                errln!(ctx, "du: option requires an argument -- 'd'");
+                                return;
+                            }
+                            break;
+                        }
+                        _ => {
+                            errln!(ctx, "du: invalid option -- '{}'", ch);
+                            errln!(ctx, "Try 'du --help' for more information.");
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+        if let Some(value) = depth {
+            match value.to_str().and_then(|v| v.parse().ok()) {
+                Some(depth) => opts.max_depth = Some(depth),
+                None => {
+                    errln!(ctx, "du: invalid maximum depth '{}'", quoting::display(value));
+                    return;
+                }
+            }
+        }
+    }
+
+    if summarize {
+        if let Some(depth) = opts.max_depth.filter(|&depth| depth > 0) {
+            errln!(ctx, "du: summarizing conflicts with --max-depth={}", depth);
+            return;
+        }
+        if opts.all {
+            errln!(ctx, "du: cannot both summarize and show all entries");
+            return;
+        }
+        opts.max_depth = Some(0);
+    }
+    if files.is_empty() {
+        files.push(OsStr::new("."));
+    }
+
+    // ハードリンクの重複は引数をまたいで1度だけ数える
+    let seen = Mutex::new(HashSet::new());
+    let mut table = Table::new(&[("Size", true), ("Path", false)]);
+    let mut grand_total = 0;
+    for file in files {
+        if ctx.interrupted() {
+            break;
+        }
+        let Some(tree) = walk(ctx, file, &opts, &seen) else {
+            continue;
+        };
+        for message in &tree.errors {
+            errln!(ctx, "du: {}", message);
+        }
+        grand_total += tree.totals[0];
+        tree.report(0, &opts, &mut table);
+    }
+    if opts.total {
+        table.push(vec![size_cell(grand_total, &opts), Cell::text("total")]);
+    }
+
+    if ctx.show_table(&table) {
+        return;
+    }
+    for row in &table.rows {
+        outln!(ctx, "{}\t{}", row[0].text, row[1].text);
+    }
+}
+
+/// 1つのファイルかディレクトリ
+struct Node {
+    /// 表示するパス
+    path: PathBuf,
+    parent: Option<usize>,
+    /// 自分自身の使用量（ディレクトリなら直下のファイルの分を含む。-a では含まない）
+    size: u64,
+    depth: usize,
+    is_dir: bool,
+    /// 実体の (デバイス, inode)。ディレクトリの循環を見つけるのに使う
+    id: (u64, u64),
+    children: Vec<usize>,
+}
+
+/// 読んだディレクトリの中身1つ
+struct Child {
+    name: OsString,
+    size: u64,
+    /// 中に入るディレクトリか
+    is_dir: bool,
+    id: (u64, u64),
+}
+
+/// たどった結果
+struct Tree {
+    nodes: Vec<Node>,
+    /// 各ノードの中身を含めた合計
+    totals: Vec<u64>,
+    errors: Vec<String>,
+}
+
+/// 並行して読むための共有状態
+struct Shared {
+    nodes: Vec<Node>,
+    /// 読むべきディレクトリ（ノードの番号）
+    queue: VecDeque<usize>,
+    /// 読み終わっていないディレクトリの数
+    pending: usize,
+    errors: Vec<String>,
+}
+
+/// 1つの引数をたどる。引数自体を調べられなければエラーを表示して None
+fn walk(ctx: &mut Context, file: &OsStr, opts: &Options, seen: &Mutex<HashSet<(u64, u64)>>) -> Option<Tree> {
+    let path = ctx.resolve(file);
+    // 引数に指定したシンボリックリンクの先がディレクトリならたどる（GNU の -D と同じ）
+    let result = match ctx.fs.symlink_metadata(&path) {
+        Ok(meta) if meta.is_symlink() && ctx.fs.is_dir(&path) => ctx.fs.metadata(&path),
+        other => other,
+    };
+    let meta = match result {
+        Ok(meta) => meta,
+        Err(e) => {
+            errln!(ctx, "du: cannot access '{}': {}", quoting::display(file), error_message(&e));
+            return None;
+        }
+    };
+
+    let root = Node {
+        path: PathBuf::from(file),
+        parent: None,
+        size: usage(&meta, opts, seen).unwrap_or(0),
+        depth: 0,
+        is_dir: meta.is_dir(),
+        id: (meta.dev(), meta.ino()),
+        children: Vec::new(),
+    };
+    let is_dir = root.is_dir;
+    let state = Mutex::new(Shared {
+        nodes: vec![root],
+        queue: if is_dir { VecDeque::from([0]) } else { VecDeque::new() },
+        pending: is_dir as usize,
+        errors: Vec::new(),
+    });
+    let ready = Condvar::new();
+    let stop = AtomicBool::new(false);
+    let finished = AtomicBool::new(false);
+
+    if is_dir {
+        let fs = ctx.fs.clone();
+        let walker = Walker {
+            fs: fs.as_ref(),
+            base: &path,
+            root_dev: meta.dev(),
+            opts,
+            seen,
+            state: &state,
+            ready: &ready,
+            stop: &stop,
+        };
+        let workers = thread::available_parallelism().map_or(1, |n| n.get()).min(MAX_WORKERS);
+        thread::scope(|scope| {
+            let handles: Vec<_> = (0..workers).map(|_| scope.spawn(|| walker.work())).collect();
+            // 中断の要求は実行環境からしか確かめられないので、終わるまでここで見張る
+            scope.spawn(|| {
+                for handle in handles {
+                    let _ = handle.join();
+                }
+                finished.store(true, Ordering::SeqCst);
+            });
+            while !finished.load(Ordering::SeqCst) {
+                if ctx.interrupted() {
+                    stop.store(true, Ordering::SeqCst);
+                    ready.notify_all();
+                }
+                thread::sleep(Duration::from_millis(10));
+            }
+        });
+    }
+
+    let Shared { nodes, errors, .. } = state.into_inner().unwrap_or_else(|e| e.into_inner());
+    // 子は親より後に作られるので、後ろから足し込めば中身を含めた合計になる
+    let mut totals: Vec<u64> = nodes.iter().map(|node| node.size).collect();
+    for i in (1..nodes.len()).rev() {
+        if let Some(parent) = nodes[i].parent {
+            totals[parent] += totals[i];
+        }
+    }
+    Some(Tree { nodes, totals, errors })
+}
+
+/// ディレクトリを並行して読む作業者
+struct Walker<'a> {
+    fs: &'a dyn Fs,
+    /// 引数の実際のパス（ノードのパスは表示用なので、これに付け足して読む）
+    base: &'a Path,
+    root_dev: u64,
+    opts: &'a Options,
+    seen: &'a Mutex<HashSet<(u64, u64)>>,
+    state: &'a Mutex<Shared>,
+    ready: &'a Condvar,
+    stop: &'a AtomicBool,
+}
+
+impl Walker<'_> {
+    /// キューが空になり、すべてのディレクトリを読み終えるまでディレクトリを読む
+    fn work(&self) {
+        loop {
+            let (index, path, depth, ancestors) = {
+                let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+                loop {
+                    if state.pending == 0 || self.stop.load(Ordering::SeqCst) {
+                        return;
+                    }
+                    if let Some(index) = state.queue.pop_front() {
+                        // 自分と先祖のディレクトリ（これと同じものが中にあれば循環している）
+                        let mut ancestors = Vec::new();
+                        let mut current = Some(index);
+                        while let Some(i) = current {
+                            ancestors.push(state.nodes[i].id);
+                            current = state.nodes[i].parent;
+                        }
+                        let node = &state.nodes[index];
+                        break (index, node.path.clone(), node.depth, ancestors);
+                    }
+                    state = self.ready.wait(state).unwrap_or_else(|e| e.into_inner());
+                }
+            };
+
+            let (entries, errors) = self.read(&path, depth, &ancestors);
+
+            let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+            state.errors.extend(errors);
+            for Child { name, size, is_dir, id } in entries {
+                let child = Node {
+                    path: path.join(&name),
+                    parent: Some(index),
+                    size,
+                    depth: depth + 1,
+                    is_dir,
+                    id,
+                    children: Vec::new(),
+                };
+                // -a でなければファイルはノードにせず、親の使用量に含める
+                if !is_dir && !self.opts.all {
+                    state.nodes[index].size += size;
+                    continue;
+                }
+                let child_index = state.nodes.len();
+                state.nodes.push(child);
+                state.nodes[index].children.push(child_index);
+                if is_dir {
+                    state.queue.push_back(child_index);
+                    state.pending += 1;
+                }
+            }
+            state.pending -= 1;
+            drop(state);
+            self.ready.notify_all();
+        }
+    }
+
+    /// ディレクトリ1つを読み、中身とエラーを返す。
+    /// `ancestors` と同じディレクトリ（-L のシンボリックリンクなどによる循環）には入らず、数えもしない
+    fn read(&self, shown: &Path, depth: usize, ancestors: &[(u64, u64)]) -> (Vec<Child>, Vec<String>) {
+        let real = self.real_path(shown, depth);
+        let mut errors = Vec::new();
+        let names = match self.fs.read_dir(&real) {
+            Ok(names) => names,
+            Err(e) => {
+                errors.push(format!("cannot read directory '{}': {}", quoting::display(shown.as_os_str()), error_message(&e)));
+                return (Vec::new(), errors);
+            }
+        };
+        let mut entries = Vec::with_capacity(names.len());
+        for name in names {
+            let child = real.join(&name);
+            let result = if self.opts.dereference { self.fs.metadata(&child) } else { self.fs.symlink_metadata(&child) };
+            match result {
+                Ok(meta) => {
+                    // -x なら別のファイルシステムのディレクトリには入らず、数えもしない
+                    if self.opts.one_file_system && meta.is_dir() && meta.dev() != self.root_dev {
+                        continue;
+                    }
+                    let id = (meta.dev(), meta.ino());
+                    if meta.is_dir() && ancestors.contains(&id) {
+                        let shown = shown.join(&name);
+                        errors.push(format!(
+                            "WARNING: Circular directory structure.\n\
+                             This almost certainly means that you have a corrupted file system.\n\
+                             NOTIFY YOUR SYSTEM MANAGER.\n\
+                             The following directory is part of the cycle:\n  '{}'",
+                            quoting::display(shown.as_os_str())
+                        ));
+                        continue;
+                    }
+                    // 2度目に出会ったハードリンクは数えも表示もしない
+                    if let Some(size) = usage(&meta, self.opts, self.seen) {
+                        entries.push(Child { name, size, is_dir: meta.is_dir(), id });
+                    }
+                }
+                Err(e) => {
+                    let shown = shown.join(&name);
+                    errors.push(format!("cannot access '{}': {}", quoting::display(shown.as_os_str()), error_message(&e)));
+                }
+            }
+        }
+        (entries, errors)
+    }
+
+    /// 表示用のパスを実際のパスに直す（表示用のパスの先頭 `depth` 個より前が引数にあたる）
+    fn real_path(&self, shown: &Path, depth: usize) -> PathBuf {
+        let components: Vec<_> = shown.components().collect();
+        let start = components.len() - depth;
+        components[start..].iter().fold(self.base.to_path_buf(), |path, part| path.join(part))
+    }
+}
+
+/// ファイル1つの使用量。既に数えたハードリンクなら None
+fn usage(meta: &Stat, opts: &Options, seen: &Mutex<HashSet<(u64, u64)>>) -> Option<u64> {
+    if !opts.count_links && !meta.is_dir() && meta.nlink() > 1 {
+        let mut seen = seen.lock().unwrap_or_else(|e| e.into_inner());
+        if !seen.insert((meta.dev(), meta.ino())) {
+            return None;
+        }
+    }
+    Some(if opts.apparent { meta.len() } else { meta.blocks() * 512 })
+}
+
+impl Tree {
+    /// 表示するノードを中身が先になる順で表に加える
+    fn report(&self, index: usize, opts: &Options, table: &mut Table) {
+        let node = &self.nodes[index];
+        let mut children = node.children.clone();
+        children.sort_by(|&a, &b| self.nodes[a].path.cmp(&self.nodes[b].path));
+        for child in children {
+            self.report(child, opts, table);
+        }
+        let shown = opts.max_depth.is_none_or(|max| node.depth <= max);
+        if shown && (node.is_dir || opts.all || index == 0) {
+            let path = quoting::display(node.path.as_os_str());
+            table.push(vec![size_cell(self.totals[index], opts), Cell::text(path)]);
+        }
+    }
+}
+
+/// 使用量のセル。-h なら `1.5K`、それ以外は単位（既定 1K）で切り上げた数
+fn size_cell(bytes: u64, opts: &Options) -> Cell {
+    let text = if opts.human {
+        file_info::human_size(bytes)
+    } else {
+        bytes.div_ceil(opts.block_size).to_string()
+    };
+    Cell::number(text, bytes as f64)
+}
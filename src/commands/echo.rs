@@ -0,0 +1,121 @@
+//! echoコマンド。引数を空白で区切って1行に表示する
+//!
+//! 対応オプション:
+//! - `-n` 最後の改行を出力しない
+//! - `-e` バックスラッシュによるエスケープを解釈する（`-E` で解釈しない。既定）
+//!
+//! エスケープは `\\` `\a` `\b` `\c`（以降を何も出力しない） `\e` `\f` `\n` `\r` `\t` `\v`
+//! `\0NNN`（8進3桁まで） `\xHH`（16進2桁まで）。
+//! オプションとみなすのは `-neE` の文字だけからなる引数で、それ以外（`--` も）はそのまま表示する。
+
+use crate::context::Context;
+use std::ffi::OsString;
+use std::io::Write;
+use std::os::unix::ffi::OsStrExt;
+
+/// コマンド名
+pub const NAME: &str = "echo";
+
+pub fn run(ctx: &mut Context, args: &[OsString]) {
+    let mut newline = true;
+    let mut escapes = false;
+    let mut operands = args;
+    while let Some((first, rest)) = operands.split_first() {
+        let flags = first.as_bytes();
+        if flags.len() < 2 || flags[0] != b'-' || !flags[1..].iter().all(|b| b"neE".contains(b)) {
+            break;
+        }
+        for flag in &flags[1..] {
+            match flag {
+                b'n' => newline = false,
+                b'e' => escapes = true,
+                _ => escapes = false,
+            }
+        }
+        operands = rest;
+    }
+
+    let mut out = Vec::new();
+    for (i, arg) in operands.iter().enumerate() {
+        if i > 0 {
+            out.push(b' ');
+        }
+        if escapes {
+            if !unescape(arg.as_bytes(), &mut out) {
+                newline = false;
+                break;
+            }
+        } else {
+            out.extend_from_slice(arg.as_bytes());
+        }
+    }
+    if newline {
+        out.push(b'\n');
+    }
+    let _ = ctx.stdout.write_all(&out);
+    let _ = ctx.stdout.flush();
+}
+
+/// `echo -e` のエスケープを解釈して `out` に追加する（printf の `%b` でも使う）。
+/// `\c` に出会ったらそこで止めて false を返す
+pub(crate) fn unescape(arg: &[u8], out: &mut Vec<u8>) -> bool {
+    let mut i = 0;
+    while i < arg.len() {
+        if arg[i] != b'\\' || i + 1 == arg.len() {
+            out.push(arg[i]);
+            i += 1;
+            continue;
+        }
+        let c = arg[i + 1];
+        i += 2;
+        let b = match c {
+            b'\\' => b'\\',
+            b'a' => 0x07,
+            b'b' => 0x08,
+            b'c' => return false,
+            b'e' => 0x1b,
+            b'f' => 0x0c,
+            b'n' => b'\n',
+            b'r' => b'\r',
+            b't' => b'\t',
+            b'v' => 0x0b,
+            b'0' => {
+                let (value, len) = parse_digits(&arg[i..], 8, 3);
+                i += len;
+                value as u8
+            }
+            b'x' => {
+                let (value, len) = parse_digits(&arg[i..], 16, 2);
+                if len == 0 {
+                    out.extend_from_slice(b"\\x");
+                    continue;
+                }
+                i += len;
+                value as u8
+            }
+            // 未知のエスケープはそのまま残す
+            _ => {
+                out.push(b'\\');
+                c
+            }
+        };
+        out.push(b);
+    }
+    true
+}
+
+/// 先頭から最大 `max` 桁の数字を読み、(値, 桁数) を返す
+pub(crate) fn parse_digits(bytes: &[u8], radix: u32, max: usize) -> (u32, usize) {
+    let mut value = 0u32;
+    let mut len = 0;
+    for &b in bytes.iter().take(max) {
+        match (b as char).to_digit(radix) {
+            Some(d) => {
+                value = value * radix + d;
+                len += 1;
+            }
+            None => break,
+        }
+    }
+    (value, len)
+}
@@ -0,0 +1,609 @@
+//! findコマンド。ディレクトリをたどり、条件に合うファイルを表示・削除・処理する
+//!
+//! `find [-L|-P] [path...] [expression]` の形で使う。path を省略すると `.` を探す。
+//!
+//! 式の要素:
+//! - 条件: `-name GLOB` / `-iname GLOB` / `-path GLOB` / `-ipath GLOB`（`crate::glob` で照合）、
+//!   `-type [fdlpsbc]`（`,` で複数可）、`-size [+-]N[cwbkMG]`、`-mtime [+-]N` / `-mmin [+-]N`、
+//!   `-newer FILE`、`-empty`、`-true` / `-false`
+//! - 動作: `-print` / `-print0`、`-delete`、`-exec CMD {} ;`（1件ごと）/ `-exec CMD {} +`（まとめて）、
+//!   `-prune`、`-quit`
+//! - 演算子: `( EXPR )`、`! EXPR` / `-not`、`EXPR -a EXPR` / `-and`（省略可）、`EXPR -o EXPR` / `-or`
+//! - オプション: `-maxdepth N` / `-mindepth N`、`-depth`（中身を先に処理する）、`-xdev`（別のファイルシステムに入らない）
+//!
+//! 動作を1つも含まない式は、全体が真のファイルを表示する（`-print` を補う）。
+//! `-exec` で実行できるのはビルトインコマンドだけ。`-delete` は rm と同じく undo で元に戻せる。
+//! `-L` ではシンボリックリンクをたどり、ディレクトリの循環を検出したら報告して中に入らない。
+
+use super::error_message;
+use crate::context::Context;
+use crate::errln;
+use crate::fs_action::FsAction;
+use crate::glob::Glob;
+use crate::journal::Recorder;
+use crate::quoting;
+use crate::vfs::Stat;
+use std::ffi::{OsStr, OsString};
+use std::io::Write;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// コマンド名
+pub const NAME: &str = "find";
+
+/// `-exec ... +` で1回に渡すパスの最大数
+const EXEC_BATCH: usize = 1000;
+
+/// `+N` / `-N` / `N` の比較
+#[derive(Debug, Clone, Copy)]
+enum Compare {
+    Greater(u64),
+    Less(u64),
+    Equal(u64),
+}
+
+impl Compare {
+    fn parse(s: &str) -> Option<(Compare, &str)> {
+        let (make, rest): (fn(u64) -> Compare, &str) = match s.as_bytes().first() {
+            Some(b'+') => (Compare::Greater, &s[1..]),
+            Some(b'-') => (Compare::Less, &s[1..]),
+            _ => (Compare::Equal, s),
+        };
+        let end = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        let n = rest[..end].parse().ok()?;
+        Some((make(n), &rest[end..]))
+    }
+
+    fn matches(self, value: u64) -> bool {
+        match self {
+            Compare::Greater(n) => value > n,
+            Compare::Less(n) => value < n,
+            Compare::Equal(n) => value == n,
+        }
+    }
+}
+
+/// 式の要素
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    True,
+    False,
+    Name(Glob),
+    Path(Glob),
+    /// 種類を表す文字（`f` `d` `l` など）の一覧
+    Type(Vec<char>),
+    /// 比較と単位のバイト数
+    Size(Compare, u64),
+    /// 更新からの経過時間の比較と単位の秒数
+    Modified(Compare, u64),
+    /// 基準のファイルの更新日時 (秒, ナノ秒)
+    Newer(i64, i64),
+    Empty,
+    Print,
+    Print0,
+    Delete,
+    /// `-exec CMD ARGS ;`
+    Exec(Vec<String>),
+    /// `-exec CMD ARGS {} +`。番号はまとめて実行するパスの置き場所
+    ExecBatch(Vec<String>, usize),
+    Prune,
+    Quit,
+}
+
+impl Expr {
+    /// 動作（表示・削除・実行）を含むか
+    fn has_action(&self) -> bool {
+        match self {
+            Expr::And(a, b) | Expr::Or(a, b) => a.has_action() || b.has_action(),
+            Expr::Not(a) => a.has_action(),
+            Expr::Print | Expr::Print0 | Expr::Delete | Expr::Exec(_) | Expr::ExecBatch(..) | Expr::Quit => true,
+            _ => false,
+        }
+    }
+}
+
+/// 式の外で指定するオプション
+struct Options {
+    follow: bool,
+    max_depth: usize,
+    min_depth: usize,
+    depth_first: bool,
+    xdev: bool,
+}
+
+/// 式を解析する
+struct Parser<'a> {
+    args: &'a [OsString],
+    pos: usize,
+    opts: Options,
+    /// `-exec ... +` の数
+    batches: usize,
+    now: i64,
+    ctx: &'a Context,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&str> {
+        self.args.get(self.pos).map(|arg| arg.to_str().unwrap_or(""))
+    }
+
+    fn next_value(&mut self, option: &str) -> Result<&'a OsStr, String> {
+        let value = self.args.get(self.pos).ok_or_else(|| format!("missing argument to '{}'", option))?;
+        self.pos += 1;
+        Ok(value)
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut left = self.and()?;
+        while matches!(self.peek(), Some("-o") | Some("-or")) {
+            self.pos += 1;
+            let right = self.and()?;
+            left = Expr::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut left = self.unary()?;
+        loop {
+            match self.peek() {
+                None | Some("-o") | Some("-or") | Some(")") => return Ok(left),
+                Some("-a") | Some("-and") => self.pos += 1,
+                Some(_) => {}
+            }
+            let right = self.unary()?;
+            left = Expr::And(Box::new(left), Box::new(right));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        match self.peek() {
+            Some("!") | Some("-not") => {
+                self.pos += 1;
+                Ok(Expr::Not(Box::new(self.unary()?)))
+            }
+            Some("(") => {
+                self.pos += 1;
+                if self.peek() == Some(")") {
+                    return Err("invalid expression; empty parentheses are not allowed.".to_string());
+                }
+                let inner = self.or()?;
+                if self.peek() != Some(")") {
+                    return Err("invalid expression; I was expecting to find a ')' somewhere but did not see one.".to_string());
+                }
+                self.pos += 1;
+                Ok(inner)
+            }
+            Some(_) => self.primary(),
+            None => Err("invalid expression; expected an expression".to_string()),
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        let arg = self.args[self.pos].to_string_lossy().into_owned();
+        self.pos += 1;
+        let glob = |value: &OsStr, case_insensitive: bool| {
+            let pattern = value.to_string_lossy();
+            if case_insensitive { Glob::new_case_insensitive(&pattern) } else { Glob::new(&pattern) }
+        };
+        let expr = match arg.as_str() {
+            "-true" => Expr::True,
+            "-false" => Expr::False,
+            "-name" | "-iname" => Expr::Name(glob(self.next_value(&arg)?, arg == "-iname")?),
+            "-path" | "-ipath" | "-wholename" | "-iwholename" => {
+                Expr::Path(glob(self.next_value(&arg)?, arg.starts_with("-i"))?)
+            }
+            "-type" => {
+                let value = self.next_value(&arg)?.to_string_lossy().into_owned();
+                let kinds: Vec<char> = value.split(',').filter_map(|kind| kind.chars().next()).collect();
+                let valid = value.split(',').all(|kind| kind.len() == 1 && "fdlpsbc".contains(kind));
+                if kinds.is_empty() || !valid {
+                    return Err(format!("Unknown argument to -type: {}", value));
+                }
+                Expr::Type(kinds)
+            }
+            "-size" => {
+                let value = self.next_value(&arg)?.to_string_lossy().into_owned();
+                let unit = |suffix: &str| -> Option<u64> {
+                    Some(match suffix {
+                        "" | "b" => 512,
+                        "c" => 1,
+                        "w" => 2,
+                        "k" => 1 << 10,
+                        "M" => 1 << 20,
+                        "G" => 1 << 30,
+                        _ => return None,
+                    })
+                };
+                match Compare::parse(&value).and_then(|(compare, suffix)| Some((compare, unit(suffix)?))) {
+                    Some((compare, unit)) => Expr::Size(compare, unit),
+                    None => return Err(format!("invalid -size type '{}'", value)),
+                }
+            }
+            "-mtime" | "-mmin" => {
+                let value = self.next_value(&arg)?.to_string_lossy().into_owned();
+                match Compare::parse(&value) {
+                    Some((compare, "")) => Expr::Modified(compare, if arg == "-mtime" { 86400 } else { 60 }),
+                    _ => return Err(format!("invalid argument '{}' to '{}'", value, arg)),
+                }
+            }
+            "-newer" => {
+                let value = self.next_value(&arg)?;
+                let meta = self
+                    .ctx
+                    .fs
+                    .metadata(&self.ctx.resolve(value))
+                    .map_err(|e| format!("'{}': {}", quoting::display(value), error_message(&e)))?;
+                Expr::Newer(meta.mtime().secs, meta.mtime().nanos)
+            }
+            "-empty" => Expr::Empty,
+            "-print" => Expr::Print,
+            "-print0" => Expr::Print0,
+            "-delete" => {
+                // 中身を先に削除できるよう、ディレクトリは中身の後に処理する
+                self.opts.depth_first = true;
+                Expr::Delete
+            }
+            "-exec" => {
+                let mut command = Vec::new();
+                loop {
+                    let Some(word) = self.args.get(self.pos) else {
+                        return Err("missing argument to '-exec'".to_string());
+                    };
+                    self.pos += 1;
+                    let word = word.to_string_lossy().into_owned();
+                    if word == ";" {
+                        break;
+                    }
+                    if word == "+" && command.last().is_some_and(|last| last == "{}") {
+                        command.pop();
+                        if command.is_empty() {
+                            return Err("missing argument to '-exec'".to_string());
+                        }
+                        self.batches += 1;
+                        return Ok(Expr::ExecBatch(command, self.batches - 1));
+                    }
+                    command.push(word);
+                }
+                if command.is_empty() {
+                    return Err("missing argument to '-exec'".to_string());
+                }
+                Expr::Exec(command)
+            }
+            "-prune" => Expr::Prune,
+            "-quit" => Expr::Quit,
+            "-maxdepth" | "-mindepth" => {
+                let value = self.next_value(&arg)?.to_string_lossy().into_owned();
+                let depth = value.parse().map_err(|_| format!("Expected a positive decimal integer argument to {}, but got '{}'", arg, value))?;
+                if arg == "-maxdepth" {
+                    self.opts.max_depth = depth;
+                } else {
+                    self.opts.min_depth = depth;
+                }
+                Expr::True
+            }
+            "-depth" | "-d" => {
+                self.opts.depth_first = true;
+                Expr::True
+            }
+            "-xdev" | "-mount" => {
+                self.opts.xdev = true;
+                Expr::True
+            }
+            _ if arg.starts_with('-') => return Err(format!("unknown predicate '{}'", arg)),
+            _ => return Err(format!("paths must precede expression: '{}'", arg)),
+        };
+        Ok(expr)
+    }
+}
+
+pub fn run(ctx: &mut Context, args: &[OsString]) {
+    let mut follow = false;
+    let mut start = 0;
+    while let Some(arg) = args.get(start) {
+        match arg.to_str() {
+            Some("-L") => follow = true,
+            Some("-P") => follow = false,
+            Some("--") => {
+                start += 1;
+                break;
+            }
+            _ => break,
+        }
+        start += 1;
+    }
+    // 式の前にあるものが探す場所
+    let mut paths: Vec<&OsStr> = Vec::new();
+    let mut end = start;
+    while let Some(arg) = args.get(end) {
+        let s = arg.to_str().unwrap_or("");
+        if s.len() > 1 && s.starts_with('-') || s == "(" || s == "!" {
+            break;
+        }
+        paths.push(arg);
+        end += 1;
+    }
+    if paths.is_empty() {
+        paths.push(OsStr::new("."));
+    }
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+    let opts = Options { follow, max_depth: usize::MAX, min_depth: 0, depth_first: false, xdev: false };
+    let mut parser = Parser { args: &args[end..], pos: 0, opts, batches: 0, now, ctx };
+    let parsed = if parser.args.is_empty() { Ok(Expr::True) } else { parser.or() };
+    let mut expr = match parsed {
+        Ok(_) if parser.pos < parser.args.len() => {
+            errln!(ctx, "find: invalid expression; you have too many ')'");
+            return;
+        }
+        Ok(expr) => expr,
+        Err(message) => {
+            errln!(ctx, "find: {}", message);
+            return;
+        }
+    };
+    if !expr.has_action() {
+        expr = Expr::And(Box::new(expr), Box::new(Expr::Print));
+    }
+    let Parser { opts, batches, now, .. } = parser;
+
+    let mut walker = Walker {
+        recorder: ctx.recorder(NAME, args),
+        opts,
+        now,
+        batches: vec![Vec::new(); batches],
+        ancestors: Vec::new(),
+        device: 0,
+        prune: false,
+        quit: false,
+    };
+    for path in paths {
+        if walker.quit || ctx.interrupted() {
+            break;
+        }
+        let resolved = ctx.resolve(path);
+        walker.ancestors.clear();
+        // -name などは探す場所の最後の要素と照合する（`.` や `/` はそのもの）
+        let name = Path::new(path).file_name().unwrap_or(path);
+        walker.visit(ctx, &expr, &resolved, Path::new(path), name, 0);
+    }
+    walker.flush_batches(ctx, &expr);
+    if let Some(recorder) = walker.recorder {
+        if let Err(e) = recorder.commit() {
+            errln!(ctx, "find: cannot record for undo: {}", error_message(&e));
+        }
+    }
+    let _ = ctx.stdout.flush();
+}
+
+/// 調べている1つのファイル
+struct Entry<'a> {
+    path: &'a Path,
+    /// 出力・-path・-exec に使うパス（探す場所として指定したパスから始まる）。
+    /// UTF-8 でない名前もそのまま持ち、エラーの表示にだけ `quoting::display` を使う
+    shown: &'a Path,
+    name: &'a OsStr,
+    meta: &'a Stat,
+}
+
+/// ディレクトリをたどりながら式を評価する
+struct Walker {
+    opts: Options,
+    now: i64,
+    recorder: Option<Recorder>,
+    /// `-exec ... +` ごとにまとめているパス
+    batches: Vec<Vec<OsString>>,
+    /// たどっている途中のディレクトリの (デバイス, inode)（循環の検出に使う）
+    ancestors: Vec<(u64, u64, String)>,
+    /// 探し始めた場所のデバイス（-xdev 用）
+    device: u64,
+    /// `-prune` でこのディレクトリの中に入らない
+    prune: bool,
+    /// `-quit` で終える
+    quit: bool,
+}
+
+impl Walker {
+    fn visit(&mut self, ctx: &mut Context, expr: &Expr, path: &Path, shown: &Path, name: &OsStr, depth: usize) {
+        if self.quit || ctx.interrupted() {
+            return;
+        }
+        // -L ではリンク先を調べる（リンク切れはリンク自体）
+        let meta = match ctx.fs.symlink_metadata(path) {
+            Ok(meta) if self.opts.follow && meta.is_symlink() => ctx.fs.metadata(path).unwrap_or(meta),
+            Ok(meta) => meta,
+            Err(e) => {
+                errln!(ctx, "find: '{}': {}", quoting::display(shown.as_os_str()), error_message(&e));
+                return;
+            }
+        };
+        if depth == 0 {
+            self.device = meta.dev();
+        }
+        let id = (meta.dev(), meta.ino());
+        if meta.is_dir() {
+            if let Some((_, _, first)) = self.ancestors.iter().find(|(dev, ino, _)| (*dev, *ino) == id) {
+                let shown = quoting::display(shown.as_os_str());
+                errln!(ctx, "find: File system loop detected; '{}' is part of the same file system loop as '{}'.", shown, first);
+                return;
+            }
+        }
+        let visible = depth >= self.opts.min_depth;
+        let entry = Entry { path, shown, name, meta: &meta };
+        self.prune = false;
+        if visible && !self.opts.depth_first {
+            self.eval(ctx, expr, &entry);
+        }
+        let descend = meta.is_dir()
+            && depth < self.opts.max_depth
+            && (self.opts.depth_first || !self.prune)
+            && !(self.opts.xdev && meta.dev() != self.device);
+        if descend && !self.quit {
+            match ctx.fs.read_dir(path) {
+                Ok(mut names) => {
+                    names.sort();
+                    self.ancestors.push((id.0, id.1, quoting::display(shown.as_os_str())));
+                    for child in names {
+                        self.visit(ctx, expr, &path.join(&child), &shown.join(&child), &child, depth + 1);
+                    }
+                    self.ancestors.pop();
+                }
+                Err(e) => errln!(ctx, "find: '{}': {}", quoting::display(shown.as_os_str()), error_message(&e)),
+            }
+        }
+        if visible && self.opts.depth_first && !self.quit {
+            self.eval(ctx, expr, &entry);
+        }
+    }
+
+    /// 式を評価する（動作はここで実行する）
+    fn eval(&mut self, ctx: &mut Context, expr: &Expr, entry: &Entry) -> bool {
+        let meta = entry.meta;
+        match expr {
+            Expr::And(a, b) => self.eval(ctx, a, entry) && self.eval(ctx, b, entry),
+            Expr::Or(a, b) => self.eval(ctx, a, entry) || self.eval(ctx, b, entry),
+            Expr::Not(a) => !self.eval(ctx, a, entry),
+            Expr::True => true,
+            Expr::False => false,
+            Expr::Name(glob) => glob.matches(entry.name),
+            Expr::Path(glob) => glob.matches(entry.shown.as_os_str()),
+            Expr::Type(kinds) => {
+                let kind = meta.file_type();
+                kinds.iter().any(|&k| match k {
+                    'f' => kind.is_file(),
+                    'd' => kind.is_dir(),
+                    'l' => kind.is_symlink(),
+                    'p' => kind.is_fifo(),
+                    's' => kind.is_socket(),
+                    'b' => kind.is_block_device(),
+                    'c' => kind.is_char_device(),
+                    _ => false,
+                })
+            }
+            Expr::Size(compare, unit) => compare.matches(meta.len().div_ceil(*unit)),
+            Expr::Modified(compare, unit) => {
+                let age = (self.now - meta.mtime().secs).max(0) as u64;
+                compare.matches(age / unit)
+            }
+            Expr::Newer(secs, nanos) => (meta.mtime().secs, meta.mtime().nanos) > (*secs, *nanos),
+            Expr::Empty => {
+                if meta.is_dir() {
+                    ctx.fs.read_dir(entry.path).is_ok_and(|names| names.is_empty())
+                } else {
+                    meta.is_file() && meta.is_empty()
+                }
+            }
+            Expr::Print => {
+                let _ = ctx.stdout.write_all(entry.shown.as_os_str().as_bytes());
+                let _ = ctx.stdout.write_all(b"\n");
+                true
+            }
+            Expr::Print0 => {
+                let _ = ctx.stdout.write_all(entry.shown.as_os_str().as_bytes());
+                let _ = ctx.stdout.write_all(b"\0");
+                true
+            }
+            Expr::Delete => self.delete(ctx, entry),
+            Expr::Exec(command) => {
+                let args: Vec<OsString> = command.iter().map(|word| substitute(word, entry.shown.as_os_str())).collect();
+                exec(ctx, &args, false)
+            }
+            Expr::ExecBatch(command, id) => {
+                self.batches[*id].push(entry.shown.as_os_str().to_os_string());
+                if self.batches[*id].len() >= EXEC_BATCH {
+                    let paths = std::mem::take(&mut self.batches[*id]);
+                    exec(ctx, &batch_args(command, paths), true);
+                }
+                true
+            }
+            Expr::Prune => {
+                self.prune = true;
+                true
+            }
+            Expr::Quit => {
+                self.quit = true;
+                true
+            }
+        }
+    }
+
+    /// `-delete`。探し始めた場所の `.` は削除しない
+    fn delete(&mut self, ctx: &mut Context, entry: &Entry) -> bool {
+        if entry.name == "." {
+            return true;
+        }
+        let result = if entry.meta.is_dir() {
+            ctx.perform(FsAction::RemoveDir(entry.path)).map(|()| {
+                if let Some(recorder) = self.recorder.as_mut() {
+                    recorder.removed_dir(entry.path, entry.meta.mode() & 0o7777);
+                }
+            })
+        } else {
+            // rm と同じく、undo が有効なら退避場所へ移し、移せなければ退避せずに削除する
+            match self.recorder.as_mut() {
+                Some(recorder) => match recorder.remove(entry.path) {
+                    Ok(()) => Ok(()),
+                    Err(_) => ctx.perform(FsAction::RemoveFile(entry.path)).map(|()| recorder.lost(entry.path)),
+                },
+                None => ctx.perform(FsAction::RemoveFile(entry.path)),
+            }
+        };
+        match result {
+            Ok(()) => true,
+            Err(e) => {
+                errln!(ctx, "find: cannot delete '{}': {}", quoting::display(entry.shown.as_os_str()), error_message(&e));
+                false
+            }
+        }
+    }
+
+    /// `-exec ... +` でまとめていたパスを渡して実行する
+    fn flush_batches(&mut self, ctx: &mut Context, expr: &Expr) {
+        match expr {
+            Expr::And(a, b) | Expr::Or(a, b) => {
+                self.flush_batches(ctx, a);
+                self.flush_batches(ctx, b);
+            }
+            Expr::Not(a) => self.flush_batches(ctx, a),
+            Expr::ExecBatch(command, id) if !self.batches[*id].is_empty() => {
+                let paths = std::mem::take(&mut self.batches[*id]);
+                exec(ctx, &batch_args(command, paths), true);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// `-exec` の引数の `{}` をパスに置き換える（UTF-8 でないパスもそのまま渡す）
+fn substitute(word: &str, path: &OsStr) -> OsString {
+    let mut out = OsString::new();
+    for (i, part) in word.split("{}").enumerate() {
+        if i > 0 {
+            out.push(path);
+        }
+        out.push(part);
+    }
+    out
+}
+
+/// `-exec ... +` の引数（コマンドの後ろにまとめたパスを並べる）
+fn batch_args(command: &[String], paths: Vec<OsString>) -> Vec<OsString> {
+    command.iter().map(OsString::from).chain(paths).collect()
+}
+
+/// ビルトインコマンドを実行し、終了ステータスが 0 なら true を返す。見つからなければ報告して false。
+/// find 自身のそれまでのエラーは残す。`batch`（`-exec ... +`）なら失敗も find の終了ステータスに反映する（GNU と同じ）
+fn exec(ctx: &mut Context, words: &[OsString], batch: bool) -> bool {
+    let name = words[0].to_string_lossy();
+    let saved = ctx.status;
+    if !super::dispatch(ctx, &name, &words[1..]) {
+        errln!(ctx, "find: '{}': No such file or directory", name);
+        return false;
+    }
+    let succeeded = ctx.status == 0;
+    ctx.status = if saved != 0 || (batch && !succeeded) { 1 } else { 0 };
+    succeeded
+}
@@ -0,0 +1,860 @@
+//! grepコマンド。ファイルの中から正規表現に一致する行を探して表示する
+//!
+//! 対応オプション:
+//! - `-E` 拡張正規表現 / `-F` 固定文字列 / `-G` 基本正規表現（既定）
+//! - `-e PATTERN` パターンを指定する（複数可） / `-f FILE` ファイルからパターンを読む
+//! - `-i` 大文字・小文字を区別しない / `-v` 一致しない行を選ぶ
+//! - `-w` 単語全体に一致 / `-x` 行全体に一致
+//! - `-n` 行番号を付ける / `-c` 一致した行数だけ表示する / `-o` 一致した部分だけ表示する
+//! - `-l` / `-L` 一致する・しないファイルの名前だけ表示する
+//! - `-q` 何も表示しない / `-s` ファイルのエラーを表示しない / `-m NUM` NUM 行で打ち切る
+//! - `-H` / `-h` ファイル名を常に付ける・付けない
+//! - `-r` ディレクトリを再帰的に探す（`-R` はシンボリックリンクもたどる）。
+//!   Git リポジトリの中では .gitignore で除外されたファイルと `.git` を読み飛ばす（`--no-ignore` で無効）
+//! - `--include=GLOB` / `--exclude=GLOB` / `--exclude-dir=GLOB` 再帰するときに調べるファイルを絞る
+//! - `-A NUM` / `-B NUM` / `-C NUM`（`-NUM`） 一致した行の後・前・前後の行も表示する
+//! - `-a` バイナリファイルもテキストとして扱う / `-I` バイナリファイルを読み飛ばす
+//!   （既定では NUL を含むファイルは一致したことだけを表示する）
+//! - `--color[=WHEN]` 一致した部分とファイル名・行番号を色付けする（既定は `auto`）
+//!
+//! ファイルを指定しないか `-` を指定すると標準入力を読む（`-r` ではカレントディレクトリを探す）。
+//! 終了ステータスは一致した行があれば 0、なければ 1、読めないファイルなどがあれば 2。
+
+use super::cat::LineReader;
+use super::error_message;
+use crate::context::Context;
+use crate::gitignore::Ignores;
+use crate::glob::Glob;
+use crate::ls_colors::ColorMode;
+use crate::quoting;
+use crate::{errln, outln};
+use regex::bytes::{Regex, RegexBuilder};
+use std::collections::{HashSet, VecDeque};
+use std::ffi::{OsStr, OsString};
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+/// コマンド名
+pub const NAME: &str = "grep";
+
+/// 色付けの既定値（GNU grep の GREP_COLORS の既定と同じ）
+const MATCH_COLOR: &str = "01;31";
+const FILENAME_COLOR: &str = "35";
+const LINE_NUMBER_COLOR: &str = "32";
+const SEPARATOR_COLOR: &str = "36";
+
+/// パターンの書式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Syntax {
+    Basic,
+    Extended,
+    Fixed,
+}
+
+/// バイナリファイルの扱い（`--binary-files`）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinaryFiles {
+    /// 一致したことだけを表示する
+    Binary,
+    /// テキストとして扱う（`-a`）
+    Text,
+    /// 一致しないものとして読み飛ばす（`-I`）
+    WithoutMatch,
+}
+
+/// 解析済みのオプション
+struct Options {
+    patterns: Vec<String>,
+    syntax: Syntax,
+    ignore_case: bool,
+    invert: bool,
+    word: bool,
+    whole_line: bool,
+    line_number: bool,
+    count: bool,
+    files_with_matches: bool,
+    files_without_match: bool,
+    only_matching: bool,
+    quiet: bool,
+    no_messages: bool,
+    max_count: Option<u64>,
+    recursive: bool,
+    dereference: bool,
+    after: usize,
+    before: usize,
+    with_filename: Option<bool>,
+    color: ColorMode,
+    binary_files: BinaryFiles,
+    includes: Vec<Glob>,
+    excludes: Vec<Glob>,
+    exclude_dirs: Vec<Glob>,
+    gitignore: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            patterns: Vec::new(),
+            syntax: Syntax::Basic,
+            ignore_case: false,
+            invert: false,
+            word: false,
+            whole_line: false,
+            line_number: false,
+            count: false,
+            files_with_matches: false,
+            files_without_match: false,
+            only_matching: false,
+            quiet: false,
+            no_messages: false,
+            max_count: None,
+            recursive: false,
+            dereference: false,
+            after: 0,
+            before: 0,
+            with_filename: None,
+            color: ColorMode::Auto,
+            binary_files: BinaryFiles::Binary,
+            includes: Vec::new(),
+            excludes: Vec::new(),
+            exclude_dirs: Vec::new(),
+            gitignore: true,
+        }
+    }
+}
+
+/// 値を取る長いオプション
+const LONG_WITH_VALUE: &[&str] = &[
+    "regexp", "file", "max-count", "after-context", "before-context", "context",
+    "binary-files", "include", "exclude", "exclude-dir",
+];
+
+pub fn run(ctx: &mut Context, args: &[OsString]) {
+    let mut opts = Options::default();
+    let mut pattern_files: Vec<String> = Vec::new();
+    let mut operands: Vec<&OsStr> = Vec::new();
+    let mut only_operands = false;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let Some(s) = arg.to_str().filter(|s| !only_operands && s.starts_with('-') && s.len() > 1) else {
+            operands.push(arg);
+            continue;
+        };
+        if s == "--" {
+            only_operands = true;
+            continue;
+        }
+        if let Some(long) = s.strip_prefix("--") {
+            let (name, inline) = match long.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (long, None),
+            };
+            let value = if LONG_WITH_VALUE.contains(&name) {
+                match inline.or_else(|| args.next().map(|v| v.to_string_lossy().into_owned())) {
+                    Some(value) => Some(value),
+                    None => {
+                        errln!(ctx, "grep: option '--{}' requires an argument", name);
+                        return;
+                    }
+                }
+            } else {
+                inline
+            };
+            if !apply_long(ctx, &mut opts, &mut pattern_files, name, value) {
+                return;
+            }
+            continue;
+        }
+        // -5 は -C 5 と同じ
+        if s[1..].bytes().all(|b| b.is_ascii_digit()) {
+            if !apply_short(ctx, &mut opts, &mut pattern_files, 'C', Some(s[1..].to_string())) {
+                return;
+            }
+            continue;
+        }
+        for (i, ch) in s[1..].char_indices() {
+            if matches!(ch, 'e' | 'f' | 'm' | 'A' | 'B' | 'C') {
+                let rest = &s[i + 2..];
+                let value = if rest.is_empty() {
+                    match args.next() {
+                        Some(value) => value.to_string_lossy().into_owned(),
+                        None => {
+                            errln!(ctx, "grep: option requires an argument -- '{}'", ch);
+                            return;
+                        }
+                    }
+                } else {
+                    rest.to_string()
+                };
+                if !apply_short(ctx, &mut opts, &mut pattern_files, ch, Some(value)) {
+                    return;
+                }
+                break;
+            }
+            if !apply_short(ctx, &mut opts, &mut pattern_files, ch, None) {
+                return;
+            }
+        }
+    }
+
+    for file in &pattern_files {
+        let mut text = String::new();
+        let result = if file == "-" {
+            ctx.stdin.read_to_string(&mut text)
+        } else {
+            ctx.fs.open(&ctx.resolve(file)).and_then(|mut reader| reader.read_to_string(&mut text))
+        };
+        if let Err(e) = result {
+            errln!(ctx, "grep: {}: {}", file, error_message(&e));
+            return;
+        }
+        opts.patterns.extend(text.lines().map(str::to_string));
+    }
+    if opts.patterns.is_empty() && pattern_files.is_empty() {
+        if operands.is_empty() {
+            errln!(ctx, "Usage: grep [OPTION]... PATTERNS [FILE]...");
+            return;
+        }
+        let pattern = operands.remove(0).to_string_lossy().into_owned();
+        opts.patterns.extend(pattern.split('\n').map(str::to_string));
+    }
+
+    let matcher = match Matcher::new(&opts) {
+        Ok(matcher) => matcher,
+        Err(message) => {
+            errln!(ctx, "grep: {}", message);
+            return;
+        }
+    };
+
+    // -r でファイルを指定しなければカレントディレクトリを探す（表示では "./" を付けない）
+    let implicit_dir = operands.is_empty() && opts.recursive;
+    if operands.is_empty() {
+        operands.push(OsStr::new(if opts.recursive { "." } else { "-" }));
+    }
+    let with_filename = opts.with_filename.unwrap_or(operands.len() > 1 || opts.recursive);
+    let mut search = Search {
+        colors: opts.color.enabled(ctx.is_tty),
+        opts,
+        matcher,
+        with_filename,
+        printed_any: false,
+        stop: false,
+        matched: false,
+    };
+
+    for operand in operands {
+        if search.stop || ctx.interrupted() {
+            break;
+        }
+        if operand == "-" {
+            let mut stdin = std::mem::replace(&mut ctx.stdin, Box::new(io::empty()));
+            let result = search.file(ctx, &mut stdin, "(standard input)");
+            ctx.stdin = stdin;
+            search.report(ctx, "(standard input)", result);
+            continue;
+        }
+        let path = ctx.resolve(operand);
+        let shown = if implicit_dir { String::new() } else { quoting::display(operand) };
+        search.path(ctx, &path, &shown, None, &mut HashSet::new());
+    }
+    let _ = ctx.stdout.flush();
+    // GNU grep と同じく、一致した行があれば 0、なければ 1、読めないファイルなどがあれば 2
+    // （-q で一致したときはエラーがあっても 0）
+    ctx.status = match (search.matched, ctx.status != 0) {
+        (true, false) => 0,
+        (true, true) if search.opts.quiet => 0,
+        (_, true) => 2,
+        (false, false) => 1,
+    };
+}
+
+/// 長いオプションを反映する。誤りがあれば表示して false を返す
+fn apply_long(ctx: &mut Context, opts: &mut Options, pattern_files: &mut Vec<String>, name: &str, value: Option<String>) -> bool {
+    let short = match name {
+        "regexp" => 'e',
+        "file" => 'f',
+        "extended-regexp" => 'E',
+        "fixed-strings" => 'F',
+        "basic-regexp" => 'G',
+        "ignore-case" => 'i',
+        "invert-match" => 'v',
+        "word-regexp" => 'w',
+        "line-regexp" => 'x',
+        "line-number" => 'n',
+        "count" => 'c',
+        "files-with-matches" => 'l',
+        "files-without-match" => 'L',
+        "only-matching" => 'o',
+        "quiet" | "silent" => 'q',
+        "no-messages" => 's',
+        "max-count" => 'm',
+        "recursive" => 'r',
+        "dereference-recursive" => 'R',
+        "with-filename" => 'H',
+        "no-filename" => 'h',
+        "text" => 'a',
+        "after-context" => 'A',
+        "before-context" => 'B',
+        "context" => 'C',
+        "no-ignore-case" => {
+            opts.ignore_case = false;
+            return true;
+        }
+        "no-ignore" => {
+            opts.gitignore = false;
+            return true;
+        }
+        "color" | "colour" => {
+            match value.as_deref().map(ColorMode::parse) {
+                None => opts.color = ColorMode::Auto,
+                Some(Some(mode)) => opts.color = mode,
+                Some(None) => {
+                    errln!(ctx, "grep: invalid argument '{}' for '--color'", value.unwrap_or_default());
+                    return false;
+                }
+            }
+            return true;
+        }
+        "binary-files" => {
+            opts.binary_files = match value.as_deref() {
+                Some("binary") => BinaryFiles::Binary,
+                Some("text") => BinaryFiles::Text,
+                Some("without-match") => BinaryFiles::WithoutMatch,
+                _ => {
+                    errln!(ctx, "grep: unknown binary-files type");
+                    return false;
+                }
+            };
+            return true;
+        }
+        "include" | "exclude" | "exclude-dir" => {
+            let pattern = value.unwrap_or_default();
+            let glob = match Glob::new(&pattern) {
+                Ok(glob) => glob,
+                Err(message) => {
+                    errln!(ctx, "grep: {}", message);
+                    return false;
+                }
+            };
+            match name {
+                "include" => opts.includes.push(glob),
+                "exclude" => opts.excludes.push(glob),
+                _ => opts.exclude_dirs.push(glob),
+            }
+            return true;
+        }
+        _ => {
+            errln!(ctx, "grep: unrecognized option '--{}'", name);
+            return false;
+        }
+    };
+    apply_short(ctx, opts, pattern_files, short, value)
+}
+
+/// 短いオプションを反映する。誤りがあれば表示して false を返す
+fn apply_short(ctx: &mut Context, opts: &mut Options, pattern_files: &mut Vec<String>, ch: char, value: Option<String>) -> bool {
+    let number = |ctx: &mut Context, what: &str| -> Option<u64> {
+        let value = value.as_deref().unwrap_or_default();
+        let parsed = value.parse::<u64>().ok();
+        if parsed.is_none() {
+            errln!(ctx, "grep: {}: invalid {} argument", value, what);
+        }
+        parsed
+    };
+    match ch {
+        'e' => opts.patterns.extend(value.unwrap_or_default().split('\n').map(str::to_string)),
+        'f' => pattern_files.push(value.unwrap_or_default()),
+        'E' => opts.syntax = Syntax::Extended,
+        'F' => opts.syntax = Syntax::Fixed,
+        'G' => opts.syntax = Syntax::Basic,
+        'i' | 'y' => opts.ignore_case = true,
+        'v' => opts.invert = true,
+        'w' => opts.word = true,
+        'x' => opts.whole_line = true,
+        'n' => opts.line_number = true,
+        'c' => opts.count = true,
+        'l' => opts.files_with_matches = true,
+        'L' => opts.files_without_match = true,
+        'o' => opts.only_matching = true,
+        'q' => opts.quiet = true,
+        's' => opts.no_messages = true,
+        'r' => opts.recursive = true,
+        'R' => (opts.recursive, opts.dereference) = (true, true),
+        'H' => opts.with_filename = Some(true),
+        'h' => opts.with_filename = Some(false),
+        'a' => opts.binary_files = BinaryFiles::Text,
+        'I' => opts.binary_files = BinaryFiles::WithoutMatch,
+        'm' => match number(ctx, "max count") {
+            Some(n) => opts.max_count = Some(n),
+            None => return false,
+        },
+        'A' | 'B' | 'C' => {
+            let Some(n) = number(ctx, "context length") else {
+                return false;
+            };
+            let n = n as usize;
+            match ch {
+                'A' => opts.after = n,
+                'B' => opts.before = n,
+                _ => (opts.after, opts.before) = (n, n),
+            }
+        }
+        _ => {
+            errln!(ctx, "grep: invalid option -- '{}'", ch);
+            return false;
+        }
+    }
+    true
+}
+
+/// 行がパターンに一致するかを調べる
+struct Matcher {
+    regex: Regex,
+    /// 一致した部分の前後が単語の区切りであることを求める（`-w`）
+    word: bool,
+}
+
+impl Matcher {
+    fn new(opts: &Options) -> Result<Matcher, String> {
+        let alternatives: Vec<String> = opts
+            .patterns
+            .iter()
+            .map(|pattern| match opts.syntax {
+                Syntax::Fixed => regex::escape(pattern),
+                Syntax::Extended => translate(pattern, false),
+                Syntax::Basic => translate(pattern, true),
+            })
+            .map(|pattern| format!("(?:{})", pattern))
+            .collect();
+        let mut pattern = alternatives.join("|");
+        if opts.whole_line {
+            pattern = format!("^(?:{})$", pattern);
+        }
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(opts.ignore_case)
+            .build()
+            .map_err(|e| match e {
+                regex::Error::Syntax(message) => {
+                    let last = message.lines().last().unwrap_or_default();
+                    last.strip_prefix("error: ").unwrap_or(last).to_string()
+                }
+                other => other.to_string(),
+            })?;
+        Ok(Matcher { regex, word: opts.word })
+    }
+
+    /// `start` 以降で最初に一致する範囲
+    fn find_at(&self, line: &[u8], start: usize) -> Option<(usize, usize)> {
+        let mut from = start;
+        while from <= line.len() {
+            let found = self.regex.find_at(line, from)?;
+            let (s, e) = (found.start(), found.end());
+            if !self.word || !is_word_char(char_before(line, s)) && !is_word_char(char_after(line, e)) {
+                return Some((s, e));
+            }
+            from = s + 1;
+        }
+        None
+    }
+
+    fn is_match(&self, line: &[u8]) -> bool {
+        self.find_at(line, 0).is_some()
+    }
+}
+
+fn is_word_char(c: Option<char>) -> bool {
+    c.is_some_and(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// `i` の直前の文字
+fn char_before(line: &[u8], i: usize) -> Option<char> {
+    (1..=4.min(i)).find_map(|k| std::str::from_utf8(&line[i - k..i]).ok()).and_then(|s| s.chars().next_back())
+}
+
+/// `i` から始まる文字
+fn char_after(line: &[u8], i: usize) -> Option<char> {
+    (1..=4.min(line.len() - i)).find_map(|k| std::str::from_utf8(&line[i..i + k]).ok()).and_then(|s| s.chars().next())
+}
+
+/// POSIX の正規表現を regex クレートの書式に直す。
+/// 基本正規表現（`basic`）では `\(` `\)` `\{` `\}` `\|` `\+` `\?` が特別な意味を持ち、
+/// 裸の `(` などは文字そのものになる。`[...]` の中の `\` `[` `&` `~` は文字そのものとして扱う
+fn translate(pattern: &str, basic: bool) -> String {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut out = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '[' => {
+                // 文字クラスは `]` まで写す
+                out.push('[');
+                i += 1;
+                if chars.get(i) == Some(&'^') {
+                    out.push('^');
+                    i += 1;
+                }
+                if chars.get(i) == Some(&']') {
+                    out.push_str("\\]");
+                    i += 1;
+                }
+                while i < chars.len() && chars[i] != ']' {
+                    match chars[i] {
+                        '[' if matches!(chars.get(i + 1), Some(':') | Some('=') | Some('.')) => {
+                            let close = chars[i + 1];
+                            let end = (i + 2..chars.len().saturating_sub(1))
+                                .find(|&j| chars[j] == close && chars[j + 1] == ']')
+                                .unwrap_or(chars.len() - 1);
+                            out.extend(&chars[i..(end + 2).min(chars.len())]);
+                            i = end + 2;
+                            continue;
+                        }
+                        '\\' | '[' | '&' | '~' => {
+                            out.push('\\');
+                            out.push(chars[i]);
+                        }
+                        other => out.push(other),
+                    }
+                    i += 1;
+                }
+                if i < chars.len() {
+                    out.push(']');
+                }
+            }
+            '\\' if basic => match chars.get(i + 1) {
+                Some(&next @ ('(' | ')' | '{' | '}' | '|' | '+' | '?')) => {
+                    out.push(next);
+                    i += 1;
+                }
+                Some(&next) => {
+                    out.push('\\');
+                    out.push(next);
+                    i += 1;
+                }
+                None => out.push_str("\\\\"),
+            },
+            '(' | ')' | '{' | '}' | '|' | '+' | '?' if basic => {
+                out.push('\\');
+                out.push(c);
+            }
+            // 先頭（やグループの始まり）の `*` は文字そのもの
+            '*' if out.is_empty() || out.ends_with('(') || out.ends_with('|') || out.ends_with('^') => out.push_str("\\*"),
+            _ => out.push(c),
+        }
+        i += 1;
+    }
+    out
+}
+
+/// 複数のファイルにまたがる検索の状態
+struct Search {
+    opts: Options,
+    matcher: Matcher,
+    with_filename: bool,
+    colors: bool,
+    /// 行を1つでも表示したか（前後の行の区切り `--` に使う）
+    printed_any: bool,
+    /// これ以上調べなくてよい（`-q` で一致した）
+    stop: bool,
+    /// 一致した行があったか（終了ステータスに使う）
+    matched: bool,
+}
+
+impl Search {
+    /// ファイルかディレクトリを調べる。`ignores` は再帰中に親ディレクトリから引き継ぐ .gitignore、
+    /// `visited` はたどっている途中のディレクトリ（循環の検出に使う）
+    fn path(&mut self, ctx: &mut Context, path: &Path, shown: &str, ignores: Option<&mut Ignores>, visited: &mut HashSet<(u64, u64)>) {
+        if self.stop || ctx.interrupted() {
+            return;
+        }
+        let meta = match ctx.fs.metadata(path) {
+            Ok(meta) => meta,
+            Err(e) => {
+                if !self.opts.no_messages {
+                    errln!(ctx, "grep: {}: {}", shown, error_message(&e));
+                }
+                return;
+            }
+        };
+        if !meta.is_dir() {
+            self.search_file(ctx, path, shown);
+            return;
+        }
+        if !self.opts.recursive {
+            if !self.opts.no_messages {
+                errln!(ctx, "grep: {}: Is a directory", shown);
+            }
+            return;
+        }
+        // -R でシンボリックリンクをたどるときの循環を防ぐ
+        if !visited.insert((meta.dev(), meta.ino())) {
+            if !self.opts.no_messages {
+                errln!(ctx, "grep: {}: warning: recursive directory loop", shown);
+            }
+            return;
+        }
+        let mut own_ignores;
+        let ignores = match ignores {
+            Some(ignores) => ignores,
+            None => {
+                own_ignores = Ignores::above(ctx.fs.as_ref(), path);
+                &mut own_ignores
+            }
+        };
+        let mut names = match ctx.fs.read_dir(path) {
+            Ok(names) => names,
+            Err(e) => {
+                if !self.opts.no_messages {
+                    errln!(ctx, "grep: {}: {}", shown, error_message(&e));
+                }
+                visited.remove(&(meta.dev(), meta.ino()));
+                return;
+            }
+        };
+        names.sort();
+        if self.opts.gitignore {
+            ignores.enter(ctx.fs.as_ref(), path);
+        }
+        for name in names {
+            if self.stop || ctx.interrupted() {
+                break;
+            }
+            let child = path.join(&name);
+            let child_shown = match shown {
+                "" => quoting::display(&name),
+                _ if shown.ends_with('/') => format!("{}{}", shown, quoting::display(&name)),
+                _ => format!("{}/{}", shown, quoting::display(&name)),
+            };
+            let meta = match if self.opts.dereference { ctx.fs.metadata(&child) } else { ctx.fs.symlink_metadata(&child) } {
+                Ok(meta) => meta,
+                Err(e) => {
+                    if !self.opts.no_messages {
+                        errln!(ctx, "grep: {}: {}", child_shown, error_message(&e));
+                    }
+                    continue;
+                }
+            };
+            let name = OsStr::new(&name);
+            if meta.is_dir() {
+                if self.opts.exclude_dirs.iter().any(|glob| glob.matches(name))
+                    || self.opts.gitignore && ignores.is_ignored(&child, true)
+                {
+                    continue;
+                }
+                self.path(ctx, &child, &child_shown, Some(ignores), visited);
+            } else if meta.is_file() {
+                // 再帰中はシンボリックリンク（-r）やデバイス・FIFO は読まない
+                if !self.opts.includes.is_empty() && !self.opts.includes.iter().any(|glob| glob.matches(name))
+                    || self.opts.excludes.iter().any(|glob| glob.matches(name))
+                    || self.opts.gitignore && ignores.is_ignored(&child, false)
+                {
+                    continue;
+                }
+                self.search_file(ctx, &child, &child_shown);
+            }
+        }
+        if self.opts.gitignore {
+            ignores.leave();
+        }
+        visited.remove(&(meta.dev(), meta.ino()));
+    }
+
+    /// 1つのファイルを開いて調べる
+    fn search_file(&mut self, ctx: &mut Context, path: &Path, shown: &str) {
+        let result = ctx.fs.open(path).and_then(|mut reader| self.file(ctx, &mut reader, shown));
+        self.report(ctx, shown, result);
+    }
+
+    /// 読み取りのエラーを表示する
+    fn report(&mut self, ctx: &mut Context, shown: &str, result: io::Result<()>) {
+        match result {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => self.stop = true,
+            Err(e) => {
+                if !self.opts.no_messages {
+                    errln!(ctx, "grep: {}: {}", shown, error_message(&e));
+                }
+            }
+        }
+    }
+
+    /// 読み込み元から一致する行を探して表示する
+    fn file(&mut self, ctx: &mut Context, reader: &mut dyn Read, shown: &str) -> io::Result<()> {
+        let opts = &self.opts;
+        let listing = opts.quiet || opts.count || opts.files_with_matches || opts.files_without_match;
+        let context = !listing && !opts.only_matching && (opts.after > 0 || opts.before > 0);
+        let mut lines = LineReader::new();
+        let mut line = Vec::new();
+        let mut lineno = 0u64;
+        let mut count = 0u64;
+        let mut before: VecDeque<(u64, Vec<u8>)> = VecDeque::new();
+        let mut after_left = 0usize;
+        let mut last_printed: Option<u64> = None;
+        let mut done = false;
+
+        while lines.read_line(ctx, reader, &mut line)? {
+            lineno += 1;
+            let binary = self.opts.binary_files != BinaryFiles::Text && lines.saw_nul;
+            if binary && self.opts.binary_files == BinaryFiles::WithoutMatch {
+                return Ok(());
+            }
+            let content = line.strip_suffix(b"\n").unwrap_or(&line);
+            // -m の数に達したら、後の行だけを表示して終える
+            if done {
+                if after_left == 0 {
+                    break;
+                }
+                self.print_line(ctx, shown, lineno, content, false, context, &mut last_printed);
+                after_left -= 1;
+                continue;
+            }
+            let selected = self.matcher.is_match(content) != self.opts.invert;
+            if !selected {
+                if after_left > 0 {
+                    self.print_line(ctx, shown, lineno, content, false, context, &mut last_printed);
+                    after_left -= 1;
+                } else if self.opts.before > 0 && context {
+                    if before.len() == self.opts.before {
+                        before.pop_front();
+                    }
+                    before.push_back((lineno, content.to_vec()));
+                }
+                continue;
+            }
+
+            count += 1;
+            self.matched = true;
+            if self.opts.quiet {
+                self.stop = true;
+                return Ok(());
+            }
+            if self.opts.files_with_matches || self.opts.files_without_match {
+                break;
+            }
+            done = self.opts.max_count.is_some_and(|max| count >= max);
+            if self.opts.count {
+                continue;
+            }
+            if binary {
+                outln!(ctx, "grep: {}: binary file matches", shown);
+                return Ok(());
+            }
+            for (n, text) in std::mem::take(&mut before) {
+                self.print_line(ctx, shown, n, &text, false, context, &mut last_printed);
+            }
+            if self.opts.only_matching {
+                self.print_matches(ctx, shown, lineno, content);
+            } else {
+                self.print_line(ctx, shown, lineno, content, true, context, &mut last_printed);
+            }
+            after_left = self.opts.after;
+        }
+        // -m 0 では何も読まない
+        if self.opts.max_count == Some(0) {
+            count = 0;
+        }
+
+        if self.opts.count {
+            let prefix = if self.with_filename { self.prefix(shown, None, ':') } else { String::new() };
+            outln!(ctx, "{}{}", prefix, count);
+        }
+        if self.opts.files_with_matches && count > 0 || self.opts.files_without_match && count == 0 {
+            let name = self.paint(FILENAME_COLOR, shown);
+            outln!(ctx, "{}", name);
+        }
+        Ok(())
+    }
+
+    /// 1行を表示する。`selected` なら一致した部分を色付けし、区切りを `:` にする（前後の行は `-`）
+    #[allow(clippy::too_many_arguments)]
+    fn print_line(&mut self, ctx: &mut Context, shown: &str, lineno: u64, content: &[u8], selected: bool, context: bool, last_printed: &mut Option<u64>) {
+        if last_printed.is_some_and(|last| lineno <= last) {
+            return;
+        }
+        // 離れた行の間には `--` を挟む
+        if context && self.printed_any && last_printed.is_none_or(|last| lineno > last + 1) {
+            let separator = self.paint(SEPARATOR_COLOR, "--");
+            outln!(ctx, "{}", separator);
+        }
+        *last_printed = Some(lineno);
+        self.printed_any = true;
+
+        let mut out = self.prefix(shown, Some(lineno), if selected { ':' } else { '-' }).into_bytes();
+        if selected && self.colors && !self.opts.invert {
+            let mut pos = 0;
+            let mut start = 0;
+            while let Some((s, e)) = self.matcher.find_at(content, start) {
+                if e == s {
+                    start = s + 1;
+                    continue;
+                }
+                out.extend_from_slice(&content[pos..s]);
+                out.extend_from_slice(format!("\x1b[{}m", MATCH_COLOR).as_bytes());
+                out.extend_from_slice(&content[s..e]);
+                out.extend_from_slice(b"\x1b[0m");
+                pos = e;
+                start = e;
+                if start > content.len() {
+                    break;
+                }
+            }
+            out.extend_from_slice(&content[pos..]);
+        } else {
+            out.extend_from_slice(content);
+        }
+        out.push(b'\n');
+        let _ = ctx.stdout.write_all(&out);
+    }
+
+    /// 一致した部分を1つずつ別の行に表示する（`-o`）
+    fn print_matches(&mut self, ctx: &mut Context, shown: &str, lineno: u64, content: &[u8]) {
+        if self.opts.invert {
+            return;
+        }
+        let mut start = 0;
+        while let Some((s, e)) = self.matcher.find_at(content, start) {
+            start = if e == s { s + 1 } else { e };
+            if e == s {
+                continue;
+            }
+            let mut out = self.prefix(shown, Some(lineno), ':').into_bytes();
+            let text = &content[s..e];
+            if self.colors {
+                out.extend_from_slice(format!("\x1b[{}m", MATCH_COLOR).as_bytes());
+                out.extend_from_slice(text);
+                out.extend_from_slice(b"\x1b[0m");
+            } else {
+                out.extend_from_slice(text);
+            }
+            out.push(b'\n');
+            let _ = ctx.stdout.write_all(&out);
+            self.printed_any = true;
+        }
+    }
+
+    /// 行の前に付けるファイル名と行番号
+    fn prefix(&self, shown: &str, lineno: Option<u64>, separator: char) -> String {
+        let mut prefix = String::new();
+        let separator = self.paint(SEPARATOR_COLOR, &separator.to_string());
+        if self.with_filename {
+            prefix.push_str(&self.paint(FILENAME_COLOR, shown));
+            prefix.push_str(&separator);
+        }
+        if let Some(lineno) = lineno.filter(|_| self.opts.line_number) {
+            prefix.push_str(&self.paint(LINE_NUMBER_COLOR, &lineno.to_string()));
+            prefix.push_str(&separator);
+        }
+        prefix
+    }
+
+    /// 色付けが有効なら SGR で囲む
+    fn paint(&self, sgr: &str, text: &str) -> String {
+        if self.colors {
+            format!("\x1b[{}m{}\x1b[0m", sgr, text)
+        } else {
+            text.to_string()
+        }
+    }
+}
@@ -0,0 +1,259 @@
+//! headコマンド。ファイルの先頭部分を表示する
+//!
+//! 対応オプション:
+//! - `-n N` / `--lines=N` 先頭の N 行（既定 10）。`-n -N` なら末尾の N 行を除いたすべて
+//! - `-c N` / `--bytes=N` 先頭の N バイト。`-c -N` なら末尾の N バイトを除いたすべて
+//! - `-q` / `-v` ファイル名の見出し（`==> name <==`）を付けない・常に付ける
+//!   （既定は複数のファイルを指定したときだけ付ける）
+//! - `-N` は `-n N` と同じ
+//!
+//! N には `b`（512）、`K`（1024）、`KB`（1000）、`M`、`MB`、`G`、`GB` などの単位を付けられる。
+//! ファイルを指定しないか `-` を指定すると標準入力を読む。
+
+use super::cat::{self, input_name, read_some};
+use super::error_message;
+use crate::context::Context;
+use crate::errln;
+use std::ffi::{OsStr, OsString};
+use std::io::{self, Read, Write};
+
+/// コマンド名
+pub const NAME: &str = "head";
+
+/// 一度に読むバイト数
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// 表示する範囲
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Span {
+    /// 先頭の N 行（N バイト）
+    First(u64),
+    /// 末尾の N 行（N バイト）を除いたすべて
+    AllBut(u64),
+}
+
+/// 数える単位
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Unit {
+    Lines,
+    Bytes,
+}
+
+/// `10`・`2K`・`1MB` のような数を解釈する（単位は `b` 512、`K` 1024、`KB` 1000 など）
+pub(crate) fn parse_count(s: &str) -> Option<u64> {
+    let (digits, suffix) = s.split_at(s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len()));
+    let multiplier: u64 = match suffix {
+        "" => 1,
+        "b" => 512,
+        "K" | "KiB" => 1 << 10,
+        "KB" | "kB" => 1000,
+        "M" | "MiB" => 1 << 20,
+        "MB" => 1000 * 1000,
+        "G" | "GiB" => 1 << 30,
+        "GB" => 1000 * 1000 * 1000,
+        "T" | "TiB" => 1 << 40,
+        "TB" => 1000 * 1000 * 1000 * 1000,
+        _ => return None,
+    };
+    digits.parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+pub fn run(ctx: &mut Context, args: &[OsString]) {
+    let mut unit = Unit::Lines;
+    let mut span = Span::First(10);
+    let mut headers: Option<bool> = None;
+    let mut files: Vec<&OsStr> = Vec::new();
+    let mut only_files = false;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let Some(s) = arg.to_str().filter(|s| !only_files && s.starts_with('-') && s.len() > 1) else {
+            files.push(arg);
+            continue;
+        };
+        // 値を取るオプション（-n N / -nN / --lines=N / --lines N）
+        let (value_unit, value) = match s {
+            "--" => {
+                only_files = true;
+                continue;
+            }
+            "--quiet" | "--silent" => {
+                headers = Some(false);
+                continue;
+            }
+            "--verbose" => {
+                headers = Some(true);
+                continue;
+            }
+            "-n" | "--lines" | "-c" | "--bytes" => {
+                let value_unit = if s.contains('c') || s == "--bytes" { Unit::Bytes } else { Unit::Lines };
+                match args.next() {
+                    Some(value) => (value_unit, value.to_string_lossy().into_owned()),
+                    None => {
+                        errln!(ctx, "head: option requires an argument -- '{}'", s.trim_start_matches('-'));
+                        return;
+                    }
+                }
+            }
+            _ if s.starts_with("--lines=") => (Unit::Lines, s["--lines=".len()..].to_string()),
+            _ if s.starts_with("--bytes=") => (Unit::Bytes, s["--bytes=".len()..].to_string()),
+            _ if s.starts_with("--") => {
+                errln!(ctx, "head: unrecognized option '{}'", s);
+                return;
+            }
+            // -5 は -n 5 と同じ
+            _ if s[1..].starts_with(|c: char| c.is_ascii_digit()) => (Unit::Lines, s[1..].to_string()),
+            _ => {
+                let mut value = None;
+                for (i, ch) in s[1..].char_indices() {
+                    match ch {
+                        'q' => headers = Some(false),
+                        'v' => headers = Some(true),
+                        'n' | 'c' => {
+                            let rest = &s[i + 2..];
+                            let unit = if ch == 'c' { Unit::Bytes } else { Unit::Lines };
+                            value = match (rest.is_empty(), if rest.is_empty() { args.next() } else { None }) {
+                                (false, _) => Some((unit, rest.to_string())),
+                                (true, Some(next)) => Some((unit, next.to_string_lossy().into_owned())),
+                                (true, None) => {
+                                    errln!(ctx, "head: option requires an argument -- '{}'", ch);
+                                    return;
+                                }
+                            };
+                            break;
+                        }
+                        _ => {
+                            errln!(ctx, "head: invalid option -- '{}'", ch);
+                            return;
+                        }
+                    }
+                }
+                match value {
+                    Some(value) => value,
+                    None => continue,
+                }
+            }
+        };
+
+        let (all_but, digits) = match value.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, value.as_str()),
+        };
+        let Some(count) = parse_count(digits) else {
+            let what = if value_unit == Unit::Bytes { "bytes" } else { "lines" };
+            errln!(ctx, "head: invalid number of {}: '{}'", what, value);
+            return;
+        };
+        unit = value_unit;
+        span = if all_but { Span::AllBut(count) } else { Span::First(count) };
+    }
+    if files.is_empty() {
+        files.push(OsStr::new("-"));
+    }
+
+    let show_headers = headers.unwrap_or(files.len() > 1);
+    for (i, file) in files.iter().enumerate() {
+        if show_headers {
+            let blank = if i == 0 { "" } else { "\n" };
+            let _ = writeln!(ctx.stdout, "{}==> {} <==", blank, input_name(file));
+        }
+        let result = cat::with_input(ctx, file, |ctx, reader| match (unit, span) {
+            (Unit::Lines, Span::First(n)) => first_lines(ctx, reader, n),
+            (Unit::Bytes, Span::First(n)) => first_bytes(ctx, reader, n),
+            (unit, Span::AllBut(n)) => all_but_last(ctx, reader, unit, n),
+        });
+        match result {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) if e.kind() == io::ErrorKind::Interrupted => break,
+            Ok(Err(e)) => errln!(ctx, "head: error reading '{}': {}", input_name(file), error_message(&e)),
+            Err(e) => errln!(ctx, "head: cannot open '{}' for reading: {}", input_name(file), error_message(&e)),
+        }
+    }
+    let _ = ctx.stdout.flush();
+}
+
+/// 先頭の `count` 行を書き出す
+fn first_lines(ctx: &mut Context, reader: &mut dyn Read, mut count: u64) -> io::Result<()> {
+    let mut buf = vec![0u8; BUFFER_SIZE];
+    while count > 0 {
+        let n = read_some(ctx, reader, &mut buf)?;
+        if n == 0 {
+            break;
+        }
+        let mut end = n;
+        for (i, _) in buf[..n].iter().enumerate().filter(|(_, &b)| b == b'\n') {
+            count -= 1;
+            if count == 0 {
+                end = i + 1;
+                break;
+            }
+        }
+        let _ = ctx.stdout.write_all(&buf[..end]);
+    }
+    Ok(())
+}
+
+/// 先頭の `count` バイトを書き出す
+fn first_bytes(ctx: &mut Context, reader: &mut dyn Read, mut count: u64) -> io::Result<()> {
+    let mut buf = vec![0u8; BUFFER_SIZE];
+    while count > 0 {
+        let want = count.min(BUFFER_SIZE as u64) as usize;
+        let n = read_some(ctx, reader, &mut buf[..want])?;
+        if n == 0 {
+            break;
+        }
+        let _ = ctx.stdout.write_all(&buf[..n]);
+        count -= n as u64;
+    }
+    Ok(())
+}
+
+/// 末尾の `count` 行（バイト）を除いて書き出す。
+/// 末尾の分だけを手元に残しながら読み進めるので、入力全体は読み込まない
+fn all_but_last(ctx: &mut Context, reader: &mut dyn Read, unit: Unit, count: u64) -> io::Result<()> {
+    let mut buf = vec![0u8; BUFFER_SIZE];
+    let mut pending: Vec<u8> = Vec::new();
+    // pending に含まれる改行の数（行単位のとき）
+    let mut newlines = 0u64;
+    loop {
+        let n = read_some(ctx, reader, &mut buf)?;
+        if n == 0 {
+            // 改行で終わらない最後の行も1行と数える
+            if unit == Unit::Lines && pending.last().is_some_and(|&b| b != b'\n') {
+                let end = line_end(&pending, (newlines + 1).saturating_sub(count));
+                let _ = ctx.stdout.write_all(&pending[..end]);
+            }
+            return Ok(());
+        }
+        pending.extend_from_slice(&buf[..n]);
+        let ready = match unit {
+            Unit::Bytes => pending.len().saturating_sub(count.try_into().unwrap_or(usize::MAX)),
+            Unit::Lines => {
+                newlines += buf[..n].iter().filter(|&&b| b == b'\n').count() as u64;
+                // 後に count 行以上が続く行は書き出してよい
+                let excess = newlines.saturating_sub(count);
+                newlines -= excess;
+                line_end(&pending, excess)
+            }
+        };
+        let _ = ctx.stdout.write_all(&pending[..ready]);
+        pending.drain(..ready);
+    }
+}
+
+/// `data` の先頭から `lines` 行分の長さ（`lines` 個目の改行の直後の位置）
+pub(crate) fn line_end(data: &[u8], lines: u64) -> usize {
+    if lines == 0 {
+        return 0;
+    }
+    let mut seen = 0;
+    for (i, &b) in data.iter().enumerate() {
+        if b == b'\n' {
+            seen += 1;
+            if seen == lines {
+                return i + 1;
+            }
+        }
+    }
+    data.len()
+}
@@ -1,33 +1,202 @@
 //! helpコマンド。使用可能なコマンド一覧を表示する
 
+use crate::context::Context;
+use crate::outln;
+use std::ffi::OsString;
+
 /// コマンド名
 pub const NAME: &str = "help";
 
-pub fn run(_args: &[&str]) {
-    println!("=== Simple Terminal - Available Commands ===\n");
-
-    println!("File and Directory Operations:");
-    println!("  ls [dir]      - List directory contents");
-    println!("  cd [dir]      - Change directory");
-    println!("  pwd           - Print working directory");
-    println!("  mkdir <dir>   - Create directory");
-    println!("  rmdir <dir>   - Remove empty directory");
-    println!("  rm <file>     - Remove file");
-    println!("    -f          - Force removal (ignore errors)");
-    println!("    -r, -R      - Remove directories and their contents recursively");
-
-    println!("\nTerminal Control:");
-    println!("  clear         - Clear screen");
-    println!("  help          - Show this help message");
-    println!("  exit/quit     - Exit the terminal");
-
-    println!("\nShortcuts:");
-    println!("  Ctrl+C        - Interrupt running command");
-    println!("  Ctrl+D        - Exit on empty line");
-
-    println!("\nExamples:");
-    println!("  rm file.txt           - Remove a file");
-    println!("  rm -rf directory/     - Remove a directory and all its contents");
-    println!("  mkdir new_folder      - Create a new directory");
-    println!("  rmdir old_folder      - Remove an empty directory");
+pub fn run(ctx: &mut Context, _args: &[OsString]) {
+    outln!(ctx, "=== Simple Terminal - Available Commands ===\n");
+
+    print_commands(ctx);
+    outln!(ctx, "  exit/quit     - Exit the terminal");
+
+    outln!(ctx, "\nShortcuts:");
+    outln!(ctx, "  Ctrl+C        - Interrupt running command");
+    outln!(ctx, "  Ctrl+D        - Exit on empty line");
+
+    outln!(ctx, "\nExamples:");
+    outln!(ctx, "  rm file.txt           - Remove a file");
+    outln!(ctx, "  rm -rf directory/     - Remove a directory and all its contents");
+    outln!(ctx, "  mkdir new_folder      - Create a new directory");
+    outln!(ctx, "  rmdir old_folder      - Remove an empty directory");
+}
+
+/// CLI版・GUI版で共通のコマンド一覧を表示する
+pub fn print_commands(ctx: &mut Context) {
+    outln!(ctx, "File and Directory Operations:");
+    outln!(ctx, "  ls [dir...]   - List directory contents");
+    outln!(ctx, "    -l          - Long format (permissions, owner, size, mtime)");
+    outln!(ctx, "    -a, -A      - Show hidden files (-A omits . and ..)");
+    outln!(ctx, "    -h          - Human-readable sizes");
+    outln!(ctx, "    -R          - List subdirectories recursively");
+    outln!(ctx, "    -t, -S, -X  - Sort by time, size or extension");
+    outln!(ctx, "    -r          - Reverse sort order");
+    outln!(ctx, "    -1          - One entry per line");
+    outln!(ctx, "    --color[=WHEN] - Colorize by file type (WHEN: auto, always, never)");
+    outln!(ctx, "    --quoting-style=WORD - literal, shell, shell-always, shell-escape,");
+    outln!(ctx, "                   shell-escape-always, c, escape (-N, -Q, -b)");
+    outln!(ctx, "    -q, --show-control-chars - Print unprintable characters as '?' / as is");
+    outln!(ctx, "  cd [dir]      - Change directory (cd - returns to $OLDPWD)");
+    outln!(ctx, "  pwd           - Print working directory");
+    outln!(ctx, "  pushd [dir|+N|-N] - Save the current directory on the stack and change to dir");
+    outln!(ctx, "    -n          - Only add dir to the stack");
+    outln!(ctx, "  popd [+N|-N]  - Remove the top (or Nth) stack entry and change to the new top");
+    outln!(ctx, "  dirs          - Show the directory stack (~N refers to its entries)");
+    outln!(ctx, "    -v, -p, -l, -c - Numbered / one per line / full paths / clear the stack");
+    outln!(ctx, "  z [keyword...] - Jump to the most frecent visited directory matching the keywords");
+    outln!(ctx, "    -l          - List matching directories with their scores");
+    outln!(ctx, "    -x [dir]    - Remove dir (default: current directory) from the database");
+    outln!(ctx, "    --clean     - Remove directories that no longer exist");
+    outln!(ctx, "  zi [keyword...] - Choose a matching directory interactively and jump to it");
+    outln!(ctx, "  mkdir <dir>   - Create directory");
+    outln!(ctx, "    -p          - Create parent directories as needed, no error if existing");
+    outln!(ctx, "    -m MODE     - Set permissions (octal or symbolic, e.g. 755, u=rwx,go=rx)");
+    outln!(ctx, "    -v          - Print each created directory");
+    outln!(ctx, "  rmdir <dir>   - Remove empty directory");
+    outln!(ctx, "    -p          - Also remove empty parent directories");
+    outln!(ctx, "    --ignore-fail-on-non-empty - Ignore directories that are not empty");
+    outln!(ctx, "    -v          - Print each removed directory");
+    outln!(ctx, "  cp <src> <dest> - Copy files (into <dest> if it is a directory)");
+    outln!(ctx, "    -r, -R      - Copy directories recursively");
+    outln!(ctx, "    -p, -a      - Preserve mode, timestamps and ownership (-a = -rPp)");
+    outln!(ctx, "    -i, -n, -f  - Ask before, never, or force overwriting");
+    outln!(ctx, "    -L, -P      - Follow symlinks / copy symlinks as links");
+    outln!(ctx, "    -v          - Print each copied file");
+    outln!(ctx, "  mv <src> <dest> - Move or rename files (into <dest> if it is a directory)");
+    outln!(ctx, "    -t <dir>    - Move all sources into <dir>");
+    outln!(ctx, "    -i, -n, -f  - Ask before, never, or always overwrite");
+    outln!(ctx, "    -v          - Print each moved file");
+    outln!(ctx, "  rm <file>     - Remove file");
+    outln!(ctx, "    -f          - Force removal (ignore errors)");
+    outln!(ctx, "    -r, -R      - Remove directories and their contents recursively");
+    outln!(ctx, "    -i, -I      - Prompt before every removal / once before many or recursive");
+    outln!(ctx, "    -d          - Remove empty directories");
+    outln!(ctx, "    -v          - Print each removed path");
+    outln!(ctx, "    --one-file-system - Do not descend into other file systems");
+    outln!(ctx, "    --no-preserve-root - Allow removing '/' recursively (refused by default)");
+    outln!(ctx, "    --trash     - Move to the trash instead (default: 'trash' in config)");
+    outln!(ctx, "  trash-list    - List trashed files");
+    outln!(ctx, "  trash-restore <path> - Restore a trashed file to its original location");
+    outln!(ctx, "  trash-empty [days] - Permanently delete trashed files (older than days)");
+    outln!(ctx, "  undo [n]      - Undo the last n rm/rmdir/mv/cp/mkdir operations");
+    outln!(ctx, "    -l          - List operations that can be undone");
+    outln!(ctx, "    -f          - Skip conflicting changes instead of stopping");
+    outln!(ctx, "  touch <file>  - Update timestamps, creating empty files if missing");
+    outln!(ctx, "    -a, -m      - Change only the access / modification time");
+    outln!(ctx, "    -c          - Do not create files");
+    outln!(ctx, "    -d DATE, -t STAMP, -r FILE - Use DATE, [[CC]YY]MMDDhhmm[.ss] or FILE's times");
+    outln!(ctx, "  stat <file>   - Show file size, inode, links, permissions, owner and times");
+    outln!(ctx, "    -c FORMAT   - Use FORMAT (%n %s %a %A %U %G %y ...) followed by a newline");
+    outln!(ctx, "    --printf=FORMAT - Like -c, interpreting escapes and without a newline");
+    outln!(ctx, "    -L, -t      - Follow symlinks / terse output");
+    outln!(ctx, "  chmod MODE <file> - Change permissions (octal or symbolic, e.g. 644, u+x,go-w)");
+    outln!(ctx, "    -R          - Change directories recursively");
+    outln!(ctx, "    -v, -c, -f  - Print every file / only changes / suppress errors");
+    outln!(ctx, "  chown OWNER[:GROUP] <file> - Change owner and group (USER:, :GROUP)");
+    outln!(ctx, "  chgrp GROUP <file> - Change group");
+    outln!(ctx, "    -R, -h      - Recursively / change symlinks themselves");
+    outln!(ctx, "    -v, -c, -f  - Print every file / only changes / suppress errors");
+    outln!(ctx, "  ln <target> [link] - Create a hard link (into [link] if it is a directory)");
+    outln!(ctx, "    -s, -r      - Create a symbolic link / with a target relative to the link");
+    outln!(ctx, "    -f          - Replace an existing file");
+    outln!(ctx, "    -n, -T, -t DIR - Treat a link to a directory as a file / never / link into DIR");
+    outln!(ctx, "    -v          - Print each created link");
+    outln!(ctx, "  du [path...]  - Show disk usage of directories (sortable table in the GUI)");
+    outln!(ctx, "    -a, -s, -c  - Include files / only totals / grand total");
+    outln!(ctx, "    -h, -b, -k, -m - Human-readable / bytes / KiB / MiB");
+    outln!(ctx, "    -d N        - Show directories at most N levels deep");
+    outln!(ctx, "    -L, -l, -x  - Follow symlinks / count hard links again / one file system");
+    outln!(ctx, "  df [file...]  - Show free space of mounted file systems");
+    outln!(ctx, "    -h, -i, -T  - Human-readable / inodes / file system type");
+    outln!(ctx, "    -a, -l      - All file systems / only local ones");
+    outln!(ctx, "    -t TYPE, -x TYPE, --total - Only / exclude TYPE, add a total line");
+    outln!(ctx, "  tree [dir...] - Show directory contents as a tree (collapsible in the GUI)");
+    outln!(ctx, "    -L N, -a, -d - Limit depth / include hidden files / directories only");
+    outln!(ctx, "    -I PATTERN  - Omit names matching PATTERN (alternatives separated by |)");
+    outln!(ctx, "    --gitignore - Omit files excluded by .gitignore");
+    outln!(ctx, "    -s, -h, -D  - Show size / human-readable size / modification time");
+    outln!(ctx, "    -J          - Print JSON");
+    outln!(ctx, "    --charset=ascii, --noreport - Draw ASCII branches / omit the summary line");
+
+    outln!(ctx, "  find [path...] [expr] - Search for files in a directory hierarchy");
+    outln!(ctx, "    -name, -iname, -path GLOB - Match the name or path with a wildcard");
+    outln!(ctx, "    -type [fdlpsbc] - Match the file type");
+    outln!(ctx, "    -size [+-]N[ckMG], -mtime [+-]N, -mmin [+-]N, -newer FILE, -empty");
+    outln!(ctx, "    -maxdepth N, -mindepth N - Limit the depth of the search");
+    outln!(ctx, "    ( ), !, -a, -o - Group, negate and combine tests");
+    outln!(ctx, "    -print, -print0, -delete, -prune, -quit - Actions");
+    outln!(ctx, "    -exec CMD {{}} ; / -exec CMD {{}} + - Run a builtin for each / all matches");
+    outln!(ctx, "    -L          - Follow symbolic links");
+    outln!(ctx, "  cat [file...] - Print files (standard input if none or '-')");
+    outln!(ctx, "    -n, -b      - Number all lines / non-blank lines");
+    outln!(ctx, "    -s          - Squeeze repeated blank lines");
+    outln!(ctx, "    -A          - Show tabs, line ends and non-printing characters (-vET)");
+    outln!(ctx, "  head [file...] - Print the first 10 lines");
+    outln!(ctx, "    -n N, -c N  - First N lines / bytes (-N: all but the last N)");
+    outln!(ctx, "    -q, -v      - Never / always print file name headers");
+    outln!(ctx, "  tail [file...] - Print the last 10 lines");
+    outln!(ctx, "    -n N, -c N  - Last N lines / bytes (+N: starting at line / byte N)");
+    outln!(ctx, "    -f          - Keep printing appended data, following rotated files");
+    outln!(ctx, "    -F          - Like -f, and wait for files that do not exist yet");
+    outln!(ctx, "    -s SECS     - Check interval while following (default 1)");
+    outln!(ctx, "    -q, -v      - Never / always print file name headers");
+    outln!(ctx, "  grep <pattern> [file...] - Print lines matching a regular expression");
+    outln!(ctx, "    -E, -F      - Extended regex / fixed strings (default: basic regex)");
+    outln!(ctx, "    -i, -v      - Ignore case / select non-matching lines");
+    outln!(ctx, "    -w, -x      - Match whole words / whole lines");
+    outln!(ctx, "    -n, -c, -o  - Line numbers / count only / print only the matched parts");
+    outln!(ctx, "    -l, -L      - Print names of files with / without matches");
+    outln!(ctx, "    -r, -R      - Search directories recursively, honoring .gitignore (--no-ignore)");
+    outln!(ctx, "    --include=GLOB, --exclude=GLOB, --exclude-dir=GLOB - Filter searched files");
+    outln!(ctx, "    -A N, -B N, -C N - Print N lines of context after / before / around matches");
+    outln!(ctx, "    -a, -I      - Treat binary files as text / skip them");
+    outln!(ctx, "    --color[=WHEN] - Highlight matches (WHEN: auto, always, never)");
+    outln!(ctx, "  wc [file...]  - Count lines, words and bytes");
+    outln!(ctx, "    -l, -w, -c  - Lines / words / bytes");
+    outln!(ctx, "    -m, -L      - Characters (UTF-8) / width of the longest line");
+    outln!(ctx, "  sort [file...] - Sort lines");
+    outln!(ctx, "    -n, -h, -g, -V - Numeric / human (2K, 1M) / float / version order");
+    outln!(ctx, "    -r, -f, -b  - Reverse / ignore case / ignore leading blanks");
+    outln!(ctx, "    -k POS1[,POS2] - Sort by a key (e.g. -k2,2n), -t SEP - Field separator");
+    outln!(ctx, "    -s, -u      - Stable sort / output only the first of equal lines");
+    outln!(ctx, "    -c          - Check whether input is sorted");
+    outln!(ctx, "    -o FILE     - Write the result to FILE");
+    outln!(ctx, "    -S SIZE, -T DIR - Memory to use before sorting via temporary files in DIR");
+    outln!(ctx, "  uniq [in [out]] - Merge adjacent identical lines");
+    outln!(ctx, "    -c          - Prefix lines with the number of occurrences");
+    outln!(ctx, "    -d, -D, -u  - Only repeated lines (one / all) / only unique lines");
+    outln!(ctx, "    -i, -f N, -s N, -w N - Ignore case / fields / chars, compare N chars");
+    outln!(ctx, "  cut [file...] - Print selected parts of lines");
+    outln!(ctx, "    -f LIST, -c LIST, -b LIST - Fields / characters / bytes (e.g. 1,3-5,7-)");
+    outln!(ctx, "    -d DELIM, -s - Field delimiter (default tab) / skip lines without it");
+    outln!(ctx, "    --complement, --output-delimiter=STR");
+    outln!(ctx, "  tr SET1 [SET2] - Translate, delete or squeeze characters from standard input");
+    outln!(ctx, "    -d, -s, -c  - Delete / squeeze repeats / complement SET1");
+    outln!(ctx, "                  Sets: a-z, [:alpha:], [:upper:], \\n, [x*N]");
+    outln!(ctx, "  echo [text...] - Print arguments separated by spaces");
+    outln!(ctx, "    -n, -e      - No trailing newline / interpret escapes (\\n, \\t, \\c, ...)");
+    outln!(ctx, "  printf FORMAT [arg...] - Print arguments according to FORMAT (repeated for extra args)");
+    outln!(ctx, "                  Conversions: %s %b %c %d %i %u %o %x %X %f %e %g %q %%");
+    outln!(ctx, "  test EXPR, [ EXPR ], [[ EXPR ]] - Evaluate a condition (exit status 0 if true)");
+    outln!(ctx, "    -e, -f, -d, -x, -r, -w, -s, -L FILE - File exists / is a file, directory, ...");
+    outln!(ctx, "    F1 -nt F2, F1 -ot F2 - Newer / older than");
+    outln!(ctx, "    -z, -n, =, !=, <, > - String tests ([[ == ]] matches wildcards, =~ regex)");
+    outln!(ctx, "    -eq, -ne, -lt, -le, -gt, -ge - Integer comparisons");
+    outln!(ctx, "    !, ( ), -a, -o - Negate, group and combine (&&, || inside [[ ]])");
+
+    outln!(ctx, "  --dry-run     - (rm, rmdir, mkdir, cp, mv, touch, ln, chmod,");
+    outln!(ctx, "                  chown, chgrp, find, trash-*, undo) Print planned changes only");
+
+    outln!(ctx, "\nPipelines and Command Lists:");
+    outln!(ctx, "  cmd1 | cmd2   - Connect builtins (e.g. ls | sort -r | head -n 3)");
+    outln!(ctx, "  cmd1; cmd2    - Run commands one after another");
+    outln!(ctx, "  cmd1 && cmd2  - Run cmd2 only if cmd1 succeeds (|| only if it fails)");
+
+    outln!(ctx, "\nTerminal Control:");
+    outln!(ctx, "  set -n / +n   - Turn dry-run mode on / off for all commands");
+    outln!(ctx, "  clear         - Clear screen");
+    outln!(ctx, "  help          - Show this help message");
 }
@@ -0,0 +1,292 @@
+//! lnコマンド。ハードリンクやシンボリックリンクを作る
+//!
+//! 使い方:
+//! - `ln TARGET LINK_NAME` TARGET へのリンク LINK_NAME を作る
+//! - `ln TARGET` カレントディレクトリに TARGET と同じ名前のリンクを作る
+//! - `ln TARGET... DIRECTORY`（`ln -t DIRECTORY TARGET...`）DIRECTORY の中に作る
+//!
+//! 対応オプション:
+//! - `-s` シンボリックリンクを作る（既定はハードリンク）
+//! - `-f` 既存のリンク先ファイルを削除してから作る
+//! - `-r` シンボリックリンクの参照先をリンクの場所からの相対パスにする（`-s` と一緒に使う）
+//! - `-n` LINK_NAME がディレクトリへのシンボリックリンクなら、ディレクトリでなく普通のファイルとして扱う
+//! - `-T` LINK_NAME を常にリンクの名前として扱う、`-t DIR` リンクを作るディレクトリを指定する
+//! - `-v` 作ったリンクを表示する
+
+use super::error_message;
+use crate::context::Context;
+use crate::fs_action::FsAction;
+use crate::quoting;
+use crate::{errln, outln};
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// コマンド名
+pub const NAME: &str = "ln";
+
+/// 解析済みのオプション
+struct Options {
+    symbolic: bool,
+    force: bool,
+    relative: bool,
+    no_dereference: bool,
+    no_target_directory: bool,
+    verbose: bool,
+}
+
+pub fn run(ctx: &mut Context, args: &[OsString]) {
+    let mut opts = Options {
+        symbolic: false,
+        force: false,
+        relative: false,
+        no_dereference: false,
+        no_target_directory: false,
+        verbose: false,
+    };
+    let mut target_directory: Option<&OsStr> = None;
+    let mut operands: Vec<&OsStr> = Vec::new();
+    let mut only_operands = false;
+    let mut args = args.iter();
+
+    while let Some(os_arg) = args.next() {
+        let arg = match os_arg.to_str() {
+            Some(arg) if !only_operands && arg.starts_with('-') && arg != "-" => arg,
+            _ => {
+                operands.push(os_arg);
+                continue;
+            }
+        };
+        match arg {
+            "--" => only_operands = true,
+            "--symbolic" => opts.symbolic = true,
+            "--force" => opts.force = true,
+            "--relative" => opts.relative = true,
+            "--no-dereference" => opts.no_dereference = true,
+            "--no-target-directory" => opts.no_target_directory = true,
+            "--verbose" => opts.verbose = true,
+            "--target-directory" => match args.next() {
+                Some(dir) => target_directory = Some(dir),
+                None => {
+                    errln!(ctx, "ln: option '--target-directory' requires an argument");
+                    return;
+                }
+            },
+            _ if arg.starts_with("--target-directory=") => {
+                target_directory = Some(OsStr::new(&arg["--target-directory=".len()..]));
+            }
+            _ if arg.starts_with("--") => {
+                errln!(ctx, "ln: unrecognized option '{}'", arg);
+                errln!(ctx, "Try 'ln --help' for more information.");
+                return;
+            }
+            _ => {
+                for (i, ch) in arg[1..].char_indices() {
+                    match ch {
+                        's' => opts.symbolic = true,
+                        'f' => opts.force = true,
+                        'r' => opts.relative = true,
+                        'n' => opts.no_dereference = true,
+                        'T' => opts.no_target_directory = true,
+                        'v' => opts.verbose = true,
+                        't' => {
+                            let rest = &arg[1 + i + 1..];
+                            if !rest.is_empty() {
+                                target_directory = Some(OsStr::new(rest));
+                            } else if let Some(dir) = args.next() {
+                                target_directory = Some(dir);
+                            } else {
+                                errln!(ctx, "ln: option requires an argument -- 't'");
+                                return;
+                            }
+                            break;
+                        }
+                        _ => {
+                            errln!(ctx, "ln: invalid option -- '{}'", ch);
+                            errln!(ctx, "Try 'ln --help' for more information.");
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // ドライランでは予定の操作だけを表示する
+    opts.verbose &= !ctx.dry_run;
+
+    if opts.relative && !opts.symbolic {
+        errln!(ctx, "ln: cannot do --relative without --symbolic");
+        return;
+    }
+    if target_directory.is_some() && opts.no_target_directory {
+        errln!(ctx, "ln: cannot combine --target-directory and --no-target-directory");
+        return;
+    }
+    if operands.is_empty() {
+        errln!(ctx, "ln: missing file operand");
+        errln!(ctx, "Try 'ln --help' for more information.");
+        return;
+    }
+
+    // (参照先, 作るリンク) の組を決める
+    let mut links: Vec<(&OsStr, PathBuf)> = Vec::new();
+    if let Some(dir) = target_directory {
+        if !ctx.fs.is_dir(&ctx.resolve(dir)) {
+            errln!(ctx, "ln: target '{}' is not a directory", quoting::display(dir));
+            return;
+        }
+        links.extend(operands.iter().map(|target| (*target, in_directory(dir, target))));
+    } else if operands.len() == 1 {
+        if opts.no_target_directory {
+            errln!(ctx, "ln: missing destination file operand after '{}'", quoting::display(operands[0]));
+            return;
+        }
+        links.push((operands[0], in_directory(OsStr::new("."), operands[0])));
+    } else {
+        let (last, targets) = operands.split_last().unwrap();
+        let last_path = ctx.resolve(last);
+        // -n ならディレクトリへのシンボリックリンクをディレクトリとみなさない
+        let is_dir = match ctx.fs.symlink_metadata(&last_path) {
+            Ok(meta) if meta.is_symlink() && opts.no_dereference => false,
+            Ok(_) => ctx.fs.is_dir(&last_path),
+            Err(_) => false,
+        };
+        if !opts.no_target_directory && is_dir {
+            links.extend(targets.iter().map(|target| (*target, in_directory(last, target))));
+        } else if targets.len() > 1 {
+            if opts.no_target_directory {
+                errln!(ctx, "ln: extra operand '{}'", quoting::display(operands[2]));
+            } else {
+                errln!(ctx, "ln: target '{}' is not a directory", quoting::display(last));
+            }
+            errln!(ctx, "Try 'ln --help' for more information.");
+            return;
+        } else {
+            links.push((targets[0], PathBuf::from(last)));
+        }
+    }
+
+    for (target, link) in links {
+        if ctx.interrupted() {
+            break;
+        }
+        if let Err(e) = make_link(ctx, target, &link, &opts) {
+            let shown = quoting::display(link.as_os_str());
+            if opts.symbolic {
+                errln!(ctx, "ln: failed to create symbolic link '{}': {}", shown, error_message(&e));
+            } else {
+                errln!(ctx, "ln: failed to create hard link '{}' => '{}': {}", shown, quoting::display(target), error_message(&e));
+            }
+        }
+    }
+}
+
+/// ディレクトリ `dir` の中の、`target` と同じ名前のパス
+fn in_directory(dir: &OsStr, target: &OsStr) -> PathBuf {
+    let target = Path::new(target);
+    match target.file_name() {
+        Some(name) => Path::new(dir).join(name),
+        None => Path::new(dir).join(target),
+    }
+}
+
+/// リンクを1つ作る
+fn make_link(ctx: &mut Context, target: &OsStr, link: &Path, opts: &Options) -> io::Result<()> {
+    let link_path = ctx.resolve(link);
+    let target_path = ctx.resolve(target);
+
+    if !opts.symbolic {
+        let meta = ctx.fs.metadata(&target_path)?;
+        if meta.is_dir() {
+            return Err(io::Error::other("hard link not allowed for directory"));
+        }
+    }
+
+    if let Ok(existing) = ctx.fs.symlink_metadata(&link_path) {
+        if !opts.force {
+            return Err(io::Error::from_raw_os_error(libc::EEXIST));
+        }
+        if existing.is_dir() {
+            return Err(io::Error::other("cannot overwrite directory"));
+        }
+        // 自分自身へのハードリンクで元のファイルを消さないようにする
+        if !opts.symbolic {
+            if let Ok(meta) = ctx.fs.metadata(&target_path) {
+                if (meta.dev(), meta.ino()) == (existing.dev(), existing.ino()) {
+                    return Err(io::Error::other("are the same file"));
+                }
+            }
+        }
+        ctx.perform(FsAction::RemoveFile(&link_path))?;
+    }
+
+    if opts.symbolic {
+        let stored = if opts.relative {
+            relative_target(ctx, &target_path, &link_path)
+        } else {
+            PathBuf::from(target)
+        };
+        ctx.perform(FsAction::Symlink { target: &stored, path: &link_path })?;
+        if opts.verbose {
+            outln!(ctx, "'{}' -> '{}'", quoting::display(link.as_os_str()), quoting::display(stored.as_os_str()));
+        }
+    } else {
+        ctx.perform(FsAction::HardLink { target: &target_path, path: &link_path })?;
+        if opts.verbose {
+            outln!(ctx, "'{}' => '{}'", quoting::display(link.as_os_str()), quoting::display(target));
+        }
+    }
+    Ok(())
+}
+
+/// リンクを置くディレクトリから見た `target` の相対パス（-r）。
+/// 両方とも実在する部分はシンボリックリンクを解決してから比べる
+fn relative_target(ctx: &Context, target: &Path, link: &Path) -> PathBuf {
+    let target = canonicalize_missing(ctx, target);
+    let link_dir = canonicalize_missing(ctx, link.parent().unwrap_or(Path::new("/")));
+
+    let target_parts: Vec<Component> = target.components().collect();
+    let dir_parts: Vec<Component> = link_dir.components().collect();
+    let common = target_parts.iter().zip(&dir_parts).take_while(|(a, b)| a == b).count();
+
+    let mut relative = PathBuf::new();
+    for _ in common..dir_parts.len() {
+        relative.push("..");
+    }
+    for part in &target_parts[common..] {
+        relative.push(part);
+    }
+    if relative.as_os_str().is_empty() {
+        relative.push(".");
+    }
+    relative
+}
+
+/// 存在する最も長い先頭部分だけを canonicalize し、残りは `.` `..` を字面で解決する
+fn canonicalize_missing(ctx: &Context, path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    let mut existing = normalized.as_path();
+    let mut rest: Vec<&OsStr> = Vec::new();
+    loop {
+        if let Ok(resolved) = ctx.fs.canonicalize(existing) {
+            return rest.iter().rev().fold(resolved, |path, name| path.join(name));
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                rest.push(name);
+                existing = parent;
+            }
+            _ => return normalized,
+        }
+    }
+}
@@ -1,44 +1,478 @@
-//! lsコマンドの簡易実装。ディレクトリの内容を表示する
+//! lsコマンド。ディレクトリの内容を表示する
+//!
+//! 対応オプション:
+//! - `-l` 詳細表示（パーミッション・リンク数・所有者・グループ・サイズ・更新日時・リンク先）
+//! - `-a` / `-A` 隠しファイルも表示（`-A` は `.` と `..` を除く）
+//! - `-h` サイズを人間向けの単位で表示
+//! - `-R` サブディレクトリを再帰的に表示
+//! - `-t` / `-S` / `-X` 更新日時・サイズ・拡張子順に並べる（`-r` で逆順）
+//! - `-1` 1行に1項目（端末以外への出力ではこれが既定。`ls | wc -l` などで使える）
+//! - `--color[=WHEN]` ファイル種別ごとに色付け
+//! - `--quoting-style=WORD` / `-Q` / `-b` / `-N` ファイル名のクォート形式
+//! - `-q` / `--show-control-chars` 表示できない文字を `?` にする・しない（既定は端末へ出力するときだけ `?`）
+//!
+//! ファイル名は OsStr のまま扱い、UTF-8 として不正な名前も省略せずに
+//! クォートして表示する（端末への出力時の既定は shell-escape 形式）。
+//! 端末以外への出力（`ls | ...`）では GNU と同じく名前のバイト列をそのまま書き出す。
+//!
+//! CLI版・GUI版とも、この実装を `Context` 経由で共有する。
 
-use std::fs;
+use super::error_message;
+use crate::columns;
+use crate::context::Context;
+use crate::file_info::{self, NameCache};
+use crate::ls_colors::{ColorMode, LsColors};
+use crate::quoting::{self, QuotingStyle};
+use crate::vfs::{Fs, Stat};
+use crate::{errln, outln};
+use std::cmp::Ordering;
+use std::ffi::{OsStr, OsString};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 /// コマンド名
 pub const NAME: &str = "ls";
 
+/// 並び順の基準
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SortKey {
+    Name,
+    Time,
+    Size,
+    Extension,
+}
+
+/// 隠しファイルの扱い
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Hidden {
+    /// `.` で始まる名前を表示しない（既定）
+    Hide,
+    /// `.` と `..` 以外を表示する（-A）
+    AlmostAll,
+    /// `.` と `..` も含めすべて表示する（-a）
+    All,
+}
+
+/// 解析済みのオプション
+struct Options {
+    long: bool,
+    hidden: Hidden,
+    human: bool,
+    recursive: bool,
+    sort: SortKey,
+    reverse: bool,
+    one_per_line: bool,
+    color: ColorMode,
+    /// 明示指定がなければ出力先に応じて決める
+    quoting: Option<QuotingStyle>,
+    /// 表示できない文字を `?` にするか（-q）。明示指定がなければ端末へ出力するときだけ
+    hide_control: Option<bool>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            long: false,
+            hidden: Hidden::Hide,
+            human: false,
+            recursive: false,
+            sort: SortKey::Name,
+            reverse: false,
+            one_per_line: false,
+            color: ColorMode::Auto,
+            quoting: None,
+            hide_control: None,
+        }
+    }
+}
+
+/// 表示対象の1項目
+struct Entry {
+    /// 名前（引数で指定された場合はその文字列）
+    name: OsString,
+    path: PathBuf,
+    /// シンボリックリンク自体の属性（lstat）
+    meta: Stat,
+}
+
+impl Entry {
+    /// ディレクトリか（-l 以外ではリンク先がディレクトリの場合も含む）
+    fn is_dir(&self, fs: &dyn Fs, follow_links: bool) -> bool {
+        if self.meta.is_dir() {
+            return true;
+        }
+        follow_links && self.meta.is_symlink() && fs.is_dir(&self.path)
+    }
+}
+
 /// 引数省略時はカレントディレクトリを表示する
-pub fn run(args: &[&str]) {
-    let dir = if args.is_empty() { "." } else { args[0] };
-
-    match fs::read_dir(dir) {
-        Ok(entries) => {
-            let mut files: Vec<String> = Vec::new();
-            for entry in entries.flatten() {
-                if let Some(name) = entry.file_name().to_str() {
-                    if entry.path().is_dir() {
-                        files.push(format!("{}/", name));
+pub fn run(ctx: &mut Context, args: &[OsString]) {
+    let mut opts = Options::default();
+    let mut operands: Vec<&OsStr> = Vec::new();
+    let mut only_operands = false;
+
+    for os_arg in args {
+        // UTF-8 でない引数はオプションになりえない
+        let arg = match os_arg.to_str() {
+            Some(arg) if !only_operands && arg.starts_with('-') && arg != "-" => arg,
+            _ => {
+                operands.push(os_arg);
+                continue;
+            }
+        };
+        if arg == "--" {
+            only_operands = true;
+        } else if let Some(when) = parse_color_option(arg) {
+            match when {
+                Some(mode) => opts.color = mode,
+                None => {
+                    errln!(ctx, "ls: invalid argument '{}' for '--color'", arg);
+                    errln!(ctx, "Valid arguments are: 'always', 'auto', 'never'");
+                    return;
+                }
+            }
+        } else if arg == "--hide-control-chars" || arg == "--show-control-chars" {
+            opts.hide_control = Some(arg == "--hide-control-chars");
+        } else if let Some(word) = arg.strip_prefix("--quoting-style=") {
+            match QuotingStyle::parse(word) {
+                Some(style) => opts.quoting = Some(style),
+                None => {
+                    errln!(ctx, "ls: invalid argument '{}' for '--quoting-style'", word);
+                    errln!(ctx, "Valid arguments are: 'literal', 'shell', 'shell-always', 'shell-escape', 'shell-escape-always', 'c', 'escape'");
+                    return;
+                }
+            }
+        } else if arg.starts_with("--") {
+            errln!(ctx, "ls: unrecognized option '{}'", arg);
+            return;
+        } else {
+            for ch in arg.chars().skip(1) {
+                match ch {
+                    'l' => opts.long = true,
+                    'a' => opts.hidden = Hidden::All,
+                    'A' => opts.hidden = Hidden::AlmostAll,
+                    'h' => opts.human = true,
+                    'R' => opts.recursive = true,
+                    't' => opts.sort = SortKey::Time,
+                    'S' => opts.sort = SortKey::Size,
+                    'X' => opts.sort = SortKey::Extension,
+                    'r' => opts.reverse = true,
+                    '1' => opts.one_per_line = true,
+                    'Q' => opts.quoting = Some(QuotingStyle::C),
+                    'b' => opts.quoting = Some(QuotingStyle::Escape),
+                    'N' => opts.quoting = Some(QuotingStyle::Literal),
+                    'q' => opts.hide_control = Some(true),
+                    _ => {
+                        errln!(ctx, "ls: invalid option -- '{}'", ch);
+                        errln!(ctx, "Try 'ls --help' for more information.");
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    if operands.is_empty() {
+        operands.push(OsStr::new("."));
+    }
+
+    // GNU ls と同じく、端末へは shell-escape、それ以外へは名前のバイト列をそのまま出力する
+    if opts.quoting.is_none() {
+        opts.quoting = Some(if ctx.is_tty { QuotingStyle::ShellEscape } else { QuotingStyle::Literal });
+    }
+    opts.hide_control.get_or_insert(ctx.is_tty);
+    // 複数列に並べるのも端末へ出力するときだけ
+    if !ctx.is_tty {
+        opts.one_per_line = true;
+    }
+    let colors = opts.color.enabled(ctx.is_tty).then(LsColors::from_env);
+    let mut lister = Lister {
+        opts,
+        colors,
+        names: NameCache::default(),
+        fs: Arc::clone(&ctx.fs),
+    };
+    lister.list_operands(ctx, &operands);
+}
+
+/// `--color` / `--color=WHEN` を解釈する。
+/// 該当しない引数なら None、WHEN が不正なら Some(None) を返す
+pub fn parse_color_option(arg: &str) -> Option<Option<ColorMode>> {
+    if arg == "--color" {
+        return Some(Some(ColorMode::Always));
+    }
+    arg.strip_prefix("--color=").map(ColorMode::parse)
+}
+
+/// 一覧表示の処理本体
+struct Lister {
+    opts: Options,
+    colors: Option<LsColors>,
+    names: NameCache,
+    fs: Arc<dyn Fs>,
+}
+
+impl Lister {
+    /// コマンドライン引数をファイルとディレクトリに分けて表示する。
+    /// ファイルをまとめて先に表示し、続いて各ディレクトリを見出し付きで表示する
+    fn list_operands(&mut self, ctx: &mut Context, operands: &[&OsStr]) {
+        let mut files = Vec::new();
+        let mut dirs = Vec::new();
+
+        for operand in operands {
+            let path = ctx.resolve(operand);
+            match self.fs.symlink_metadata(&path) {
+                Ok(meta) => {
+                    let entry = Entry {
+                        name: operand.to_os_string(),
+                        path,
+                        meta,
+                    };
+                    // -l 以外では引数のシンボリックリンクをたどる
+                    if entry.is_dir(self.fs.as_ref(), !self.opts.long) {
+                        dirs.push(entry);
                     } else {
-                        files.push(name.to_string());
+                        files.push(entry);
                     }
                 }
+                Err(e) => {
+                    errln!(ctx, "ls: cannot access '{}': {}", quoting::display(operand), error_message(&e));
+                }
+            }
+        }
+
+        let show_headers = operands.len() > 1 || self.opts.recursive;
+
+        self.sort(&mut files);
+        if !files.is_empty() {
+            self.print_entries(ctx, &files, None);
+        }
+
+        self.sort(&mut dirs);
+        for (i, dir) in dirs.iter().enumerate() {
+            if i > 0 || !files.is_empty() {
+                outln!(ctx);
+            }
+            self.list_directory(ctx, &dir.name, &dir.path, show_headers);
+        }
+    }
+
+    /// 1つのディレクトリの内容を表示する（-R なら再帰）
+    fn list_directory(&mut self, ctx: &mut Context, label: &OsStr, path: &Path, show_header: bool) {
+        if show_header {
+            let mut header = self.quote(label);
+            header.push(b':');
+            write_line(ctx, &header);
+        }
+
+        let mut entries = match self.read_entries(path) {
+            Ok(entries) => entries,
+            Err(e) => {
+                errln!(ctx, "ls: cannot open directory '{}': {}", quoting::display(label), error_message(&e));
+                return;
+            }
+        };
+        self.sort(&mut entries);
+
+        let total = entries.iter().map(|e| e.meta.blocks()).sum::<u64>();
+        self.print_entries(ctx, &entries, Some(total));
+
+        if self.opts.recursive {
+            for entry in &entries {
+                if entry.meta.is_dir() && entry.name != "." && entry.name != ".." {
+                    let child_label = Path::new(label).join(&entry.name);
+                    outln!(ctx);
+                    self.list_directory(ctx, child_label.as_os_str(), &entry.path, true);
+                }
             }
+        }
+    }
 
-            files.sort();
+    /// ディレクトリ内の項目を読み取り、隠しファイルの設定に従って絞り込む
+    fn read_entries(&self, dir: &Path) -> std::io::Result<Vec<Entry>> {
+        let mut entries = Vec::new();
 
-            // 20文字幅・4項目ごとに改行して表示する
-            let mut count = 0;
-            for file in files {
-                print!("{:<20}", file);
-                count += 1;
-                if count % 4 == 0 {
-                    println!();
+        if self.opts.hidden == Hidden::All {
+            for name in [".", ".."] {
+                let path = dir.join(name);
+                if let Ok(meta) = self.fs.symlink_metadata(&path) {
+                    entries.push(Entry {
+                        name: OsString::from(name),
+                        path,
+                        meta,
+                    });
                 }
             }
-            if count % 4 != 0 {
-                println!();
+        }
+
+        for name in self.fs.read_dir(dir)? {
+            if self.opts.hidden == Hidden::Hide && name.as_bytes().starts_with(b".") {
+                continue;
+            }
+            let path = dir.join(&name);
+            if let Ok(meta) = self.fs.symlink_metadata(&path) {
+                entries.push(Entry { name, path, meta });
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// オプションに従って並べ替える。同順位は名前順
+    fn sort(&self, entries: &mut [Entry]) {
+        let by_name = |a: &Entry, b: &Entry| a.name.cmp(&b.name);
+        entries.sort_by(|a, b| {
+            let order = match self.opts.sort {
+                SortKey::Name => Ordering::Equal,
+                // 新しいもの・大きいものが先
+                SortKey::Time => {
+                    let (a, b) = (a.meta.mtime(), b.meta.mtime());
+                    (b.secs, b.nanos).cmp(&(a.secs, a.nanos))
+                }
+                SortKey::Size => b.meta.len().cmp(&a.meta.len()),
+                SortKey::Extension => extension(&a.name).cmp(extension(&b.name)),
+            };
+            order.then_with(|| by_name(a, b))
+        });
+        if self.opts.reverse {
+            entries.reverse();
+        }
+    }
+
+    /// 項目を表示形式に応じて出力する。`total` はディレクトリ表示時のブロック数（512B単位）
+    fn print_entries(&mut self, ctx: &mut Context, entries: &[Entry], total: Option<u64>) {
+        if self.opts.long {
+            self.print_long(ctx, entries, total);
+        } else {
+            self.print_short(ctx, entries);
+        }
+    }
+
+    /// 名前のみの表示。`-1` なら1行1項目、それ以外は複数列
+    fn print_short(&self, ctx: &mut Context, entries: &[Entry]) {
+        let names: Vec<Vec<u8>> = entries
+            .iter()
+            .map(|entry| {
+                let mut shown = self.paint(entry);
+                if entry.meta.is_dir() {
+                    shown.push(b'/');
+                }
+                shown
+            })
+            .collect();
+
+        if self.opts.one_per_line {
+            for shown in names {
+                write_line(ctx, &shown);
             }
+            return;
         }
-        Err(e) => {
-            eprintln!("ls: cannot access '{}': {}", dir, e);
+
+        // 出力先の幅に収まる列数で縦優先に並べる
+        let names: Vec<&[u8]> = names.iter().map(Vec::as_slice).collect();
+        for line in columns::format_columns_bytes(&names, ctx.width) {
+            write_line(ctx, &line);
         }
     }
+
+    /// 詳細表示（-l）。各列の幅をそろえて出力する
+    fn print_long(&mut self, ctx: &mut Context, entries: &[Entry], total: Option<u64>) {
+        if let Some(blocks) = total {
+            // st_blocks は 512 バイト単位、表示は 1K 単位
+            let kib = blocks.div_ceil(2);
+            if self.opts.human {
+                outln!(ctx, "total {}", file_info::human_size(kib * 1024));
+            } else {
+                outln!(ctx, "total {}", kib);
+            }
+        }
+
+        let mut rows: Vec<[String; 6]> = Vec::with_capacity(entries.len());
+        for entry in entries {
+            rows.push([
+                file_info::mode_string(&entry.meta),
+                entry.meta.nlink().to_string(),
+                self.names.user(entry.meta.uid()).to_string(),
+                self.names.group(entry.meta.gid()).to_string(),
+                self.size_column(&entry.meta),
+                file_info::ls_time(entry.meta.mtime().secs),
+            ]);
+        }
+
+        let width = |col: usize| rows.iter().map(|r| r[col].chars().count()).max().unwrap_or(0);
+        let (nlink_w, user_w, group_w, size_w) = (width(1), width(2), width(3), width(4));
+
+        for (entry, row) in entries.iter().zip(&rows) {
+            let mut name = self.paint(entry);
+            if entry.meta.is_symlink() {
+                if let Ok(target) = self.fs.read_link(&entry.path) {
+                    let target_name = self.quote(target.as_os_str());
+                    let target_path = entry.path.parent().unwrap_or(Path::new(".")).join(&target);
+                    let shown = match &self.colors {
+                        Some(colors) => colors.paint_bytes(self.fs.as_ref(), &target_name, &target_path),
+                        None => target_name,
+                    };
+                    name.extend_from_slice(b" -> ");
+                    name.extend_from_slice(&shown);
+                }
+            }
+            let mut line = format!(
+                "{} {:>nlink_w$} {:<user_w$} {:<group_w$} {:>size_w$} {} ",
+                row[0],
+                row[1],
+                row[2],
+                row[3],
+                row[4],
+                row[5],
+            )
+            .into_bytes();
+            line.extend_from_slice(&name);
+            write_line(ctx, &line);
+        }
+    }
+
+    /// サイズ列。デバイスファイルは "major, minor" を表示する
+    fn size_column(&self, meta: &Stat) -> String {
+        let file_type = meta.file_type();
+        if file_type.is_block_device() || file_type.is_char_device() {
+            let (major, minor) = file_info::device_numbers(meta.rdev());
+            return format!("{}, {}", major, minor);
+        }
+        if self.opts.human {
+            file_info::human_size(meta.len())
+        } else {
+            meta.len().to_string()
+        }
+    }
+
+    /// 名前をクォートし、色付けが有効なら装飾する
+    fn paint(&self, entry: &Entry) -> Vec<u8> {
+        let shown = self.quote(&entry.name);
+        match &self.colors {
+            Some(colors) => colors.paint_bytes_with_metadata(self.fs.as_ref(), &shown, &entry.path, &entry.meta),
+            None => shown,
+        }
+    }
+
+    /// 指定の形式で名前をクォートする（literal で -q でなければ元のバイト列のまま）
+    fn quote(&self, name: &OsStr) -> Vec<u8> {
+        let style = self.opts.quoting.unwrap_or(QuotingStyle::Literal);
+        quoting::quote_bytes(name, style, self.opts.hide_control.unwrap_or(true))
+    }
+}
+
+/// 1行を書き出す（名前は UTF-8 とは限らないのでバイト列のまま）
+fn write_line(ctx: &mut Context, line: &[u8]) {
+    let _ = ctx.stdout.write_all(line);
+    let _ = ctx.stdout.write_all(b"\n");
+}
+
+/// 拡張子（最後の `.` 以降）。先頭の `.` は隠しファイルの印なので拡張子とみなさない
+fn extension(name: &OsStr) -> &[u8] {
+    let bytes = name.as_bytes();
+    match bytes.iter().rposition(|&b| b == b'.') {
+        Some(0) | None => b"",
+        Some(i) => &bytes[i + 1..],
+    }
 }
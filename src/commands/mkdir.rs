@@ -1,28 +1,194 @@
 //! mkdirコマンド。指定したディレクトリを作成する
+//!
+//! 対応オプション:
+//! - `-p` 親ディレクトリもまとめて作成し、既に存在してもエラーにしない
+//! - `-m MODE` 作成するディレクトリのパーミッション（`755` や `u=rwx,go=rx` など chmod 形式）
+//! - `-v` 作成したディレクトリを表示する
 
-use std::fs;
-use std::path::Path;
+use super::error_message;
+use crate::context::Context;
+use crate::fs_action::FsAction;
+use crate::fs_util;
+use crate::journal::Recorder;
+use crate::mode::Mode;
+use crate::quoting;
+use crate::{errln, outln};
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::path::{Path, PathBuf};
 
 /// コマンド名
 pub const NAME: &str = "mkdir";
 
-pub fn run(args: &[&str]) {
-    if args.is_empty() {
-        eprintln!("mkdir: missing operand");
-        eprintln!("Try 'mkdir --help' for more information.");
+/// 解析済みのオプション
+struct Options {
+    parents: bool,
+    verbose: bool,
+    /// -m で指定したモード（umask 適用前の 0777 を基準に解釈する）
+    mode: Option<Mode>,
+}
+
+pub fn run(ctx: &mut Context, args: &[OsString]) {
+    let mut opts = Options {
+        parents: false,
+        verbose: false,
+        mode: None,
+    };
+    let mut dirs: Vec<&OsStr> = Vec::new();
+    let mut only_operands = false;
+    let mut recorder = ctx.recorder(NAME, args);
+    let mut args = args.iter();
+
+    while let Some(os_arg) = args.next() {
+        let arg = match os_arg.to_str() {
+            Some(arg) if !only_operands && arg.starts_with('-') && arg != "-" => arg,
+            _ => {
+                dirs.push(os_arg);
+                continue;
+            }
+        };
+        // -m の引数（-m MODE / -mMODE / --mode=MODE / --mode MODE）
+        let mut mode_spec: Option<&OsStr> = None;
+        match arg {
+            "--" => only_operands = true,
+            "--parents" => opts.parents = true,
+            "--verbose" => opts.verbose = true,
+            "--mode" => match args.next() {
+                Some(spec) => mode_spec = Some(spec),
+                None => {
+                    errln!(ctx, "mkdir: option '--mode' requires an argument");
+                    return;
+                }
+            },
+            _ if arg.starts_with("--mode=") => mode_spec = Some(OsStr::new(&arg["--mode=".len()..])),
+            _ if arg.starts_with("--") => {
+                errln!(ctx, "mkdir: unrecognized option '{}'", arg);
+                return;
+            }
+            _ => {
+                for (i, ch) in arg[1..].char_indices() {
+                    match ch {
+                        'p' => opts.parents = true,
+                        'v' => opts.verbose = true,
+                        'm' => {
+                            let rest = &arg[1 + i + 1..];
+                            if !rest.is_empty() {
+                                mode_spec = Some(OsStr::new(rest));
+                            } else if let Some(spec) = args.next() {
+                                mode_spec = Some(spec);
+                            } else {
+                                errln!(ctx, "mkdir: option requires an argument -- 'm'");
+                                return;
+                            }
+                            break;
+                        }
+                        _ => {
+                            errln!(ctx, "mkdir: invalid option -- '{}'", ch);
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+        if let Some(spec) = mode_spec {
+            match spec.to_str().and_then(Mode::parse) {
+                Some(mode) => opts.mode = Some(mode),
+                None => {
+                    errln!(ctx, "mkdir: invalid mode '{}'", quoting::display(spec));
+                    return;
+                }
+            }
+        }
+    }
+
+    // ドライランでは予定の操作だけを表示する
+    opts.verbose &= !ctx.dry_run;
+
+    if dirs.is_empty() {
+        errln!(ctx, "mkdir: missing operand");
+        errln!(ctx, "Try 'mkdir --help' for more information.");
         return;
     }
 
-    for dir in args {
-        let path = Path::new(dir);
+    let umask = fs_util::umask();
+    // -m の指定は chmod と同じく 0777 を基準に適用する（対象省略時のみ umask が効く）
+    let mode = opts.mode.as_ref().map(|mode| mode.apply(0o777, true, umask));
 
-        if path.exists() {
-            eprintln!("mkdir: cannot create directory '{}': File exists", dir);
-            continue;
+    for dir in dirs {
+        let path = ctx.resolve(dir);
+        let result = if opts.parents {
+            create_parents(ctx, &path, Path::new(dir), mode, umask, opts.verbose, recorder.as_mut())
+        } else {
+            ctx.perform(FsAction::CreateDir { path: &path, mode }).map(|_| {
+                if let Some(recorder) = recorder.as_mut() {
+                    recorder.created(&path);
+                }
+                if opts.verbose {
+                    outln!(ctx, "mkdir: created directory '{}'", quoting::display(dir));
+                }
+            })
+        };
+        if let Err(e) = result {
+            errln!(ctx, "mkdir: cannot create directory '{}': {}", quoting::display(dir), error_message(&e));
         }
+    }
+
+    if let Some(recorder) = recorder {
+        if let Err(e) = recorder.commit() {
+            errln!(ctx, "mkdir: cannot record for undo: {}", error_message(&e));
+        }
+    }
+}
+
+/// 親ディレクトリも含めて作る（-p）。既に存在するディレクトリはそのままにする。
+/// 途中の親は umask に従ったモードに所有者の書き込み・実行権を加えて作る
+fn create_parents(
+    ctx: &mut Context,
+    path: &Path,
+    shown: &Path,
+    mode: Option<u32>,
+    umask: u32,
+    verbose: bool,
+    mut recorder: Option<&mut Recorder>,
+) -> io::Result<()> {
+    // 作る必要がある（存在しない）部分を末尾から集める
+    let mut missing: Vec<(PathBuf, PathBuf)> = Vec::new();
+    let (mut current, mut current_shown) = (path.to_path_buf(), shown.to_path_buf());
+    loop {
+        match ctx.fs.metadata(&current) {
+            Ok(meta) if meta.is_dir() => break,
+            Ok(_) => return Err(io::Error::from_raw_os_error(libc::EEXIST)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        missing.push((current.clone(), current_shown.clone()));
+        match (current.parent(), current.file_name()) {
+            (Some(parent), Some(_)) => {
+                current = parent.to_path_buf();
+                current_shown = current_shown.parent().map(Path::to_path_buf).unwrap_or_default();
+            }
+            _ => break,
+        }
+    }
 
-        if let Err(e) = fs::create_dir(path) {
-            eprintln!("mkdir: cannot create directory '{}': {}", dir, e);
+    let parent_mode = (0o777 & !umask) | 0o300;
+    for (i, (dir, dir_shown)) in missing.iter().rev().enumerate() {
+        let is_last = i + 1 == missing.len();
+        let mode = if is_last { mode } else { Some(parent_mode) };
+        let result = ctx.perform(FsAction::CreateDir { path: dir, mode });
+        match result {
+            Ok(()) => {
+                if let Some(recorder) = recorder.as_deref_mut() {
+                    recorder.created(dir);
+                }
+                if verbose {
+                    outln!(ctx, "mkdir: created directory '{}'", quoting::display(dir_shown.as_os_str()));
+                }
+            }
+            // 他のプロセスが先に作った場合
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists && ctx.fs.is_dir(dir) => {}
+            Err(e) => return Err(e),
         }
     }
+    Ok(())
 }
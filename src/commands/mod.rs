@@ -1,45 +1,163 @@
 //! ビルトインコマンドをまとめるモジュール
 //!
 //! 1コマンド1ファイル構成。各モジュールは自身の名前 `NAME` と
-//! 実行関数 `run(&[&str])` を公開する。ここでそれらをテーブル化し、
+//! 実行関数 `run(&mut Context, &[OsString])` を公開する。ここでそれらをテーブル化し、
 //! 名前からの検索・実行（dispatch）を提供する。
+//!
+//! ファイルを変更するコマンドの `--dry-run` はここで取り除き、
+//! `Context::dry_run` を立てて実行する（各コマンドは `Context::perform` を通して変更する）。
 
+pub mod cat;
 pub mod cd;
+pub mod chmod;
+pub mod chown;
 pub mod clear;
+pub mod cp;
+pub mod cut;
+pub mod df;
+pub mod dirs;
+pub mod du;
+pub mod echo;
+pub mod find;
+pub mod grep;
+pub mod head;
 pub mod help;
+pub mod ln;
 pub mod ls;
 pub mod mkdir;
+pub mod mv;
+pub mod popd;
+pub mod printf;
+pub mod pushd;
 pub mod pwd;
 pub mod rm;
 pub mod rmdir;
+pub mod set;
+pub mod sort;
+pub mod stat;
+pub mod tail;
+pub mod test;
+pub mod touch;
+pub mod tr;
+pub mod trash_empty;
+pub mod trash_list;
+pub mod trash_restore;
+pub mod tree;
+pub mod undo;
+pub mod uniq;
+pub mod wc;
+pub mod z;
+pub mod zi;
+
+use crate::context::Context;
+use std::ffi::OsString;
 
 /// 1つのビルトインコマンドを表す（名前と実行関数の組）
 pub struct Command {
     pub name: &'static str,
-    pub run: fn(&[&str]),
+    pub run: fn(&mut Context, &[OsString]),
+    /// `--dry-run` に対応するか（ファイルを変更するコマンド）
+    pub dry_run: bool,
+}
+
+impl Command {
+    /// コマンドを実行する。`--dry-run` が指定されていれば、その間だけドライランにする。
+    /// 終了ステータスは 0 から始め、コマンドがエラーを表示するか自分で設定したものになる
+    pub fn call(&self, ctx: &mut Context, args: &[OsString]) {
+        ctx.status = 0;
+        if !self.dry_run {
+            return (self.run)(ctx, args);
+        }
+        // `--` より後の "--dry-run" はファイル名として残す
+        let end = args.iter().position(|arg| arg == "--").unwrap_or(args.len());
+        if !args[..end].iter().any(|arg| arg == "--dry-run") {
+            return (self.run)(ctx, args);
+        }
+        let args: Vec<OsString> = args[..end]
+            .iter()
+            .filter(|arg| *arg != "--dry-run")
+            .chain(&args[end..])
+            .cloned()
+            .collect();
+        let saved = ctx.dry_run;
+        ctx.dry_run = true;
+        (self.run)(ctx, &args);
+        ctx.dry_run = saved;
+    }
 }
 
 /// 登録済みビルトインコマンド一覧。
 /// 新しいコマンドはモジュールを追加し、ここに1行足すだけでよい。
 pub const COMMANDS: &[Command] = &[
-    Command { name: cd::NAME, run: cd::run },
-    Command { name: pwd::NAME, run: pwd::run },
-    Command { name: ls::NAME, run: ls::run },
-    Command { name: rm::NAME, run: rm::run },
-    Command { name: rmdir::NAME, run: rmdir::run },
-    Command { name: mkdir::NAME, run: mkdir::run },
-    Command { name: clear::NAME, run: clear::run },
-    Command { name: help::NAME, run: help::run },
+    Command { name: cd::NAME, run: cd::run, dry_run: false },
+    Command { name: pwd::NAME, run: pwd::run, dry_run: false },
+    Command { name: pushd::NAME, run: pushd::run, dry_run: false },
+    Command { name: popd::NAME, run: popd::run, dry_run: false },
+    Command { name: dirs::NAME, run: dirs::run, dry_run: false },
+    Command { name: z::NAME, run: z::run, dry_run: false },
+    Command { name: zi::NAME, run: zi::run, dry_run: false },
+    Command { name: ls::NAME, run: ls::run, dry_run: false },
+    Command { name: cp::NAME, run: cp::run, dry_run: true },
+    Command { name: mv::NAME, run: mv::run, dry_run: true },
+    Command { name: rm::NAME, run: rm::run, dry_run: true },
+    Command { name: rmdir::NAME, run: rmdir::run, dry_run: true },
+    Command { name: mkdir::NAME, run: mkdir::run, dry_run: true },
+    Command { name: touch::NAME, run: touch::run, dry_run: true },
+    Command { name: ln::NAME, run: ln::run, dry_run: true },
+    Command { name: chmod::NAME, run: chmod::run, dry_run: true },
+    Command { name: chown::NAME, run: chown::run, dry_run: true },
+    Command { name: chown::CHGRP, run: chown::run_chgrp, dry_run: true },
+    Command { name: stat::NAME, run: stat::run, dry_run: false },
+    Command { name: du::NAME, run: du::run, dry_run: false },
+    Command { name: df::NAME, run: df::run, dry_run: false },
+    Command { name: tree::NAME, run: tree::run, dry_run: false },
+    Command { name: cat::NAME, run: cat::run, dry_run: false },
+    Command { name: head::NAME, run: head::run, dry_run: false },
+    Command { name: tail::NAME, run: tail::run, dry_run: false },
+    Command { name: grep::NAME, run: grep::run, dry_run: false },
+    Command { name: find::NAME, run: find::run, dry_run: true },
+    Command { name: wc::NAME, run: wc::run, dry_run: false },
+    Command { name: sort::NAME, run: sort::run, dry_run: false },
+    Command { name: uniq::NAME, run: uniq::run, dry_run: false },
+    Command { name: cut::NAME, run: cut::run, dry_run: false },
+    Command { name: tr::NAME, run: tr::run, dry_run: false },
+    Command { name: echo::NAME, run: echo::run, dry_run: false },
+    Command { name: printf::NAME, run: printf::run, dry_run: false },
+    Command { name: test::NAME, run: test::run, dry_run: false },
+    Command { name: test::BRACKET, run: test::run_bracket, dry_run: false },
+    Command { name: test::DOUBLE_BRACKET, run: test::run_double_bracket, dry_run: false },
+    Command { name: trash_list::NAME, run: trash_list::run, dry_run: false },
+    Command { name: trash_restore::NAME, run: trash_restore::run, dry_run: true },
+    Command { name: trash_empty::NAME, run: trash_empty::run, dry_run: true },
+    Command { name: undo::NAME, run: undo::run, dry_run: true },
+    Command { name: set::NAME, run: set::run, dry_run: false },
+    Command { name: clear::NAME, run: clear::run, dry_run: false },
+    Command { name: help::NAME, run: help::run, dry_run: false },
 ];
 
+/// 名前に一致するコマンドを探す
+pub fn find(name: &str) -> Option<&'static Command> {
+    COMMANDS.iter().find(|cmd| cmd.name == name)
+}
+
 /// 名前に一致するコマンドを実行する。
 /// 該当コマンドがなければ何もせず false を返す。
-pub fn dispatch(name: &str, args: &[&str]) -> bool {
-    for cmd in COMMANDS {
-        if cmd.name == name {
-            (cmd.run)(args);
-            return true;
+pub fn dispatch(ctx: &mut Context, name: &str, args: &[OsString]) -> bool {
+    match find(name) {
+        Some(cmd) => {
+            cmd.call(ctx, args);
+            true
         }
+        None => false,
+    }
+}
+
+/// I/O エラーの表示用メッセージ。
+/// "No such file or directory (os error 2)" の末尾のエラー番号を除く
+pub fn error_message(e: &std::io::Error) -> String {
+    let message = e.to_string();
+    match message.find(" (os error") {
+        Some(i) => message[..i].to_string(),
+        None => message,
     }
-    false
 }
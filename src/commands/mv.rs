@@ -0,0 +1,304 @@
+//! mvコマンド。ファイル・ディレクトリを移動（名前を変更）する
+//!
+//! 対応オプション:
+//! - `-f` / `-i` / `-n` 既存ファイルを確認せず上書き・確認してから上書き・上書きしない（最後の指定が有効）
+//! - `-t DIR` / `--target-directory=DIR` 移動先のディレクトリを先に指定する
+//! - `-v` 移動したファイルを表示する
+//!
+//! まず rename(2) で移動し、ファイルシステムをまたぐ場合（EXDEV）は
+//! 移動先と同じディレクトリの一時名へコピーしてから rename で置き換え、最後に移動元を削除する。
+//! コピーが途中で失敗した場合は一時ファイルを消して移動元を残すため、中途半端な状態にはならない。
+//!
+//! undo が有効なら、上書きされる移動先は先に退避場所へ移しておく。
+
+use super::cp::{self, CopyOptions, Copier, Overwrite, Reflink};
+use super::error_message;
+use crate::context::Context;
+use crate::fs_action::FsAction;
+use crate::journal::{Recorder, Stash};
+use crate::quoting;
+use crate::{errln, outln};
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+
+/// コマンド名
+pub const NAME: &str = "mv";
+
+/// 解析済みのオプション
+struct Options {
+    overwrite: Overwrite,
+    verbose: bool,
+    /// -t で指定した移動先ディレクトリ
+    target_dir: Option<OsString>,
+}
+
+pub fn run(ctx: &mut Context, args: &[OsString]) {
+    let mut opts = Options {
+        overwrite: Overwrite::Default,
+        verbose: false,
+        target_dir: None,
+    };
+    let mut operands: Vec<&OsStr> = Vec::new();
+    let mut only_operands = false;
+    let recorder = ctx.recorder(NAME, args);
+    let mut args = args.iter();
+
+    while let Some(os_arg) = args.next() {
+        let arg = match os_arg.to_str() {
+            Some(arg) if !only_operands && arg.starts_with('-') && arg != "-" => arg,
+            _ => {
+                operands.push(os_arg);
+                continue;
+            }
+        };
+        match arg {
+            "--" => only_operands = true,
+            "--force" => opts.overwrite = Overwrite::Force,
+            "--interactive" => opts.overwrite = Overwrite::Interactive,
+            "--no-clobber" => opts.overwrite = Overwrite::NoClobber,
+            "--verbose" => opts.verbose = true,
+            "--target-directory" => match args.next() {
+                Some(dir) => opts.target_dir = Some(dir.clone()),
+                None => {
+                    errln!(ctx, "mv: option '--target-directory' requires an argument");
+                    return;
+                }
+            },
+            _ if arg.starts_with("--target-directory=") => {
+                opts.target_dir = Some(OsString::from(&arg["--target-directory=".len()..]));
+            }
+            _ if arg.starts_with("--") => {
+                errln!(ctx, "mv: unrecognized option '{}'", arg);
+                return;
+            }
+            _ => {
+                for (i, ch) in arg[1..].char_indices() {
+                    match ch {
+                        'f' => opts.overwrite = Overwrite::Force,
+                        'i' => opts.overwrite = Overwrite::Interactive,
+                        'n' => opts.overwrite = Overwrite::NoClobber,
+                        'v' => opts.verbose = true,
+                        // -t DIR または -tDIR
+                        't' => {
+                            let rest = &arg[1 + i + 1..];
+                            if !rest.is_empty() {
+                                opts.target_dir = Some(OsString::from(rest));
+                            } else if let Some(dir) = args.next() {
+                                opts.target_dir = Some(dir.clone());
+                            } else {
+                                errln!(ctx, "mv: option requires an argument -- 't'");
+                                return;
+                            }
+                            break;
+                        }
+                        _ => {
+                            errln!(ctx, "mv: invalid option -- '{}'", ch);
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // ドライランでは予定の操作だけを表示する
+    opts.verbose &= !ctx.dry_run;
+
+    // 移動先ディレクトリと移動元の組を決める
+    let (target, sources, into_dir) = match &opts.target_dir {
+        Some(dir) => {
+            if !ctx.fs.is_dir(&ctx.resolve(dir)) {
+                errln!(ctx, "mv: target directory '{}' is not a directory", quoting::display(dir));
+                return;
+            }
+            (Path::new(dir.as_os_str()), &operands[..], true)
+        }
+        None => match operands.split_last() {
+            None => {
+                errln!(ctx, "mv: missing file operand");
+                errln!(ctx, "Try 'mv --help' for more information.");
+                return;
+            }
+            Some((target, [])) => {
+                errln!(ctx, "mv: missing destination file operand after '{}'", quoting::display(target));
+                return;
+            }
+            Some((target, sources)) => {
+                let into_dir = ctx.fs.is_dir(&ctx.resolve(target));
+                if sources.len() > 1 && !into_dir {
+                    errln!(ctx, "mv: target '{}' is not a directory", quoting::display(target));
+                    return;
+                }
+                (Path::new(*target), sources, into_dir)
+            }
+        },
+    };
+    if sources.is_empty() {
+        errln!(ctx, "mv: missing file operand");
+        return;
+    }
+
+    let mut mover = Mover {
+        opts: &opts,
+        copier: Copier::new(
+            NAME,
+            CopyOptions {
+                recursive: true,
+                preserve: true,
+                overwrite: Overwrite::Default,
+                dereference: false,
+                reflink: Reflink::Auto,
+                verbose: false,
+            },
+        ),
+        recorder,
+    };
+    for source in sources {
+        let src_shown = Path::new(source);
+        let dst_shown: PathBuf = match src_shown.file_name() {
+            Some(name) if into_dir => target.join(name),
+            _ => target.to_path_buf(),
+        };
+        mover.move_one(ctx, src_shown, &dst_shown);
+    }
+    mover.copier.finish(ctx);
+
+    if let Some(recorder) = mover.recorder {
+        if let Err(e) = recorder.commit() {
+            errln!(ctx, "mv: cannot record for undo: {}", error_message(&e));
+        }
+    }
+}
+
+/// 1つずつ移動を行う
+struct Mover<'a> {
+    opts: &'a Options,
+    /// ファイルシステムをまたぐ移動で使うコピー処理
+    copier: Copier,
+    /// undo 用の記録
+    recorder: Option<Recorder>,
+}
+
+impl Mover<'_> {
+    /// `src_shown` を `dst_shown` へ移動する（どちらもカレントディレクトリ基準）
+    fn move_one(&mut self, ctx: &mut Context, src_shown: &Path, dst_shown: &Path) {
+        let src = ctx.resolve(src_shown);
+        let dst = ctx.resolve(dst_shown);
+        let (src_name, dst_name) = (shown(src_shown), shown(dst_shown));
+
+        let src_meta = match ctx.fs.symlink_metadata(&src) {
+            Ok(meta) => meta,
+            Err(e) => {
+                errln!(ctx, "mv: cannot stat '{}': {}", src_name, error_message(&e));
+                return;
+            }
+        };
+
+        let dst_meta = ctx.fs.symlink_metadata(&dst).ok();
+        if let Some(dst_meta) = &dst_meta {
+            if dst_meta.dev() == src_meta.dev() && dst_meta.ino() == src_meta.ino() {
+                errln!(ctx, "mv: '{}' and '{}' are the same file", src_name, dst_name);
+                return;
+            }
+            // rename(2) と同じく、ディレクトリとそれ以外は互いに置き換えない
+            if dst_meta.is_dir() && !src_meta.is_dir() {
+                errln!(ctx, "mv: cannot overwrite directory '{}' with non-directory", dst_name);
+                return;
+            }
+            if !dst_meta.is_dir() && src_meta.is_dir() {
+                errln!(ctx, "mv: cannot overwrite non-directory '{}' with directory '{}'", dst_name, src_name);
+                return;
+            }
+            match self.opts.overwrite {
+                Overwrite::NoClobber => return,
+                Overwrite::Interactive => {
+                    if !ctx.confirm(&format!("mv: overwrite '{}'? ", dst_name)) {
+                        return;
+                    }
+                }
+                Overwrite::Default | Overwrite::Force => {}
+            }
+        }
+
+        if src_meta.is_dir() && cp::is_inside(ctx.fs.as_ref(), &src, &dst) {
+            errln!(ctx, "mv: cannot move '{}' to a subdirectory of itself, '{}'", src_name, dst_name);
+            return;
+        }
+
+        // 上書きされる移動先を退避しておく
+        let overwritten: Option<Stash> = match (&mut self.recorder, &dst_meta) {
+            (Some(recorder), Some(_)) => Some(recorder.stash_existing(&dst, false)),
+            _ => None,
+        };
+
+        let moved = match ctx.perform(FsAction::Rename { from: &src, to: &dst }) {
+            Ok(()) => true,
+            Err(e) if e.raw_os_error() == Some(libc::EXDEV) => self.move_across(ctx, &src, &dst, src_shown, dst_shown),
+            Err(e) => {
+                errln!(ctx, "mv: cannot move '{}' to '{}': {}", src_name, dst_name, error_message(&e));
+                false
+            }
+        };
+        if let Some(recorder) = self.recorder.as_mut() {
+            match (moved, overwritten) {
+                (true, overwritten) => recorder.moved(&src, &dst, overwritten),
+                (false, Some(stash)) => recorder.unstash(&stash, &dst),
+                (false, None) => {}
+            }
+        }
+        if moved && self.opts.verbose {
+            outln!(ctx, "renamed '{}' -> '{}'", src_name, dst_name);
+        }
+    }
+
+    /// ファイルシステムをまたいで移動する。
+    /// 一時名へのコピーがすべて成功したときだけ移動先を置き換え、移動元を削除する。
+    /// 移動先を置き換えられたら true（エラーはここで表示する）
+    fn move_across(
+        &mut self,
+        ctx: &mut Context,
+        src: &Path,
+        dst: &Path,
+        src_shown: &Path,
+        dst_shown: &Path,
+    ) -> bool {
+        let temp = temp_path(dst);
+        self.copier.add_total(ctx, src);
+        // コピー中のエラーは Copier が表示する
+        if !self.copier.copy(ctx, src, &temp, src_shown, dst_shown) {
+            let _ = ctx.fs.remove_all(&temp);
+            return false;
+        }
+
+        // 既存の移動先（空のディレクトリを含む）は rename で置き換わる
+        if let Err(e) = ctx.perform(FsAction::Rename { from: &temp, to: dst }) {
+            let _ = ctx.fs.remove_all(&temp);
+            errln!(
+                ctx,
+                "mv: cannot move '{}' to '{}': {}",
+                shown(src_shown),
+                shown(dst_shown),
+                error_message(&e)
+            );
+            return false;
+        }
+
+        if let Err(e) = ctx.fs.remove_all(src) {
+            errln!(ctx, "mv: cannot remove '{}': {}", shown(src_shown), error_message(&e));
+        }
+        true
+    }
+}
+
+/// 移動先と同じディレクトリに置く一時名（隠しファイル）
+fn temp_path(dst: &Path) -> PathBuf {
+    let mut name = OsString::from(".");
+    name.push(dst.file_name().unwrap_or(OsStr::new("mv")));
+    name.push(format!(".mv-tmp-{}", std::process::id()));
+    dst.with_file_name(name)
+}
+
+/// メッセージ用の名前
+fn shown(path: &Path) -> String {
+    quoting::display(path.as_os_str())
+}
@@ -0,0 +1,67 @@
+//! popdコマンド。ディレクトリスタックから取り除く
+//!
+//! 使い方:
+//! - `popd` スタックの先頭を取り除き、そこへ移動する
+//! - `popd +N`（`-N`）先頭（末尾）から N 番目を取り除く（`+0` は `popd` と同じ）
+//! - `popd -n` 移動せず、カレントディレクトリの次の項目を取り除く
+//!
+//! 成功したら `dirs` と同じ形でスタックを表示する。
+
+use super::cd::change_directory;
+use super::dirs::print_stack;
+use crate::context::Context;
+use crate::dir_stack;
+use crate::errln;
+use std::ffi::OsString;
+
+/// コマンド名
+pub const NAME: &str = "popd";
+
+pub fn run(ctx: &mut Context, args: &[OsString]) {
+    let mut no_change = false;
+    let mut index = None;
+
+    for os_arg in args {
+        let arg = os_arg.to_string_lossy();
+        if arg == "-n" {
+            no_change = true;
+        } else if dir_stack::is_index(&arg) {
+            match ctx.dir_stack.index(&arg) {
+                Some(i) => index = Some(i),
+                None => {
+                    errln!(ctx, "popd: {}: directory stack index out of range", arg);
+                    return;
+                }
+            }
+        } else if arg.starts_with('-') {
+            errln!(ctx, "popd: {}: invalid option", arg);
+            errln!(ctx, "Try 'popd --help' for more information.");
+            return;
+        } else {
+            errln!(ctx, "popd: {}: invalid argument", arg);
+            return;
+        }
+    }
+
+    if ctx.dir_stack.is_empty() {
+        errln!(ctx, "popd: directory stack empty");
+        return;
+    }
+    match index.unwrap_or(0) {
+        // 先頭（カレントディレクトリ）を取り除くので、次の項目へ移動する
+        0 if !no_change => {
+            let next = ctx.dir_stack.dirs[0].clone();
+            if !change_directory(ctx, NAME, Some(next.as_os_str())) {
+                return;
+            }
+            ctx.dir_stack.dirs.remove(0);
+        }
+        0 => {
+            ctx.dir_stack.dirs.remove(0);
+        }
+        i => {
+            ctx.dir_stack.dirs.remove(i - 1);
+        }
+    }
+    print_stack(ctx);
+}
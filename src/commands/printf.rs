@@ -0,0 +1,532 @@
+//! printfコマンド。書式に従って引数を表示する（POSIX の printf）
+//!
+//! `printf FORMAT [ARGUMENT]...`
+//!
+//! 書式の変換指定は `%[フラグ][幅][.精度]変換` の形で、変換は次のとおり:
+//! - `d` `i` 符号付き10進 / `o` `u` `x` `X` 符号なしの8進・10進・16進
+//! - `f` `F` `e` `E` `g` `G` 浮動小数点数
+//! - `c` 引数の最初の1バイト / `s` 文字列 / `b` `echo -e` と同じエスケープを解釈した文字列
+//! - `q` シェルの入力として使える形にクォートした文字列 / `%%` `%` そのもの
+//!
+//! フラグは `-`（左寄せ） `+`（符号を付ける） ` `（正なら空白） `#`（別形式） `0`（0で埋める）。
+//! 幅と精度に `*` を書くと引数から取る。
+//! 書式中のエスケープは `\\` `\"` `\a` `\b` `\c`（以降を何も出力しない） `\e` `\f` `\n` `\r` `\t` `\v`
+//! `\NNN`（8進3桁まで） `\xHH` `\uHHHH` `\UHHHHHHHH`。
+//!
+//! 引数が書式の変換指定より多ければ、すべて使い切るまで書式を繰り返す。足りない分は
+//! 空文字列（数値の変換では 0）とみなす。数値は 10進・`0x`（16進）・`0`（8進）のほか、
+//! `'c` の形で文字コードを書ける。数値として読めない引数はエラーを表示して 0 とし、
+//! 最後まで出力してから終了ステータス 1 で終わる。
+
+use super::echo;
+use crate::context::Context;
+use crate::quoting::{self, QuotingStyle};
+use crate::errln;
+use std::ffi::{OsStr, OsString};
+use std::io::Write;
+use std::os::unix::ffi::OsStrExt;
+
+/// コマンド名
+pub const NAME: &str = "printf";
+
+/// 変換指定のフラグ・幅・精度
+#[derive(Debug, Default, Clone, Copy)]
+struct Spec {
+    left: bool,
+    plus: bool,
+    space: bool,
+    alternate: bool,
+    zero: bool,
+    width: usize,
+    precision: Option<usize>,
+}
+
+/// 書式を1回分出力した結果
+enum Flow {
+    /// 書式の終わりまで出力した
+    Done,
+    /// `\c` などで出力を打ち切る
+    Stop,
+}
+
+/// 書式を展開しながら出力をためる
+struct Printer<'a> {
+    args: &'a [OsString],
+    /// 次に使う引数の位置
+    next: usize,
+    out: Vec<u8>,
+    /// 数値として読めない引数などのエラー
+    errors: Vec<String>,
+}
+
+impl<'a> Printer<'a> {
+    fn next_arg(&mut self) -> Option<&'a OsStr> {
+        let arg = self.args.get(self.next)?;
+        self.next += 1;
+        Some(arg)
+    }
+
+    /// 書式を1回分展開する
+    fn format(&mut self, format: &[u8]) -> Result<Flow, String> {
+        let mut i = 0;
+        while i < format.len() {
+            match format[i] {
+                b'\\' => {
+                    i += 1;
+                    if !self.escape(format, &mut i) {
+                        return Ok(Flow::Stop);
+                    }
+                }
+                b'%' if format.get(i + 1) == Some(&b'%') => {
+                    self.out.push(b'%');
+                    i += 2;
+                }
+                b'%' => {
+                    let start = i;
+                    i += 1;
+                    let spec = self.spec(format, &mut i);
+                    // 長さの修飾子（%ld など）は意味を持たないので読み飛ばす
+                    while i < format.len() && b"hlLjzt".contains(&format[i]) {
+                        i += 1;
+                    }
+                    let Some(&conversion) = format.get(i) else {
+                        return Err(format!("{}: missing format character", String::from_utf8_lossy(&format[start..])));
+                    };
+                    i += 1;
+                    if !self.convert(conversion, spec, &format[start..i])? {
+                        return Ok(Flow::Stop);
+                    }
+                }
+                b => {
+                    self.out.push(b);
+                    i += 1;
+                }
+            }
+        }
+        Ok(Flow::Done)
+    }
+
+    /// `%` の直後からフラグ・幅・精度を読む
+    fn spec(&mut self, format: &[u8], i: &mut usize) -> Spec {
+        let mut spec = Spec::default();
+        while let Some(&b) = format.get(*i) {
+            match b {
+                b'-' => spec.left = true,
+                b'+' => spec.plus = true,
+                b' ' => spec.space = true,
+                b'#' => spec.alternate = true,
+                b'0' => spec.zero = true,
+                _ => break,
+            }
+            *i += 1;
+        }
+        if format.get(*i) == Some(&b'*') {
+            *i += 1;
+            let width = self.int_arg();
+            if width < 0 {
+                spec.left = true;
+            }
+            spec.width = width.unsigned_abs().min(usize::MAX as u64) as usize;
+        } else {
+            let (width, len) = decimal(&format[*i..]);
+            spec.width = width;
+            *i += len;
+        }
+        if format.get(*i) == Some(&b'.') {
+            *i += 1;
+            if format.get(*i) == Some(&b'*') {
+                *i += 1;
+                // 負の精度は指定しなかったものとみなす
+                let precision = self.int_arg();
+                spec.precision = (precision >= 0).then_some(precision as usize);
+            } else {
+                let (precision, len) = decimal(&format[*i..]);
+                spec.precision = Some(precision);
+                *i += len;
+            }
+        }
+        spec
+    }
+
+    /// 1つの変換を出力する。`%b` の中の `\c` で打ち切るなら false を返す
+    fn convert(&mut self, conversion: u8, spec: Spec, directive: &[u8]) -> Result<bool, String> {
+        // 整数で精度を指定したときは 0 ではなく空白で埋める
+        let int_spec = Spec { zero: spec.zero && spec.precision.is_none(), ..spec };
+        match conversion {
+            b'd' | b'i' => {
+                let value = self.int_arg();
+                let sign = sign(value < 0, spec);
+                let digits = integer_digits(value.unsigned_abs() as u128, 10, false, spec.precision);
+                self.pad_number(sign, &digits, int_spec);
+            }
+            b'o' | b'u' | b'x' | b'X' => {
+                let value = self.uint_arg();
+                let radix = match conversion {
+                    b'o' => 8,
+                    b'u' => 10,
+                    _ => 16,
+                };
+                let mut digits = integer_digits(value as u128, radix, conversion == b'X', spec.precision);
+                let mut prefix = "";
+                if spec.alternate {
+                    if conversion == b'o' && !digits.starts_with('0') {
+                        digits.insert(0, '0');
+                    } else if conversion == b'x' && value != 0 {
+                        prefix = "0x";
+                    } else if conversion == b'X' && value != 0 {
+                        prefix = "0X";
+                    }
+                }
+                self.pad_number(prefix, &digits, int_spec);
+            }
+            b'f' | b'F' | b'e' | b'E' | b'g' | b'G' => {
+                let value = self.float_arg();
+                let sign = sign(value.is_sign_negative() && !value.is_nan(), spec);
+                let body = float_body(value.abs(), conversion, spec);
+                if value.is_finite() {
+                    self.pad_number(sign, &body, spec);
+                } else {
+                    // inf と nan は 0 で埋めない
+                    self.pad(format!("{}{}", sign, body).as_bytes(), Spec { zero: false, ..spec });
+                }
+            }
+            b'c' => {
+                let arg = self.next_arg().map(|arg| arg.as_bytes()).unwrap_or_default();
+                self.pad(&arg[..arg.len().min(1)], spec);
+            }
+            b's' => {
+                let arg = self.next_arg().map(|arg| arg.as_bytes()).unwrap_or_default();
+                let end = spec.precision.map_or(arg.len(), |p| p.min(arg.len()));
+                self.pad(&arg[..end], spec);
+            }
+            b'b' => {
+                let arg = self.next_arg().map(|arg| arg.as_bytes()).unwrap_or_default();
+                let mut text = Vec::new();
+                let more = echo::unescape(arg, &mut text);
+                if let Some(precision) = spec.precision {
+                    text.truncate(precision);
+                }
+                self.pad(&text, spec);
+                return Ok(more);
+            }
+            b'q' => {
+                let arg = self.next_arg().unwrap_or_default();
+                let quoted = if arg.is_empty() { "''".to_string() } else { quoting::quote(arg, QuotingStyle::ShellEscape) };
+                self.pad(quoted.as_bytes(), spec);
+            }
+            _ => {
+                return Err(format!("{}: invalid conversion specification", String::from_utf8_lossy(directive)));
+            }
+        }
+        Ok(true)
+    }
+
+    /// 書式中の `\` の直後（位置 `i`）からエスケープを1つ読む。`\c` なら false を返す
+    fn escape(&mut self, format: &[u8], i: &mut usize) -> bool {
+        let Some(&c) = format.get(*i) else {
+            self.out.push(b'\\');
+            return true;
+        };
+        *i += 1;
+        let b = match c {
+            b'\\' | b'"' | b'\'' | b'?' => c,
+            b'a' => 0x07,
+            b'b' => 0x08,
+            b'c' => return false,
+            b'e' => 0x1b,
+            b'f' => 0x0c,
+            b'n' => b'\n',
+            b'r' => b'\r',
+            b't' => b'\t',
+            b'v' => 0x0b,
+            b'0'..=b'7' => {
+                let (value, len) = echo::parse_digits(&format[*i - 1..], 8, 3);
+                *i += len - 1;
+                value as u8
+            }
+            b'x' | b'u' | b'U' => {
+                let max = match c {
+                    b'x' => 2,
+                    b'u' => 4,
+                    _ => 8,
+                };
+                let (value, len) = echo::parse_digits(&format[*i..], 16, max);
+                if len == 0 {
+                    self.out.extend_from_slice(&[b'\\', c]);
+                    return true;
+                }
+                *i += len;
+                if c == b'x' {
+                    value as u8
+                } else {
+                    let ch = char::from_u32(value).unwrap_or(char::REPLACEMENT_CHARACTER);
+                    let mut buf = [0u8; 4];
+                    self.out.extend_from_slice(ch.encode_utf8(&mut buf).as_bytes());
+                    return true;
+                }
+            }
+            // 未知のエスケープはそのまま残す
+            _ => {
+                self.out.push(b'\\');
+                c
+            }
+        };
+        self.out.push(b);
+        true
+    }
+
+    /// 符号付き整数の引数（なければ 0）
+    fn int_arg(&mut self) -> i64 {
+        let Some(arg) = self.next_arg() else {
+            return 0;
+        };
+        let value = self.number(arg);
+        match i64::try_from(value) {
+            Ok(value) => value,
+            Err(_) => {
+                self.errors.push(format!("{}: Numerical result out of range", quoting::display(arg)));
+                if value < 0 { i64::MIN } else { i64::MAX }
+            }
+        }
+    }
+
+    /// 符号なし整数の引数。負の数は 2 の補数として扱う（C の printf と同じ）
+    fn uint_arg(&mut self) -> u64 {
+        let Some(arg) = self.next_arg() else {
+            return 0;
+        };
+        let value = self.number(arg);
+        if let Ok(value) = u64::try_from(value) {
+            return value;
+        }
+        match i64::try_from(value) {
+            Ok(value) => value as u64,
+            Err(_) => {
+                self.errors.push(format!("{}: Numerical result out of range", quoting::display(arg)));
+                if value < 0 { i64::MIN as u64 } else { u64::MAX }
+            }
+        }
+    }
+
+    /// 浮動小数点数の引数（なければ 0）
+    fn float_arg(&mut self) -> f64 {
+        let Some(arg) = self.next_arg() else {
+            return 0.0;
+        };
+        let text = String::from_utf8_lossy(arg.as_bytes());
+        let trimmed = text.trim_start();
+        if let Some(value) = char_code(trimmed) {
+            return value as f64;
+        }
+        let hex = trimmed.trim_start_matches(['+', '-']);
+        if hex.starts_with("0x") || hex.starts_with("0X") {
+            return self.number(arg) as f64;
+        }
+        // 先頭から数として読める最も長い部分を使う
+        let end = (1..=trimmed.len())
+            .rev()
+            .filter(|&end| trimmed.is_char_boundary(end))
+            .find(|&end| trimmed[..end].parse::<f64>().is_ok());
+        match end {
+            Some(end) => {
+                if end < trimmed.len() {
+                    self.errors.push(format!("{}: value not completely converted", quoting::display(arg)));
+                }
+                trimmed[..end].parse().unwrap_or(0.0)
+            }
+            None => {
+                self.errors.push(format!("{}: expected a numeric value", quoting::display(arg)));
+                0.0
+            }
+        }
+    }
+
+    /// 整数の引数を読む。読めなければエラーを記録し、読めた部分（なければ 0）を返す
+    fn number(&mut self, arg: &OsStr) -> i128 {
+        let text = String::from_utf8_lossy(arg.as_bytes());
+        let trimmed = text.trim_start();
+        if let Some(value) = char_code(trimmed) {
+            return value as i128;
+        }
+        let (negative, unsigned) = match trimmed.as_bytes().first() {
+            Some(b'-') => (true, &trimmed[1..]),
+            Some(b'+') => (false, &trimmed[1..]),
+            _ => (false, trimmed),
+        };
+        let (radix, digits) = if let Some(hex) = unsigned.strip_prefix("0x").or_else(|| unsigned.strip_prefix("0X")) {
+            (16, hex)
+        } else if unsigned.len() > 1 && unsigned.starts_with('0') {
+            (8, &unsigned[1..])
+        } else {
+            (10, unsigned)
+        };
+        let len = digits.bytes().take_while(|b| (*b as char).is_digit(radix)).count();
+        // 16進の "0x" だけのときなどは数字がないので読めない
+        if len == 0 && radix != 8 {
+            self.errors.push(format!("{}: expected a numeric value", quoting::display(arg)));
+            return 0;
+        }
+        let mut value: i128 = 0;
+        for b in digits[..len].bytes() {
+            let digit = (b as char).to_digit(radix).unwrap_or(0) as i128;
+            value = value.saturating_mul(radix as i128).saturating_add(digit);
+        }
+        if len < digits.len() {
+            self.errors.push(format!("{}: value not completely converted", quoting::display(arg)));
+        }
+        if negative { -value } else { value }
+    }
+
+    /// 幅に合わせて空白で埋めて出力する
+    fn pad(&mut self, text: &[u8], spec: Spec) {
+        let fill = spec.width.saturating_sub(text.len());
+        if !spec.left {
+            self.out.extend(std::iter::repeat_n(b' ', fill));
+        }
+        self.out.extend_from_slice(text);
+        if spec.left {
+            self.out.extend(std::iter::repeat_n(b' ', fill));
+        }
+    }
+
+    /// 数値を出力する。`0` フラグなら符号や `0x` の後ろを 0 で埋める
+    fn pad_number(&mut self, prefix: &str, digits: &str, spec: Spec) {
+        let len = prefix.len() + digits.len();
+        if spec.zero && !spec.left && spec.width > len {
+            self.out.extend_from_slice(prefix.as_bytes());
+            self.out.extend(std::iter::repeat_n(b'0', spec.width - len));
+            self.out.extend_from_slice(digits.as_bytes());
+        } else {
+            self.pad(format!("{}{}", prefix, digits).as_bytes(), spec);
+        }
+    }
+}
+
+pub fn run(ctx: &mut Context, args: &[OsString]) {
+    let args = match args.first() {
+        Some(first) if first == "--" => &args[1..],
+        _ => args,
+    };
+    let Some((format, args)) = args.split_first() else {
+        errln!(ctx, "printf: missing operand");
+        return;
+    };
+    let mut printer = Printer { args, next: 0, out: Vec::new(), errors: Vec::new() };
+    let result = loop {
+        let before = printer.next;
+        match printer.format(format.as_bytes()) {
+            Ok(Flow::Done) => {}
+            Ok(Flow::Stop) => break Ok(()),
+            Err(message) => break Err(message),
+        }
+        // 引数が残っていれば書式を繰り返す（引数を使わない書式なら1回だけ）
+        if printer.next >= args.len() || printer.next == before {
+            break Ok(());
+        }
+    };
+    let _ = ctx.stdout.write_all(&printer.out);
+    let _ = ctx.stdout.flush();
+    for message in printer.errors {
+        errln!(ctx, "printf: {}", message);
+    }
+    if let Err(message) = result {
+        errln!(ctx, "printf: {}", message);
+    }
+}
+
+/// `'c` や `"c` の形なら、その文字のコード
+fn char_code(text: &str) -> Option<u32> {
+    let rest = text.strip_prefix(['\'', '"'])?;
+    Some(rest.chars().next().map_or(0, |c| c as u32))
+}
+
+/// 先頭の10進数を読み、(値, 桁数) を返す
+fn decimal(bytes: &[u8]) -> (usize, usize) {
+    let len = bytes.iter().take_while(|b| b.is_ascii_digit()).count();
+    let value = bytes[..len].iter().fold(0usize, |n, b| n.saturating_mul(10).saturating_add((b - b'0') as usize));
+    (value, len)
+}
+
+/// 符号の表示（`-`、`+` フラグなら `+`、空白フラグなら ` `）
+fn sign(negative: bool, spec: Spec) -> &'static str {
+    if negative {
+        "-"
+    } else if spec.plus {
+        "+"
+    } else if spec.space {
+        " "
+    } else {
+        ""
+    }
+}
+
+/// 整数の数字の並び。精度は最小の桁数（精度 0 で値が 0 なら空）
+fn integer_digits(value: u128, radix: u32, upper: bool, precision: Option<usize>) -> String {
+    let mut digits = match (radix, upper) {
+        (8, _) => format!("{:o}", value),
+        (16, false) => format!("{:x}", value),
+        (16, true) => format!("{:X}", value),
+        _ => value.to_string(),
+    };
+    match precision {
+        Some(0) if value == 0 => digits.clear(),
+        Some(precision) if precision > digits.len() => digits.insert_str(0, &"0".repeat(precision - digits.len())),
+        _ => {}
+    }
+    digits
+}
+
+/// 符号を除いた浮動小数点数の表示（`value` は 0 以上）
+fn float_body(value: f64, conversion: u8, spec: Spec) -> String {
+    let upper = conversion.is_ascii_uppercase();
+    if !value.is_finite() {
+        let text = if value.is_nan() { "nan" } else { "inf" };
+        return if upper { text.to_uppercase() } else { text.to_string() };
+    }
+    let precision = spec.precision.unwrap_or(6);
+    let text = match conversion.to_ascii_lowercase() {
+        b'f' => {
+            let mut text = format!("{:.*}", precision, value);
+            if spec.alternate && precision == 0 {
+                text.push('.');
+            }
+            text
+        }
+        b'e' => exponential(value, precision, spec.alternate),
+        _ => {
+            // %g: 指数が -4 以上で精度より小さければ %f、それ以外は %e の形にし、末尾の 0 を除く
+            let precision = precision.max(1);
+            let exponent = exponential(value, precision - 1, false)
+                .rsplit_once('e')
+                .and_then(|(_, exponent)| exponent.parse::<i32>().ok())
+                .unwrap_or(0);
+            let mut text = if exponent >= -4 && exponent < precision as i32 {
+                format!("{:.*}", (precision as i32 - 1 - exponent) as usize, value)
+            } else {
+                exponential(value, precision - 1, false)
+            };
+            if spec.alternate {
+                if !text.contains('.') {
+                    let at = text.find('e').unwrap_or(text.len());
+                    text.insert(at, '.');
+                }
+            } else if text.contains('.') {
+                let at = text.find('e').unwrap_or(text.len());
+                let (mantissa, exponent) = text.split_at(at);
+                let mantissa = mantissa.trim_end_matches('0').trim_end_matches('.');
+                text = format!("{}{}", mantissa, exponent);
+            }
+            text
+        }
+    };
+    if upper { text.to_uppercase() } else { text }
+}
+
+/// C の `%e` と同じ形（`1.500000e+02`）にする
+fn exponential(value: f64, precision: usize, alternate: bool) -> String {
+    let text = format!("{:.*e}", precision, value);
+    let (mantissa, exponent) = text.split_once('e').unwrap_or((&text, "0"));
+    let exponent: i32 = exponent.parse().unwrap_or(0);
+    let point = if alternate && precision == 0 { "." } else { "" };
+    format!("{}{}e{}{:02}", mantissa, point, if exponent < 0 { '-' } else { '+' }, exponent.unsigned_abs())
+}
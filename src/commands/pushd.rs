@@ -0,0 +1,108 @@
+//! pushdコマンド。ディレクトリスタックに積んでから移動する
+//!
+//! 使い方:
+//! - `pushd DIR` カレントディレクトリをスタックに積み、DIR へ移動する
+//! - `pushd` カレントディレクトリとスタックの先頭を入れ替える
+//! - `pushd +N`（`-N`）先頭（末尾）から N 番目が先頭になるようにスタックを回転し、そこへ移動する
+//! - `pushd -n DIR` 移動せず、DIR をスタックに積むだけにする
+//! - `pushd -` 直前のディレクトリ（`$OLDPWD`）を DIR として使う
+//!
+//! 成功したら `dirs` と同じ形でスタックを表示する。
+
+use super::cd::change_directory;
+use super::dirs::print_stack;
+use super::error_message;
+use crate::context::Context;
+use crate::dir_stack;
+use crate::errln;
+use crate::quoting;
+use std::ffi::{OsStr, OsString};
+use std::path::PathBuf;
+
+/// コマンド名
+pub const NAME: &str = "pushd";
+
+pub fn run(ctx: &mut Context, args: &[OsString]) {
+    let mut no_change = false;
+    let mut operand: Option<&OsStr> = None;
+    let mut only_operands = false;
+
+    for os_arg in args {
+        let arg = os_arg.to_string_lossy();
+        if !only_operands && arg == "--" {
+            only_operands = true;
+        } else if !only_operands && arg == "-n" {
+            no_change = true;
+        } else if !only_operands && arg.starts_with('-') && arg != "-" && !dir_stack::is_index(&arg) {
+            errln!(ctx, "pushd: {}: invalid option", arg);
+            errln!(ctx, "Try 'pushd --help' for more information.");
+            return;
+        } else if operand.is_some() {
+            errln!(ctx, "pushd: too many arguments");
+            return;
+        } else {
+            operand = Some(os_arg);
+        }
+    }
+
+    // `pushd -` は直前のディレクトリ
+    let oldpwd = ctx.oldpwd.clone();
+    if operand.is_some_and(|dir| dir == "-") {
+        match &oldpwd {
+            Some(dir) => operand = Some(dir.as_os_str()),
+            None => {
+                errln!(ctx, "pushd: OLDPWD not set");
+                return;
+            }
+        }
+    }
+
+    match operand {
+        None => {
+            if ctx.dir_stack.is_empty() {
+                errln!(ctx, "pushd: no other directory");
+                return;
+            }
+            let top = ctx.dir_stack.dirs[0].clone();
+            if !change_directory(ctx, NAME, Some(top.as_os_str())) {
+                return;
+            }
+            ctx.dir_stack.dirs[0] = ctx.oldpwd.clone().unwrap_or_default();
+        }
+        Some(spec) if dir_stack::is_index(&spec.to_string_lossy()) => {
+            let Some(index) = ctx.dir_stack.index(&spec.to_string_lossy()) else {
+                errln!(ctx, "pushd: {}: directory stack index out of range", spec.to_string_lossy());
+                return;
+            };
+            // 全体を回転して index 番目を先頭にする
+            let mut entries: Vec<PathBuf> = ctx.dir_stack.entries(&ctx.cwd).map(PathBuf::from).collect();
+            entries.rotate_left(index);
+            if !change_directory(ctx, NAME, Some(entries[0].as_os_str())) {
+                return;
+            }
+            ctx.dir_stack.dirs = entries.split_off(1);
+        }
+        Some(dir) if no_change => {
+            let path = ctx.resolve(dir);
+            match ctx.fs.metadata(&path).and_then(|_| ctx.fs.canonicalize(&path)) {
+                Ok(canonical) if ctx.fs.is_dir(&canonical) => ctx.dir_stack.dirs.insert(0, canonical),
+                Ok(_) => {
+                    errln!(ctx, "pushd: {}: Not a directory", quoting::display(dir));
+                    return;
+                }
+                Err(e) => {
+                    errln!(ctx, "pushd: {}: {}", quoting::display(dir), error_message(&e));
+                    return;
+                }
+            }
+        }
+        Some(dir) => {
+            if !change_directory(ctx, NAME, Some(dir)) {
+                return;
+            }
+            let previous = ctx.oldpwd.clone().unwrap_or_default();
+            ctx.dir_stack.dirs.insert(0, previous);
+        }
+    }
+    print_stack(ctx);
+}
@@ -1,13 +1,12 @@
 //! pwdコマンド。現在の作業ディレクトリを表示する
 
-use std::env;
+use crate::context::Context;
+use crate::outln;
+use std::ffi::OsString;
 
 /// コマンド名
 pub const NAME: &str = "pwd";
 
-pub fn run(_args: &[&str]) {
-    match env::current_dir() {
-        Ok(path) => println!("{}", path.display()),
-        Err(e) => eprintln!("pwd: {}", e),
-    }
+pub fn run(ctx: &mut Context, _args: &[OsString]) {
+    outln!(ctx, "{}", ctx.cwd.display());
 }
@@ -1,69 +1,470 @@
-//! rmコマンド。オプション -f（強制）, -r/-R（再帰）に対応する
+//! rmコマンド。ファイル・ディレクトリを削除する
+//!
+//! 対応オプション:
+//! - `-f` 存在しないファイルを無視し、確認もしない
+//! - `-i` 1つ削除するごとに確認する
+//! - `-I` 4つ以上のファイルを削除する場合と再帰削除の場合に、最初に1度だけ確認する
+//! - `-r` / `-R` ディレクトリを中身ごと削除する
+//! - `-d` 空のディレクトリを削除する
+//! - `-v` 削除したパスをすべて表示する
+//! - `--one-file-system` 引数と異なるファイルシステム上のディレクトリには入らない
+//! - `--preserve-root[=all]` `/` の再帰削除を拒否する（既定で有効。`all` ならマウントポイントも拒否）
+//! - `--no-preserve-root` 上記の保護を無効にする
+//! - `--trash` / `--no-trash` 完全に削除せずゴミ箱へ移す・移さない
+//!   （既定は設定ファイルの `trash` の値）
+//!
+//! `.` と `..` は常に削除を拒否する。確認は `Context::confirm` で行い、
+//! GUI版ではダイアログで表示される。ゴミ箱へ移した項目は `Context::trashed` に記録し、
+//! GUI版の「削除を取り消す」や `trash-restore` で元に戻せる。
+//!
+//! undo が有効なら（`Context::journal`）、削除するファイルは完全には消さず
+//! 退避場所へ移し、`undo` で元に戻せるようにする。
 
-use std::fs;
+use super::error_message;
+use crate::context::Context;
+use crate::fs_action::FsAction;
+use crate::journal::Recorder;
+use crate::quoting;
+use crate::sandbox;
+use crate::trash;
+use crate::vfs::Stat;
+use crate::{errln, outln};
+use std::ffi::{OsStr, OsString};
+use std::io;
 use std::path::Path;
 
 /// コマンド名
 pub const NAME: &str = "rm";
 
-pub fn run(args: &[&str]) {
-    if args.is_empty() {
-        eprintln!("rm: missing operand");
-        eprintln!("Try 'rm --help' for more information.");
-        return;
-    }
+/// 確認のタイミング
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Prompt {
+    /// 確認しない
+    Never,
+    /// 最初に1度だけ（-I）
+    Once,
+    /// 1つごとに（-i）
+    Always,
+}
+
+/// `/` の保護
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PreserveRoot {
+    Off,
+    /// `/` の再帰削除を拒否する
+    Root,
+    /// マウントポイントの再帰削除も拒否する
+    All,
+}
+
+/// 解析済みのオプション
+struct Options {
+    force: bool,
+    prompt: Prompt,
+    recursive: bool,
+    dir: bool,
+    verbose: bool,
+    one_file_system: bool,
+    preserve_root: PreserveRoot,
+    /// 完全に削除せずゴミ箱へ移す
+    trash: bool,
+}
 
-    let mut force = false;
-    let mut recursive = false;
-    let mut files = Vec::new();
+pub fn run(ctx: &mut Context, args: &[OsString]) {
+    let mut opts = Options {
+        force: false,
+        prompt: Prompt::Never,
+        recursive: false,
+        dir: false,
+        verbose: false,
+        one_file_system: false,
+        preserve_root: PreserveRoot::Root,
+        trash: ctx.config.trash,
+    };
+    let mut files: Vec<&OsStr> = Vec::new();
+    let mut only_operands = false;
 
     // 引数をオプションとファイルパスに振り分ける
-    for arg in args {
-        if arg.starts_with('-') {
-            for ch in arg.chars().skip(1) {
-                match ch {
-                    'f' => force = true,
-                    'r' | 'R' => recursive = true,
-                    _ => {
-                        eprintln!("rm: invalid option -- '{}'", ch);
-                        return;
+    for os_arg in args {
+        let arg = match os_arg.to_str() {
+            Some(arg) if !only_operands && arg.starts_with('-') && arg != "-" => arg,
+            _ => {
+                files.push(os_arg);
+                continue;
+            }
+        };
+        match arg {
+            "--" => only_operands = true,
+            "--force" => {
+                opts.force = true;
+                opts.prompt = Prompt::Never;
+            }
+            "--interactive" | "--interactive=always" => {
+                opts.force = false;
+                opts.prompt = Prompt::Always;
+            }
+            "--interactive=once" => {
+                opts.force = false;
+                opts.prompt = Prompt::Once;
+            }
+            "--interactive=never" => opts.prompt = Prompt::Never,
+            "--recursive" => opts.recursive = true,
+            "--dir" => opts.dir = true,
+            "--verbose" => opts.verbose = true,
+            "--one-file-system" => opts.one_file_system = true,
+            "--preserve-root" => opts.preserve_root = PreserveRoot::Root,
+            "--preserve-root=all" => opts.preserve_root = PreserveRoot::All,
+            "--no-preserve-root" => opts.preserve_root = PreserveRoot::Off,
+            "--trash" => opts.trash = true,
+            "--no-trash" => opts.trash = false,
+            _ if arg.starts_with("--interactive=") => {
+                errln!(ctx, "rm: invalid argument '{}' for '--interactive'", &arg["--interactive=".len()..]);
+                errln!(ctx, "Valid arguments are: 'never', 'once', 'always'");
+                return;
+            }
+            _ if arg.starts_with("--") => {
+                errln!(ctx, "rm: unrecognized option '{}'", arg);
+                return;
+            }
+            _ => {
+                for ch in arg[1..].chars() {
+                    match ch {
+                        'f' => {
+                            opts.force = true;
+                            opts.prompt = Prompt::Never;
+                        }
+                        'i' => {
+                            opts.force = false;
+                            opts.prompt = Prompt::Always;
+                        }
+                        'I' => {
+                            opts.force = false;
+                            opts.prompt = Prompt::Once;
+                        }
+                        'r' | 'R' => opts.recursive = true,
+                        'd' => opts.dir = true,
+                        'v' => opts.verbose = true,
+                        _ => {
+                            errln!(ctx, "rm: invalid option -- '{}'", ch);
+                            return;
+                        }
                     }
                 }
             }
-        } else {
-            files.push(*arg);
         }
     }
 
+    // ドライランでは予定の操作だけを表示する
+    opts.verbose &= !ctx.dry_run;
+
     if files.is_empty() {
-        eprintln!("rm: missing operand");
+        if !opts.force {
+            errln!(ctx, "rm: missing operand");
+            errln!(ctx, "Try 'rm --help' for more information.");
+        }
         return;
     }
 
+    // -I: 多数のファイルや再帰削除は最初にまとめて確認する
+    if opts.prompt == Prompt::Once && (opts.recursive || files.len() > 3) {
+        let count = files.len();
+        let noun = if count == 1 { "argument" } else { "arguments" };
+        let question = if opts.recursive {
+            format!("rm: remove {} {} recursively? ", count, noun)
+        } else {
+            format!("rm: remove {} {}? ", count, noun)
+        };
+        if !ctx.confirm(&question) {
+            return;
+        }
+    }
+
+    let mut remover = Remover {
+        opts: &opts,
+        device: 0,
+        recorder: ctx.recorder(NAME, args),
+    };
     for file in files {
-        let path = Path::new(file);
+        let shown = Path::new(file);
+        if is_dot_or_dot_dot(shown) {
+            errln!(
+                ctx,
+                "rm: refusing to remove '.' or '..' directory: skipping '{}'",
+                quoting::display(file)
+            );
+            continue;
+        }
 
-        if !path.exists() {
-            if !force {
-                eprintln!("rm: cannot remove '{}': No such file or directory", file);
+        let path = ctx.resolve(file);
+        let meta = match ctx.fs.symlink_metadata(&path) {
+            Ok(meta) => meta,
+            Err(e) => {
+                if !(opts.force && e.kind() == io::ErrorKind::NotFound) {
+                    errln!(ctx, "rm: cannot remove '{}': {}", quoting::display(file), error_message(&e));
+                }
+                continue;
             }
+        };
+
+        if meta.is_dir() && opts.recursive && !check_preserve_root(ctx, &path, file, opts.preserve_root) {
+            continue;
+        }
+
+        if opts.trash {
+            move_to_trash(ctx, &path, shown, &meta, &opts, remover.recorder.as_mut());
             continue;
         }
+        remover.device = meta.dev();
+        remover.remove(ctx, &path, shown, &meta);
+    }
+
+    if let Some(recorder) = remover.recorder {
+        if let Err(e) = recorder.commit() {
+            errln!(ctx, "rm: cannot record for undo: {}", error_message(&e));
+        }
+    }
+}
+
+/// 1つの引数をゴミ箱へ移す（ディレクトリは中身ごとそのまま移す）
+fn move_to_trash(
+    ctx: &mut Context,
+    path: &Path,
+    shown: &Path,
+    meta: &Stat,
+    opts: &Options,
+    recorder: Option<&mut Recorder>,
+) {
+    let name = quoting::display(shown.as_os_str());
+    if meta.is_dir() && !opts.recursive {
+        let is_empty = ctx.fs.read_dir(path).is_ok_and(|names| names.is_empty());
+        if !(opts.dir && is_empty) {
+            errln!(ctx, "rm: cannot remove '{}': Is a directory", name);
+            return;
+        }
+    }
+    if opts.prompt == Prompt::Always {
+        let kind = if meta.is_dir() { "directory" } else { file_kind(meta) };
+        if !ctx.confirm(&format!("rm: move {} '{}' to trash? ", kind, name)) {
+            return;
+        }
+    }
+    if ctx.preview(&FsAction::Trash(path)) {
+        return;
+    }
+    if !ctx.fs.is_native() {
+        // ゴミ箱はサンドボックスの外にある
+        let e = match ctx.sandbox {
+            Some(_) => sandbox::denied(),
+            None => io::Error::from_raw_os_error(libc::EOPNOTSUPP),
+        };
+        errln!(ctx, "rm: cannot move '{}' to trash: {}", name, error_message(&e));
+        return;
+    }
+    match trash::trash(path) {
+        Ok(item) => {
+            if opts.verbose {
+                outln!(ctx, "trashed '{}'", name);
+            }
+            if let Some(recorder) = recorder {
+                recorder.trashed(item.clone());
+            }
+            ctx.trashed.push(item);
+        }
+        Err(e) => errln!(ctx, "rm: cannot move '{}' to trash: {}", name, error_message(&e)),
+    }
+}
+
+/// 最後の要素が `.` か `..` か（"dir/." や "../.." も含む）
+fn is_dot_or_dot_dot(path: &Path) -> bool {
+    let bytes = path.as_os_str().as_encoded_bytes();
+    let trimmed = {
+        let mut end = bytes.len();
+        while end > 1 && bytes[end - 1] == b'/' {
+            end -= 1;
+        }
+        &bytes[..end]
+    };
+    let last = trimmed.rsplit(|&b| b == b'/').next().unwrap_or(trimmed);
+    last == b"." || last == b".."
+}
+
+/// `/`（と `all` ならマウントポイント）の再帰削除を拒否する。削除してよければ true
+fn check_preserve_root(ctx: &mut Context, path: &Path, shown: &OsStr, preserve: PreserveRoot) -> bool {
+    if preserve == PreserveRoot::Off {
+        return true;
+    }
+    let Ok(real) = ctx.fs.canonicalize(path) else {
+        return true;
+    };
+    if real == Path::new("/") {
+        if shown == OsStr::new("/") {
+            errln!(ctx, "rm: it is dangerous to operate recursively on '/'");
+        } else {
+            errln!(
+                ctx,
+                "rm: it is dangerous to operate recursively on '{}' (same as '/')",
+                quoting::display(shown)
+            );
+        }
+        errln!(ctx, "rm: use --no-preserve-root to override this failsafe");
+        return false;
+    }
+    if preserve == PreserveRoot::All {
+        let parent_dev = real.parent().and_then(|p| ctx.fs.metadata(p).ok()).map(|m| m.dev());
+        let dev = ctx.fs.metadata(&real).map(|m| m.dev()).ok();
+        if parent_dev.is_some() && parent_dev != dev {
+            errln!(
+                ctx,
+                "rm: skipping '{}', since it's on a different device",
+                quoting::display(shown)
+            );
+            errln!(ctx, "rm: and --preserve-root=all is in effect");
+            return false;
+        }
+    }
+    true
+}
 
-        if path.is_dir() {
-            if recursive {
-                if let Err(e) = fs::remove_dir_all(path) {
-                    if !force {
-                        eprintln!("rm: cannot remove '{}': {}", file, e);
+/// 引数を順に削除する
+struct Remover<'a> {
+    opts: &'a Options,
+    /// 処理中の引数があるファイルシステム（--one-file-system 用）
+    device: u64,
+    /// undo 用の記録（None なら退避せずに削除する）
+    recorder: Option<Recorder>,
+}
+
+impl Remover<'_> {
+    /// `path` を削除する（ディレクトリなら中身から）。削除できたら true
+    fn remove(&mut self, ctx: &mut Context, path: &Path, shown: &Path, meta: &Stat) -> bool {
+        let name = quoting::display(shown.as_os_str());
+
+        if !meta.is_dir() {
+            if self.opts.prompt == Prompt::Always
+                && !ctx.confirm(&format!("rm: remove {} '{}'? ", file_kind(meta), name))
+            {
+                return false;
+            }
+            return match self.remove_file(ctx, path) {
+                Ok(()) => {
+                    if self.opts.verbose {
+                        outln!(ctx, "removed '{}'", name);
+                    }
+                    true
+                }
+                Err(e) => self.report(ctx, &name, &e),
+            };
+        }
+
+        if !self.opts.recursive {
+            if !self.opts.dir {
+                errln!(ctx, "rm: cannot remove '{}': Is a directory", name);
+                return false;
+            }
+        } else {
+            if self.opts.one_file_system && meta.dev() != self.device {
+                errln!(ctx, "rm: skipping '{}', since it's on a different device", name);
+                return false;
+            }
+            // 1つずつ確認・表示する必要がなければ、ディレクトリごと退避場所へ移す
+            if self.opts.prompt != Prompt::Always && !self.opts.one_file_system && !self.opts.verbose {
+                if let Some(recorder) = self.recorder.as_mut() {
+                    if recorder.remove(path).is_ok() {
+                        return true;
                     }
                 }
-            } else {
-                eprintln!("rm: cannot remove '{}': Is a directory", file);
             }
-        } else if let Err(e) = fs::remove_file(path) {
-            if !force {
-                eprintln!("rm: cannot remove '{}': {}", file, e);
+            if !self.remove_contents(ctx, path, shown, &name) {
+                return false;
             }
         }
+
+        if self.opts.prompt == Prompt::Always && !ctx.confirm(&format!("rm: remove directory '{}'? ", name)) {
+            return false;
+        }
+        match ctx.perform(FsAction::RemoveDir(path)) {
+            Ok(()) => {
+                if let Some(recorder) = self.recorder.as_mut() {
+                    recorder.removed_dir(path, meta.mode() & 0o7777);
+                }
+                if self.opts.verbose {
+                    outln!(ctx, "removed directory '{}'", name);
+                }
+                true
+            }
+            Err(e) => self.report(ctx, &name, &e),
+        }
+    }
+
+    /// ディレクトリの中身を削除する。すべて削除できたら true
+    fn remove_contents(&mut self, ctx: &mut Context, path: &Path, shown: &Path, name: &str) -> bool {
+        let mut entries = match ctx.fs.read_dir(path) {
+            Ok(names) => names,
+            Err(e) => return self.report(ctx, name, &e),
+        };
+        if entries.is_empty() {
+            return true;
+        }
+        if self.opts.prompt == Prompt::Always
+            && !ctx.confirm(&format!("rm: descend into directory '{}'? ", name))
+        {
+            return false;
+        }
+        entries.sort();
+
+        let mut all_removed = true;
+        for entry in entries {
+            let child = path.join(&entry);
+            let child_shown = shown.join(&entry);
+            let meta = match ctx.fs.symlink_metadata(&child) {
+                Ok(meta) => meta,
+                Err(e) => {
+                    all_removed &= self.report(ctx, &quoting::display(child_shown.as_os_str()), &e);
+                    continue;
+                }
+            };
+            all_removed &= self.remove(ctx, &child, &child_shown, &meta);
+        }
+        all_removed
+    }
+
+    /// ファイルを削除する。undo が有効なら退避場所へ移し、移せなければ退避せずに削除する
+    fn remove_file(&mut self, ctx: &mut Context, path: &Path) -> io::Result<()> {
+        let Some(recorder) = self.recorder.as_mut() else {
+            return ctx.perform(FsAction::RemoveFile(path));
+        };
+        if recorder.remove(path).is_err() {
+            ctx.perform(FsAction::RemoveFile(path))?;
+            recorder.lost(path);
+        }
+        Ok(())
+    }
+
+    /// 削除の失敗を表示する（-f で存在しない場合を除く）。常に false を返す
+    fn report(&self, ctx: &mut Context, name: &str, e: &io::Error) -> bool {
+        if !(self.opts.force && e.kind() == io::ErrorKind::NotFound) {
+            errln!(ctx, "rm: cannot remove '{}': {}", name, error_message(e));
+        }
+        false
+    }
+}
+
+/// 確認メッセージ用のファイル種別
+fn file_kind(meta: &Stat) -> &'static str {
+    let file_type = meta.file_type();
+    if file_type.is_symlink() {
+        "symbolic link"
+    } else if file_type.is_fifo() {
+        "fifo"
+    } else if file_type.is_socket() {
+        "socket"
+    } else if file_type.is_block_device() {
+        "block special file"
+    } else if file_type.is_char_device() {
+        "character special file"
+    } else if meta.is_empty() {
+        "regular empty file"
+    } else {
+        "regular file"
     }
 }
@@ -1,40 +1,132 @@
 //! rmdirコマンド。空のディレクトリのみ削除できる
+//!
+//! 対応オプション:
+//! - `-p` 指定したパスの親も順に削除する（`rmdir -p a/b/c` は `a/b/c`, `a/b`, `a` を削除）
+//! - `--ignore-fail-on-non-empty` 空でないために削除できなかった場合はエラーにしない
+//! - `-v` 削除したディレクトリを表示する
+//!
+//! エラーは rmdir(2) の errno に基づいて表示する（ENOTEMPTY なら "Directory not empty"）。
 
-use std::fs;
+use super::error_message;
+use crate::context::Context;
+use crate::fs_action::FsAction;
+use crate::journal::Recorder;
+use crate::quoting;
+use crate::{errln, outln};
+use std::ffi::{OsStr, OsString};
+use std::io;
 use std::path::Path;
 
 /// コマンド名
 pub const NAME: &str = "rmdir";
 
-pub fn run(args: &[&str]) {
-    if args.is_empty() {
-        eprintln!("rmdir: missing operand");
-        eprintln!("Try 'rmdir --help' for more information.");
-        return;
+/// 解析済みのオプション
+struct Options {
+    parents: bool,
+    ignore_non_empty: bool,
+    verbose: bool,
+}
+
+pub fn run(ctx: &mut Context, args: &[OsString]) {
+    let mut opts = Options {
+        parents: false,
+        ignore_non_empty: false,
+        verbose: false,
+    };
+    let mut dirs: Vec<&OsStr> = Vec::new();
+    let mut only_operands = false;
+
+    for os_arg in args {
+        let arg = match os_arg.to_str() {
+            Some(arg) if !only_operands && arg.starts_with('-') && arg != "-" => arg,
+            _ => {
+                dirs.push(os_arg);
+                continue;
+            }
+        };
+        match arg {
+            "--" => only_operands = true,
+            "--parents" => opts.parents = true,
+            "--ignore-fail-on-non-empty" => opts.ignore_non_empty = true,
+            "--verbose" => opts.verbose = true,
+            _ if arg.starts_with("--") => {
+                errln!(ctx, "rmdir: unrecognized option '{}'", arg);
+                return;
+            }
+            _ => {
+                for ch in arg[1..].chars() {
+                    match ch {
+                        'p' => opts.parents = true,
+                        'v' => opts.verbose = true,
+                        _ => {
+                            errln!(ctx, "rmdir: invalid option -- '{}'", ch);
+                            return;
+                        }
+                    }
+                }
+            }
+        }
     }
 
-    for dir in args {
-        let path = Path::new(dir);
+    // ドライランでは予定の操作だけを表示する
+    opts.verbose &= !ctx.dry_run;
+
+    if dirs.is_empty() {
+        errln!(ctx, "rmdir: missing operand");
+        errln!(ctx, "Try 'rmdir --help' for more information.");
+        return;
+    }
 
-        if !path.exists() {
-            eprintln!("rmdir: failed to remove '{}': No such file or directory", dir);
-            continue;
+    let mut recorder = ctx.recorder(NAME, args);
+    for dir in dirs {
+        let mut current = Path::new(dir);
+        loop {
+            if !remove(ctx, current, &opts, recorder.as_mut()) {
+                break;
+            }
+            // -p なら親を順にたどる（"a/b/c" → "a/b" → "a"）
+            match current.parent() {
+                Some(parent) if opts.parents && !parent.as_os_str().is_empty() && parent != Path::new("/") => {
+                    current = parent;
+                }
+                _ => break,
+            }
         }
+    }
 
-        if !path.is_dir() {
-            eprintln!("rmdir: failed to remove '{}': Not a directory", dir);
-            continue;
+    if let Some(recorder) = recorder {
+        if let Err(e) = recorder.commit() {
+            errln!(ctx, "rmdir: cannot record for undo: {}", error_message(&e));
         }
+    }
+}
 
-        match fs::remove_dir(path) {
-            Ok(_) => {}
-            Err(e) => {
-                if e.kind() == std::io::ErrorKind::Other {
-                    eprintln!("rmdir: failed to remove '{}': Directory not empty", dir);
-                } else {
-                    eprintln!("rmdir: failed to remove '{}': {}", dir, e);
-                }
+/// ディレクトリを1つ削除する。削除できたら true
+fn remove(ctx: &mut Context, dir: &Path, opts: &Options, recorder: Option<&mut Recorder>) -> bool {
+    let shown = quoting::display(dir.as_os_str());
+    if opts.verbose {
+        outln!(ctx, "rmdir: removing directory, '{}'", shown);
+    }
+    let path = ctx.resolve(dir);
+    // 元に戻すときに同じパーミッションで作り直せるよう、削除前に控えておく
+    let mode = ctx.fs.symlink_metadata(&path).map(|meta| meta.mode() & 0o7777).unwrap_or(0o755);
+    match ctx.perform(FsAction::RemoveDir(&path)) {
+        Ok(()) => {
+            if let Some(recorder) = recorder {
+                recorder.removed_dir(&path, mode);
+            }
+            true
+        }
+        Err(e) => {
+            if !(opts.ignore_non_empty && is_not_empty(&e)) {
+                errln!(ctx, "rmdir: failed to remove '{}': {}", shown, error_message(&e));
             }
+            false
         }
     }
 }
+
+/// 空でないために削除できなかったか（POSIX では ENOTEMPTY の代わりに EEXIST もありうる）
+fn is_not_empty(e: &io::Error) -> bool {
+    matches!(e.raw_os_error(), Some(libc::ENOTEMPTY) | Some(libc::EEXIST))
+}
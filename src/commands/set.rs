@@ -0,0 +1,45 @@
+//! setコマンド。シェル全体の動作を切り替える
+//!
+//! - `set -n` ドライランにする。ファイルを変更するコマンドは実行せず、予定の操作を表示する
+//! - `set +n` ドライランを解除する
+//! - `set -o dryrun` / `set +o dryrun` 上記の長い形式
+//! - `set` / `set -o` 現在の設定を表示する
+
+use crate::context::Context;
+use crate::quoting;
+use crate::{errln, outln};
+use std::ffi::OsString;
+
+/// コマンド名
+pub const NAME: &str = "set";
+
+pub fn run(ctx: &mut Context, args: &[OsString]) {
+    if args.is_empty() || (args.len() == 1 && args[0] == "-o") {
+        outln!(ctx, "dryrun\t{}", if ctx.dry_run { "on" } else { "off" });
+        return;
+    }
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("-n") => ctx.dry_run = true,
+            Some("+n") => ctx.dry_run = false,
+            Some(flag @ ("-o" | "+o")) => match args.next().and_then(|name| name.to_str()) {
+                Some("dryrun") => ctx.dry_run = flag == "-o",
+                Some(name) => {
+                    errln!(ctx, "set: {}: invalid option name", name);
+                    return;
+                }
+                None => {
+                    errln!(ctx, "set: option '{}' requires an argument", flag);
+                    return;
+                }
+            },
+            _ => {
+                errln!(ctx, "set: invalid argument '{}'", quoting::display(arg));
+                errln!(ctx, "Usage: set [-n | +n] [-o dryrun | +o dryrun]");
+                return;
+            }
+        }
+    }
+}
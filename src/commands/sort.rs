@@ -0,0 +1,977 @@
+//! sortコマンド。行を並べ替える
+//!
+//! 対応オプション:
+//! - `-n` 数値順 / `-h` 単位付きの数値順（`2K` < `1M`） / `-g` 浮動小数点数順（`1e3` など）
+//! - `-V` バージョン番号順（`file2` < `file10`）
+//! - `-r` 逆順 / `-f` 大文字・小文字を区別しない / `-d` 英数字と空白だけで比べる
+//! - `-b` 先頭の空白を無視する / `-i` 表示できない文字を無視する
+//! - `-k POS1[,POS2]` 比べる範囲（キー）。POS は `フィールド[.文字][オプション]` で、
+//!   オプションには上の `bdfghinrV` を付けられる。複数指定すると順に比べる
+//! - `-t SEP` フィールドの区切り文字（既定は空白から空白以外への変わり目）
+//! - `-s` 安定ソート / `-u` キーが等しい行は最初の1行だけ出力する
+//! - `-c` / `-C` 並べ替えずに、並んでいるかだけ調べる（`-c` は最初の乱れた行を表示する）
+//! - `-o FILE` 結果をファイルに書く（すべて読んでから開くので、入力と同じファイルでもよい）
+//! - `-z` 行の区切りを改行ではなく NUL にする
+//! - `-S SIZE` メモリに置く量（既定 64M。単位なしは KiB）
+//! - `-T DIR` 一時ファイルを置くディレクトリ（既定は環境変数 TMPDIR か /tmp。
+//!   サンドボックスの中ではルートの下の tmp で、ルートの外のディレクトリは使えない）
+//!
+//! 比較はバイト列として行う（C ロケールと同じ）。キーが等しい行は `-s` や `-u` がなければ
+//! 行全体で比べる。キーにオプションを付けると、そのキーには全体のオプション（`-n` など）は効かない。
+//!
+//! 入力が `-S` の量を超えたら、そこまでを並べ替えて一時ファイルに書き出し、
+//! 最後にすべてをマージして出力する（外部マージソート）。一時ファイルは終わったら消す。
+
+use super::cat::{self, input_name, LineReader};
+use super::error_message;
+use crate::context::Context;
+use crate::quoting;
+use crate::vfs::{FileReader, Fs};
+use crate::errln;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::mem;
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::Arc;
+use std::vec;
+
+/// コマンド名
+pub const NAME: &str = "sort";
+
+/// メモリに置く量の既定値
+const DEFAULT_BUFFER_SIZE: usize = 64 << 20;
+
+/// 1行ごとにかかるメモリの見積もり（行の内容以外）
+const LINE_OVERHEAD: usize = mem::size_of::<Vec<u8>>();
+
+/// 出力をまとめて書き出す量
+const OUTPUT_CHUNK: usize = 64 * 1024;
+
+/// 一時ファイル名の通し番号（同時に動く sort どうしで重ならないようにする）
+static TEMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// 比べ方
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    #[default]
+    Text,
+    Numeric,
+    Human,
+    General,
+    Version,
+}
+
+/// キーごとのオプション
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct KeyOptions {
+    mode: Mode,
+    reverse: bool,
+    /// -f
+    fold: bool,
+    /// -d
+    dictionary: bool,
+    /// -i
+    printable_only: bool,
+    /// キーの始まりの前の空白を飛ばす（-b または POS1 の b）
+    skip_start_blanks: bool,
+    /// キーの終わりを数えるときに空白を飛ばす（-b または POS2 の b）
+    skip_end_blanks: bool,
+}
+
+impl KeyOptions {
+    /// `bdfghinrV` の1文字を反映する。`b` は `is_end` なら終わり側に効く
+    fn set(&mut self, option: char, is_end: bool) -> bool {
+        match option {
+            'b' if is_end => self.skip_end_blanks = true,
+            'b' => self.skip_start_blanks = true,
+            'd' => self.dictionary = true,
+            'f' => self.fold = true,
+            'g' => self.mode = Mode::General,
+            'h' => self.mode = Mode::Human,
+            'i' => self.printable_only = true,
+            'n' => self.mode = Mode::Numeric,
+            'r' => self.reverse = true,
+            'V' => self.mode = Mode::Version,
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// 比べる範囲（-k）
+#[derive(Debug, Clone, Copy)]
+struct Key {
+    /// 始まりのフィールド（1から）
+    start_field: usize,
+    /// 始まりのフィールド内の文字位置（1から）
+    start_char: usize,
+    /// 終わりのフィールド。None なら行末まで
+    end_field: Option<usize>,
+    /// 終わりのフィールド内の文字位置（その文字まで含む）。0 ならフィールドの終わりまで
+    end_char: usize,
+    opts: KeyOptions,
+    /// オプションを付けたか（付けていなければ全体のオプションを使う）
+    has_opts: bool,
+}
+
+impl Key {
+    /// 行全体を比べるキー
+    fn whole_line(opts: KeyOptions) -> Key {
+        Key { start_field: 1, start_char: 1, end_field: None, end_char: 0, opts, has_opts: true }
+    }
+
+    /// `F[.C][OPTS][,F[.C][OPTS]]` を解析する
+    fn parse(spec: &str) -> Option<Key> {
+        let (start, end) = match spec.split_once(',') {
+            Some((start, end)) => (start, Some(end)),
+            None => (spec, None),
+        };
+        let mut opts = KeyOptions::default();
+        let mut has_opts = false;
+        let (start_field, start_char) = parse_position(start, false, &mut opts, &mut has_opts)?;
+        if start_field == 0 || start_char == Some(0) {
+            return None;
+        }
+        let (end_field, end_char) = match end {
+            Some(end) => {
+                let (field, ch) = parse_position(end, true, &mut opts, &mut has_opts)?;
+                if field == 0 {
+                    return None;
+                }
+                (Some(field), ch.unwrap_or(0))
+            }
+            None => (None, 0),
+        };
+        Some(Key { start_field, start_char: start_char.unwrap_or(1), end_field, end_char, opts, has_opts })
+    }
+
+    /// 行のうちキーにあたる部分
+    fn extract<'a>(&self, line: &'a [u8], separator: Option<u8>) -> &'a [u8] {
+        let mut start = field_start(line, self.start_field, separator);
+        if self.opts.skip_start_blanks {
+            start = skip_blanks(line, start);
+        }
+        start = (start + self.start_char - 1).min(line.len());
+        let end = match self.end_field {
+            None => line.len(),
+            Some(field) if self.end_char == 0 => field_end(line, field, separator),
+            Some(field) => {
+                let mut begin = field_start(line, field, separator);
+                if self.opts.skip_end_blanks {
+                    begin = skip_blanks(line, begin);
+                }
+                (begin + self.end_char).min(field_end(line, field, separator).max(begin)).min(line.len())
+            }
+        };
+        if end <= start {
+            &[]
+        } else {
+            &line[start..end]
+        }
+    }
+}
+
+/// キーの位置 `F[.C][OPTS]` を解析し、(フィールド, 文字位置) を返す
+fn parse_position(s: &str, is_end: bool, opts: &mut KeyOptions, has_opts: &mut bool) -> Option<(usize, Option<usize>)> {
+    let digits_end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let field = s[..digits_end].parse().ok()?;
+    let mut rest = &s[digits_end..];
+    let mut ch = None;
+    if let Some(after) = rest.strip_prefix('.') {
+        let end = after.find(|c: char| !c.is_ascii_digit()).unwrap_or(after.len());
+        ch = Some(after[..end].parse().ok()?);
+        rest = &after[end..];
+    }
+    for option in rest.chars() {
+        if !opts.set(option, is_end) {
+            return None;
+        }
+        *has_opts = true;
+    }
+    Some((field, ch))
+}
+
+fn is_blank(byte: u8) -> bool {
+    byte == b' ' || byte == b'\t'
+}
+
+fn skip_blanks(line: &[u8], mut pos: usize) -> usize {
+    while pos < line.len() && is_blank(line[pos]) {
+        pos += 1;
+    }
+    pos
+}
+
+/// `field` 番目（1から）のフィールドの始まり。区切り文字がなければ、フィールドは前の空白を含む
+fn field_start(line: &[u8], field: usize, separator: Option<u8>) -> usize {
+    let mut pos = 0;
+    for _ in 1..field {
+        match separator {
+            Some(sep) => match line[pos..].iter().position(|&b| b == sep) {
+                Some(i) => pos += i + 1,
+                None => return line.len(),
+            },
+            None => {
+                pos = skip_blanks(line, pos);
+                while pos < line.len() && !is_blank(line[pos]) {
+                    pos += 1;
+                }
+            }
+        }
+    }
+    pos
+}
+
+/// `field` 番目のフィールドの終わり
+fn field_end(line: &[u8], field: usize, separator: Option<u8>) -> usize {
+    let start = field_start(line, field, separator);
+    match separator {
+        Some(sep) => line[start..].iter().position(|&b| b == sep).map_or(line.len(), |i| start + i),
+        None => {
+            let mut pos = skip_blanks(line, start);
+            while pos < line.len() && !is_blank(line[pos]) {
+                pos += 1;
+            }
+            pos
+        }
+    }
+}
+
+/// 数値（-n）。整数部は先頭の 0 を、小数部は末尾の 0 を除いて持つ
+struct Number<'a> {
+    negative: bool,
+    int: &'a [u8],
+    frac: &'a [u8],
+}
+
+/// 先頭の空白に続く `-123.45` の形の数を読む。数でなければ 0
+fn parse_number(s: &[u8]) -> (Number<'_>, usize) {
+    let mut pos = skip_blanks(s, 0);
+    let negative = s.get(pos) == Some(&b'-');
+    if negative {
+        pos += 1;
+    }
+    let int_start = pos;
+    while pos < s.len() && s[pos].is_ascii_digit() {
+        pos += 1;
+    }
+    let mut int = &s[int_start..pos];
+    while int.first() == Some(&b'0') {
+        int = &int[1..];
+    }
+    let mut frac: &[u8] = &[];
+    if s.get(pos) == Some(&b'.') {
+        let frac_start = pos + 1;
+        pos = frac_start;
+        while pos < s.len() && s[pos].is_ascii_digit() {
+            pos += 1;
+        }
+        frac = &s[frac_start..pos];
+        while frac.last() == Some(&b'0') {
+            frac = &frac[..frac.len() - 1];
+        }
+    }
+    // -0 は 0 と同じ
+    let negative = negative && !(int.is_empty() && frac.is_empty());
+    (Number { negative, int, frac }, pos)
+}
+
+fn compare_numbers(a: &Number, b: &Number) -> Ordering {
+    match (a.negative, b.negative) {
+        (false, true) => Ordering::Greater,
+        (true, false) => Ordering::Less,
+        (negative, _) => {
+            let magnitude = a.int.len().cmp(&b.int.len()).then(a.int.cmp(b.int)).then(a.frac.cmp(b.frac));
+            if negative { magnitude.reverse() } else { magnitude }
+        }
+    }
+}
+
+fn compare_numeric(a: &[u8], b: &[u8]) -> Ordering {
+    compare_numbers(&parse_number(a).0, &parse_number(b).0)
+}
+
+/// 単位付きの数（-h）。単位の大きさで比べ、同じ単位なら数で比べる
+fn compare_human(a: &[u8], b: &[u8]) -> Ordering {
+    let (a_number, a_rank) = human_number(a);
+    let (b_number, b_rank) = human_number(b);
+    let sign = |n: &Number| if n.negative { -1 } else if n.int.is_empty() && n.frac.is_empty() { 0 } else { 1 };
+    match sign(&a_number).cmp(&sign(&b_number)) {
+        Ordering::Equal => {
+            let rank = if a_number.negative { b_rank.cmp(&a_rank) } else { a_rank.cmp(&b_rank) };
+            rank.then_with(|| compare_numbers(&a_number, &b_number))
+        }
+        other => other,
+    }
+}
+
+fn human_number(s: &[u8]) -> (Number<'_>, usize) {
+    let (number, end) = parse_number(s);
+    let rank = s.get(end).and_then(|&c| b"KMGTPEZYRQ".iter().position(|&u| u == c.to_ascii_uppercase()));
+    (number, rank.map_or(0, |r| r + 1))
+}
+
+/// 浮動小数点数（-g）。数でないものは先頭、次に NaN、その後に数の順
+fn compare_general(a: &[u8], b: &[u8]) -> Ordering {
+    let rank = |v: Option<f64>| match v {
+        None => 0,
+        Some(v) if v.is_nan() => 1,
+        Some(_) => 2,
+    };
+    let (a, b) = (parse_float(a), parse_float(b));
+    rank(a).cmp(&rank(b)).then_with(|| match (a, b) {
+        (Some(a), Some(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+        _ => Ordering::Equal,
+    })
+}
+
+/// 先頭の空白に続く、浮動小数点数として読める最も長い部分
+fn parse_float(s: &[u8]) -> Option<f64> {
+    let start = skip_blanks(s, 0);
+    let text = String::from_utf8_lossy(&s[start..]);
+    let text = text.as_ref();
+    let end = text
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '+'))
+        .unwrap_or(text.len());
+    (1..=end).rev().filter(|&i| text.is_char_boundary(i)).find_map(|i| text[..i].parse().ok())
+}
+
+/// バージョン番号順（-V）。数字の並びは数として、それ以外は文字として比べる。
+/// 文字は英字がそれ以外の記号より前、`~` は何よりも前（Debian のバージョン比較と同じ）
+fn compare_version(a: &[u8], b: &[u8]) -> Ordering {
+    fn order(c: Option<&u8>) -> i32 {
+        match c {
+            None => 0,
+            Some(c) if c.is_ascii_digit() => 0,
+            Some(c) if c.is_ascii_alphabetic() => *c as i32,
+            Some(b'~') => -1,
+            Some(c) => *c as i32 + 256,
+        }
+    }
+    let is_digit = |s: &[u8], i: usize| s.get(i).is_some_and(u8::is_ascii_digit);
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        while (i < a.len() && !is_digit(a, i)) || (j < b.len() && !is_digit(b, j)) {
+            let (ac, bc) = (order(a.get(i)), order(b.get(j)));
+            if ac != bc {
+                return ac.cmp(&bc);
+            }
+            i += 1;
+            j += 1;
+        }
+        while a.get(i) == Some(&b'0') {
+            i += 1;
+        }
+        while b.get(j) == Some(&b'0') {
+            j += 1;
+        }
+        let mut first_diff = Ordering::Equal;
+        while is_digit(a, i) && is_digit(b, j) {
+            if first_diff == Ordering::Equal {
+                first_diff = a[i].cmp(&b[j]);
+            }
+            i += 1;
+            j += 1;
+        }
+        if is_digit(a, i) {
+            return Ordering::Greater;
+        }
+        if is_digit(b, j) {
+            return Ordering::Less;
+        }
+        if first_diff != Ordering::Equal {
+            return first_diff;
+        }
+    }
+    Ordering::Equal
+}
+
+/// -f -d -i を反映した文字列としての比較
+fn compare_text(a: &[u8], b: &[u8], opts: &KeyOptions) -> Ordering {
+    if !(opts.fold || opts.dictionary || opts.printable_only) {
+        return a.cmp(b);
+    }
+    let keep = |c: &&u8| {
+        (!opts.dictionary || is_blank(**c) || c.is_ascii_alphanumeric())
+            && (!opts.printable_only || (0x20..0x7f).contains(*c))
+    };
+    let fold = |c: &u8| if opts.fold { c.to_ascii_uppercase() } else { *c };
+    a.iter().filter(keep).map(fold).cmp(b.iter().filter(keep).map(fold))
+}
+
+fn compare_key(a: &[u8], b: &[u8], opts: &KeyOptions) -> Ordering {
+    let ordering = match opts.mode {
+        Mode::Text => compare_text(a, b, opts),
+        Mode::Numeric => compare_numeric(a, b),
+        Mode::Human => compare_human(a, b),
+        Mode::General => compare_general(a, b),
+        Mode::Version => compare_version(a, b),
+    };
+    if opts.reverse { ordering.reverse() } else { ordering }
+}
+
+/// 並べ替えの設定
+struct Sorter {
+    keys: Vec<Key>,
+    separator: Option<u8>,
+    /// 全体の -r（行全体での比べ直しにも効く）
+    reverse: bool,
+    stable: bool,
+    unique: bool,
+    /// 行の区切り（-z なら NUL）
+    delim: u8,
+}
+
+impl Sorter {
+    /// キーで比べる（キーが等しければ行全体で比べ直す）
+    fn compare(&self, a: &[u8], b: &[u8]) -> Ordering {
+        let ordering = self.compare_keys(a, b);
+        if ordering != Ordering::Equal || self.stable || self.unique {
+            return ordering;
+        }
+        if self.reverse { b.cmp(a) } else { a.cmp(b) }
+    }
+
+    /// キーだけで比べる（-u で等しいとみなすかの判定に使う）
+    fn compare_keys(&self, a: &[u8], b: &[u8]) -> Ordering {
+        for key in &self.keys {
+            let ordering = compare_key(key.extract(a, self.separator), key.extract(b, self.separator), &key.opts);
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+        Ordering::Equal
+    }
+
+    fn sort(&self, lines: &mut [Vec<u8>]) {
+        lines.sort_by(|a, b| self.compare(a, b));
+    }
+}
+
+/// 並べ替えた結果の書き出し先。まとめて書き出し、-u なら等しい行を飛ばす
+struct Output<'s> {
+    sorter: &'s Sorter,
+    buf: Vec<u8>,
+    last: Option<Vec<u8>>,
+}
+
+impl<'s> Output<'s> {
+    fn new(sorter: &'s Sorter) -> Self {
+        Output { sorter, buf: Vec::with_capacity(OUTPUT_CHUNK), last: None }
+    }
+
+    fn push(&mut self, ctx: &mut Context, line: Vec<u8>) -> io::Result<()> {
+        if self.sorter.unique {
+            if self.last.as_ref().is_some_and(|last| self.sorter.compare_keys(last, &line) == Ordering::Equal) {
+                return Ok(());
+            }
+            self.buf.extend_from_slice(&line);
+            self.last = Some(line);
+        } else {
+            self.buf.extend_from_slice(&line);
+        }
+        self.buf.push(self.sorter.delim);
+        if self.buf.len() >= OUTPUT_CHUNK {
+            self.flush(ctx)?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self, ctx: &mut Context) -> io::Result<()> {
+        if ctx.interrupted() {
+            return Err(io::ErrorKind::Interrupted.into());
+        }
+        let result = ctx.stdout.write_all(&self.buf);
+        self.buf.clear();
+        result
+    }
+}
+
+/// 並べ替えの途中で書き出した一時ファイル。不要になったら消す
+struct TempFile {
+    path: PathBuf,
+    fs: Arc<dyn Fs>,
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = self.fs.remove_file(&self.path);
+    }
+}
+
+/// マージする入力の1つ（一時ファイルか、メモリに残った最後の部分）
+enum Run {
+    File(BufReader<Box<dyn FileReader>>),
+    Memory(vec::IntoIter<Vec<u8>>),
+}
+
+impl Run {
+    fn next(&mut self, delim: u8) -> io::Result<Option<Vec<u8>>> {
+        match self {
+            Run::File(reader) => {
+                let mut line = Vec::new();
+                if reader.read_until(delim, &mut line)? == 0 {
+                    return Ok(None);
+                }
+                if line.last() == Some(&delim) {
+                    line.pop();
+                }
+                Ok(Some(line))
+            }
+            Run::Memory(lines) => Ok(lines.next()),
+        }
+    }
+}
+
+/// マージ中の各部分の先頭の行。`BinaryHeap` は最大のものから出すので、順序を逆にしてある
+struct Head<'s> {
+    line: Vec<u8>,
+    /// どの部分から読んだか（入力順）
+    run: usize,
+    sorter: &'s Sorter,
+}
+
+impl Ord for Head<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.sorter.compare(&self.line, &other.line).then(self.run.cmp(&other.run)).reverse()
+    }
+}
+
+impl PartialOrd for Head<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Head<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Head<'_> {}
+
+/// 読み込んだ行と、書き出した一時ファイル
+struct Chunks<'s> {
+    sorter: &'s Sorter,
+    lines: Vec<Vec<u8>>,
+    /// `lines` のおおよそのメモリ量
+    size: usize,
+    limit: usize,
+    temp_dir: PathBuf,
+    temps: Vec<TempFile>,
+    /// 一時ファイルは `ctx.fs` を通して扱う（サンドボックスの外には置けない）
+    fs: Arc<dyn Fs>,
+    /// 一時ファイルの読み書きに失敗したときの説明（エラーの表示に使う）
+    temp_error: Option<String>,
+}
+
+impl Chunks<'_> {
+    fn push(&mut self, line: Vec<u8>) -> io::Result<()> {
+        self.size += line.len() + LINE_OVERHEAD;
+        self.lines.push(line);
+        if self.size >= self.limit {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    /// ここまでの行を並べ替えて一時ファイルに書き出す
+    fn spill(&mut self) -> io::Result<()> {
+        self.sorter.sort(&mut self.lines);
+        let n = TEMP_COUNTER.fetch_add(1, AtomicOrdering::SeqCst);
+        let path = self.temp_dir.join(format!("taminal-sort-{}-{}", process::id(), n));
+        let file = match self.fs.create_new(&path, 0o600) {
+            Ok(file) => file,
+            Err(e) => {
+                self.temp_error = Some(format!("cannot create temporary file in '{}'", self.temp_dir.display()));
+                return Err(e);
+            }
+        };
+        self.temps.push(TempFile { path: path.clone(), fs: Arc::clone(&self.fs) });
+        let mut writer = BufWriter::new(file);
+        let result = self.lines.drain(..).try_for_each(|line| {
+            writer.write_all(&line)?;
+            writer.write_all(&[self.sorter.delim])
+        });
+        if let Err(e) = result.and_then(|()| writer.flush()) {
+            self.temp_error = Some(format!("write failed: {}", path.display()));
+            return Err(e);
+        }
+        self.size = 0;
+        Ok(())
+    }
+
+    /// すべての行を並べた順に書き出す
+    fn finish(&mut self, ctx: &mut Context) -> io::Result<()> {
+        let sorter = self.sorter;
+        let mut lines = mem::take(&mut self.lines);
+        sorter.sort(&mut lines);
+        let mut output = Output::new(sorter);
+        if self.temps.is_empty() {
+            for line in lines {
+                output.push(ctx, line)?;
+            }
+            return output.flush(ctx);
+        }
+
+        // 各部分の先頭をヒープに入れ、最も小さいものから出す。等しければ先に読んだ部分を優先する（安定）
+        let mut runs = Vec::new();
+        for temp in &self.temps {
+            match self.fs.open(&temp.path) {
+                Ok(file) => runs.push(Run::File(BufReader::new(file))),
+                Err(e) => return Err(self.read_failed(temp.path.clone(), e)),
+            }
+        }
+        runs.push(Run::Memory(lines.into_iter()));
+        let mut heap = BinaryHeap::new();
+        for run in 0..runs.len() {
+            if let Some(line) = self.next_line(&mut runs, run)? {
+                heap.push(Head { line, run, sorter });
+            }
+        }
+        while let Some(Head { line, run, .. }) = heap.pop() {
+            if let Some(next) = self.next_line(&mut runs, run)? {
+                heap.push(Head { line: next, run, sorter });
+            }
+            output.push(ctx, line)?;
+        }
+        output.flush(ctx)
+    }
+
+    /// `runs[run]` の次の行。一時ファイルを読めなければそのパスを `temp_error` に残す
+    fn next_line(&mut self, runs: &mut [Run], run: usize) -> io::Result<Option<Vec<u8>>> {
+        match runs[run].next(self.sorter.delim) {
+            Err(e) if run < self.temps.len() => Err(self.read_failed(self.temps[run].path.clone(), e)),
+            result => result,
+        }
+    }
+
+    fn read_failed(&mut self, path: PathBuf, e: io::Error) -> io::Error {
+        self.temp_error = Some(format!("cannot read: {}", path.display()));
+        e
+    }
+}
+
+/// `-S` の量。単位なしは KiB
+fn parse_buffer_size(s: &str) -> Option<usize> {
+    let (digits, suffix) = s.split_at(s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len()));
+    let multiplier: usize = match suffix {
+        "b" => 1,
+        "" | "k" | "K" => 1 << 10,
+        "m" | "M" => 1 << 20,
+        "g" | "G" => 1 << 30,
+        "t" | "T" => 1 << 40,
+        _ => return None,
+    };
+    digits.parse::<usize>().ok()?.checked_mul(multiplier).filter(|&n| n > 0)
+}
+
+pub fn run(ctx: &mut Context, args: &[OsString]) {
+    let mut global = KeyOptions::default();
+    let mut keys: Vec<Key> = Vec::new();
+    let mut separator = None;
+    let mut stable = false;
+    let mut unique = false;
+    let mut check: Option<bool> = None;
+    let mut output_file: Option<&OsStr> = None;
+    let mut delim = b'\n';
+    let mut buffer_size = DEFAULT_BUFFER_SIZE;
+    let mut temp_dir: Option<PathBuf> = None;
+    let mut files: Vec<&OsStr> = Vec::new();
+    let mut only_files = false;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let Some(s) = arg.to_str().filter(|s| !only_files && s.starts_with('-') && s.len() > 1) else {
+            files.push(arg);
+            continue;
+        };
+        // 値を取るオプション（-k 2 / -k2 / --key=2 / --key 2）
+        let (option, value): (char, &OsStr) = match s {
+            "--" => {
+                only_files = true;
+                continue;
+            }
+            "--numeric-sort" => {
+                global.mode = Mode::Numeric;
+                continue;
+            }
+            "--human-numeric-sort" => {
+                global.mode = Mode::Human;
+                continue;
+            }
+            "--general-numeric-sort" => {
+                global.mode = Mode::General;
+                continue;
+            }
+            "--version-sort" => {
+                global.mode = Mode::Version;
+                continue;
+            }
+            "--reverse" => {
+                global.reverse = true;
+                continue;
+            }
+            "--ignore-case" => {
+                global.fold = true;
+                continue;
+            }
+            "--dictionary-order" => {
+                global.dictionary = true;
+                continue;
+            }
+            "--ignore-nonprinting" => {
+                global.printable_only = true;
+                continue;
+            }
+            "--ignore-leading-blanks" => {
+                (global.skip_start_blanks, global.skip_end_blanks) = (true, true);
+                continue;
+            }
+            "--stable" => {
+                stable = true;
+                continue;
+            }
+            "--unique" => {
+                unique = true;
+                continue;
+            }
+            "--check" | "--check=diagnose-first" => {
+                check = Some(true);
+                continue;
+            }
+            "--check=quiet" | "--check=silent" => {
+                check = Some(false);
+                continue;
+            }
+            "--zero-terminated" => {
+                delim = b'\0';
+                continue;
+            }
+            _ if s.starts_with("--") => {
+                let (name, value) = match s.split_once('=') {
+                    Some((name, value)) => (name, Some(OsStr::new(value))),
+                    None => (s, None),
+                };
+                let option = match name {
+                    "--key" => 'k',
+                    "--field-separator" => 't',
+                    "--output" => 'o',
+                    "--buffer-size" => 'S',
+                    "--temporary-directory" => 'T',
+                    _ => {
+                        errln!(ctx, "sort: unrecognized option '{}'", s);
+                        return;
+                    }
+                };
+                match value.or_else(|| args.next().map(|v| v.as_os_str())) {
+                    Some(value) => (option, value),
+                    None => {
+                        errln!(ctx, "sort: option '{}' requires an argument", name);
+                        return;
+                    }
+                }
+            }
+            _ => {
+                let mut value = None;
+                for (i, ch) in s[1..].char_indices() {
+                    match ch {
+                        'k' | 't' | 'o' | 'S' | 'T' => {
+                            let rest = &s[i + 2..];
+                            value = match (rest.is_empty(), if rest.is_empty() { args.next() } else { None }) {
+                                (false, _) => Some((ch, OsStr::new(rest))),
+                                (true, Some(next)) => Some((ch, next.as_os_str())),
+                                (true, None) => {
+                                    errln!(ctx, "sort: option requires an argument -- '{}'", ch);
+                                    return;
+                                }
+                            };
+                            break;
+                        }
+                        's' => stable = true,
+                        'u' => unique = true,
+                        'c' => check = Some(true),
+                        'C' => check = Some(false),
+                        'z' => delim = b'\0',
+                        'b' => (global.skip_start_blanks, global.skip_end_blanks) = (true, true),
+                        _ if global.set(ch, false) => {}
+                        _ => {
+                            errln!(ctx, "sort: invalid option -- '{}'", ch);
+                            return;
+                        }
+                    }
+                }
+                match value {
+                    Some(value) => value,
+                    None => continue,
+                }
+            }
+        };
+
+        match option {
+            'k' => match value.to_str().and_then(Key::parse) {
+                Some(key) => keys.push(key),
+                None => {
+                    errln!(ctx, "sort: invalid field specification '{}'", value.to_string_lossy());
+                    return;
+                }
+            },
+            't' => match value.as_encoded_bytes() {
+                [byte] => separator = Some(*byte),
+                b"\\0" => separator = Some(b'\0'),
+                [] => {
+                    errln!(ctx, "sort: empty tab");
+                    return;
+                }
+                _ => {
+                    errln!(ctx, "sort: multi-character tab '{}'", value.to_string_lossy());
+                    return;
+                }
+            },
+            'o' => output_file = Some(value),
+            'S' => match value.to_str().and_then(parse_buffer_size) {
+                Some(size) => buffer_size = size,
+                None => {
+                    errln!(ctx, "sort: invalid -S argument '{}'", value.to_string_lossy());
+                    return;
+                }
+            },
+            _ => temp_dir = Some(ctx.resolve(value)),
+        }
+    }
+    if files.is_empty() {
+        files.push(OsStr::new("-"));
+    }
+
+    // オプションを付けていないキーは全体のオプションで比べる
+    for key in &mut keys {
+        if !key.has_opts {
+            key.opts = global;
+        }
+    }
+    if keys.is_empty() {
+        keys.push(Key::whole_line(global));
+    }
+    let sorter = Sorter { keys, separator, reverse: global.reverse, stable, unique, delim };
+
+    if let Some(diagnose) = check {
+        if files.len() > 1 {
+            errln!(ctx, "sort: extra operand '{}' not allowed with -c", quoting::display(files[1]));
+            return;
+        }
+        let result = cat::with_input(ctx, files[0], |ctx, reader| check_order(ctx, reader, &sorter, files[0], diagnose));
+        match result {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) if e.kind() == io::ErrorKind::Interrupted => {}
+            Ok(Err(e)) | Err(e) => errln!(ctx, "sort: cannot read: {}: {}", quoting::display(files[0]), error_message(&e)),
+        }
+        return;
+    }
+
+    // サンドボックスの中の既定はルートの下の tmp。-T や TMPDIR がルートの外なら ctx.fs が拒む
+    let default_temp_dir = match &ctx.sandbox {
+        Some(root) => root.join("tmp"),
+        None => PathBuf::from("/tmp"),
+    };
+    let temp_dir = temp_dir.or_else(|| env::var_os("TMPDIR").map(|dir| ctx.resolve(dir))).unwrap_or(default_temp_dir);
+    let mut chunks = Chunks {
+        sorter: &sorter,
+        lines: Vec::new(),
+        size: 0,
+        limit: buffer_size,
+        temp_dir,
+        temps: Vec::new(),
+        fs: Arc::clone(&ctx.fs),
+        temp_error: None,
+    };
+    for file in &files {
+        let result = cat::with_input(ctx, file, |ctx, reader| read_lines(ctx, reader, &mut chunks));
+        match result {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) if e.kind() == io::ErrorKind::Interrupted => return,
+            Ok(Err(e)) | Err(e) => {
+                match chunks.temp_error.take() {
+                    Some(what) => errln!(ctx, "sort: {}: {}", what, error_message(&e)),
+                    None => errln!(ctx, "sort: cannot read: {}: {}", quoting::display(file), error_message(&e)),
+                }
+                return;
+            }
+        }
+    }
+
+    // 出力先のファイルは、入力をすべて読んでから開く（`sort -o f f` のため）
+    let saved = match output_file {
+        Some(path) => match ctx.fs.create(&ctx.resolve(path), 0o666) {
+            Ok(file) => Some(mem::replace(&mut ctx.stdout, Box::new(BufWriter::new(file)))),
+            Err(e) => {
+                errln!(ctx, "sort: open failed: {}: {}", quoting::display(path), error_message(&e));
+                return;
+            }
+        },
+        None => None,
+    };
+    let result = chunks.finish(ctx).and_then(|()| ctx.stdout.flush());
+    if let Some(stdout) = saved {
+        ctx.stdout = stdout;
+    }
+    match result {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+        Err(e) if chunks.temp_error.is_some() => {
+            errln!(ctx, "sort: {}: {}", chunks.temp_error.take().unwrap_or_default(), error_message(&e));
+        }
+        Err(e) if output_file.is_some() => {
+            errln!(ctx, "sort: write failed: {}: {}", output_file.map(quoting::display).unwrap_or_default(), error_message(&e));
+        }
+        // 標準出力の書き込みエラー（パイプの先が閉じたなど）は他のコマンドと同じく無視する
+        Err(_) => {}
+    }
+}
+
+/// 入力を1行ずつ読み、区切りを除いて `chunks` に入れる
+fn read_lines(ctx: &mut Context, reader: &mut dyn Read, chunks: &mut Chunks) -> io::Result<()> {
+    let delim = chunks.sorter.delim;
+    let mut lines = LineReader::new();
+    let mut line = Vec::new();
+    while lines.read_record(ctx, reader, delim, &mut line)? {
+        if line.last() == Some(&delim) {
+            line.pop();
+        }
+        chunks.push(mem::take(&mut line))?;
+    }
+    Ok(())
+}
+
+/// 並んでいるか調べる（-c / -C）。`diagnose` なら最初の乱れた行を表示する
+fn check_order(ctx: &mut Context, reader: &mut dyn Read, sorter: &Sorter, name: &OsStr, diagnose: bool) -> io::Result<()> {
+    let mut lines = LineReader::new();
+    let mut prev: Option<Vec<u8>> = None;
+    let mut line = Vec::new();
+    let mut number = 0u64;
+    while lines.read_record(ctx, reader, sorter.delim, &mut line)? {
+        number += 1;
+        if line.last() == Some(&sorter.delim) {
+            line.pop();
+        }
+        if let Some(prev) = &prev {
+            let ordering = sorter.compare(prev, &line);
+            if ordering == Ordering::Greater || sorter.unique && ordering == Ordering::Equal {
+                if diagnose {
+                    let name = if name == "-" { "-".to_string() } else { input_name(name) };
+                    errln!(ctx, "sort: {}:{}: disorder: {}", name, number, String::from_utf8_lossy(&line));
+                }
+                // -C でも終了ステータスで並んでいないことを知らせる
+                ctx.status = 1;
+                return Ok(());
+            }
+        }
+        prev = Some(mem::take(&mut line));
+    }
+    Ok(())
+}
@@ -0,0 +1,346 @@
+//! statコマンド。ファイルの詳しい情報を表示する
+//!
+//! 対応オプション:
+//! - `-L` シンボリックリンクをたどる（`--dereference`）
+//! - `-c FORMAT` 既定の代わりに FORMAT で表示し、最後に改行を付ける（`--format=FORMAT`）
+//! - `--printf=FORMAT` FORMAT で表示する。バックスラッシュのエスケープを解釈し、改行は付けない
+//! - `-t` 1行の簡潔な形式で表示する（`--terse`）
+//!
+//! FORMAT の変換指定（`%-10s` のように幅・左寄せ `-`・ゼロ埋め `0`・精度も書ける）:
+//! `%a` 8進のパーミッション、`%A` `-rw-r--r--` 形式、`%b` ブロック数、`%B` ブロックの単位、
+//! `%d` `%D` デバイス番号（10進・16進）、`%f` 16進の生のモード、`%F` 種類、`%g` `%G` gid・グループ名、
+//! `%h` リンク数、`%i` inode、`%n` 名前、`%N` 引用符付きの名前（リンクなら参照先も）、
+//! `%o` I/O ブロックサイズ、`%r` `%t` `%T` デバイスファイルのデバイス番号（10進・major・minor の16進）、
+//! `%s` サイズ、`%u` `%U` uid・ユーザー名、`%w` `%W` 作成日時（不明なので `-` と 0）、
+//! `%x` `%X` アクセス日時、`%y` `%Y` 更新日時、`%z` `%Z` 状態変更日時（大文字は UNIX 秒）、`%%`。
+//! `%d` `%r` の前に `H`（major）や `L`（minor）を付けられる。
+
+use super::echo;
+use super::error_message;
+use crate::context::Context;
+use crate::file_info::{self, NameCache};
+use crate::fs_util::FileTime;
+use crate::errln;
+use crate::quoting::{self, QuotingStyle};
+use crate::vfs::Stat;
+use std::ffi::{OsStr, OsString};
+use std::io::Write;
+
+/// コマンド名
+pub const NAME: &str = "stat";
+
+/// 既定の表示形式（通常のファイル）
+const DEFAULT_FORMAT: &str = "  File: %N\n  Size: %-10s\tBlocks: %-10b IO Block: %-6o %F\n\
+Device: %Hd,%Ld\tInode: %-11i Links: %h\n\
+Access: (%04a/%10.10A)  Uid: (%5u/%8U)   Gid: (%5g/%8G)\n\
+Access: %x\nModify: %y\nChange: %z\n Birth: %w\n";
+
+/// 既定の表示形式（デバイスファイル）
+const DEVICE_FORMAT: &str = "  File: %N\n  Size: %-10s\tBlocks: %-10b IO Block: %-6o %F\n\
+Device: %Hd,%Ld\tInode: %-11i Links: %-5h Device type: %Hr,%Lr\n\
+Access: (%04a/%10.10A)  Uid: (%5u/%8U)   Gid: (%5g/%8G)\n\
+Access: %x\nModify: %y\nChange: %z\n Birth: %w\n";
+
+/// `-t` の表示形式
+const TERSE_FORMAT: &str = "%n %s %b %f %u %g %D %i %h %t %T %X %Y %Z %W %o\n";
+
+pub fn run(ctx: &mut Context, args: &[OsString]) {
+    let mut dereference = false;
+    let mut terse = false;
+    // (書式, エスケープを解釈して改行を付けないか)
+    let mut format: Option<(String, bool)> = None;
+    let mut files: Vec<&OsStr> = Vec::new();
+    let mut only_operands = false;
+    let mut args = args.iter();
+
+    while let Some(os_arg) = args.next() {
+        let arg = match os_arg.to_str() {
+            Some(arg) if !only_operands && arg.starts_with('-') && arg != "-" => arg,
+            _ => {
+                files.push(os_arg);
+                continue;
+            }
+        };
+        match arg {
+            "--" => only_operands = true,
+            "--dereference" => dereference = true,
+            "--terse" => terse = true,
+            "--format" | "--printf" => match args.next() {
+                Some(value) => format = Some((value.to_string_lossy().into_owned(), arg == "--printf")),
+                None => {
+                    errln!(ctx, "stat: option '{}' requires an argument", arg);
+                    return;
+                }
+            },
+            _ if arg.starts_with("--format=") => format = Some((arg["--format=".len()..].to_string(), false)),
+            _ if arg.starts_with("--printf=") => format = Some((arg["--printf=".len()..].to_string(), true)),
+            _ if arg.starts_with("--") => {
+                errln!(ctx, "stat: unrecognized option '{}'", arg);
+                errln!(ctx, "Try 'stat --help' for more information.");
+                return;
+            }
+            _ => {
+                for (i, ch) in arg[1..].char_indices() {
+                    match ch {
+                        'L' => dereference = true,
+                        't' => terse = true,
+                        'c' => {
+                            let rest = &arg[1 + i + 1..];
+                            let value = if !rest.is_empty() {
+                                Some(rest.to_string())
+                            } else {
+                                args.next().map(|value| value.to_string_lossy().into_owned())
+                            };
+                            match value {
+                                Some(value) => format = Some((value, false)),
+                                None => {
+                                    errln!(ctx, "stat: option requires an argument -- 'c'");
+                                    return;
+                                }
+                            }
+                            break;
+                        }
+                        _ => {
+                            errln!(ctx, "stat: invalid option -- '{}'", ch);
+                            errln!(ctx, "Try 'stat --help' for more information.");
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    if files.is_empty() {
+        errln!(ctx, "stat: missing operand");
+        errln!(ctx, "Try 'stat --help' for more information.");
+        return;
+    }
+
+    let mut names = NameCache::default();
+    for file in files {
+        let path = ctx.resolve(file);
+        let result = if dereference { ctx.fs.metadata(&path) } else { ctx.fs.symlink_metadata(&path) };
+        let meta = match result {
+            Ok(meta) => meta,
+            Err(e) => {
+                errln!(ctx, "stat: cannot stat '{}': {}", quoting::display(file), error_message(&e));
+                continue;
+            }
+        };
+        let target = if meta.is_symlink() { ctx.fs.read_link(&path).ok() } else { None };
+        let entry = Entry {
+            name: file,
+            target: target.as_ref().map(|target| target.as_os_str()),
+            meta: &meta,
+        };
+
+        let mut out = Vec::new();
+        match &format {
+            Some((format, true)) => {
+                let mut unescaped = Vec::new();
+                echo::unescape(format.as_bytes(), &mut unescaped);
+                render(&String::from_utf8_lossy(&unescaped), &entry, &mut names, &mut out);
+            }
+            Some((format, false)) => {
+                render(format, &entry, &mut names, &mut out);
+                out.push(b'\n');
+            }
+            None if terse => render(TERSE_FORMAT, &entry, &mut names, &mut out),
+            None => {
+                let kind = meta.file_type();
+                let format = if kind.is_block_device() || kind.is_char_device() { DEVICE_FORMAT } else { DEFAULT_FORMAT };
+                render(format, &entry, &mut names, &mut out);
+            }
+        }
+        let _ = ctx.stdout.write_all(&out);
+    }
+    let _ = ctx.stdout.flush();
+}
+
+/// 表示するファイル
+struct Entry<'a> {
+    /// 引数で指定された名前
+    name: &'a OsStr,
+    /// シンボリックリンクの参照先
+    target: Option<&'a OsStr>,
+    meta: &'a Stat,
+}
+
+/// 書式に従って `out` に書き出す
+fn render(format: &str, entry: &Entry, names: &mut NameCache, out: &mut Vec<u8>) {
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            let mut buf = [0; 4];
+            out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+        // フラグ・幅・精度
+        let mut spec = String::from("%");
+        let (mut left, mut zero) = (false, false);
+        while let Some(&flag @ ('-' | '0' | '#' | '+' | ' ' | '\'')) = chars.peek() {
+            left |= flag == '-';
+            zero |= flag == '0';
+            spec.push(flag);
+            chars.next();
+        }
+        let mut width = String::new();
+        while let Some(&digit) = chars.peek().filter(|c| c.is_ascii_digit()) {
+            width.push(digit);
+            chars.next();
+        }
+        let mut precision: Option<usize> = None;
+        if chars.peek() == Some(&'.') {
+            chars.next();
+            let mut digits = String::new();
+            while let Some(&digit) = chars.peek().filter(|c| c.is_ascii_digit()) {
+                digits.push(digit);
+                chars.next();
+            }
+            precision = Some(digits.parse().unwrap_or(0));
+        }
+        spec.push_str(&width);
+
+        // %Hd %Ld %Hr %Lr
+        let mut part = None;
+        if let Some(&half @ ('H' | 'L')) = chars.peek() {
+            chars.next();
+            part = Some(half);
+        }
+        let Some(conversion) = chars.next() else {
+            out.extend_from_slice(spec.as_bytes());
+            break;
+        };
+        let value = match part {
+            Some(half) if matches!(conversion, 'd' | 'r') => {
+                let dev = if conversion == 'd' { entry.meta.dev() } else { entry.meta.rdev() };
+                let (major, minor) = file_info::device_numbers(dev);
+                Some(Value::Number((if half == 'H' { major } else { minor }).to_string()))
+            }
+            Some(half) => {
+                spec.push(half);
+                None
+            }
+            None => convert(conversion, entry, names),
+        };
+        let Some(value) = value else {
+            // 未知の指定はそのまま出す
+            spec.push(conversion);
+            out.extend_from_slice(spec.as_bytes());
+            continue;
+        };
+
+        let width: usize = width.parse().unwrap_or(0);
+        let (text, numeric) = match value {
+            Value::Number(n) => (n, true),
+            Value::Text(text) => (text, false),
+        };
+        let text = match precision {
+            Some(precision) if !numeric => text.chars().take(precision).collect(),
+            _ => text,
+        };
+        let padded = if left {
+            format!("{:<width$}", text)
+        } else if zero && numeric {
+            format!("{:0>width$}", text)
+        } else {
+            format!("{:>width$}", text)
+        };
+        out.extend_from_slice(padded.as_bytes());
+    }
+}
+
+/// 変換結果。数値（8進・16進を含む）はゼロ埋めの対象になる
+enum Value {
+    Number(String),
+    Text(String),
+}
+
+/// 1つの変換指定を値にする。未知の指定なら None
+fn convert(conversion: char, entry: &Entry, names: &mut NameCache) -> Option<Value> {
+    let meta = entry.meta;
+    let (major, minor) = file_info::device_numbers(meta.rdev());
+    let value = match conversion {
+        'a' => Value::Number(format!("{:o}", meta.mode() & 0o7777)),
+        'A' => Value::Text(file_info::mode_string(meta)),
+        'b' => Value::Number(meta.blocks().to_string()),
+        'B' => Value::Number(512.to_string()),
+        'd' => Value::Number(meta.dev().to_string()),
+        'D' => Value::Number(format!("{:x}", meta.dev())),
+        'f' => Value::Number(format!("{:x}", meta.mode())),
+        'F' => Value::Text(type_name(meta).to_string()),
+        'g' => Value::Number(meta.gid().to_string()),
+        'G' => Value::Text(names.group(meta.gid()).to_string()),
+        'h' => Value::Number(meta.nlink().to_string()),
+        'i' => Value::Number(meta.ino().to_string()),
+        'n' => Value::Text(quoting::display(entry.name)),
+        'N' => {
+            let mut text = quoting::quote(entry.name, QuotingStyle::ShellEscapeAlways);
+            if let Some(target) = entry.target {
+                text.push_str(" -> ");
+                text.push_str(&quoting::quote(target, QuotingStyle::ShellEscapeAlways));
+            }
+            Value::Text(text)
+        }
+        'o' => Value::Number(meta.blksize().to_string()),
+        'r' => Value::Number(meta.rdev().to_string()),
+        's' => Value::Number(meta.len().to_string()),
+        't' => Value::Number(format!("{:x}", major)),
+        'T' => Value::Number(format!("{:x}", minor)),
+        'u' => Value::Number(meta.uid().to_string()),
+        'U' => Value::Text(names.user(meta.uid()).to_string()),
+        'w' => Value::Text("-".to_string()),
+        'W' => Value::Number(0.to_string()),
+        'x' => Value::Text(human_time(meta.atime())),
+        'X' => Value::Number(meta.atime().secs.to_string()),
+        'y' => Value::Text(human_time(meta.mtime())),
+        'Y' => Value::Number(meta.mtime().secs.to_string()),
+        'z' => Value::Text(human_time(meta.ctime())),
+        'Z' => Value::Number(meta.ctime().secs.to_string()),
+        '%' => Value::Text("%".to_string()),
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// `%F` のファイルの種類
+fn type_name(meta: &Stat) -> &'static str {
+    let kind = meta.file_type();
+    if kind.is_dir() {
+        "directory"
+    } else if kind.is_symlink() {
+        "symbolic link"
+    } else if kind.is_fifo() {
+        "fifo"
+    } else if kind.is_socket() {
+        "socket"
+    } else if kind.is_block_device() {
+        "block special file"
+    } else if kind.is_char_device() {
+        "character special file"
+    } else if meta.is_empty() {
+        "regular empty file"
+    } else {
+        "regular file"
+    }
+}
+
+/// "2024-03-01 12:34:56.123456789 +0900" 形式の日時
+fn human_time(time: FileTime) -> String {
+    let t = file_info::local_time(time.secs);
+    let offset = t.utc_offset.abs() / 60;
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:09} {}{:02}{:02}",
+        t.year,
+        t.month,
+        t.day,
+        t.hour,
+        t.minute,
+        t.second,
+        time.nanos,
+        if t.utc_offset < 0 { '-' } else { '+' },
+        offset / 60,
+        offset % 60
+    )
+}
@@ -0,0 +1,568 @@
+//! tailコマンド。ファイルの末尾部分を表示する
+//!
+//! 対応オプション:
+//! - `-n N` / `--lines=N` 末尾の N 行（既定 10）。`-n +N` なら N 行目から最後まで
+//! - `-c N` / `--bytes=N` 末尾の N バイト。`-c +N` なら N バイト目から最後まで
+//! - `-f` / `--follow` 表示した後もファイルを名前で追いかけ、追記された内容を表示し続ける。
+//!   ファイルが置き換えられた（ログのローテーションなど）ときは新しいファイルを開き直し、
+//!   切り詰められたときは先頭から読み直す
+//! - `-F` `-f --retry` と同じ。開けないファイルも現れるまで待つ
+//! - `-s N` / `--sleep-interval=N` 追いかけるときに確かめる間隔（秒、既定 1）
+//! - `-q` / `-v` ファイル名の見出し（`==> name <==`）を付けない・常に付ける
+//! - `-N` は `-n N` と、先頭の引数の `+N` は `-n +N` と同じ
+//!
+//! 追いかけている間は inotify で変化を待つ（使えないときは一定の間隔で確かめる）。
+//! Ctrl+C（GUI では Stop ボタン）で終了する。
+
+use super::cat::{input_name, read_some};
+use super::error_message;
+use super::head::{line_end, parse_count};
+use crate::context::Context;
+use crate::errln;
+use crate::vfs::FileReader;
+use std::ffi::{CString, OsStr, OsString};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// コマンド名
+pub const NAME: &str = "tail";
+
+/// 一度に読むバイト数
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// 待っている間に中断を確かめる間隔
+const INTERRUPT_CHECK: Duration = Duration::from_millis(100);
+
+/// 表示する範囲
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Span {
+    /// 末尾の N 行（N バイト）
+    Last(u64),
+    /// N 行目（N バイト目）から最後まで
+    From(u64),
+}
+
+/// 数える単位
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Unit {
+    Lines,
+    Bytes,
+}
+
+/// 解析済みのオプション
+struct Options {
+    unit: Unit,
+    span: Span,
+    follow: bool,
+    retry: bool,
+    interval: Duration,
+    headers: Option<bool>,
+}
+
+pub fn run(ctx: &mut Context, args: &[OsString]) {
+    let mut opts = Options {
+        unit: Unit::Lines,
+        span: Span::Last(10),
+        follow: false,
+        retry: false,
+        interval: Duration::from_secs(1),
+        headers: None,
+    };
+    let mut files: Vec<&OsStr> = Vec::new();
+    let mut only_files = false;
+
+    let mut args = args.iter().enumerate();
+    while let Some((index, arg)) = args.next() {
+        let Some(s) = arg.to_str().filter(|s| !only_files && s.len() > 1) else {
+            files.push(arg);
+            continue;
+        };
+        // 先頭の引数の +N は -n +N と同じ
+        if index == 0 && s.starts_with('+') && parse_count(&s[1..]).is_some() {
+            if !set_span(ctx, &mut opts, Unit::Lines, s) {
+                return;
+            }
+            continue;
+        }
+        if !s.starts_with('-') {
+            files.push(arg);
+            continue;
+        }
+        // 値を取るオプションは (オプション名, 値) にまとめる
+        let (option, value) = match s {
+            "--" => {
+                only_files = true;
+                continue;
+            }
+            "--follow" | "--follow=name" => {
+                opts.follow = true;
+                continue;
+            }
+            "--retry" => {
+                opts.retry = true;
+                continue;
+            }
+            "--quiet" | "--silent" => {
+                opts.headers = Some(false);
+                continue;
+            }
+            "--verbose" => {
+                opts.headers = Some(true);
+                continue;
+            }
+            "-n" | "--lines" | "-c" | "--bytes" | "-s" | "--sleep-interval" => match args.next() {
+                Some((_, value)) => (s.trim_start_matches('-').chars().next().unwrap_or('n'), value.to_string_lossy().into_owned()),
+                None => {
+                    errln!(ctx, "tail: option requires an argument -- '{}'", s.trim_start_matches('-'));
+                    return;
+                }
+            },
+            _ if s.starts_with("--lines=") => ('n', s["--lines=".len()..].to_string()),
+            _ if s.starts_with("--bytes=") => ('c', s["--bytes=".len()..].to_string()),
+            _ if s.starts_with("--sleep-interval=") => ('s', s["--sleep-interval=".len()..].to_string()),
+            _ if s.starts_with("--") => {
+                errln!(ctx, "tail: unrecognized option '{}'", s);
+                return;
+            }
+            // -5 は -n 5 と同じ
+            _ if s[1..].starts_with(|c: char| c.is_ascii_digit()) => ('n', s[1..].to_string()),
+            _ => {
+                let mut value = None;
+                for (i, ch) in s[1..].char_indices() {
+                    match ch {
+                        'f' => opts.follow = true,
+                        'F' => (opts.follow, opts.retry) = (true, true),
+                        'q' => opts.headers = Some(false),
+                        'v' => opts.headers = Some(true),
+                        'n' | 'c' | 's' => {
+                            let rest = &s[i + 2..];
+                            value = match (rest.is_empty(), if rest.is_empty() { args.next() } else { None }) {
+                                (false, _) => Some((ch, rest.to_string())),
+                                (true, Some((_, next))) => Some((ch, next.to_string_lossy().into_owned())),
+                                (true, None) => {
+                                    errln!(ctx, "tail: option requires an argument -- '{}'", ch);
+                                    return;
+                                }
+                            };
+                            break;
+                        }
+                        _ => {
+                            errln!(ctx, "tail: invalid option -- '{}'", ch);
+                            return;
+                        }
+                    }
+                }
+                match value {
+                    Some(value) => value,
+                    None => continue,
+                }
+            }
+        };
+
+        let ok = match option {
+            's' => match value.parse::<f64>().ok().and_then(|secs| Duration::try_from_secs_f64(secs).ok()) {
+                Some(interval) => {
+                    opts.interval = interval;
+                    true
+                }
+                None => {
+                    errln!(ctx, "tail: invalid number of seconds: '{}'", value);
+                    false
+                }
+            },
+            'c' => set_span(ctx, &mut opts, Unit::Bytes, &value),
+            _ => set_span(ctx, &mut opts, Unit::Lines, &value),
+        };
+        if !ok {
+            return;
+        }
+    }
+    if files.is_empty() {
+        files.push(OsStr::new("-"));
+    }
+
+    let show_headers = opts.headers.unwrap_or(files.len() > 1);
+    let mut followed = Vec::new();
+    for (i, &file) in files.iter().enumerate() {
+        if show_headers {
+            let blank = if i == 0 { "" } else { "\n" };
+            let _ = writeln!(ctx.stdout, "{}==> {} <==", blank, input_name(file));
+        }
+        // 標準入力は追いかけない
+        if file == "-" {
+            let mut stdin = std::mem::replace(&mut ctx.stdin, Box::new(io::empty()));
+            let result = print_stream(ctx, &mut stdin, &opts);
+            ctx.stdin = stdin;
+            if let Err(e) = result {
+                if e.kind() == io::ErrorKind::Interrupted {
+                    break;
+                }
+                errln!(ctx, "tail: error reading 'standard input': {}", error_message(&e));
+            }
+            continue;
+        }
+        let path = ctx.resolve(file);
+        let mut target = Followed { name: file.to_os_string(), path, reader: None, id: None, pos: 0, missing: false };
+        match ctx.fs.open(&target.path) {
+            Ok(mut reader) => match print_file(ctx, &mut reader, &opts) {
+                Ok(pos) => {
+                    target.id = ctx.fs.metadata(&target.path).ok().map(|stat| (stat.dev(), stat.ino()));
+                    target.pos = pos;
+                    target.reader = Some(reader);
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => break,
+                Err(e) => errln!(ctx, "tail: error reading '{}': {}", input_name(file), error_message(&e)),
+            },
+            Err(e) => {
+                errln!(ctx, "tail: cannot open '{}' for reading: {}", input_name(file), error_message(&e));
+                target.missing = true;
+            }
+        }
+        followed.push(target);
+    }
+    let _ = ctx.stdout.flush();
+
+    if opts.follow && !ctx.interrupted() {
+        follow(ctx, &mut followed, &opts, show_headers);
+    }
+}
+
+/// `N` / `+N` / `-N` を解釈して表示する範囲に設定する
+fn set_span(ctx: &mut Context, opts: &mut Options, unit: Unit, value: &str) -> bool {
+    let (from, digits) = match value.as_bytes().first() {
+        Some(b'+') => (true, &value[1..]),
+        Some(b'-') => (false, &value[1..]),
+        _ => (false, value),
+    };
+    let Some(count) = parse_count(digits) else {
+        let what = if unit == Unit::Bytes { "bytes" } else { "lines" };
+        errln!(ctx, "tail: invalid number of {}: '{}'", what, value);
+        return false;
+    };
+    opts.unit = unit;
+    opts.span = if from { Span::From(count) } else { Span::Last(count) };
+    true
+}
+
+/// 開いたファイルの指定の範囲を書き出し、読み終えた位置を返す。
+/// 通常のファイルは末尾へシークして必要な部分だけを読む
+fn print_file(ctx: &mut Context, reader: &mut Box<dyn FileReader>, opts: &Options) -> io::Result<u64> {
+    let len = match reader.seek(SeekFrom::End(0)) {
+        Ok(len) if len > 0 => len,
+        // 長さの分からないもの（/proc のファイルなど）は先頭から読む
+        _ => {
+            reader.seek(SeekFrom::Start(0)).ok();
+            return print_stream(ctx, reader, opts);
+        }
+    };
+    let start = match (opts.unit, opts.span) {
+        (Unit::Bytes, Span::Last(n)) => len.saturating_sub(n),
+        (Unit::Bytes, Span::From(n)) => n.saturating_sub(1).min(len),
+        (Unit::Lines, Span::Last(n)) => last_lines_start(reader, len, n)?,
+        (Unit::Lines, Span::From(_)) => {
+            reader.seek(SeekFrom::Start(0))?;
+            return print_stream(ctx, reader, opts);
+        }
+    };
+    reader.seek(SeekFrom::Start(start))?;
+    Ok(start + copy_to_end(ctx, reader)?)
+}
+
+/// 末尾の `count` 行が始まる位置を、ファイルの後ろからブロックごとに読んで探す
+fn last_lines_start(reader: &mut Box<dyn FileReader>, len: u64, count: u64) -> io::Result<u64> {
+    if count == 0 {
+        return Ok(len);
+    }
+    let mut buf = vec![0u8; BUFFER_SIZE];
+    let mut end = len;
+    // 最後の改行は行の終わりなので数えない
+    let mut remaining = count + 1;
+    let mut first_block = true;
+    while end > 0 {
+        let start = end.saturating_sub(BUFFER_SIZE as u64);
+        let block = &mut buf[..(end - start) as usize];
+        reader.seek(SeekFrom::Start(start))?;
+        reader.read_exact(block)?;
+        if first_block && block.last() != Some(&b'\n') {
+            remaining -= 1;
+        }
+        first_block = false;
+        for (i, &b) in block.iter().enumerate().rev() {
+            if b == b'\n' {
+                remaining -= 1;
+                if remaining == 0 {
+                    return Ok(start + i as u64 + 1);
+                }
+            }
+        }
+        end = start;
+    }
+    Ok(0)
+}
+
+/// シークできない入力から指定の範囲を書き出し、読んだバイト数を返す
+fn print_stream(ctx: &mut Context, reader: &mut dyn Read, opts: &Options) -> io::Result<u64> {
+    let mut buf = vec![0u8; BUFFER_SIZE];
+    let mut total = 0u64;
+    match opts.span {
+        Span::From(n) => {
+            // 先頭の n - 1 行（バイト）を読み飛ばす
+            let mut skip = n.saturating_sub(1);
+            loop {
+                let n = read_some(ctx, reader, &mut buf)?;
+                if n == 0 {
+                    return Ok(total);
+                }
+                total += n as u64;
+                let mut start = 0;
+                if skip > 0 {
+                    start = match opts.unit {
+                        Unit::Bytes => skip.min(n as u64) as usize,
+                        Unit::Lines => line_end(&buf[..n], skip),
+                    };
+                    skip -= match opts.unit {
+                        Unit::Bytes => start as u64,
+                        Unit::Lines => buf[..start].iter().filter(|&&b| b == b'\n').count() as u64,
+                    };
+                }
+                let _ = ctx.stdout.write_all(&buf[start..n]);
+            }
+        }
+        Span::Last(count) => {
+            // 末尾の分だけを手元に残しながら最後まで読む
+            let mut pending: Vec<u8> = Vec::new();
+            let mut newlines = 0u64;
+            loop {
+                let n = read_some(ctx, reader, &mut buf)?;
+                if n == 0 {
+                    break;
+                }
+                total += n as u64;
+                pending.extend_from_slice(&buf[..n]);
+                let drop = match opts.unit {
+                    Unit::Bytes => pending.len().saturating_sub(count.try_into().unwrap_or(usize::MAX)),
+                    Unit::Lines => {
+                        newlines += buf[..n].iter().filter(|&&b| b == b'\n').count() as u64;
+                        let excess = newlines.saturating_sub(count);
+                        newlines -= excess;
+                        line_end(&pending, excess)
+                    }
+                };
+                pending.drain(..drop);
+            }
+            // 改行で終わらない最後の行も1行と数える
+            let start = match opts.unit {
+                Unit::Lines if pending.last().is_some_and(|&b| b != b'\n') => {
+                    line_end(&pending, (newlines + 1).saturating_sub(count))
+                }
+                _ => 0,
+            };
+            let _ = ctx.stdout.write_all(&pending[start..]);
+            Ok(total)
+        }
+    }
+}
+
+/// 最後まで読んで書き出し、読んだバイト数を返す
+fn copy_to_end(ctx: &mut Context, reader: &mut dyn Read) -> io::Result<u64> {
+    let mut buf = vec![0u8; BUFFER_SIZE];
+    let mut total = 0u64;
+    loop {
+        let n = read_some(ctx, reader, &mut buf)?;
+        if n == 0 {
+            return Ok(total);
+        }
+        let _ = ctx.stdout.write_all(&buf[..n]);
+        total += n as u64;
+    }
+}
+
+/// 追いかけているファイル
+struct Followed {
+    /// 引数で指定された名前
+    name: OsString,
+    path: PathBuf,
+    /// 開いているファイル（見つからない間は None）
+    reader: Option<Box<dyn FileReader>>,
+    /// 開いているファイルの (デバイス, inode)。置き換えられたかを調べるのに使う
+    id: Option<(u64, u64)>,
+    /// 読み終えた位置
+    pos: u64,
+    /// 見つからないことを報告済みか
+    missing: bool,
+}
+
+/// ファイルの変化を待ちながら、追記された内容を書き出し続ける
+fn follow(ctx: &mut Context, files: &mut [Followed], opts: &Options, show_headers: bool) {
+    // inotify は実際のファイルシステムでだけ使える
+    let watcher = (ctx.fs.is_native() || ctx.sandbox.is_some()).then(Watcher::new).flatten();
+    if let Some(watcher) = &watcher {
+        for file in files.iter() {
+            if let Some(parent) = file.path.parent() {
+                watcher.watch(parent, libc::IN_CREATE | libc::IN_MOVED_TO);
+            }
+        }
+    }
+    // 最後に内容を書き出したファイル（見出しを出し直すかの判断に使う）
+    let mut last = files.len().checked_sub(1);
+    loop {
+        for (i, file) in files.iter_mut().enumerate() {
+            let data = poll(ctx, file);
+            if !data.is_empty() {
+                if show_headers && last != Some(i) {
+                    let _ = writeln!(ctx.stdout, "\n==> {} <==", input_name(&file.name));
+                }
+                let _ = ctx.stdout.write_all(&data);
+                last = Some(i);
+            }
+            if let (Some(watcher), Some(_)) = (&watcher, &file.reader) {
+                let mask = libc::IN_MODIFY | libc::IN_ATTRIB | libc::IN_DELETE_SELF | libc::IN_MOVE_SELF;
+                watcher.watch(&file.path, mask);
+            }
+        }
+        let _ = ctx.stdout.flush();
+
+        if !opts.retry && files.iter().all(|file| file.reader.is_none()) {
+            errln!(ctx, "tail: no files remaining");
+            return;
+        }
+        if !wait(ctx, watcher.as_ref(), opts.interval) {
+            return;
+        }
+    }
+}
+
+/// ファイルの状態を確かめ、新しく読めた内容を返す。
+/// 置き換えられていれば古いファイルの残りを読んでから新しいファイルを開き直す
+fn poll(ctx: &mut Context, file: &mut Followed) -> Vec<u8> {
+    let mut data = Vec::new();
+    let name = input_name(&file.name);
+    match ctx.fs.metadata(&file.path) {
+        Err(e) => {
+            if file.reader.is_some() {
+                read_available(ctx, file, &mut data);
+                errln!(ctx, "tail: '{}' has become inaccessible: {}", name, error_message(&e));
+                file.reader = None;
+                file.missing = true;
+            }
+        }
+        Ok(stat) => {
+            let id = (stat.dev(), stat.ino());
+            if file.reader.is_some() && file.id != Some(id) {
+                read_available(ctx, file, &mut data);
+                errln!(ctx, "tail: '{}' has been replaced;  following new file", name);
+                file.reader = None;
+            } else if file.reader.is_none() && file.missing {
+                errln!(ctx, "tail: '{}' has appeared;  following new file", name);
+            }
+            if file.reader.is_none() {
+                match ctx.fs.open(&file.path) {
+                    Ok(reader) => {
+                        file.reader = Some(reader);
+                        file.id = Some(id);
+                        file.pos = 0;
+                        file.missing = false;
+                    }
+                    Err(e) => {
+                        if !file.missing {
+                            errln!(ctx, "tail: cannot open '{}' for reading: {}", name, error_message(&e));
+                        }
+                        file.missing = true;
+                        return data;
+                    }
+                }
+            } else if stat.is_file() && stat.len() < file.pos {
+                errln!(ctx, "tail: {}: file truncated", name);
+                if let Some(reader) = &mut file.reader {
+                    file.pos = reader.seek(SeekFrom::Start(0)).unwrap_or(0);
+                }
+            }
+            read_available(ctx, file, &mut data);
+        }
+    }
+    data
+}
+
+/// 開いているファイルから今読めるだけ読んで `data` に足す
+fn read_available(ctx: &mut Context, file: &mut Followed, data: &mut Vec<u8>) {
+    let Some(reader) = &mut file.reader else {
+        return;
+    };
+    let mut buf = vec![0u8; BUFFER_SIZE];
+    loop {
+        match read_some(ctx, reader, &mut buf) {
+            Ok(0) => return,
+            Ok(n) => {
+                data.extend_from_slice(&buf[..n]);
+                file.pos += n as u64;
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => return,
+            Err(e) => {
+                errln!(ctx, "tail: error reading '{}': {}", input_name(&file.name), error_message(&e));
+                file.reader = None;
+                return;
+            }
+        }
+    }
+}
+
+/// 変化があるか `interval` が過ぎるまで待つ。中断されたら false を返す
+fn wait(ctx: &Context, watcher: Option<&Watcher>, interval: Duration) -> bool {
+    let deadline = Instant::now() + interval;
+    loop {
+        if ctx.interrupted() {
+            return false;
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return true;
+        }
+        let slice = (deadline - now).min(INTERRUPT_CHECK);
+        match watcher {
+            Some(watcher) => {
+                if watcher.wait(slice) {
+                    return true;
+                }
+            }
+            None => thread::sleep(slice),
+        }
+    }
+}
+
+/// inotify によるファイルの変化の監視
+struct Watcher {
+    fd: OwnedFd,
+}
+
+impl Watcher {
+    fn new() -> Option<Self> {
+        let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return None;
+        }
+        Some(Watcher { fd: unsafe { OwnedFd::from_raw_fd(fd) } })
+    }
+
+    /// `path` を監視に加える（すでに監視していれば何も変わらない）。失敗しても間隔による確認が残るので無視する
+    fn watch(&self, path: &Path, mask: u32) {
+        if let Ok(path) = CString::new(path.as_os_str().as_bytes()) {
+            unsafe { libc::inotify_add_watch(self.fd.as_raw_fd(), path.as_ptr(), mask) };
+        }
+    }
+
+    /// 最大 `timeout` だけイベントを待つ。届いたイベントは読み捨てて true を返す
+    fn wait(&self, timeout: Duration) -> bool {
+        let mut pollfd = libc::pollfd { fd: self.fd.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+        let ready = unsafe { libc::poll(&mut pollfd, 1, timeout.as_millis() as libc::c_int) };
+        if ready <= 0 {
+            return false;
+        }
+        let mut buf = [0u8; 4096];
+        while unsafe { libc::read(self.fd.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len()) } > 0 {}
+        true
+    }
+}
@@ -0,0 +1,374 @@
+//! test / [ / [[ コマンド。条件式を評価し、結果を終了ステータスで返す
+//! （真なら 0、偽なら 1、式の誤りは 2）
+//!
+//! `[ EXPR ]` は `test EXPR` と同じで、最後の引数は `]` でなければならない。
+//! `[[ EXPR ]]` は条件の結合に `-a` `-o` の代わりに `&&` `||` を使い、
+//! `==` `!=` の右辺をワイルドカードのパターンとして、`=~` を正規表現として照合する。
+//!
+//! 対応する式:
+//! - ファイル: `-e`（存在する） `-f`（通常ファイル） `-d`（ディレクトリ） `-L` `-h`（シンボリックリンク）
+//!   `-b` `-c` `-p` `-S`（ブロック・キャラクタデバイス、FIFO、ソケット） `-s`（空でない）
+//!   `-r` `-w` `-x`（読み・書き・実行できる） `-u` `-g` `-k`（setuid・setgid・スティッキー）
+//!   `-O` `-G`（自分が所有者・グループ） `-t FD`（端末につながっている）
+//! - ファイルの比較: `A -nt B`（A が新しい） `A -ot B`（A が古い） `A -ef B`（同じファイル）
+//! - 文字列: `-z S`（空） `-n S` / `S`（空でない） `A = B` `A == B` `A != B` `A < B` `A > B`（辞書順）
+//! - 整数: `-eq` `-ne` `-lt` `-le` `-gt` `-ge`
+//! - 結合: `! EXPR` `( EXPR )` `EXPR -a EXPR` `EXPR -o EXPR`（`[[` では `&&` `||`）
+//!
+//! test と `[` は POSIX と同じく、引数が4つまでなら個数によって解釈を決める
+//! （`test -f` は "-f" が空でない文字列なので真）。
+
+use crate::context::Context;
+use crate::glob::Glob;
+use crate::vfs::{FileKind, Stat};
+use crate::errln;
+use regex::bytes::Regex;
+use std::ffi::{OsStr, OsString};
+use std::os::unix::ffi::OsStrExt;
+
+/// コマンド名
+pub const NAME: &str = "test";
+/// `[ EXPR ]` の形のコマンド名
+pub const BRACKET: &str = "[";
+/// `[[ EXPR ]]` の形のコマンド名
+pub const DOUBLE_BRACKET: &str = "[[";
+
+pub fn run(ctx: &mut Context, args: &[OsString]) {
+    evaluate(ctx, NAME, args, false);
+}
+
+pub fn run_bracket(ctx: &mut Context, args: &[OsString]) {
+    match args.split_last() {
+        Some((last, args)) if last == "]" => evaluate(ctx, BRACKET, args, false),
+        _ => fail(ctx, BRACKET, "missing ']'".to_string()),
+    }
+}
+
+pub fn run_double_bracket(ctx: &mut Context, args: &[OsString]) {
+    match args.split_last() {
+        Some((last, args)) if last == "]]" && !args.is_empty() => evaluate(ctx, DOUBLE_BRACKET, args, true),
+        Some((last, _)) if last == "]]" => fail(ctx, DOUBLE_BRACKET, "expression expected".to_string()),
+        _ => fail(ctx, DOUBLE_BRACKET, "missing ']]'".to_string()),
+    }
+}
+
+/// 式を評価して終了ステータスを設定する
+fn evaluate(ctx: &mut Context, name: &str, args: &[OsString], extended: bool) {
+    let result = {
+        let mut parser = Parser { ctx, args, pos: 0, extended };
+        if extended { parser.expression() } else { parser.posix() }
+    };
+    match result {
+        Ok(true) => ctx.status = 0,
+        Ok(false) => ctx.status = 1,
+        Err(message) => fail(ctx, name, message),
+    }
+}
+
+/// 式の誤り。終了ステータスは 2
+fn fail(ctx: &mut Context, name: &str, message: String) {
+    errln!(ctx, "{}: {}", name, message);
+    ctx.status = 2;
+}
+
+/// 2つの引数をとる演算子か
+fn is_binary(op: &OsStr, extended: bool) -> bool {
+    let common = ["=", "==", "!=", "<", ">", "-eq", "-ne", "-lt", "-le", "-gt", "-ge", "-nt", "-ot", "-ef"];
+    common.iter().any(|b| op == *b) || (if extended { op == "=~" } else { op == "-a" || op == "-o" })
+}
+
+/// 1つの引数をとる演算子か
+fn is_unary(op: &OsStr) -> bool {
+    match op.as_bytes() {
+        [b'-', c] => b"efdLhbcpSsrwxugkOGtzn".contains(c),
+        _ => false,
+    }
+}
+
+/// 引数を前から読んで式を評価する
+struct Parser<'a> {
+    ctx: &'a Context,
+    args: &'a [OsString],
+    pos: usize,
+    /// `[[` の式か
+    extended: bool,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self, offset: usize) -> Option<&'a OsStr> {
+        self.args.get(self.pos + offset).map(|arg| arg.as_os_str())
+    }
+
+    fn take(&mut self) -> Option<&'a OsStr> {
+        let arg = self.peek(0)?;
+        self.pos += 1;
+        Some(arg)
+    }
+
+    /// test と `[`。引数が4つまでは個数で解釈を決め、それより多ければ一般の式として読む
+    fn posix(&mut self) -> Result<bool, String> {
+        let args = self.args;
+        let result = match args.len() {
+            0 => false,
+            1 => !args[0].is_empty(),
+            2 if args[0] == "!" => args[1].is_empty(),
+            2 if is_unary(&args[0]) => self.unary(&args[0], &args[1])?,
+            2 => return Err(format!("{}: unary operator expected", args[0].to_string_lossy())),
+            3 if is_binary(&args[1], false) => self.binary(&args[0], &args[1], &args[2])?,
+            3 if args[0] == "!" => {
+                self.pos = 1;
+                !self.posix_rest()?
+            }
+            3 if args[0] == "(" && args[2] == ")" => !args[1].is_empty(),
+            3 => return Err(format!("{}: binary operator expected", args[1].to_string_lossy())),
+            4 if args[0] == "!" => {
+                self.pos = 1;
+                !self.posix_rest()?
+            }
+            4 if args[0] == "(" && args[3] == ")" => {
+                let mut inner = Parser { ctx: self.ctx, args: &args[1..3], pos: 0, extended: false };
+                inner.posix()?
+            }
+            _ => self.expression()?,
+        };
+        Ok(result)
+    }
+
+    /// `pos` 以降を独立した test の引数として評価する（`! ...` の残り）
+    fn posix_rest(&mut self) -> Result<bool, String> {
+        let mut inner = Parser { ctx: self.ctx, args: &self.args[self.pos..], pos: 0, extended: false };
+        inner.posix()
+    }
+
+    /// 式全体を読む。余った引数があれば誤り
+    fn expression(&mut self) -> Result<bool, String> {
+        let result = self.or()?;
+        match self.peek(0) {
+            Some(extra) => Err(format!("extra argument '{}'", extra.to_string_lossy())),
+            None => Ok(result),
+        }
+    }
+
+    fn or_token(&self) -> &'static str {
+        if self.extended { "||" } else { "-o" }
+    }
+
+    fn and_token(&self) -> &'static str {
+        if self.extended { "&&" } else { "-a" }
+    }
+
+    /// `A -o B`（`A || B`）。評価は短絡しない（右辺の誤りも報告する）
+    fn or(&mut self) -> Result<bool, String> {
+        let mut result = self.and()?;
+        while self.peek(0).is_some_and(|arg| arg == self.or_token()) {
+            self.pos += 1;
+            result |= self.and()?;
+        }
+        Ok(result)
+    }
+
+    /// `A -a B`（`A && B`）
+    fn and(&mut self) -> Result<bool, String> {
+        let mut result = self.not()?;
+        while self.peek(0).is_some_and(|arg| arg == self.and_token()) {
+            self.pos += 1;
+            result &= self.not()?;
+        }
+        Ok(result)
+    }
+
+    /// `! A`
+    fn not(&mut self) -> Result<bool, String> {
+        if self.peek(0).is_some_and(|arg| arg == "!") && self.peek(1).is_some() {
+            self.pos += 1;
+            return Ok(!self.not()?);
+        }
+        self.primary()
+    }
+
+    /// `( A )`、単項・二項の演算、文字列1つ
+    fn primary(&mut self) -> Result<bool, String> {
+        let Some(first) = self.peek(0) else {
+            let after = self.pos.checked_sub(1).map(|i| self.args[i].to_string_lossy());
+            return Err(match after {
+                Some(after) => format!("missing argument after '{}'", after),
+                None => "expression expected".to_string(),
+            });
+        };
+        // 二項演算を先に調べる（`-f = -f` は文字列の比較）
+        if let (Some(op), Some(right)) = (self.peek(1), self.peek(2)) {
+            let connector = op == self.and_token() || op == self.or_token();
+            if is_binary(op, self.extended) && !connector {
+                self.pos += 3;
+                return self.binary(first, op, right);
+            }
+        }
+        if first == "(" && self.peek(1).is_some() {
+            self.pos += 1;
+            let result = self.or()?;
+            if self.take().is_none_or(|arg| arg != ")") {
+                return Err("')' expected".to_string());
+            }
+            return Ok(result);
+        }
+        if is_unary(first) {
+            if let Some(operand) = self.peek(1) {
+                self.pos += 2;
+                return self.unary(first, operand);
+            }
+            // [[ では引数のない単項演算子は誤り（test では空でない文字列）
+            if self.extended {
+                return Err(format!("unexpected argument to unary operator '{}'", first.to_string_lossy()));
+            }
+        }
+        if let Some(op) = self.peek(1).filter(|op| is_binary(op, self.extended)) {
+            return Err(format!("missing argument after '{}'", op.to_string_lossy()));
+        }
+        self.pos += 1;
+        Ok(!first.is_empty())
+    }
+
+    /// 単項演算子 `op` を `operand` に適用する
+    fn unary(&self, op: &OsStr, operand: &OsStr) -> Result<bool, String> {
+        let op = op.as_bytes()[1];
+        match op {
+            b'z' => return Ok(operand.is_empty()),
+            b'n' => return Ok(!operand.is_empty()),
+            b't' => {
+                let fd = parse_integer(operand)?;
+                let fd = i32::try_from(fd).map_err(|_| format!("invalid integer '{}'", operand.to_string_lossy()))?;
+                return Ok(unsafe { libc::isatty(fd) } == 1);
+            }
+            _ => {}
+        }
+        // 空のパス名はカレントディレクトリではなく、存在しないものとして扱う
+        if operand.is_empty() {
+            return Ok(false);
+        }
+        let path = self.ctx.resolve(operand);
+        let stat = if matches!(op, b'L' | b'h') { self.ctx.fs.symlink_metadata(&path) } else { self.ctx.fs.metadata(&path) };
+        let Ok(stat) = stat else {
+            return Ok(false);
+        };
+        let kind = stat.file_type();
+        Ok(match op {
+            b'e' => true,
+            b'f' => kind.is_file(),
+            b'd' => kind.is_dir(),
+            b'L' | b'h' => kind.is_symlink(),
+            b'b' => kind.is_block_device(),
+            b'c' => kind.is_char_device(),
+            b'p' => kind.is_fifo(),
+            b'S' => kind.is_socket(),
+            b's' => !stat.is_empty(),
+            b'r' => accessible(&stat, 0o4),
+            b'w' => accessible(&stat, 0o2),
+            b'x' => accessible(&stat, 0o1),
+            b'u' => stat.mode() & 0o4000 != 0,
+            b'g' => stat.mode() & 0o2000 != 0,
+            b'k' => stat.mode() & 0o1000 != 0,
+            b'O' => stat.uid() == unsafe { libc::geteuid() },
+            _ => stat.gid() == unsafe { libc::getegid() },
+        })
+    }
+
+    /// 二項演算子 `op` で `left` と `right` を比べる
+    fn binary(&self, left: &OsStr, op: &OsStr, right: &OsStr) -> Result<bool, String> {
+        let op = op.to_str().unwrap_or_default();
+        Ok(match op {
+            "=" | "==" if self.extended => matches_pattern(left, right),
+            "!=" if self.extended => !matches_pattern(left, right),
+            "=" | "==" => left == right,
+            "!=" => left != right,
+            "<" => left.as_bytes() < right.as_bytes(),
+            ">" => left.as_bytes() > right.as_bytes(),
+            "=~" => {
+                let pattern = right.to_string_lossy();
+                let regex = Regex::new(&pattern).map_err(|_| format!("invalid regular expression '{}'", pattern))?;
+                regex.is_match(left.as_bytes())
+            }
+            "-a" => !left.is_empty() && !right.is_empty(),
+            "-o" => !left.is_empty() || !right.is_empty(),
+            "-nt" | "-ot" => {
+                let mtime = |path: &OsStr| {
+                    let stat = self.ctx.fs.metadata(&self.ctx.resolve(path)).ok()?;
+                    Some((stat.mtime().secs, stat.mtime().nanos))
+                };
+                let (left, right) = (mtime(left), mtime(right));
+                // 存在しないファイル（None）はどのファイルよりも古い
+                if op == "-nt" { left > right } else { left < right }
+            }
+            "-ef" => {
+                let id = |path: &OsStr| {
+                    let stat = self.ctx.fs.metadata(&self.ctx.resolve(path)).ok()?;
+                    Some((stat.dev(), stat.ino()))
+                };
+                let left = id(left);
+                left.is_some() && left == id(right)
+            }
+            _ => {
+                let (left, right) = (parse_integer(left)?, parse_integer(right)?);
+                match op {
+                    "-eq" => left == right,
+                    "-ne" => left != right,
+                    "-lt" => left < right,
+                    "-le" => left <= right,
+                    "-gt" => left > right,
+                    _ => left >= right,
+                }
+            }
+        })
+    }
+}
+
+/// 前後の空白を許して整数として読む
+fn parse_integer(arg: &OsStr) -> Result<i128, String> {
+    let text = arg.to_string_lossy();
+    let trimmed = text.trim();
+    let digits = trimmed.strip_prefix(['+', '-']).unwrap_or(trimmed);
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(format!("invalid integer '{}'", text));
+    }
+    trimmed.parse().map_err(|_| format!("integer out of range '{}'", text))
+}
+
+/// `[[ A == PATTERN ]]` の照合。パターンとして読めなければ文字列として比べる
+fn matches_pattern(text: &OsStr, pattern: &OsStr) -> bool {
+    match Glob::new(&pattern.to_string_lossy()) {
+        Ok(glob) => glob.matches(text),
+        Err(_) => text == pattern,
+    }
+}
+
+/// 実効ユーザーが `want`（読み 4・書き 2・実行 1）の権限を持つか。
+/// root は読み書きは常に、実行はいずれかの実行ビットがあるかディレクトリならできる
+fn accessible(stat: &Stat, want: u32) -> bool {
+    let mode = stat.mode();
+    let uid = unsafe { libc::geteuid() };
+    if uid == 0 {
+        return want != 0o1 || mode & 0o111 != 0 || stat.file_type() == FileKind::Dir;
+    }
+    let shift = if stat.uid() == uid {
+        6
+    } else if in_group(stat.gid()) {
+        3
+    } else {
+        0
+    };
+    (mode >> shift) & want != 0
+}
+
+/// 実効グループか補助グループに `gid` が含まれるか
+fn in_group(gid: u32) -> bool {
+    if gid == unsafe { libc::getegid() } {
+        return true;
+    }
+    let count = unsafe { libc::getgroups(0, std::ptr::null_mut()) };
+    if count <= 0 {
+        return false;
+    }
+    let mut groups = vec![0 as libc::gid_t; count as usize];
+    let count = unsafe { libc::getgroups(count, groups.as_mut_ptr()) };
+    groups.truncate(count.max(0) as usize);
+    groups.contains(&gid)
+}
@@ -0,0 +1,388 @@
+//! touchコマンド。ファイルのアクセス日時・更新日時を変える。存在しなければ空のファイルを作る
+//!
+//! 対応オプション:
+//! - `-a` アクセス日時だけを変える（`--time=atime`）
+//! - `-m` 更新日時だけを変える（`--time=mtime`）
+//! - `-c` 存在しないファイルを作らない（`--no-create`）
+//! - `-d DATE` 現在時刻の代わりに DATE を使う（`--date=DATE`）
+//! - `-t STAMP` `[[CC]YY]MMDDhhmm[.ss]` 形式で日時を指定する
+//! - `-r FILE` FILE の日時を使う（`--reference=FILE`）
+//! - `-h` シンボリックリンク自体の日時を変える（`--no-dereference`）
+//!
+//! DATE には `2024-03-01`、`2024-03-01 12:34[:56[.789]]`（`T` 区切りも可）、`@UNIX秒`、
+//! `now` `today` `yesterday` `tomorrow`、`3 days ago` `+2 hours` のような相対指定を書ける。
+//! 日時はローカル時刻として解釈する。
+
+use super::error_message;
+use crate::context::Context;
+use crate::fs_action::FsAction;
+use crate::fs_util::FileTime;
+use crate::errln;
+use crate::quoting;
+use std::ffi::{OsStr, OsString};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// コマンド名
+pub const NAME: &str = "touch";
+
+/// 解析済みのオプション
+struct Options {
+    access: bool,
+    modify: bool,
+    no_create: bool,
+    no_dereference: bool,
+    /// -d / -t / -r で指定した日時（(atime, mtime)）
+    times: Option<(FileTime, FileTime)>,
+}
+
+pub fn run(ctx: &mut Context, args: &[OsString]) {
+    let mut opts = Options {
+        access: false,
+        modify: false,
+        no_create: false,
+        no_dereference: false,
+        times: None,
+    };
+    let mut files: Vec<&OsStr> = Vec::new();
+    let mut only_operands = false;
+    let mut args = args.iter();
+
+    while let Some(os_arg) = args.next() {
+        let arg = match os_arg.to_str() {
+            Some(arg) if !only_operands && arg.starts_with('-') && arg != "-" => arg,
+            _ => {
+                files.push(os_arg);
+                continue;
+            }
+        };
+        // 引数を取るオプション（短い形式の文字, 値）
+        let mut with_value: Option<(char, &OsStr)> = None;
+        if let Some(long) = arg.strip_prefix("--") {
+            let (name, value) = match long.split_once('=') {
+                Some((name, value)) => (name, Some(OsStr::new(value))),
+                None => (long, None),
+            };
+            let short = match name {
+                "" => {
+                    only_operands = true;
+                    continue;
+                }
+                "no-create" => Some('c'),
+                "no-dereference" => Some('h'),
+                "date" => Some('d'),
+                "reference" => Some('r'),
+                "time" => Some('T'),
+                _ => None,
+            };
+            match short {
+                Some(short @ ('c' | 'h')) if value.is_none() => set_flag(&mut opts, short),
+                Some(short @ ('d' | 'r' | 'T')) => match value.or_else(|| args.next().map(OsString::as_os_str)) {
+                    Some(value) => with_value = Some((short, value)),
+                    None => {
+                        errln!(ctx, "touch: option '--{}' requires an argument", name);
+                        return;
+                    }
+                },
+                _ => {
+                    errln!(ctx, "touch: unrecognized option '{}'", arg);
+                    errln!(ctx, "Try 'touch --help' for more information.");
+                    return;
+                }
+            }
+        } else {
+            for (i, ch) in arg[1..].char_indices() {
+                match ch {
+                    'a' | 'm' | 'c' | 'h' => set_flag(&mut opts, ch),
+                    // 互換のために受け付けて無視する
+                    'f' => {}
+                    'd' | 't' | 'r' => {
+                        let rest = &arg[1 + i + 1..];
+                        let value = if !rest.is_empty() {
+                            Some(OsStr::new(rest))
+                        } else {
+                            args.next().map(OsString::as_os_str)
+                        };
+                        match value {
+                            Some(value) => with_value = Some((ch, value)),
+                            None => {
+                                errln!(ctx, "touch: option requires an argument -- '{}'", ch);
+                                return;
+                            }
+                        }
+                        break;
+                    }
+                    _ => {
+                        errln!(ctx, "touch: invalid option -- '{}'", ch);
+                        errln!(ctx, "Try 'touch --help' for more information.");
+                        return;
+                    }
+                }
+            }
+        }
+
+        let Some((option, value)) = with_value else { continue };
+        let text = value.to_string_lossy();
+        match option {
+            'd' => match parse_date(&text) {
+                Some(time) => opts.times = Some((time, time)),
+                None => {
+                    errln!(ctx, "touch: invalid date format '{}'", text);
+                    return;
+                }
+            },
+            't' => match parse_stamp(&text) {
+                Some(time) => opts.times = Some((time, time)),
+                None => {
+                    errln!(ctx, "touch: invalid date format '{}'", text);
+                    return;
+                }
+            },
+            'r' => match ctx.fs.metadata(&ctx.resolve(value)) {
+                Ok(meta) => opts.times = Some((meta.atime(), meta.mtime())),
+                Err(e) => {
+                    errln!(ctx, "touch: failed to get attributes of '{}': {}", quoting::display(value), error_message(&e));
+                    return;
+                }
+            },
+            _ => match &*text {
+                "atime" | "access" | "use" => opts.access = true,
+                "mtime" | "modify" => opts.modify = true,
+                _ => {
+                    errln!(ctx, "touch: invalid argument '{}' for '--time'", text);
+                    errln!(ctx, "Valid arguments are: 'atime', 'access', 'use', 'mtime', 'modify'");
+                    return;
+                }
+            },
+        }
+    }
+
+    if files.is_empty() {
+        errln!(ctx, "touch: missing file operand");
+        errln!(ctx, "Try 'touch --help' for more information.");
+        return;
+    }
+
+    // -a も -m もなければ両方を変える
+    if !opts.access && !opts.modify {
+        opts.access = true;
+        opts.modify = true;
+    }
+    let (atime, mtime) = opts.times.unwrap_or_else(|| (now(), now()));
+    let atime = opts.access.then_some(atime);
+    let mtime = opts.modify.then_some(mtime);
+
+    for file in files {
+        if ctx.interrupted() {
+            break;
+        }
+        let path = ctx.resolve(file);
+        let exists = ctx.fs.symlink_metadata(&path).is_ok();
+        if !exists {
+            if opts.no_create || opts.no_dereference {
+                continue;
+            }
+            if let Err(e) = ctx.perform(FsAction::CreateFile(&path)) {
+                errln!(ctx, "touch: cannot touch '{}': {}", quoting::display(file), error_message(&e));
+                continue;
+            }
+            // 現在時刻で作ったのなら日時の設定は要らない。
+            // ドライランでは作っていないので、日時の設定も予告しない
+            if ctx.dry_run || (opts.times.is_none() && atime.is_some() && mtime.is_some()) {
+                continue;
+            }
+        }
+        let action = FsAction::SetTimes {
+            path: &path,
+            atime,
+            mtime,
+            follow_symlinks: !opts.no_dereference,
+        };
+        if let Err(e) = ctx.perform(action) {
+            errln!(ctx, "touch: setting times of '{}': {}", quoting::display(file), error_message(&e));
+        }
+    }
+}
+
+/// 引数を取らないオプションを設定する
+fn set_flag(opts: &mut Options, flag: char) {
+    match flag {
+        'a' => opts.access = true,
+        'm' => opts.modify = true,
+        'c' => opts.no_create = true,
+        _ => opts.no_dereference = true,
+    }
+}
+
+/// 現在時刻
+fn now() -> FileTime {
+    let elapsed = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    FileTime {
+        secs: elapsed.as_secs() as i64,
+        nanos: elapsed.subsec_nanos() as i64,
+    }
+}
+
+/// ローカル時刻の日時を UNIX 秒に変換する。存在しない日付なら None
+fn local_to_epoch(year: i64, month: u32, day: u32, hour: u32, minute: u32, second: u32) -> Option<i64> {
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    tm.tm_year = (year - 1900) as libc::c_int;
+    tm.tm_mon = month as libc::c_int - 1;
+    tm.tm_mday = day as libc::c_int;
+    tm.tm_hour = hour as libc::c_int;
+    tm.tm_min = minute as libc::c_int;
+    tm.tm_sec = second as libc::c_int;
+    tm.tm_isdst = -1;
+    let secs = unsafe { libc::mktime(&mut tm) };
+    // mktime は 2月30日などを翌月へ繰り上げるので、日付が変わったものは不正とする
+    if secs == -1 || tm.tm_mday != day as libc::c_int || tm.tm_mon != month as libc::c_int - 1 {
+        return None;
+    }
+    Some(secs as i64)
+}
+
+/// `-t` の `[[CC]YY]MMDDhhmm[.ss]` を解析する
+fn parse_stamp(text: &str) -> Option<FileTime> {
+    let (digits, seconds) = match text.split_once('.') {
+        Some((digits, seconds)) => (digits, Some(seconds)),
+        None => (text, None),
+    };
+    if !digits.bytes().all(|b| b.is_ascii_digit()) || seconds.is_some_and(|s| s.len() != 2 || !s.bytes().all(|b| b.is_ascii_digit())) {
+        return None;
+    }
+    let number = |s: &str| s.parse::<u32>().ok();
+    let (year, rest) = match digits.len() {
+        8 => (local_time_now().year as i64, digits),
+        10 => {
+            // POSIX の規則: 69〜99 は 1900 年代、00〜68 は 2000 年代
+            let yy = number(&digits[..2])? as i64;
+            (if yy >= 69 { 1900 + yy } else { 2000 + yy }, &digits[2..])
+        }
+        12 => (number(&digits[..4])? as i64, &digits[4..]),
+        _ => return None,
+    };
+    let second = match seconds {
+        Some(s) => number(s)?,
+        None => 0,
+    };
+    let secs = local_to_epoch(year, number(&rest[..2])?, number(&rest[2..4])?, number(&rest[4..6])?, number(&rest[6..8])?, second)?;
+    Some(FileTime { secs, nanos: 0 })
+}
+
+/// 現在のローカル時刻
+fn local_time_now() -> crate::file_info::LocalTime {
+    crate::file_info::local_time(now().secs)
+}
+
+/// `-d` の日時指定を解析する
+fn parse_date(text: &str) -> Option<FileTime> {
+    let text = text.trim();
+    if let Some(epoch) = text.strip_prefix('@') {
+        return parse_seconds(epoch);
+    }
+
+    let lower = text.to_ascii_lowercase();
+    let words: Vec<&str> = lower.split_whitespace().collect();
+    let today = || {
+        let t = local_time_now();
+        local_to_epoch(t.year as i64, t.month, t.day, 0, 0, 0)
+    };
+    match words.as_slice() {
+        [] | ["now"] => return Some(now()),
+        ["today"] => return today().map(|secs| FileTime { secs, nanos: 0 }),
+        ["yesterday"] => return today().map(|secs| FileTime { secs: secs - 86_400, nanos: 0 }),
+        ["tomorrow"] => return today().map(|secs| FileTime { secs: secs + 86_400, nanos: 0 }),
+        _ => {}
+    }
+    if let Some(offset) = parse_relative(&words) {
+        let now = now();
+        return Some(FileTime { secs: now.secs + offset, nanos: now.nanos });
+    }
+
+    // YYYY-MM-DD[( |T)HH:MM[:SS[.frac]]]
+    let (date, time) = match text.split_once([' ', 'T']) {
+        Some((date, time)) => (date, Some(time.trim())),
+        None => (text, None),
+    };
+    let mut parts = date.splitn(3, '-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: u32 = parts.next()?.parse().ok()?;
+    let day: u32 = parts.next()?.parse().ok()?;
+    let (mut hour, mut minute, mut second, mut nanos) = (0, 0, 0, 0);
+    if let Some(time) = time {
+        let (clock, fraction) = match time.split_once(['.', ',']) {
+            Some((clock, fraction)) => (clock, Some(fraction)),
+            None => (time, None),
+        };
+        let mut fields = clock.split(':');
+        hour = fields.next()?.parse().ok()?;
+        minute = fields.next()?.parse().ok()?;
+        if let Some(field) = fields.next() {
+            second = field.parse().ok()?;
+        }
+        if fields.next().is_some() {
+            return None;
+        }
+        if let Some(fraction) = fraction {
+            nanos = parse_fraction(fraction)?;
+        }
+    }
+    let secs = local_to_epoch(year, month, day, hour, minute, second)?;
+    Some(FileTime { secs, nanos })
+}
+
+/// `123` や `123.5` を UNIX 秒として解析する
+fn parse_seconds(text: &str) -> Option<FileTime> {
+    let (whole, fraction) = match text.split_once('.') {
+        Some((whole, fraction)) => (whole, Some(fraction)),
+        None => (text, None),
+    };
+    let secs: i64 = whole.parse().ok()?;
+    let nanos = match fraction {
+        Some(fraction) => parse_fraction(fraction)?,
+        None => 0,
+    };
+    // 負の値の小数部は 0 から遠ざかる向きに数える
+    if whole.starts_with('-') && nanos > 0 {
+        return Some(FileTime { secs: secs - 1, nanos: 1_000_000_000 - nanos });
+    }
+    Some(FileTime { secs, nanos })
+}
+
+/// 小数部（最大9桁を使う）をナノ秒にする
+fn parse_fraction(text: &str) -> Option<i64> {
+    if text.is_empty() || !text.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let digits: String = text.chars().chain(std::iter::repeat('0')).take(9).collect();
+    digits.parse().ok()
+}
+
+/// `3 days ago` や `+2 hours`、`-1 week` のような相対指定を秒数にする
+fn parse_relative(words: &[&str]) -> Option<i64> {
+    let (words, sign) = match words.split_last() {
+        Some((&"ago", rest)) => (rest, -1),
+        _ => (words, 1),
+    };
+    let (amount, unit) = match words {
+        [amount, unit] => (amount.parse::<i64>().ok()?, *unit),
+        // "1day" のように数値と単位が続いている場合
+        [word] => {
+            let split = word.find(|c: char| c.is_ascii_alphabetic())?;
+            (word[..split].parse::<i64>().ok()?, &word[split..])
+        }
+        _ => return None,
+    };
+    let unit = unit.strip_suffix('s').unwrap_or(unit);
+    let scale = match unit {
+        "sec" | "second" => 1,
+        "min" | "minute" => 60,
+        "hour" => 3_600,
+        "day" => 86_400,
+        "week" => 7 * 86_400,
+        _ => return None,
+    };
+    Some(sign * amount * scale)
+}
@@ -0,0 +1,443 @@
+//! trコマンド。文字を置き換える・削除する・連続をまとめる
+//!
+//! 使い方:
+//! - `tr SET1 SET2` SET1 の文字を SET2 の同じ位置の文字に置き換える
+//!   （SET2 が短ければ最後の文字を繰り返して伸ばす。`-t` なら SET1 を SET2 の長さに切り詰める）
+//! - `tr -d SET1` SET1 の文字を削除する
+//! - `tr -s SET1` SET1 の文字の連続を1文字にまとめる（置き換え・削除と組み合わせると SET2 の文字）
+//! - `-c` / `-C` SET1 を補集合（SET1 に含まれない文字すべて）にする
+//!
+//! 集合の書き方:
+//! - `a-z` 範囲、`\n` `\t` `\\` `\NNN`（8進数）などのエスケープ
+//! - `[:alpha:]` `[:digit:]` `[:space:]` `[:upper:]` `[:lower:]` などの文字クラス
+//! - `[=c=]` 文字 c、`[c*N]` c を N 個（SET2 の `[c*]` は SET1 の長さまで）
+//!
+//! 文字は UTF-8 の1文字単位で扱う（全角文字も1文字）。UTF-8 として不正なバイトはそのまま通す。
+//! `[:lower:]` と `[:upper:]` どうしの置き換えは Unicode の大文字・小文字変換で行う。
+//! それ以外の置き換えでは、文字クラスは ASCII の範囲の文字に展開する。
+
+use super::cat::{self, read_some};
+use super::error_message;
+use crate::context::Context;
+use crate::glob::{self, NamedClass, RAW_BYTE};
+use crate::errln;
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::io::{self, Read, Write};
+
+/// コマンド名
+pub const NAME: &str = "tr";
+
+/// 一度に読むバイト数
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// 範囲を置き換え用に展開するときの上限（`\0-\U10FFFF` のような指定で止まらないように）
+const MAX_EXPANDED: usize = 1 << 20;
+
+/// 集合の要素
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Item {
+    Char(u32),
+    Range(u32, u32),
+    Class(NamedClass),
+    /// `[c*N]`。N が None なら SET1 の長さまで埋める
+    Repeat(u32, Option<usize>),
+}
+
+/// 文字の集合（SET1 / SET2）
+struct Set {
+    items: Vec<Item>,
+}
+
+impl Set {
+    /// 集合の文字列を解析する
+    fn parse(spec: &OsStr) -> Result<Set, String> {
+        let units = glob::units(spec.as_encoded_bytes());
+        let mut items = Vec::new();
+        let mut i = 0;
+        while i < units.len() {
+            if units[i] == '[' as u32 {
+                if let Some((item, next)) = parse_bracket(&units, i)? {
+                    items.push(item);
+                    i = next;
+                    continue;
+                }
+            }
+            let (low, next) = read_char(&units, i);
+            i = next;
+            if units.get(i) == Some(&('-' as u32)) && i + 1 < units.len() {
+                let (high, next) = read_char(&units, i + 1);
+                if high < low {
+                    return Err(format!(
+                        "range-endpoints of '{}-{}' are in reverse collating sequence order",
+                        display(low),
+                        display(high)
+                    ));
+                }
+                items.push(Item::Range(low, high));
+                i = next;
+            } else {
+                items.push(Item::Char(low));
+            }
+        }
+        Ok(Set { items })
+    }
+
+    fn contains(&self, c: u32) -> bool {
+        self.items.iter().any(|item| match *item {
+            Item::Char(x) | Item::Repeat(x, _) => x == c,
+            Item::Range(low, high) => (low..=high).contains(&c),
+            Item::Class(class) => char::from_u32(c).is_some_and(|c| class.contains(c)),
+        })
+    }
+
+    /// 置き換えのために文字の並びに展開する。`[c*]` は `fill` 個にする
+    fn expand(&self, fill: usize) -> Vec<u32> {
+        let mut out = Vec::new();
+        for item in &self.items {
+            match *item {
+                Item::Char(c) => out.push(c),
+                Item::Range(low, high) => out.extend(low..=high.min(low.saturating_add(MAX_EXPANDED as u32))),
+                Item::Class(class) => out.extend((0..128u32).filter(|&c| class.contains(char::from(c as u8)))),
+                Item::Repeat(c, n) => out.extend(std::iter::repeat_n(c, n.unwrap_or(fill))),
+            }
+        }
+        out
+    }
+
+    /// 1つの文字クラスだけからなるか
+    fn single_class(&self) -> Option<NamedClass> {
+        match self.items.as_slice() {
+            [Item::Class(class)] => Some(*class),
+            _ => None,
+        }
+    }
+}
+
+/// `[:class:]`・`[=c=]`・`[c*N]` を解析する。どれでもなければ None（`[` は文字そのもの）
+fn parse_bracket(units: &[u32], start: usize) -> Result<Option<(Item, usize)>, String> {
+    let find_close = |marker: char| {
+        (start + 2..units.len().saturating_sub(1))
+            .find(|&j| units[j] == marker as u32 && units[j + 1] == ']' as u32)
+    };
+    match units.get(start + 1).and_then(|&c| char::from_u32(c)) {
+        Some(':') => {
+            if let Some(end) = find_close(':') {
+                let name: String = units[start + 2..end].iter().filter_map(|&c| char::from_u32(c)).collect();
+                return match NamedClass::parse(&name) {
+                    Some(class) => Ok(Some((Item::Class(class), end + 2))),
+                    None => Err(format!("invalid character class '{}'", name)),
+                };
+            }
+        }
+        Some('=') => {
+            if let Some(end) = find_close('=') {
+                let (c, next) = read_char(units, start + 2);
+                if next != end {
+                    return Err("equivalence class operand must be a single character".to_string());
+                }
+                return Ok(Some((Item::Char(c), end + 2)));
+            }
+        }
+        _ => {}
+    }
+    if start + 1 >= units.len() {
+        return Ok(None);
+    }
+    let (c, next) = read_char(units, start + 1);
+    if units.get(next) != Some(&('*' as u32)) {
+        return Ok(None);
+    }
+    let Some(close) = units[next + 1..].iter().position(|&u| u == ']' as u32).map(|p| next + 1 + p) else {
+        return Ok(None);
+    };
+    let digits: String = units[next + 1..close].iter().filter_map(|&c| char::from_u32(c)).collect();
+    let count = if digits.is_empty() {
+        None
+    } else {
+        // 0 で始まる数は8進数
+        let parsed = if digits.starts_with('0') {
+            usize::from_str_radix(&digits, 8)
+        } else {
+            digits.parse()
+        };
+        match parsed {
+            Ok(0) => None,
+            Ok(n) => Some(n),
+            Err(_) => return Err(format!("invalid repeat count '{}' in [c*n] construct", digits)),
+        }
+    };
+    Ok(Some((Item::Repeat(c, count), close + 1)))
+}
+
+/// `units[i]` から1文字（エスケープを解釈する）を読み、次の位置を返す
+fn read_char(units: &[u32], i: usize) -> (u32, usize) {
+    if units[i] != '\\' as u32 || i + 1 >= units.len() {
+        return (units[i], i + 1);
+    }
+    let c = units[i + 1];
+    let simple = match char::from_u32(c) {
+        Some('n') => Some('\n'),
+        Some('t') => Some('\t'),
+        Some('r') => Some('\r'),
+        Some('a') => Some('\x07'),
+        Some('b') => Some('\x08'),
+        Some('f') => Some('\x0c'),
+        Some('v') => Some('\x0b'),
+        _ => None,
+    };
+    if let Some(simple) = simple {
+        return (simple as u32, i + 2);
+    }
+    // \NNN（8進数、3桁まで）
+    let is_octal = |u: u32| ('0' as u32..='7' as u32).contains(&u);
+    if is_octal(c) {
+        let mut value = 0u32;
+        let mut j = i + 1;
+        while j < units.len() && j < i + 4 && is_octal(units[j]) && value * 8 + (units[j] - '0' as u32) <= 0o377 {
+            value = value * 8 + (units[j] - '0' as u32);
+            j += 1;
+        }
+        // 0x80 以上は1バイトとして扱う（UTF-8 として不正なバイトに一致させる）
+        let unit = if value >= 0x80 { RAW_BYTE + value } else { value };
+        return (unit, j);
+    }
+    (c, i + 2)
+}
+
+/// エラーメッセージ用の文字の表示
+fn display(c: u32) -> String {
+    match char::from_u32(c) {
+        Some(c) => c.to_string(),
+        None => format!("\\{:o}", c - RAW_BYTE),
+    }
+}
+
+/// 文字の置き換え方
+enum Translation {
+    None,
+    /// SET1 の各文字から SET2 の文字へ
+    Map(HashMap<u32, u32>),
+    /// 補集合の文字をすべて1つの文字へ
+    Complement(u32),
+    /// `[:lower:]` → `[:upper:]`
+    ToUpper,
+    /// `[:upper:]` → `[:lower:]`
+    ToLower,
+}
+
+/// 変換の内容
+struct Tr {
+    set1: Set,
+    complement: bool,
+    delete: bool,
+    translation: Translation,
+    /// 連続をまとめる文字の集合と、それが補集合か
+    squeeze: Option<(Set, bool)>,
+}
+
+impl Tr {
+    fn in_set1(&self, c: u32) -> bool {
+        self.set1.contains(c) != self.complement
+    }
+
+    fn translate(&self, c: u32) -> u32 {
+        match &self.translation {
+            Translation::None => c,
+            Translation::Map(map) => map.get(&c).copied().unwrap_or(c),
+            Translation::Complement(to) => if self.in_set1(c) { *to } else { c },
+            Translation::ToUpper => char::from_u32(c).map_or(c, |ch| single_char(ch.to_uppercase(), c)),
+            Translation::ToLower => char::from_u32(c).map_or(c, |ch| single_char(ch.to_lowercase(), c)),
+        }
+    }
+}
+
+/// 大文字・小文字の変換結果が1文字ならその文字、複数文字（`ß` → `SS` など）なら元の文字
+fn single_char(mut chars: impl Iterator<Item = char>, original: u32) -> u32 {
+    match (chars.next(), chars.next()) {
+        (Some(mapped), None) => mapped as u32,
+        _ => original,
+    }
+}
+
+pub fn run(ctx: &mut Context, args: &[OsString]) {
+    let mut complement = false;
+    let mut delete = false;
+    let mut squeeze = false;
+    let mut truncate = false;
+    let mut operands: Vec<&OsStr> = Vec::new();
+    let mut only_operands = false;
+    for arg in args {
+        let Some(s) = arg.to_str().filter(|s| !only_operands && s.starts_with('-') && s.len() > 1) else {
+            operands.push(arg);
+            continue;
+        };
+        match s {
+            "--" => only_operands = true,
+            "--complement" => complement = true,
+            "--delete" => delete = true,
+            "--squeeze-repeats" => squeeze = true,
+            "--truncate-set1" => truncate = true,
+            _ if s.starts_with("--") => {
+                errln!(ctx, "tr: unrecognized option '{}'", s);
+                return;
+            }
+            _ => {
+                for ch in s[1..].chars() {
+                    match ch {
+                        'c' | 'C' => complement = true,
+                        'd' => delete = true,
+                        's' => squeeze = true,
+                        't' => truncate = true,
+                        _ => {
+                            errln!(ctx, "tr: invalid option -- '{}'", ch);
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // SET2 が必要か（置き換えるときと、削除してまとめるとき）
+    let translating = !delete && operands.len() >= 2;
+    let needed = if delete && squeeze || !delete && !squeeze { 2 } else { 1 };
+    if operands.len() < needed {
+        match operands.first() {
+            None => errln!(ctx, "tr: missing operand"),
+            Some(first) => {
+                errln!(ctx, "tr: missing operand after '{}'", first.to_string_lossy());
+                let what = if delete { "deleting and squeezing" } else { "translating" };
+                errln!(ctx, "Two strings must be given when {}.", what);
+            }
+        }
+        return;
+    }
+    if operands.len() > 2 || operands.len() == 2 && delete && !squeeze {
+        errln!(ctx, "tr: extra operand '{}'", operands[needed].to_string_lossy());
+        return;
+    }
+    let set1 = match Set::parse(operands[0]) {
+        Ok(set) if set.items.iter().any(|item| matches!(item, Item::Repeat(..))) => {
+            errln!(ctx, "tr: the [c*] repeat construct may not appear in string1");
+            return;
+        }
+        Ok(set) => set,
+        Err(message) => {
+            errln!(ctx, "tr: {}", message);
+            return;
+        }
+    };
+    let set2 = match operands.get(1).map(|s| Set::parse(s)).transpose() {
+        Ok(set) => set,
+        Err(message) => {
+            errln!(ctx, "tr: {}", message);
+            return;
+        }
+    };
+
+    let translation = match &set2 {
+        Some(set2) if translating => match build_translation(&set1, set2, complement, truncate) {
+            Ok(translation) => translation,
+            Err(message) => {
+                errln!(ctx, "tr: {}", message);
+                return;
+            }
+        },
+        _ => Translation::None,
+    };
+    // まとめるのは、SET2 があればその文字、なければ SET1（-c なら補集合）の文字
+    let squeeze = squeeze.then(|| match set2 {
+        Some(set2) => (set2, false),
+        None => (Set { items: set1.items.clone() }, complement),
+    });
+    let tr = Tr { set1, complement, delete, translation, squeeze };
+
+    let result = cat::with_input(ctx, OsStr::new("-"), |ctx, reader| filter(ctx, reader, &tr));
+    match result.and_then(|r| r) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+        Err(e) => errln!(ctx, "tr: read error: {}", error_message(&e)),
+    }
+    let _ = ctx.stdout.flush();
+}
+
+/// SET1 と SET2 から置き換え方を決める
+fn build_translation(set1: &Set, set2: &Set, complement: bool, truncate: bool) -> Result<Translation, String> {
+    match (set1.single_class(), set2.single_class()) {
+        (Some(NamedClass::Lower), Some(NamedClass::Upper)) if !complement => return Ok(Translation::ToUpper),
+        (Some(NamedClass::Upper), Some(NamedClass::Lower)) if !complement => return Ok(Translation::ToLower),
+        _ => {}
+    }
+    if set2.items.iter().any(|item| matches!(item, Item::Class(class) if !matches!(class, NamedClass::Upper | NamedClass::Lower))) {
+        return Err("the only character classes that may appear in string2 are 'upper' and 'lower'".to_string());
+    }
+    let from = set1.expand(0);
+    let fixed = set2.expand(0).len();
+    let mut to = set2.expand(from.len().saturating_sub(fixed));
+    let Some(&last) = to.last() else {
+        return Err("when not truncating set1, string2 must be non-empty".to_string());
+    };
+    if complement {
+        return Ok(Translation::Complement(last));
+    }
+    let from = if truncate { &from[..from.len().min(to.len())] } else { &from[..] };
+    to.resize(from.len().max(to.len()), last);
+    Ok(Translation::Map(from.iter().copied().zip(to).collect()))
+}
+
+/// 標準入力を変換して書き出す
+fn filter(ctx: &mut Context, reader: &mut dyn Read, tr: &Tr) -> io::Result<()> {
+    let mut buf = vec![0u8; BUFFER_SIZE];
+    // 前回の読み込みの末尾で途切れた UTF-8 の途中のバイト
+    let mut pending: Vec<u8> = Vec::new();
+    let mut out = Vec::new();
+    let mut last: Option<u32> = None;
+    loop {
+        let n = read_some(ctx, reader, &mut buf)?;
+        let eof = n == 0;
+        pending.extend_from_slice(&buf[..n]);
+        // 末尾の途切れた文字は次の読み込みまで持ち越す
+        let keep = if eof { 0 } else { incomplete_tail(&pending) };
+        let data: Vec<u8> = pending.drain(..pending.len() - keep).collect();
+        out.clear();
+        for unit in glob::units(&data) {
+            if tr.delete && tr.in_set1(unit) {
+                continue;
+            }
+            let unit = tr.translate(unit);
+            if let Some((set, complement)) = &tr.squeeze {
+                if last == Some(unit) && set.contains(unit) != *complement {
+                    continue;
+                }
+            }
+            last = Some(unit);
+            match char::from_u32(unit) {
+                Some(c) => out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+                None => out.push((unit - RAW_BYTE) as u8),
+            }
+        }
+        let _ = ctx.stdout.write_all(&out);
+        if eof {
+            return Ok(());
+        }
+    }
+}
+
+/// 末尾にある、続きのバイトを待っている UTF-8 の文字の長さ
+fn incomplete_tail(data: &[u8]) -> usize {
+    for back in 1..=data.len().min(3) {
+        let byte = data[data.len() - back];
+        // 継続バイト（10xxxxxx）以外が文字の先頭
+        if byte & 0xc0 != 0x80 {
+            let needed = match byte {
+                0xf0.. => 4,
+                0xe0.. => 3,
+                0xc0.. => 2,
+                _ => 1,
+            };
+            return if needed > back { back } else { 0 };
+        }
+    }
+    0
+}
@@ -0,0 +1,54 @@
+//! trash-emptyコマンド。ゴミ箱の項目を完全に削除する
+//!
+//! `trash-empty` ですべて、`trash-empty DAYS` で DAYS 日より前に削除した項目だけを削除する。
+
+use super::error_message;
+use crate::context::Context;
+use crate::fs_action::FsAction;
+use crate::quoting;
+use crate::sandbox;
+use crate::trash;
+use crate::{errln, outln};
+use std::ffi::OsString;
+
+/// コマンド名
+pub const NAME: &str = "trash-empty";
+
+pub fn run(ctx: &mut Context, args: &[OsString]) {
+    let mut verbose = false;
+    let mut days = None;
+    for arg in args {
+        match arg.to_str() {
+            Some("-v") | Some("--verbose") => verbose = true,
+            Some(s) if days.is_none() && s.parse::<u64>().is_ok() => days = s.parse().ok(),
+            _ => {
+                errln!(ctx, "trash-empty: invalid argument '{}'", quoting::display(arg));
+                errln!(ctx, "Usage: trash-empty [-v] [DAYS]");
+                return;
+            }
+        }
+    }
+
+    // ゴミ箱はサンドボックスの外にある
+    if ctx.sandbox.is_some() {
+        errln!(ctx, "trash-empty: {}", error_message(&sandbox::denied()));
+        return;
+    }
+    // ドライランでは予定の操作だけを表示する
+    verbose &= !ctx.dry_run;
+
+    for item in trash::list() {
+        if days.is_some_and(|days| !trash::is_older_than(&item, days)) {
+            continue;
+        }
+        let shown = quoting::display(item.original.as_os_str());
+        match ctx.perform(FsAction::PurgeTrash(&item)) {
+            Ok(()) => {
+                if verbose {
+                    outln!(ctx, "removed '{}'", shown);
+                }
+            }
+            Err(e) => errln!(ctx, "trash-empty: cannot remove '{}': {}", shown, error_message(&e)),
+        }
+    }
+}
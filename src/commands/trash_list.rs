@@ -0,0 +1,39 @@
+//! trash-listコマンド。ゴミ箱の中身を削除日時の順に表示する
+//!
+//! 引数にパスを指定すると、元の場所がそのパス以下の項目だけを表示する。
+
+use super::error_message;
+use crate::context::Context;
+use crate::quoting;
+use crate::sandbox;
+use crate::trash;
+use crate::{errln, outln};
+use std::ffi::OsString;
+
+/// コマンド名
+pub const NAME: &str = "trash-list";
+
+pub fn run(ctx: &mut Context, args: &[OsString]) {
+    if let Some(arg) = args.iter().find(|arg| arg.to_str().is_some_and(|s| s.starts_with('-'))) {
+        errln!(ctx, "trash-list: invalid option '{}'", quoting::display(arg));
+        return;
+    }
+    // ゴミ箱はサンドボックスの外にある
+    if ctx.sandbox.is_some() {
+        errln!(ctx, "trash-list: {}", error_message(&sandbox::denied()));
+        return;
+    }
+    let filters: Vec<_> = args.iter().map(|arg| ctx.resolve(arg)).collect();
+
+    for item in trash::list() {
+        if !filters.is_empty() && !filters.iter().any(|dir| item.original.starts_with(dir)) {
+            continue;
+        }
+        outln!(
+            ctx,
+            "{} {}",
+            item.deletion_date.replace('T', " "),
+            quoting::display(item.original.as_os_str())
+        );
+    }
+}
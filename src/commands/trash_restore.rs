@@ -0,0 +1,97 @@
+//! trash-restoreコマンド。ゴミ箱の項目を元の場所へ戻す
+//!
+//! `trash-restore PATH...` で、元の場所が PATH の項目のうち最も新しく削除したものを戻す。
+//! 引数なしなら、カレントディレクトリ以下から削除された項目を一覧表示する。
+
+use super::error_message;
+use crate::context::Context;
+use crate::fs_action::FsAction;
+use crate::quoting;
+use crate::sandbox;
+use crate::trash;
+use crate::{errln, outln};
+use std::ffi::OsString;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// コマンド名
+pub const NAME: &str = "trash-restore";
+
+pub fn run(ctx: &mut Context, args: &[OsString]) {
+    let mut verbose = false;
+    let mut paths = Vec::new();
+    for arg in args {
+        match arg.to_str() {
+            Some("-v") | Some("--verbose") => verbose = true,
+            Some(s) if s.starts_with('-') => {
+                errln!(ctx, "trash-restore: invalid option '{}'", s);
+                return;
+            }
+            _ => paths.push(arg),
+        }
+    }
+
+    // ゴミ箱はサンドボックスの外にある
+    if ctx.sandbox.is_some() {
+        errln!(ctx, "trash-restore: {}", error_message(&sandbox::denied()));
+        return;
+    }
+    // ドライランでは予定の操作だけを表示する
+    verbose &= !ctx.dry_run;
+
+    let items = trash::list();
+    if paths.is_empty() {
+        let here: Vec<_> = items.iter().filter(|item| item.original.starts_with(&ctx.cwd)).collect();
+        if here.is_empty() {
+            outln!(ctx, "No files trashed from current dir ('{}')", quoting::display(ctx.cwd.as_os_str()));
+            return;
+        }
+        for item in here {
+            outln!(
+                ctx,
+                "{} {}",
+                item.deletion_date.replace('T', " "),
+                quoting::display(item.original.as_os_str())
+            );
+        }
+        outln!(ctx, "Use 'trash-restore PATH' to restore one of these files.");
+        return;
+    }
+
+    for path in paths {
+        let original = normalize(&ctx.resolve(path));
+        // 同じ場所から何度も削除されていれば最も新しいものを戻す
+        let Some(item) = items.iter().rev().find(|item| item.original == original) else {
+            errln!(ctx, "trash-restore: '{}' is not in the trash", quoting::display(path));
+            continue;
+        };
+        match ctx.perform(FsAction::RestoreTrash(item)) {
+            Ok(()) => {
+                if verbose {
+                    outln!(ctx, "restored '{}'", quoting::display(path));
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                errln!(ctx, "trash-restore: cannot restore '{}': File exists", quoting::display(path));
+            }
+            Err(e) => {
+                errln!(ctx, "trash-restore: cannot restore '{}': {}", quoting::display(path), error_message(&e));
+            }
+        }
+    }
+}
+
+/// `.` や `..` を取り除いたパス（元の場所はもう存在しないので canonicalize は使えない）
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
@@ -0,0 +1,403 @@
+//! treeコマンド。ディレクトリの中身を木の形で表示する
+//!
+//! 対応オプション:
+//! - `-L N` 深さ N までを表示する
+//! - `-a` `.` で始まるファイルも表示する
+//! - `-d` ディレクトリだけを表示する
+//! - `-I PATTERN` 名前が PATTERN（`|` 区切りで複数可）に一致するものを表示しない
+//! - `--gitignore` .gitignore で除外されたファイルを表示しない
+//! - `-s` `-h` 大きさを表示する（`-h` は `4.0K` のように）、`-D` 更新日時を表示する
+//! - `-J` JSON で出力する
+//! - `--charset=ascii` 枝を ASCII 文字で描く、`--noreport` 最後の件数の行を表示しない
+//!
+//! GUI版ではディレクトリごとに開閉できる木として表示する。
+
+use super::error_message;
+use crate::context::Context;
+use crate::file_info;
+use crate::gitignore::Ignores;
+use crate::glob::Glob;
+use crate::quoting;
+use crate::tree::{Charset, TreeNode};
+use crate::vfs::Stat;
+use crate::{errln, outln};
+use std::ffi::{OsStr, OsString};
+use std::path::Path;
+
+/// コマンド名
+pub const NAME: &str = "tree";
+
+/// 解析済みのオプション
+struct Options {
+    max_depth: Option<usize>,
+    all: bool,
+    dirs_only: bool,
+    excludes: Vec<Glob>,
+    gitignore: bool,
+    size: bool,
+    human: bool,
+    date: bool,
+    json: bool,
+    charset: Charset,
+    report: bool,
+}
+
+/// 木の1項目
+struct Entry {
+    /// 表示する名前（引数はそのまま、中身はファイル名）
+    name: OsString,
+    kind: Kind,
+    /// シンボリックリンクの参照先
+    target: Option<OsString>,
+    meta: Option<Stat>,
+    /// ディレクトリを読めなかったとき
+    error: bool,
+    children: Vec<Entry>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Directory,
+    File,
+    Link,
+}
+
+pub fn run(ctx: &mut Context, args: &[OsString]) {
+    let mut opts = Options {
+        max_depth: None,
+        all: false,
+        dirs_only: false,
+        excludes: Vec::new(),
+        gitignore: false,
+        size: false,
+        human: false,
+        date: false,
+        json: false,
+        charset: Charset::Unicode,
+        report: true,
+    };
+    let mut dirs: Vec<&OsStr> = Vec::new();
+    let mut only_operands = false;
+    let mut args = args.iter();
+
+    while let Some(os_arg) = args.next() {
+        let arg = match os_arg.to_str() {
+            Some(arg) if !only_operands && arg.starts_with('-') && arg != "-" => arg,
+            _ => {
+                dirs.push(os_arg);
+                continue;
+            }
+        };
+        // 値を取るオプションとその値
+        let mut value: Option<(char, String)> = None;
+        match arg {
+            "--" => only_operands = true,
+            "--gitignore" => opts.gitignore = true,
+            "--noreport" => opts.report = false,
+            "--charset" => match args.next() {
+                Some(name) => value = Some(('c', name.to_string_lossy().into_owned())),
+                None => {
+                    errln!(ctx, "tree: option '--charset' requires an argument");
+                    return;
+                }
+            },
+            _ if arg.starts_with("--charset=") => value = Some(('c', arg["--charset=".len()..].to_string())),
+            _ if arg.starts_with("--") => {
+                errln!(ctx, "tree: unrecognized option '{}'", arg);
+                errln!(ctx, "Try 'tree --help' for more information.");
+                return;
+            }
+            _ => {
+                for (i, ch) in arg[1..].char_indices() {
+                    match ch {
+                        'a' => opts.all = true,
+                        'd' => opts.dirs_only = true,
+                        's' => opts.size = true,
+                        'h' => opts.human = true,
+                        'D' => opts.date = true,
+                        'J' => opts.json = true,
+                        'L' | 'I' => {
+                            let rest = &arg[1 + i + 1..];
+                            if !rest.is_empty() {
+                                value = Some((ch, rest.to_string()));
+                            } else if let Some(next) = args.next() {
+                                value = Some((ch, next.to_string_lossy().into_owned()));
+                            } else {
+                                errln!(ctx, "tree: option requires an argument -- '{}'", ch);
+                                return;
+                            }
+                            break;
+                        }
+                        _ => {
+                            errln!(ctx, "tree: invalid option -- '{}'", ch);
+                            errln!(ctx, "Try 'tree --help' for more information.");
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+        match value {
+            Some(('L', level)) => match level.parse() {
+                Ok(level) if level > 0 => opts.max_depth = Some(level),
+                _ => {
+                    errln!(ctx, "tree: Invalid level, must be greater than 0.");
+                    return;
+                }
+            },
+            Some(('I', patterns)) => {
+                for pattern in patterns.split('|') {
+                    match Glob::new(pattern) {
+                        Ok(glob) => opts.excludes.push(glob),
+                        Err(message) => {
+                            errln!(ctx, "tree: {}", message);
+                            return;
+                        }
+                    }
+                }
+            }
+            Some((_, name)) => match Charset::parse(&name) {
+                Some(charset) => opts.charset = charset,
+                None => {
+                    errln!(ctx, "tree: invalid charset '{}' (use utf-8 or ascii)", name);
+                    return;
+                }
+            },
+            None => {}
+        }
+    }
+
+    if dirs.is_empty() {
+        dirs.push(OsStr::new("."));
+    }
+
+    let mut walker = Walker { opts: &opts, dirs: 0, files: 0 };
+    let mut roots = Vec::new();
+    for dir in dirs {
+        if ctx.interrupted() {
+            break;
+        }
+        let path = ctx.resolve(dir);
+        let meta = match ctx.fs.metadata(&path) {
+            Ok(meta) => meta,
+            Err(e) => {
+                errln!(ctx, "tree: {}: {}", quoting::display(dir), error_message(&e));
+                continue;
+            }
+        };
+        let mut root = Entry {
+            name: dir.to_os_string(),
+            kind: if meta.is_dir() { Kind::Directory } else { Kind::File },
+            target: None,
+            meta: Some(meta),
+            error: false,
+            children: Vec::new(),
+        };
+        if root.kind == Kind::Directory {
+            let mut ignores = Ignores::above(ctx.fs.as_ref(), &path);
+            match walker.read(ctx, &path, 1, &mut ignores) {
+                Ok(children) => root.children = children,
+                Err(_) => root.error = true,
+            }
+        } else {
+            walker.files += 1;
+        }
+        roots.push(root);
+    }
+
+    if opts.json {
+        print_json(ctx, &roots, &walker);
+        return;
+    }
+    for root in &roots {
+        let node = to_node(root, &opts, true);
+        if !ctx.show_tree(&node) {
+            for line in node.to_lines(opts.charset) {
+                outln!(ctx, "{}", line);
+            }
+        }
+    }
+    if opts.report {
+        outln!(ctx);
+        let dirs = format!("{} director{}", walker.dirs, if walker.dirs == 1 { "y" } else { "ies" });
+        if opts.dirs_only {
+            outln!(ctx, "{}", dirs);
+        } else {
+            outln!(ctx, "{}, {} file{}", dirs, walker.files, if walker.files == 1 { "" } else { "s" });
+        }
+    }
+}
+
+/// ディレクトリをたどり、表示したディレクトリとファイルを数える
+struct Walker<'a> {
+    opts: &'a Options,
+    dirs: usize,
+    files: usize,
+}
+
+impl Walker<'_> {
+    /// ディレクトリ `path`（深さ `depth - 1`）の中身を名前順に読む
+    fn read(&mut self, ctx: &mut Context, path: &Path, depth: usize, ignores: &mut Ignores) -> Result<Vec<Entry>, ()> {
+        let mut names = ctx.fs.read_dir(path).map_err(|_| ())?;
+        names.sort();
+        if self.opts.gitignore {
+            ignores.enter(ctx.fs.as_ref(), path);
+        }
+
+        let mut entries = Vec::new();
+        for name in names {
+            if ctx.interrupted() {
+                break;
+            }
+            if !self.opts.all && name.as_encoded_bytes().starts_with(b".") {
+                continue;
+            }
+            if self.opts.excludes.iter().any(|glob| glob.matches(&name)) {
+                continue;
+            }
+            let child = path.join(&name);
+            let Ok(meta) = ctx.fs.symlink_metadata(&child) else {
+                continue;
+            };
+            // ディレクトリへのシンボリックリンクはディレクトリとして数えるが、中には入らない
+            let (kind, is_dir) = if meta.is_symlink() {
+                (Kind::Link, ctx.fs.is_dir(&child))
+            } else {
+                (if meta.is_dir() { Kind::Directory } else { Kind::File }, meta.is_dir())
+            };
+            if self.opts.dirs_only && !is_dir || self.opts.gitignore && ignores.is_ignored(&child, is_dir) {
+                continue;
+            }
+
+            let mut entry = Entry {
+                target: (kind == Kind::Link).then(|| ctx.fs.read_link(&child).map(|t| t.into_os_string()).unwrap_or_default()),
+                name,
+                kind,
+                meta: Some(meta),
+                error: false,
+                children: Vec::new(),
+            };
+            if is_dir {
+                self.dirs += 1;
+            } else {
+                self.files += 1;
+            }
+            if kind == Kind::Directory && self.opts.max_depth.is_none_or(|max| depth < max) {
+                match self.read(ctx, &child, depth + 1, ignores) {
+                    Ok(children) => entry.children = children,
+                    Err(_) => entry.error = true,
+                }
+            }
+            entries.push(entry);
+        }
+
+        if self.opts.gitignore {
+            ignores.leave();
+        }
+        Ok(entries)
+    }
+}
+
+/// 表示用の節にする。引数以外は名前の前に `[大きさ 日時]` を付ける
+fn to_node(entry: &Entry, opts: &Options, is_root: bool) -> TreeNode {
+    let mut label = String::new();
+    if let Some(meta) = entry.meta.as_ref().filter(|_| !is_root && (opts.size || opts.human || opts.date)) {
+        let mut fields = Vec::new();
+        if opts.human {
+            fields.push(format!("{:>4}", file_info::human_size(meta.len())));
+        } else if opts.size {
+            fields.push(format!("{:>11}", meta.len()));
+        }
+        if opts.date {
+            fields.push(file_info::ls_time(meta.mtime().secs));
+        }
+        label.push_str(&format!("[{}]  ", fields.join(" ")));
+    }
+    label.push_str(&quoting::display(&entry.name));
+    if let Some(target) = &entry.target {
+        label.push_str(&format!(" -> {}", quoting::display(target)));
+    }
+    if entry.error {
+        label.push_str("  [error opening dir]");
+    }
+
+    let mut node = TreeNode::new(label, entry.kind == Kind::Directory);
+    node.children = entry.children.iter().map(|child| to_node(child, opts, false)).collect();
+    node
+}
+
+/// -J の出力。1項目を1行にし、ディレクトリの中身は字下げして続ける
+fn print_json(ctx: &mut Context, roots: &[Entry], walker: &Walker) {
+    let mut lines = vec!["[".to_string()];
+    for root in roots {
+        json_entry(root, walker.opts, 1, &mut lines);
+        lines.last_mut().unwrap().push(',');
+    }
+    let mut report = format!("  {{\"type\":\"report\",\"directories\":{}", walker.dirs);
+    if !walker.opts.dirs_only {
+        report.push_str(&format!(",\"files\":{}", walker.files));
+    }
+    lines.push(report + "}");
+    lines.push("]".to_string());
+    for line in lines {
+        outln!(ctx, "{}", line);
+    }
+}
+
+fn json_entry(entry: &Entry, opts: &Options, level: usize, lines: &mut Vec<String>) {
+    let kind = match entry.kind {
+        Kind::Directory => "directory",
+        Kind::File => "file",
+        Kind::Link => "link",
+    };
+    let mut line = format!(
+        "{}{{\"type\":\"{}\",\"name\":{}",
+        "  ".repeat(level),
+        kind,
+        json_string(&entry.name.to_string_lossy())
+    );
+    if let Some(target) = &entry.target {
+        line.push_str(&format!(",\"target\":{}", json_string(&target.to_string_lossy())));
+    }
+    if let Some(meta) = &entry.meta {
+        if opts.size || opts.human {
+            line.push_str(&format!(",\"size\":{}", meta.len()));
+        }
+        if opts.date {
+            line.push_str(&format!(",\"time\":{}", json_string(&file_info::ls_time(meta.mtime().secs))));
+        }
+    }
+    if entry.error {
+        line.push_str(",\"error\":\"error opening dir\"");
+    }
+    if entry.kind != Kind::Directory || entry.children.is_empty() {
+        lines.push(line + "}");
+        return;
+    }
+    lines.push(line + ",\"contents\":[");
+    for (i, child) in entry.children.iter().enumerate() {
+        json_entry(child, opts, level + 1, lines);
+        if i + 1 < entry.children.len() {
+            lines.last_mut().unwrap().push(',');
+        }
+    }
+    lines.push(format!("{}]}}", "  ".repeat(level)));
+}
+
+/// JSON の文字列リテラルにする
+fn json_string(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for ch in text.chars() {
+        match ch {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\r' => quoted.push_str("\\r"),
+            _ if ch.is_control() => quoted.push_str(&format!("\\u{:04x}", ch as u32)),
+            _ => quoted.push(ch),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
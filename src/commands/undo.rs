@@ -0,0 +1,269 @@
+//! undoコマンド。rm・rmdir・mv・cp（上書き）・mkdir の操作を新しい順に元に戻す
+//!
+//! - `undo` 直前の操作を1つ元に戻す
+//! - `undo N` 直前の N 個の操作を元に戻す
+//! - `undo -l` / `--list` 元に戻せる操作を新しい順に表示する
+//! - `-f` / `--force` 競合する変更を飛ばし、残りを元に戻す
+//!
+//! 元に戻す前に、操作の後で対象が変更されていないか（別のファイルが置かれた、
+//! 内容が書き換えられたなど）を確かめる。競合があればその操作は元に戻さずに止まる。
+//! 操作の記録は `crate::journal` を参照。
+
+use super::cp::{CopyOptions, Copier, Overwrite, Reflink};
+use super::error_message;
+use crate::context::Context;
+use crate::file_info;
+use crate::fs_action::FsAction;
+use crate::journal::{Action, Entry, Fingerprint, Stash};
+use crate::quoting;
+use crate::sandbox;
+use crate::{errln, outln};
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// コマンド名
+pub const NAME: &str = "undo";
+
+pub fn run(ctx: &mut Context, args: &[OsString]) {
+    let mut list = false;
+    let mut force = false;
+    let mut count = None;
+    for arg in args {
+        match arg.to_str() {
+            Some("-l") | Some("--list") => list = true,
+            Some("-f") | Some("--force") => force = true,
+            Some(s) if count.is_none() && s.parse::<usize>().is_ok_and(|n| n > 0) => count = s.parse().ok(),
+            _ => {
+                errln!(ctx, "undo: invalid argument '{}'", quoting::display(arg));
+                errln!(ctx, "Usage: undo [-f] [N] | undo -l");
+                return;
+            }
+        }
+    }
+
+    // 操作の記録と退避場所はサンドボックスの外にある
+    if ctx.sandbox.is_some() {
+        errln!(ctx, "undo: {}", error_message(&sandbox::denied()));
+        return;
+    }
+    let Some(journal) = ctx.journal.clone() else {
+        errln!(ctx, "undo: undo is disabled (set 'undo = true' in the config file)");
+        return;
+    };
+    // 操作の記録は実際のファイルシステムに対するものだけ
+    if !ctx.fs.is_native() {
+        errln!(ctx, "undo: undo is not available on this filesystem");
+        return;
+    }
+    let entries = journal.entries();
+
+    if list {
+        for entry in entries.iter().rev() {
+            let t = file_info::local_time(entry.time);
+            outln!(
+                ctx,
+                "{:>4}  {}-{:02}-{:02} {:02}:{:02}:{:02}  {}",
+                entry.id,
+                t.year,
+                t.month,
+                t.day,
+                t.hour,
+                t.minute,
+                t.second,
+                entry.command
+            );
+        }
+        return;
+    }
+
+    let count = count.unwrap_or(1);
+    if entries.is_empty() {
+        errln!(ctx, "undo: nothing to undo");
+        return;
+    }
+    for entry in entries.iter().rev().take(count) {
+        if !undo_entry(ctx, entry, force) {
+            return;
+        }
+        // ドライランでは記録を残す
+        if ctx.dry_run {
+            continue;
+        }
+        if let Err(e) = journal.remove_entry(entry.id) {
+            errln!(ctx, "undo: cannot update the journal: {}", error_message(&e));
+            return;
+        }
+        outln!(ctx, "undone: {}", entry.command);
+    }
+}
+
+/// 1つの操作を元に戻す。すべて戻せたら true
+fn undo_entry(ctx: &mut Context, entry: &Entry, force: bool) -> bool {
+    // 先にすべての変更を確かめ、競合があれば何もしない（-f なら競合した変更だけ飛ばす）
+    let mut skip = vec![false; entry.actions.len()];
+    for (i, action) in entry.actions.iter().enumerate() {
+        if let Some(conflict) = check(action, force) {
+            errln!(ctx, "undo: cannot undo '{}': {}", entry.command, conflict);
+            if !force {
+                errln!(ctx, "undo: use 'undo -f' to skip conflicting changes");
+                return false;
+            }
+            skip[i] = true;
+        }
+    }
+
+    // 記録と逆の順に戻す（rm -r なら親ディレクトリを作り直してから中身を戻す）
+    let mut ok = true;
+    for (action, _) in entry.actions.iter().zip(&skip).rev().filter(|(_, skip)| !**skip) {
+        if let Err(e) = revert(ctx, action) {
+            errln!(
+                ctx,
+                "undo: cannot restore '{}': {}",
+                quoting::display(target(action).as_os_str()),
+                error_message(&e)
+            );
+            ok = false;
+        }
+    }
+    ok
+}
+
+/// 元に戻すと上書きしてしまうものや、操作の後の変更がないか確かめる。競合があればその説明
+fn check(action: &Action, force: bool) -> Option<String> {
+    let name = |path: &Path| quoting::display(path.as_os_str());
+    let exists = |path: &Path| fs::symlink_metadata(path).is_ok();
+    // 操作直後の状態と比べる（-f なら変更を無視する）
+    let changed = |path: &Path, after: &Option<Fingerprint>| {
+        !force
+            && match (Fingerprint::of(path), after) {
+                (Some(now), Some(after)) => !now.matches(after),
+                _ => true,
+            }
+    };
+    let saved = |stash: &Stash| matches!(stash, Stash::Saved(path) if exists(path));
+
+    match action {
+        Action::Removed { path, stash } => {
+            if exists(path) {
+                return Some(format!("'{}' already exists", name(path)));
+            }
+            if !saved(stash) {
+                return Some(format!("the contents of '{}' were not saved", name(path)));
+            }
+        }
+        Action::RemovedDir { path, .. } => {
+            if exists(path) {
+                return Some(format!("'{}' already exists", name(path)));
+            }
+        }
+        Action::Trashed { item } => {
+            if exists(&item.original) {
+                return Some(format!("'{}' already exists", name(&item.original)));
+            }
+            if !exists(&item.file_path()) {
+                return Some(format!("'{}' is no longer in the trash", name(&item.original)));
+            }
+        }
+        // 作り直された場合もあるので、同じディレクトリかどうかは問わない
+        Action::Created { path } => {
+            if !path.is_dir() {
+                return Some(format!("'{}' no longer exists", name(path)));
+            }
+        }
+        Action::Moved { from, to, overwritten, after } => {
+            if !exists(to) {
+                return Some(format!("'{}' no longer exists", name(to)));
+            }
+            if changed(to, after) {
+                return Some(format!("'{}' has changed since", name(to)));
+            }
+            if exists(from) {
+                return Some(format!("'{}' already exists", name(from)));
+            }
+            if overwritten.as_ref().is_some_and(|stash| !saved(stash)) && !force {
+                return Some(format!("the overwritten '{}' was not saved", name(to)));
+            }
+        }
+        Action::Overwritten { path, stash, after } => {
+            if !exists(path) {
+                return Some(format!("'{}' no longer exists", name(path)));
+            }
+            if changed(path, after) {
+                return Some(format!("'{}' has changed since", name(path)));
+            }
+            if !saved(stash) {
+                return Some(format!("the overwritten '{}' was not saved", name(path)));
+            }
+        }
+    }
+    None
+}
+
+/// 1つの変更を元に戻す
+fn revert(ctx: &mut Context, action: &Action) -> io::Result<()> {
+    match action {
+        Action::Removed { path, stash: Stash::Saved(stash) } => ctx.perform(FsAction::Rename { from: stash, to: path }),
+        Action::Removed { stash: Stash::Lost, .. } => Ok(()),
+        Action::RemovedDir { path, mode } => ctx.perform(FsAction::CreateDir { path, mode: Some(*mode) }),
+        Action::Trashed { item } => ctx.perform(FsAction::RestoreTrash(item)),
+        Action::Created { path } => ctx.perform(FsAction::RemoveDir(path)),
+        Action::Moved { from, to, overwritten, .. } => {
+            move_back(ctx, to, from)?;
+            match overwritten {
+                Some(Stash::Saved(stash)) => ctx.perform(FsAction::Rename { from: stash, to }),
+                _ => Ok(()),
+            }
+        }
+        Action::Overwritten { path, stash: Stash::Saved(stash), .. } => {
+            match ctx.perform(FsAction::Rename { from: stash, to: path }) {
+                // 退避場所と別のファイルシステムなら中身を書き戻す
+                Err(e) if e.raw_os_error() == Some(libc::EXDEV) => ctx.perform(FsAction::Copy { from: stash, to: path }),
+                result => result,
+            }
+        }
+        Action::Overwritten { stash: Stash::Lost, .. } => Ok(()),
+    }
+}
+
+/// `src` を `dst` へ戻す。ファイルシステムをまたぐ場合はコピーしてから削除する
+fn move_back(ctx: &mut Context, src: &Path, dst: &Path) -> io::Result<()> {
+    match ctx.perform(FsAction::Rename { from: src, to: dst }) {
+        Err(e) if e.raw_os_error() == Some(libc::EXDEV) => {
+            let mut copier = Copier::new(
+                NAME,
+                CopyOptions {
+                    recursive: true,
+                    preserve: true,
+                    overwrite: Overwrite::Default,
+                    dereference: false,
+                    reflink: Reflink::Auto,
+                    verbose: false,
+                },
+            );
+            copier.add_total(ctx, src);
+            let copied = copier.copy(ctx, src, dst, src, dst);
+            copier.finish(ctx);
+            if !copied {
+                // コピー中のエラーは Copier が表示済み
+                let _ = ctx.fs.remove_all(dst);
+                return Err(e);
+            }
+            ctx.fs.remove_all(src)
+        }
+        result => result,
+    }
+}
+
+/// 変更の対象（エラー表示用）
+fn target(action: &Action) -> &Path {
+    match action {
+        Action::Removed { path, .. }
+        | Action::RemovedDir { path, .. }
+        | Action::Created { path, .. }
+        | Action::Overwritten { path, .. } => path,
+        Action::Trashed { item } => &item.original,
+        Action::Moved { from, .. } => from,
+    }
+}
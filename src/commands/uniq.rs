@@ -0,0 +1,257 @@
+//! uniqコマンド。隣り合う同じ行を1行にまとめる
+//!
+//! 対応オプション:
+//! - `-c` 各行の前に続いた回数を付ける
+//! - `-d` 重複した行だけ（1行ずつ）出力する / `-D` 重複した行をすべて出力する
+//! - `-u` 重複しなかった行だけ出力する
+//! - `-i` 大文字・小文字を区別しない
+//! - `-f N` 先頭の N フィールド（空白区切り）を比べない / `-s N` 先頭の N 文字を比べない
+//! - `-w N` 比べるのは N 文字まで
+//! - `-z` 行の区切りを改行ではなく NUL にする
+//!
+//! `uniq [INPUT [OUTPUT]]` の形で入力と出力のファイルを指定できる（省略時や `-` は標準入出力）。
+//! 離れた位置にある同じ行はまとめないので、全体で重複を除くには `sort | uniq` とする。
+
+use super::cat::{self, LineReader};
+use super::error_message;
+use crate::context::Context;
+use crate::quoting;
+use crate::errln;
+use std::ffi::{OsStr, OsString};
+use std::io::{self, BufWriter, Read, Write};
+use std::mem;
+
+/// コマンド名
+pub const NAME: &str = "uniq";
+
+/// 解析済みのオプション
+#[derive(Debug, Default)]
+struct Options {
+    count: bool,
+    repeated: bool,
+    all_repeated: bool,
+    unique: bool,
+    ignore_case: bool,
+    skip_fields: usize,
+    skip_chars: usize,
+    check_chars: Option<usize>,
+    delim: u8,
+}
+
+impl Options {
+    /// 行のうち比べる部分
+    fn key<'a>(&self, line: &'a [u8]) -> &'a [u8] {
+        let is_blank = |b: u8| b == b' ' || b == b'\t';
+        let mut pos = 0;
+        for _ in 0..self.skip_fields {
+            while pos < line.len() && is_blank(line[pos]) {
+                pos += 1;
+            }
+            while pos < line.len() && !is_blank(line[pos]) {
+                pos += 1;
+            }
+        }
+        let start = (pos + self.skip_chars).min(line.len());
+        let end = match self.check_chars {
+            Some(n) => (start + n).min(line.len()),
+            None => line.len(),
+        };
+        &line[start..end]
+    }
+
+    fn same(&self, a: &[u8], b: &[u8]) -> bool {
+        let (a, b) = (self.key(a), self.key(b));
+        if self.ignore_case { a.eq_ignore_ascii_case(b) } else { a == b }
+    }
+}
+
+pub fn run(ctx: &mut Context, args: &[OsString]) {
+    let mut opts = Options { delim: b'\n', ..Options::default() };
+    let mut operands: Vec<&OsStr> = Vec::new();
+    let mut only_operands = false;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let Some(s) = arg.to_str().filter(|s| !only_operands && s.starts_with('-') && s.len() > 1) else {
+            operands.push(arg);
+            continue;
+        };
+        // 値を取るオプション（-f N / -fN / --skip-fields=N）
+        let (option, value) = match s {
+            "--" => {
+                only_operands = true;
+                continue;
+            }
+            "--count" => {
+                opts.count = true;
+                continue;
+            }
+            "--repeated" => {
+                opts.repeated = true;
+                continue;
+            }
+            "--all-repeated" => {
+                opts.all_repeated = true;
+                continue;
+            }
+            "--unique" => {
+                opts.unique = true;
+                continue;
+            }
+            "--ignore-case" => {
+                opts.ignore_case = true;
+                continue;
+            }
+            "--zero-terminated" => {
+                opts.delim = b'\0';
+                continue;
+            }
+            _ if s.starts_with("--") => {
+                let (name, value) = match s.split_once('=') {
+                    Some((name, value)) => (name, Some(value.to_string())),
+                    None => (s, None),
+                };
+                let option = match name {
+                    "--skip-fields" => 'f',
+                    "--skip-chars" => 's',
+                    "--check-chars" => 'w',
+                    _ => {
+                        errln!(ctx, "uniq: unrecognized option '{}'", s);
+                        return;
+                    }
+                };
+                match value.or_else(|| args.next().map(|v| v.to_string_lossy().into_owned())) {
+                    Some(value) => (option, value),
+                    None => {
+                        errln!(ctx, "uniq: option '{}' requires an argument", name);
+                        return;
+                    }
+                }
+            }
+            _ => {
+                let mut value = None;
+                for (i, ch) in s[1..].char_indices() {
+                    match ch {
+                        'c' => opts.count = true,
+                        'd' => opts.repeated = true,
+                        'D' => opts.all_repeated = true,
+                        'u' => opts.unique = true,
+                        'i' => opts.ignore_case = true,
+                        'z' => opts.delim = b'\0',
+                        'f' | 's' | 'w' => {
+                            let rest = &s[i + 2..];
+                            value = match (rest.is_empty(), if rest.is_empty() { args.next() } else { None }) {
+                                (false, _) => Some((ch, rest.to_string())),
+                                (true, Some(next)) => Some((ch, next.to_string_lossy().into_owned())),
+                                (true, None) => {
+                                    errln!(ctx, "uniq: option requires an argument -- '{}'", ch);
+                                    return;
+                                }
+                            };
+                            break;
+                        }
+                        _ => {
+                            errln!(ctx, "uniq: invalid option -- '{}'", ch);
+                            return;
+                        }
+                    }
+                }
+                match value {
+                    Some(value) => value,
+                    None => continue,
+                }
+            }
+        };
+        let Ok(n) = value.parse::<usize>() else {
+            let what = match option {
+                'f' => "number of fields to skip",
+                's' => "number of bytes to skip",
+                _ => "number of bytes to compare",
+            };
+            errln!(ctx, "uniq: {}: invalid {}", value, what);
+            return;
+        };
+        match option {
+            'f' => opts.skip_fields = n,
+            's' => opts.skip_chars = n,
+            _ => opts.check_chars = Some(n),
+        }
+    }
+    if opts.all_repeated && opts.count {
+        errln!(ctx, "uniq: printing all duplicated lines and repeat counts is meaningless");
+        return;
+    }
+    if operands.len() > 2 {
+        errln!(ctx, "uniq: extra operand '{}'", quoting::display(operands[2]));
+        return;
+    }
+    let input = operands.first().copied().unwrap_or(OsStr::new("-"));
+
+    let saved = match operands.get(1).filter(|output| **output != "-") {
+        Some(path) => match ctx.fs.create(&ctx.resolve(path), 0o666) {
+            Ok(file) => Some(mem::replace(&mut ctx.stdout, Box::new(BufWriter::new(file)))),
+            Err(e) => {
+                errln!(ctx, "uniq: {}: {}", quoting::display(path), error_message(&e));
+                return;
+            }
+        },
+        None => None,
+    };
+    let result = cat::with_input(ctx, input, |ctx, reader| filter(ctx, reader, &opts));
+    let _ = ctx.stdout.flush();
+    if let Some(stdout) = saved {
+        ctx.stdout = stdout;
+    }
+    match result {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) if e.kind() == io::ErrorKind::Interrupted => {}
+        Ok(Err(e)) | Err(e) => errln!(ctx, "uniq: {}: {}", quoting::display(input), error_message(&e)),
+    }
+}
+
+/// 入力を読み、同じ行のまとまりごとに出力する
+fn filter(ctx: &mut Context, reader: &mut dyn Read, opts: &Options) -> io::Result<()> {
+    let mut lines = LineReader::new();
+    // 今のまとまりの行（-D 以外は最初の1行だけ持つ）と、その数
+    let mut group: Vec<Vec<u8>> = Vec::new();
+    let mut count = 0u64;
+    let mut line = Vec::new();
+    loop {
+        let more = lines.read_record(ctx, reader, opts.delim, &mut line)?;
+        if more && line.last() == Some(&opts.delim) {
+            line.pop();
+        }
+        if more && group.first().is_some_and(|first| opts.same(first, &line)) {
+            count += 1;
+            if opts.all_repeated {
+                group.push(mem::take(&mut line));
+            }
+            continue;
+        }
+        if count > 0 {
+            emit(ctx, opts, &group, count);
+        }
+        if !more {
+            return Ok(());
+        }
+        group.clear();
+        group.push(mem::take(&mut line));
+        count = 1;
+    }
+}
+
+/// 1つのまとまりをオプションに従って出力する
+fn emit(ctx: &mut Context, opts: &Options, group: &[Vec<u8>], count: u64) {
+    let repeated = count > 1;
+    if opts.unique && repeated || (opts.repeated || opts.all_repeated) && !repeated {
+        return;
+    }
+    let lines = if opts.all_repeated { group } else { &group[..1] };
+    for line in lines {
+        if opts.count {
+            let _ = write!(ctx.stdout, "{:7} ", count);
+        }
+        let _ = ctx.stdout.write_all(line);
+        let _ = ctx.stdout.write_all(&[opts.delim]);
+    }
+}
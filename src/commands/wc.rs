@@ -0,0 +1,310 @@
+//! wcコマンド。行数・単語数・文字数・バイト数を数える
+//!
+//! 対応オプション:
+//! - `-l` 行数（改行の数） / `-w` 単語数（空白で区切られた並び） / `-c` バイト数
+//! - `-m` 文字数（UTF-8 として数える。不正なバイトは文字に数えない）
+//! - `-L` いちばん長い行の表示幅（全角文字は2桁、タブは8桁ごとの位置まで）
+//! - `--total=WHEN` 合計行を `auto`（複数のとき）・`always`・`only`（合計だけ）・`never`
+//!
+//! 何も指定しなければ `-lwc` と同じ。出力の順序は指定の順によらず 行・単語・文字・バイト・最長行。
+//! 単語の区切りは Unicode の空白（全角スペースなど）も含む。
+//! ファイルを指定しないか `-` を指定すると標準入力を読む。
+
+use super::cat::{self, read_some};
+use super::error_message;
+use crate::context::Context;
+use crate::quoting;
+use crate::errln;
+use std::ffi::{OsStr, OsString};
+use std::io::{self, Read, Write};
+use unicode_width::UnicodeWidthChar;
+
+/// コマンド名
+pub const NAME: &str = "wc";
+
+/// 一度に読むバイト数
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// 表示する項目
+#[derive(Debug, Default, Clone, Copy)]
+struct Fields {
+    lines: bool,
+    words: bool,
+    chars: bool,
+    bytes: bool,
+    max_line: bool,
+}
+
+/// 合計行を出すか
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Total {
+    Auto,
+    Always,
+    Only,
+    Never,
+}
+
+/// 1つの入力（または合計）の数
+#[derive(Debug, Default, Clone, Copy)]
+struct Counts {
+    lines: u64,
+    words: u64,
+    chars: u64,
+    bytes: u64,
+    max_line: u64,
+}
+
+impl Counts {
+    fn add(&mut self, other: &Counts) {
+        self.lines += other.lines;
+        self.words += other.words;
+        self.chars += other.chars;
+        self.bytes += other.bytes;
+        self.max_line = self.max_line.max(other.max_line);
+    }
+
+    /// 表示する項目の値（表示順）
+    fn values(&self, fields: Fields) -> Vec<u64> {
+        [
+            (fields.lines, self.lines),
+            (fields.words, self.words),
+            (fields.chars, self.chars),
+            (fields.bytes, self.bytes),
+            (fields.max_line, self.max_line),
+        ]
+        .into_iter()
+        .filter_map(|(shown, value)| shown.then_some(value))
+        .collect()
+    }
+}
+
+/// 読みながら数える状態
+struct Counter {
+    counts: Counts,
+    in_word: bool,
+    /// 現在の行のここまでの表示幅
+    column: u64,
+    /// 前回の読み込みの末尾で途切れた UTF-8 の途中のバイト
+    pending: Vec<u8>,
+    /// 文字単位で調べる必要があるか（-w -m -L）。なければ改行とバイト数だけ数える
+    decode: bool,
+}
+
+impl Counter {
+    fn new(fields: Fields) -> Self {
+        Counter {
+            counts: Counts::default(),
+            in_word: false,
+            column: 0,
+            pending: Vec::new(),
+            decode: fields.words || fields.chars || fields.max_line,
+        }
+    }
+
+    fn feed(&mut self, data: &[u8]) {
+        self.counts.bytes += data.len() as u64;
+        if !self.decode {
+            self.counts.lines += data.iter().filter(|&&b| b == b'\n').count() as u64;
+            return;
+        }
+        let joined;
+        let mut rest = if self.pending.is_empty() {
+            data
+        } else {
+            self.pending.extend_from_slice(data);
+            joined = std::mem::take(&mut self.pending);
+            &joined[..]
+        };
+        loop {
+            match std::str::from_utf8(rest) {
+                Ok(text) => {
+                    self.text(text);
+                    return;
+                }
+                Err(e) => {
+                    let (valid, after) = rest.split_at(e.valid_up_to());
+                    self.text(std::str::from_utf8(valid).unwrap_or_default());
+                    match e.error_len() {
+                        Some(len) => {
+                            self.invalid();
+                            rest = &after[len..];
+                        }
+                        // 次の読み込みに続く文字
+                        None => {
+                            self.pending = after.to_vec();
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// 入力の終わり。途切れたままの UTF-8 は不正なバイトとして扱う
+    fn finish(&mut self) -> Counts {
+        if !std::mem::take(&mut self.pending).is_empty() {
+            self.invalid();
+        }
+        self.end_line();
+        self.counts
+    }
+
+    fn text(&mut self, text: &str) {
+        for c in text.chars() {
+            self.counts.chars += 1;
+            match c {
+                '\n' => {
+                    self.counts.lines += 1;
+                    self.end_line();
+                }
+                '\r' | '\x0c' => self.end_line(),
+                '\t' => self.column = (self.column / 8 + 1) * 8,
+                _ => self.column += c.width().unwrap_or(0) as u64,
+            }
+            if c.is_whitespace() {
+                self.in_word = false;
+            } else if !self.in_word {
+                self.in_word = true;
+                self.counts.words += 1;
+            }
+        }
+    }
+
+    /// 不正な UTF-8 のバイト。文字には数えず、単語の一部として扱う
+    fn invalid(&mut self) {
+        if !self.in_word {
+            self.in_word = true;
+            self.counts.words += 1;
+        }
+    }
+
+    fn end_line(&mut self) {
+        self.counts.max_line = self.counts.max_line.max(self.column);
+        self.column = 0;
+    }
+}
+
+pub fn run(ctx: &mut Context, args: &[OsString]) {
+    let mut fields = Fields::default();
+    let mut total = Total::Auto;
+    let mut files: Vec<&OsStr> = Vec::new();
+    let mut only_files = false;
+    for arg in args {
+        let Some(s) = arg.to_str().filter(|s| !only_files && s.starts_with('-') && s.len() > 1) else {
+            files.push(arg);
+            continue;
+        };
+        match s {
+            "--" => only_files = true,
+            "--lines" => fields.lines = true,
+            "--words" => fields.words = true,
+            "--chars" => fields.chars = true,
+            "--bytes" => fields.bytes = true,
+            "--max-line-length" => fields.max_line = true,
+            _ if s.starts_with("--total=") => {
+                total = match &s["--total=".len()..] {
+                    "auto" => Total::Auto,
+                    "always" => Total::Always,
+                    "only" => Total::Only,
+                    "never" => Total::Never,
+                    other => {
+                        errln!(ctx, "wc: invalid argument '{}' for '--total'", other);
+                        errln!(ctx, "Valid arguments are: 'auto', 'always', 'only', 'never'");
+                        return;
+                    }
+                };
+            }
+            _ if s.starts_with("--") => {
+                errln!(ctx, "wc: unrecognized option '{}'", s);
+                return;
+            }
+            _ => {
+                for ch in s[1..].chars() {
+                    match ch {
+                        'l' => fields.lines = true,
+                        'w' => fields.words = true,
+                        'm' => fields.chars = true,
+                        'c' => fields.bytes = true,
+                        'L' => fields.max_line = true,
+                        _ => {
+                            errln!(ctx, "wc: invalid option -- '{}'", ch);
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    if !(fields.lines || fields.words || fields.chars || fields.bytes || fields.max_line) {
+        (fields.lines, fields.words, fields.bytes) = (true, true, true);
+    }
+    let reads_stdin = files.is_empty() || files.contains(&OsStr::new("-"));
+    let no_files = files.is_empty();
+    if no_files {
+        files.push(OsStr::new("-"));
+    }
+
+    // 桁をそろえるため、すべて数えてから表示する
+    let mut results: Vec<(&OsStr, Counts)> = Vec::new();
+    let mut sum = Counts::default();
+    for file in &files {
+        let result = cat::with_input(ctx, file, |ctx, reader| count(ctx, reader, fields));
+        match result {
+            Ok(Ok(counts)) => {
+                sum.add(&counts);
+                results.push((file, counts));
+            }
+            Ok(Err(e)) if e.kind() == io::ErrorKind::Interrupted => return,
+            Ok(Err(e)) | Err(e) => errln!(ctx, "wc: {}: {}", quoting::display(file), error_message(&e)),
+        }
+    }
+
+    let show_total = match total {
+        Total::Auto => files.len() > 1,
+        Total::Always | Total::Only => true,
+        Total::Never => false,
+    };
+    let columns = sum.values(fields).len();
+    let width = if total == Total::Only || columns == 1 && results.len() == 1 && !show_total {
+        1
+    } else {
+        let digits = sum.values(fields).iter().map(|v| v.to_string().len()).max().unwrap_or(1);
+        // 標準入力は大きさが前もってわからないので、GNU wc と同じく広めに取る
+        if reads_stdin { digits.max(7) } else { digits }
+    };
+    if total != Total::Only {
+        for (file, counts) in &results {
+            let name = if no_files { None } else { Some(*file) };
+            print_counts(ctx, counts, fields, width, name);
+        }
+    }
+    if show_total {
+        let name = (total != Total::Only).then_some(OsStr::new("total"));
+        print_counts(ctx, &sum, fields, width, name);
+    }
+    let _ = ctx.stdout.flush();
+}
+
+/// `reader` を最後まで読んで数える
+fn count(ctx: &mut Context, reader: &mut dyn Read, fields: Fields) -> io::Result<Counts> {
+    let mut counter = Counter::new(fields);
+    let mut buf = vec![0u8; BUFFER_SIZE];
+    loop {
+        let n = read_some(ctx, reader, &mut buf)?;
+        if n == 0 {
+            return Ok(counter.finish());
+        }
+        counter.feed(&buf[..n]);
+    }
+}
+
+/// 1行分の数を表示する（標準入力だけのときは名前を付けない）
+fn print_counts(ctx: &mut Context, counts: &Counts, fields: Fields, width: usize, name: Option<&OsStr>) {
+    let mut line: Vec<String> = counts.values(fields).iter().map(|v| format!("{:>width$}", v)).collect();
+    match name {
+        Some(name) if name == "-" => line.push("-".to_string()),
+        Some(name) => line.push(quoting::display(name)),
+        None => {}
+    }
+    let _ = writeln!(ctx.stdout, "{}", line.join(" "));
+}
@@ -0,0 +1,180 @@
+//! zコマンド。よく移動するディレクトリへキーワードで移動する（zoxide の `z` 相当）
+//!
+//! 使い方:
+//! - `z KEYWORD...` cd で移動したディレクトリの記録（`crate::frecency`）のうち、
+//!   キーワードに一致し frecency が最も高いものへ移動する（カレントディレクトリは除く）
+//! - `z` ホームへ、`z -` 直前のディレクトリへ移動する。`z DIR` は DIR が存在すればそこへ移動する
+//!
+//! 対応オプション:
+//! - `-l` 一致するディレクトリをスコアの高い順に表示する（GUI版では並べ替えられる表）
+//! - `-x [DIR]` DIR（省略時はカレントディレクトリ）を記録から取り除く
+//! - `--clean` 存在しなくなったディレクトリを記録から取り除く
+
+use super::cd::change_directory;
+use super::error_message;
+use crate::context::Context;
+use crate::frecency::DirEntry;
+use crate::quoting;
+use crate::table::{Cell, Table};
+use crate::{errln, outln};
+use std::ffi::{OsStr, OsString};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// コマンド名
+pub const NAME: &str = "z";
+
+/// 何をするか
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Jump,
+    List,
+    Remove,
+    Clean,
+}
+
+pub fn run(ctx: &mut Context, args: &[OsString]) {
+    let mut mode = Mode::Jump;
+    let mut operands: Vec<&OsStr> = Vec::new();
+    let mut only_operands = false;
+
+    for os_arg in args {
+        let arg = match os_arg.to_str() {
+            Some(arg) if !only_operands && arg.starts_with('-') && arg != "-" => arg,
+            _ => {
+                operands.push(os_arg);
+                continue;
+            }
+        };
+        match arg {
+            "--" => only_operands = true,
+            "-l" | "--list" => mode = Mode::List,
+            "-x" | "--remove" => mode = Mode::Remove,
+            "--clean" => mode = Mode::Clean,
+            _ => {
+                errln!(ctx, "z: invalid option '{}'", arg);
+                errln!(ctx, "Try 'z --help' for more information.");
+                return;
+            }
+        }
+    }
+
+    match mode {
+        Mode::Jump => jump(ctx, &operands),
+        Mode::List => {
+            let keywords = keywords(&operands);
+            let Some(found) = candidates(ctx, NAME, &keywords, false) else {
+                return;
+            };
+            list(ctx, &found);
+        }
+        Mode::Remove => remove(ctx, &operands),
+        Mode::Clean => {
+            let Some(frecency) = database(ctx, NAME) else {
+                return;
+            };
+            let fs = ctx.fs.clone();
+            match frecency.clean(&|path| fs.is_dir(path)) {
+                Ok(count) => outln!(ctx, "removed {} missing director{}", count, if count == 1 { "y" } else { "ies" }),
+                Err(e) => errln!(ctx, "z: cannot update the database: {}", error_message(&e)),
+            }
+        }
+    }
+}
+
+/// キーワードに一致する最もスコアの高いディレクトリへ移動する
+fn jump(ctx: &mut Context, operands: &[&OsStr]) {
+    match operands {
+        [] => {
+            change_directory(ctx, NAME, None);
+            return;
+        }
+        [dir] if *dir == "-" => {
+            let Some(oldpwd) = ctx.oldpwd.clone() else {
+                errln!(ctx, "z: OLDPWD not set");
+                return;
+            };
+            change_directory(ctx, NAME, Some(oldpwd.as_os_str()));
+            return;
+        }
+        // 存在するディレクトリはキーワードでなくパスとして扱う
+        [dir] if ctx.fs.is_dir(&ctx.resolve(dir)) => {
+            change_directory(ctx, NAME, Some(dir));
+            return;
+        }
+        _ => {}
+    }
+    let keywords = keywords(operands);
+    let Some(found) = candidates(ctx, NAME, &keywords, true) else {
+        return;
+    };
+    match found.first() {
+        Some(best) => {
+            let path = best.path.clone();
+            change_directory(ctx, NAME, Some(path.as_os_str()));
+        }
+        None => errln!(ctx, "z: no match found"),
+    }
+}
+
+/// 一致したディレクトリをスコアとともに表示する
+fn list(ctx: &mut Context, found: &[DirEntry]) {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    let mut table = Table::new(&[("Score", true), ("Path", false)]);
+    for entry in found {
+        let score = entry.score(now);
+        table.push(vec![
+            Cell::number(format!("{:.1}", score), score),
+            Cell::text(quoting::display(entry.path.as_os_str())),
+        ]);
+    }
+    if ctx.show_table(&table) {
+        return;
+    }
+    for row in &table.rows {
+        outln!(ctx, "{:>8} {}", row[0].text, row[1].text);
+    }
+}
+
+/// 記録からディレクトリを取り除く（-x）
+fn remove(ctx: &mut Context, operands: &[&OsStr]) {
+    let Some(frecency) = database(ctx, NAME) else {
+        return;
+    };
+    let dirs: Vec<&OsStr> = if operands.is_empty() { vec![ctx.cwd.as_os_str()] } else { operands.to_vec() };
+    for dir in dirs {
+        let path = ctx.resolve(dir);
+        // 消えたディレクトリは解決できないので、記録されたパスのまま比べる
+        let path = ctx.fs.canonicalize(&path).unwrap_or(path);
+        match frecency.remove(&path) {
+            Ok(true) => {}
+            Ok(false) => errln!(ctx, "z: '{}' is not in the database", quoting::display(dir)),
+            Err(e) => errln!(ctx, "z: cannot update the database: {}", error_message(&e)),
+        }
+    }
+}
+
+fn keywords(operands: &[&OsStr]) -> Vec<String> {
+    operands.iter().map(|arg| arg.to_string_lossy().into_owned()).collect()
+}
+
+/// 記録を使えなければエラーを表示して None
+fn database(ctx: &mut Context, name: &str) -> Option<crate::frecency::Frecency> {
+    let frecency = ctx.frecency.clone();
+    if frecency.is_none() {
+        errln!(ctx, "{}: the directory database is disabled ('frecency = false' or sandbox)", name);
+    }
+    frecency
+}
+
+/// キーワードに一致するディレクトリをスコアの高い順に返す（zi も使う）。
+/// `skip_cwd` ならカレントディレクトリを除く。記録を使えなければエラーを表示して None
+pub fn candidates(ctx: &mut Context, name: &str, keywords: &[String], skip_cwd: bool) -> Option<Vec<DirEntry>> {
+    let frecency = database(ctx, name)?;
+    let fs = ctx.fs.clone();
+    let exclude = skip_cwd.then_some(ctx.cwd.as_path());
+    Some(frecency.query(keywords, exclude, &|path: &Path| fs.is_dir(path)))
+}
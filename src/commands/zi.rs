@@ -0,0 +1,35 @@
+//! ziコマンド。z の候補から選んで移動する
+//!
+//! `zi [KEYWORD...]` キーワードに一致するディレクトリを frecency の高い順に並べて選ばせ、
+//! 選んだディレクトリへ移動する。GUI版は絞り込みのできる選択ウィンドウ、
+//! CLI版は番号の入力で選ぶ。取り消したときは終了ステータスを 1 にする。
+
+use super::cd::change_directory;
+use super::z;
+use crate::context::Context;
+use crate::quoting;
+use crate::errln;
+use std::ffi::OsString;
+
+/// コマンド名
+pub const NAME: &str = "zi";
+
+pub fn run(ctx: &mut Context, args: &[OsString]) {
+    let keywords: Vec<String> = args.iter().map(|arg| arg.to_string_lossy().into_owned()).collect();
+    let Some(found) = z::candidates(ctx, NAME, &keywords, true) else {
+        return;
+    };
+    if found.is_empty() {
+        errln!(ctx, "zi: no match found");
+        return;
+    }
+
+    let items: Vec<String> = found.iter().map(|entry| quoting::display(entry.path.as_os_str())).collect();
+    match ctx.choose("Jump to directory", &items) {
+        Some(index) => {
+            let path = found[index].path.clone();
+            change_directory(ctx, NAME, Some(path.as_os_str()));
+        }
+        None => ctx.status = 1,
+    }
+}
@@ -0,0 +1,171 @@
+//! 設定ファイル
+//!
+//! `$XDG_CONFIG_HOME/taminal/config`（未設定なら `~/.config/taminal/config`）を読む。
+//! 1行に1つ `key = value` の形式で書き、`#` 以降はコメントとして無視する。
+//!
+//! ```text
+//! # rm の既定をゴミ箱への移動にする
+//! trash = true
+//! # undo 用に残す操作の数と、退避する内容の合計サイズの上限
+//! undo_entries = 50
+//! undo_stash_size = 1G
+//! # ビルトインが扱えるファイルをこのディレクトリ以下に制限する
+//! root = /srv/classroom
+//! # プロンプトにディレクトリスタック（pushd で保存したもの）を表示する
+//! prompt_stack = true
+//! # z で使うディレクトリの記録から除くパターン（`:` 区切り、既定はホームディレクトリ）
+//! frecency_exclude = ~:/tmp/**
+//! ```
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// 設定値
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    /// rm の既定をゴミ箱への移動にするか（`trash = true`）
+    pub trash: bool,
+    /// ファイル操作を記録して undo できるようにするか（`undo = false` で無効）
+    pub undo: bool,
+    /// undo 用に残す操作の数
+    pub undo_entries: usize,
+    /// undo 用に退避する内容の合計サイズの上限（バイト）
+    pub undo_stash_size: u64,
+    /// サンドボックスのルート（`root = DIR`。起動時の `--root` が優先）
+    pub root: Option<PathBuf>,
+    /// プロンプトにディレクトリスタックを表示するか（`prompt_stack = true`）
+    pub prompt_stack: bool,
+    /// cd で移動したディレクトリを z 用に記録するか（`frecency = false` で無効）
+    pub frecency: bool,
+    /// 記録の rank の合計の上限。超えたら古いものから縮める（`frecency_max_age`）
+    pub frecency_max_age: u64,
+    /// 記録しないディレクトリのパターン（`frecency_exclude`。`~` はホームディレクトリ）
+    pub frecency_exclude: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            trash: false,
+            undo: true,
+            undo_entries: 50,
+            undo_stash_size: 1 << 30,
+            root: None,
+            prompt_stack: false,
+            frecency: true,
+            frecency_max_age: 10_000,
+            frecency_exclude: vec!["~".to_string()],
+        }
+    }
+}
+
+impl Config {
+    /// 設定ファイルのパス
+    pub fn path() -> Option<PathBuf> {
+        let base = match env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+        };
+        Some(base.join("taminal").join("config"))
+    }
+
+    /// 設定ファイルを読む。ファイルがなければ既定値を使う。
+    /// 解釈できなかった行は警告メッセージとして返す（その行は無視する）
+    pub fn load() -> (Config, Vec<String>) {
+        let Some(path) = Config::path() else {
+            return (Config::default(), Vec::new());
+        };
+        match fs::read_to_string(&path) {
+            Ok(text) => Config::parse(&text),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => (Config::default(), Vec::new()),
+            Err(e) => (Config::default(), vec![format!("taminal: cannot read '{}': {}", path.display(), e)]),
+        }
+    }
+
+    /// 設定ファイルの内容を解釈する
+    pub fn parse(text: &str) -> (Config, Vec<String>) {
+        let mut config = Config::default();
+        let mut warnings = Vec::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let result = match line.split_once('=') {
+                Some((key, value)) => config.set(key.trim(), value.trim()),
+                None => Err("expected 'key = value'".to_string()),
+            };
+            if let Err(message) = result {
+                warnings.push(format!("taminal: config line {}: {}", index + 1, message));
+            }
+        }
+        (config, warnings)
+    }
+
+    /// 1つの設定値を反映する
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "trash" => self.trash = parse_bool(key, value)?,
+            "undo" => self.undo = parse_bool(key, value)?,
+            "prompt_stack" => self.prompt_stack = parse_bool(key, value)?,
+            "frecency" => self.frecency = parse_bool(key, value)?,
+            "frecency_max_age" => {
+                self.frecency_max_age = value
+                    .parse()
+                    .ok()
+                    .filter(|&age| age > 0)
+                    .ok_or_else(|| format!("invalid number '{}' for '{}'", value, key))?;
+            }
+            "frecency_exclude" => {
+                self.frecency_exclude = value.split(':').filter(|p| !p.is_empty()).map(str::to_string).collect();
+            }
+            "undo_entries" => {
+                self.undo_entries = value
+                    .parse()
+                    .map_err(|_| format!("invalid number '{}' for '{}'", value, key))?;
+            }
+            "undo_stash_size" => {
+                self.undo_stash_size =
+                    parse_size(value).ok_or_else(|| format!("invalid size '{}' for '{}'", value, key))?;
+            }
+            "root" => {
+                let root = PathBuf::from(value);
+                if !root.is_absolute() {
+                    return Err(format!("'{}' must be an absolute path", key));
+                }
+                self.root = Some(root);
+            }
+            _ => return Err(format!("unknown key '{}'", key)),
+        }
+        Ok(())
+    }
+}
+
+/// 真偽値（true/false, yes/no, on/off, 1/0）を解釈する
+fn parse_bool(key: &str, value: &str) -> Result<bool, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Ok(true),
+        "false" | "no" | "off" | "0" => Ok(false),
+        _ => Err(format!("invalid value '{}' for '{}' (expected true or false)", value, key)),
+    }
+}
+
+/// サイズ（`512`, `100K`, `1G` など。単位は 1024 倍）を解釈する
+fn parse_size(value: &str) -> Option<u64> {
+    let (digits, unit) = match value.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => value.split_at(i),
+        None => (value, ""),
+    };
+    let shift = match unit.to_ascii_uppercase().as_str() {
+        "" | "B" => 0,
+        "K" | "KB" | "KIB" => 10,
+        "M" | "MB" | "MIB" => 20,
+        "G" | "GB" | "GIB" => 30,
+        "T" | "TB" | "TIB" => 40,
+        _ => return None,
+    };
+    digits.parse::<u64>().ok()?.checked_mul(1 << shift)
+}
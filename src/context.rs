@@ -0,0 +1,312 @@
+//! コマンド実行時の環境
+//!
+//! ビルトインコマンドはプロセスのカレントディレクトリや標準出力を直接使わず、
+//! ここで渡される `Context` を通して入出力する。CLI版は端末の標準出力を、
+//! GUI版は出力エリアへ流す書き込み先を渡すことで、同じ実装を共有できる。
+
+use crate::columns;
+use crate::config::Config;
+use crate::dir_stack::{self, DirStack};
+use crate::frecency::Frecency;
+use crate::fs_action::FsAction;
+use crate::journal::{Journal, Recorder};
+use crate::sandbox::SandboxFs;
+use crate::table::Table;
+use crate::trash::TrashedItem;
+use crate::tree::TreeNode;
+use crate::vfs::{Fs, OsFs};
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// 標準出力へ書き込む（`print!` 相当）。書き込みエラーは無視する
+#[macro_export]
+macro_rules! out {
+    ($ctx:expr, $($arg:tt)*) => {{
+        let _ = ::std::io::Write::write_fmt(&mut $ctx.stdout, format_args!($($arg)*));
+    }};
+}
+
+/// 標準出力へ1行書き込む（`println!` 相当）
+#[macro_export]
+macro_rules! outln {
+    ($ctx:expr) => {{
+        let _ = ::std::io::Write::write_all(&mut $ctx.stdout, b"\n");
+    }};
+    ($ctx:expr, $($arg:tt)*) => {{
+        let _ = ::std::io::Write::write_fmt(&mut $ctx.stdout, format_args!("{}\n", format_args!($($arg)*)));
+    }};
+}
+
+/// 標準エラーへ1行書き込む（`eprintln!` 相当）。
+/// エラーを表示したコマンドは失敗したものとして、終了ステータスが 0 なら 1 にする
+#[macro_export]
+macro_rules! errln {
+    ($ctx:expr, $($arg:tt)*) => {{
+        let _ = ::std::io::Write::write_fmt(&mut $ctx.stderr, format_args!("{}\n", format_args!($($arg)*)));
+        if $ctx.status == 0 {
+            $ctx.status = 1;
+        }
+    }};
+}
+
+/// 長い処理の進捗
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Progress {
+    /// 処理中の項目などの説明
+    pub message: String,
+    /// 処理済みの量（バイト数など）
+    pub done: u64,
+    /// 全体の量。不明なら 0
+    pub total: u64,
+}
+
+/// ユーザーとのやりとり（確認の問い合わせと進捗表示）。
+/// CLI版は端末で、GUI版はダイアログや進捗バーで実装する
+pub trait Interaction: Send {
+    /// はい/いいえで答える質問をし、「はい」なら true を返す
+    fn confirm(&mut self, question: &str) -> bool;
+
+    /// 進捗を表示する。`None` で表示を消す
+    fn progress(&mut self, progress: Option<&Progress>);
+
+    /// 実行中のコマンドの中断が求められたか（CLI版は Ctrl+C、GUI版は Stop ボタン）。
+    /// `tail -f` のように終わらないコマンドや長い処理は、これを見て途中で止まる
+    fn interrupted(&self) -> bool {
+        false
+    }
+
+    /// `items` から1つを選ばせ、選ばれた位置を返す。取り消されたか、
+    /// 選ばせられない環境なら None（既定）
+    fn choose(&mut self, _title: &str, _items: &[String]) -> Option<usize> {
+        None
+    }
+
+    /// 表を表として表示する。表示できたら true を返し、
+    /// false なら呼び出し側がテキストで出力する（既定。CLI版はこちら）
+    fn table(&mut self, _table: &Table) -> bool {
+        false
+    }
+
+    /// 木を開閉できる木として表示する。表示できたら true を返し、
+    /// false なら呼び出し側がテキストで出力する（既定。CLI版はこちら）
+    fn tree(&mut self, _tree: &TreeNode) -> bool {
+        false
+    }
+
+    /// パイプラインの段のように、並行して動く別のコマンド用のやりとりを作る。
+    /// 既定は対話なし（確認にはすべて「いいえ」）
+    fn fork(&self) -> Box<dyn Interaction> {
+        Box::new(NonInteractive)
+    }
+}
+
+/// 対話できない環境用の実装。確認にはすべて「いいえ」と答え、進捗は表示しない
+pub struct NonInteractive;
+
+impl Interaction for NonInteractive {
+    fn confirm(&mut self, _question: &str) -> bool {
+        false
+    }
+
+    fn progress(&mut self, _progress: Option<&Progress>) {}
+}
+
+/// コマンドの実行環境（カレントディレクトリと出力先）
+pub struct Context {
+    /// カレントディレクトリ。相対パスはここを基準に解決する
+    pub cwd: PathBuf,
+    /// 標準入力の読み込み元（既定は空。CLI版は端末の標準入力）
+    pub stdin: Box<dyn Read + Send>,
+    /// 標準出力の書き込み先
+    pub stdout: Box<dyn Write + Send>,
+    /// 標準エラーの書き込み先
+    pub stderr: Box<dyn Write + Send>,
+    /// 出力先がANSIカラーを表示できるか（`--color=auto` の判定に使う）
+    pub is_tty: bool,
+    /// 出力先の表示幅（桁数）。複数列表示の折り返しに使う
+    pub width: usize,
+    /// 確認の問い合わせと進捗表示の方法（既定は対話なし）
+    pub interaction: Box<dyn Interaction>,
+    /// 設定ファイルの内容
+    pub config: Config,
+    /// rm でゴミ箱へ移した項目（GUI版の「削除を取り消す」で元に戻す）
+    pub trashed: Vec<TrashedItem>,
+    /// ファイル操作の記録先（undo 用）。None なら記録しない
+    pub journal: Option<Journal>,
+    /// cd で移動したディレクトリの記録先（z 用）。None なら記録しない
+    pub frecency: Option<Frecency>,
+    /// ドライラン（`set -n` や `--dry-run`）。ファイルを変更せず予定の操作を表示する
+    pub dry_run: bool,
+    /// ファイルの読み書きに使うファイルシステム（既定は実際のもの）
+    pub fs: Arc<dyn Fs>,
+    /// サンドボックスのルート。Some ならファイル操作はこの下に制限され、外部コマンドは実行しない
+    pub sandbox: Option<PathBuf>,
+    /// 直前のコマンドの終了ステータス（0 が成功）。`&&` `||` で次を実行するかの判定に使う
+    pub status: i32,
+    /// 直前のカレントディレクトリ（`$OLDPWD`）。`cd -` と `~-` で使う
+    pub oldpwd: Option<PathBuf>,
+    /// pushd で保存したディレクトリ
+    pub dir_stack: DirStack,
+}
+
+impl Context {
+    pub fn new(
+        cwd: PathBuf,
+        stdout: Box<dyn Write + Send>,
+        stderr: Box<dyn Write + Send>,
+        is_tty: bool,
+    ) -> Self {
+        Context {
+            cwd,
+            stdin: Box::new(io::empty()),
+            stdout,
+            stderr,
+            is_tty,
+            width: columns::DEFAULT_WIDTH,
+            interaction: Box::new(NonInteractive),
+            config: Config::default(),
+            trashed: Vec::new(),
+            journal: None,
+            frecency: None,
+            dry_run: false,
+            fs: Arc::new(OsFs),
+            sandbox: None,
+            status: 0,
+            oldpwd: None,
+            dir_stack: DirStack::default(),
+        }
+    }
+
+    /// 同じ設定で別のコマンドを並行して動かすための実行環境を作る（パイプラインの段に使う）。
+    /// 出力先は端末ではないものとして扱い、ゴミ箱へ移した項目は引き継がない
+    pub fn fork(
+        &self,
+        stdin: Box<dyn Read + Send>,
+        stdout: Box<dyn Write + Send>,
+        stderr: Box<dyn Write + Send>,
+    ) -> Context {
+        Context {
+            cwd: self.cwd.clone(),
+            stdin,
+            stdout,
+            stderr,
+            is_tty: false,
+            width: self.width,
+            interaction: self.interaction.fork(),
+            config: self.config.clone(),
+            trashed: Vec::new(),
+            journal: self.journal.clone(),
+            frecency: self.frecency.clone(),
+            dry_run: self.dry_run,
+            fs: self.fs.clone(),
+            sandbox: self.sandbox.clone(),
+            status: 0,
+            oldpwd: self.oldpwd.clone(),
+            dir_stack: self.dir_stack.clone(),
+        }
+    }
+
+    /// ユーザーに確認する。`Interaction::confirm` の省略形
+    pub fn confirm(&mut self, question: &str) -> bool {
+        self.interaction.confirm(question)
+    }
+
+    /// 候補から1つを選ばせる。`Interaction::choose` の省略形
+    pub fn choose(&mut self, title: &str, items: &[String]) -> Option<usize> {
+        self.interaction.choose(title, items)
+    }
+
+    /// 表を GUI の表として表示する。出力先が端末でない（パイプラインの途中など）か、
+    /// 表を表示できない環境なら false を返すので、呼び出し側はテキストで出力する
+    pub fn show_table(&mut self, table: &Table) -> bool {
+        self.is_tty && self.interaction.table(table)
+    }
+
+    /// 木を GUI の開閉できる木として表示する。表示できなければ false を返す（`show_table` と同じ）
+    pub fn show_tree(&mut self, tree: &TreeNode) -> bool {
+        self.is_tty && self.interaction.tree(tree)
+    }
+
+    /// 中断が求められたか。`Interaction::interrupted` の省略形
+    pub fn interrupted(&self) -> bool {
+        self.interaction.interrupted()
+    }
+
+    /// ファイルシステムを変更する。ドライランなら予定の操作を表示するだけにする
+    pub fn perform(&mut self, action: FsAction) -> io::Result<()> {
+        if self.preview(&action) {
+            return Ok(());
+        }
+        action.execute(self.fs.as_ref())
+    }
+
+    /// ドライランなら予定の操作を表示して true を返す（呼び出し側は実行しない）
+    pub fn preview(&mut self, action: &FsAction) -> bool {
+        if self.dry_run {
+            let text = action.describe(&self.cwd);
+            crate::outln!(self, "would {}", text);
+        }
+        self.dry_run
+    }
+
+    /// undo 用の記録を始める。undo が無効な場合・ドライラン・実際のファイルシステム以外では None
+    pub fn recorder(&self, name: &str, args: &[OsString]) -> Option<Recorder> {
+        match &self.journal {
+            Some(journal) if !self.dry_run && self.fs.is_native() => Some(journal.begin(name, args)),
+            _ => None,
+        }
+    }
+
+    /// ファイル操作を `root` 以下に制限する（`crate::sandbox`）。
+    /// カレントディレクトリがルートの外ならルートへ移る。ゴミ箱と undo、z 用の記録は使わない
+    pub fn enter_sandbox(&mut self, root: &Path) -> io::Result<()> {
+        let fs = SandboxFs::new(root)?;
+        if !fs.is_dir(&self.cwd) {
+            self.cwd = fs.root().to_path_buf();
+        }
+        self.sandbox = Some(fs.root().to_path_buf());
+        self.fs = Arc::new(fs);
+        self.journal = None;
+        self.frecency = None;
+        self.config.trash = false;
+        Ok(())
+    }
+
+    /// ホームディレクトリ（`cd` の既定の移動先と `~`）。サンドボックスの中ではそのルート
+    pub fn home(&self) -> PathBuf {
+        match &self.sandbox {
+            Some(root) => root.clone(),
+            None => PathBuf::from(env::var_os("HOME").unwrap_or_else(|| OsString::from("/"))),
+        }
+    }
+
+    /// `~` の後ろ（最初の `/` まで）を展開する。`~` はホーム、`~+` はカレントディレクトリ、
+    /// `~-` は直前のディレクトリ、`~N` `~+N` `~-N` はディレクトリスタックの項目。
+    /// 展開できなければ None（`~` のまま残す）
+    pub fn expand_tilde(&self, prefix: &OsStr) -> Option<OsString> {
+        let path = match prefix.to_str()? {
+            "" => self.home(),
+            "+" => self.cwd.clone(),
+            "-" => self.oldpwd.clone()?,
+            spec => {
+                let index = self.dir_stack.index(spec)?;
+                self.dir_stack.entries(&self.cwd).nth(index)?.to_path_buf()
+            }
+        };
+        Some(path.into_os_string())
+    }
+
+    /// プロンプトに表示する文字列（`prompt_stack = true` ならディレクトリスタックも）
+    pub fn prompt_label(&self) -> String {
+        let stack: &[PathBuf] = if self.config.prompt_stack { &self.dir_stack.dirs } else { &[] };
+        dir_stack::prompt_label(&self.cwd, stack)
+    }
+
+    /// 引数で受け取ったパスをカレントディレクトリ基準の絶対パスにする
+    pub fn resolve(&self, path: impl AsRef<Path>) -> PathBuf {
+        self.cwd.join(path)
+    }
+}
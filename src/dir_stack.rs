@@ -0,0 +1,74 @@
+//! ディレクトリスタック（pushd・popd・dirs）
+//!
+//! スタックはカレントディレクトリを先頭（`+0`）とし、その後ろに `pushd` で保存した
+//! ディレクトリを積んだもの。ここではカレントディレクトリを除いた部分を持つ。
+//! `+N` は先頭から、`-N` は末尾から数えた位置で、`~N`（`~+N` `~-N`）の展開にも使う。
+
+use std::path::{Path, PathBuf};
+
+/// 保存したディレクトリ（カレントディレクトリを除く）
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DirStack {
+    /// 先頭が `+1`
+    pub dirs: Vec<PathBuf>,
+}
+
+impl DirStack {
+    /// カレントディレクトリを含めた項目数
+    pub fn len(&self) -> usize {
+        self.dirs.len() + 1
+    }
+
+    /// カレントディレクトリ以外に保存したディレクトリがないか
+    pub fn is_empty(&self) -> bool {
+        self.dirs.is_empty()
+    }
+
+    /// `+N` `-N` `N`（`+N` と同じ）をカレントディレクトリを 0 とした位置にする。
+    /// 範囲外や数字でなければ None
+    pub fn index(&self, spec: &str) -> Option<usize> {
+        let (from_end, digits) = match spec.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, spec.strip_prefix('+').unwrap_or(spec)),
+        };
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let n: usize = digits.parse().ok()?;
+        if n >= self.len() {
+            return None;
+        }
+        Some(if from_end { self.len() - 1 - n } else { n })
+    }
+
+    /// カレントディレクトリを先頭にした全体
+    pub fn entries<'a>(&'a self, cwd: &'a Path) -> impl Iterator<Item = &'a Path> {
+        std::iter::once(cwd).chain(self.dirs.iter().map(PathBuf::as_path))
+    }
+}
+
+/// 引数が `+N` `-N` の形（スタックの位置の指定）か
+pub fn is_index(arg: &str) -> bool {
+    arg.len() > 1 && arg.starts_with(['+', '-']) && arg[1..].bytes().all(|b| b.is_ascii_digit())
+}
+
+/// ホームディレクトリの下なら `~/...` の形にする（dirs の既定の表示）
+pub fn tilde_path(path: &Path, home: &Path) -> String {
+    match path.strip_prefix(home) {
+        Ok(rest) if rest.as_os_str().is_empty() => "~".to_string(),
+        Ok(rest) if home != Path::new("/") => format!("~/{}", rest.display()),
+        _ => path.display().to_string(),
+    }
+}
+
+/// プロンプトに表示する文字列。カレントディレクトリの名前に、
+/// `stack` があれば保存したディレクトリの名前を `[...]` で続ける（"src [docs tmp]"）
+pub fn prompt_label(cwd: &Path, stack: &[PathBuf]) -> String {
+    let name = |path: &Path| path.file_name().and_then(|s| s.to_str()).map(str::to_string);
+    let mut label = name(cwd).unwrap_or_else(|| "?".to_string());
+    if !stack.is_empty() {
+        let names: Vec<String> = stack.iter().map(|dir| name(dir).unwrap_or_else(|| dir.display().to_string())).collect();
+        label.push_str(&format!(" [{}]", names.join(" ")));
+    }
+    label
+}
@@ -0,0 +1,226 @@
+//! ファイル属性の表示用ヘルパー
+//!
+//! `ls -l` や `stat` などで使う、パーミッション文字列・人間向けサイズ表記・
+//! 所有者名/グループ名・更新日時の整形をまとめる。
+
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use crate::vfs::Stat;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// "drwxr-xr-x" 形式のパーミッション文字列を作る
+pub fn mode_string(meta: &Stat) -> String {
+    let file_type = meta.file_type();
+    let mode = meta.mode();
+
+    let kind = if file_type.is_dir() {
+        'd'
+    } else if file_type.is_symlink() {
+        'l'
+    } else if file_type.is_fifo() {
+        'p'
+    } else if file_type.is_socket() {
+        's'
+    } else if file_type.is_block_device() {
+        'b'
+    } else if file_type.is_char_device() {
+        'c'
+    } else {
+        '-'
+    };
+
+    let mut text = String::with_capacity(10);
+    text.push(kind);
+    text.push_str(&permission_string(mode));
+    text
+}
+
+/// "rwxr-xr-x" 形式のパーミッション文字列（種類の文字なし）を作る
+pub fn permission_string(mode: u32) -> String {
+    // 実行ビットと特殊ビット（setuid/setgid/sticky）の組み合わせ
+    let exec = |bit: u32, special: u32, set: char, unset: char| match (mode & bit != 0, mode & special != 0) {
+        (true, true) => set,
+        (false, true) => unset,
+        (true, false) => 'x',
+        (false, false) => '-',
+    };
+    let flag = |bit: u32, c: char| if mode & bit != 0 { c } else { '-' };
+
+    [
+        flag(0o400, 'r'),
+        flag(0o200, 'w'),
+        exec(0o100, 0o4000, 's', 'S'),
+        flag(0o040, 'r'),
+        flag(0o020, 'w'),
+        exec(0o010, 0o2000, 's', 'S'),
+        flag(0o004, 'r'),
+        flag(0o002, 'w'),
+        exec(0o001, 0o1000, 't', 'T'),
+    ]
+    .iter()
+    .collect()
+}
+
+/// "1.5K" "23M" のような人間向けのサイズ表記にする（1024単位, GNU ls -h 互換）
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 8] = ["K", "M", "G", "T", "P", "E", "Z", "Y"];
+
+    if bytes < 1024 {
+        return bytes.to_string();
+    }
+
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    value /= 1024.0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    // 10未満は小数1桁、それ以上は整数で表示する（いずれも切り上げ）
+    if value < 10.0 {
+        let rounded = (value * 10.0).ceil() / 10.0;
+        if rounded < 10.0 {
+            return format!("{:.1}{}", rounded, UNITS[unit]);
+        }
+    }
+    let rounded = value.ceil();
+    if rounded >= 1024.0 && unit < UNITS.len() - 1 {
+        return format!("1.0{}", UNITS[unit + 1]);
+    }
+    format!("{}{}", rounded as u64, UNITS[unit])
+}
+
+/// デバイス番号を (major, minor) に分ける（Linux の dev_t の形式）
+pub fn device_numbers(dev: u64) -> (u64, u64) {
+    let major = ((dev >> 8) & 0xfff) | ((dev >> 32) & !0xfff);
+    let minor = (dev & 0xff) | ((dev >> 12) & !0xff);
+    (major, minor)
+}
+
+/// uid/gid から名前を引く。同じIDの問い合わせはキャッシュする
+#[derive(Default)]
+pub struct NameCache {
+    users: HashMap<u32, String>,
+    groups: HashMap<u32, String>,
+}
+
+impl NameCache {
+    /// ユーザー名。見つからなければ数値のまま返す
+    pub fn user(&mut self, uid: u32) -> &str {
+        self.users
+            .entry(uid)
+            .or_insert_with(|| user_name(uid).unwrap_or_else(|| uid.to_string()))
+    }
+
+    /// グループ名。見つからなければ数値のまま返す
+    pub fn group(&mut self, gid: u32) -> &str {
+        self.groups
+            .entry(gid)
+            .or_insert_with(|| group_name(gid).unwrap_or_else(|| gid.to_string()))
+    }
+}
+
+/// passwd データベースからユーザー名を引く
+pub fn user_name(uid: u32) -> Option<String> {
+    let mut buf = vec![0 as libc::c_char; 4096];
+    let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut result: *mut libc::passwd = std::ptr::null_mut();
+    let ret = unsafe { libc::getpwuid_r(uid, &mut pwd, buf.as_mut_ptr(), buf.len(), &mut result) };
+    if ret != 0 || result.is_null() {
+        return None;
+    }
+    let name = unsafe { CStr::from_ptr(pwd.pw_name) };
+    Some(name.to_string_lossy().into_owned())
+}
+
+/// group データベースからグループ名を引く
+pub fn group_name(gid: u32) -> Option<String> {
+    let mut buf = vec![0 as libc::c_char; 4096];
+    let mut grp: libc::group = unsafe { std::mem::zeroed() };
+    let mut result: *mut libc::group = std::ptr::null_mut();
+    let ret = unsafe { libc::getgrgid_r(gid, &mut grp, buf.as_mut_ptr(), buf.len(), &mut result) };
+    if ret != 0 || result.is_null() {
+        return None;
+    }
+    let name = unsafe { CStr::from_ptr(grp.gr_name) };
+    Some(name.to_string_lossy().into_owned())
+}
+
+/// passwd データベースからユーザーを引き、(uid, ログイングループの gid) を返す
+pub fn user_by_name(name: &str) -> Option<(u32, u32)> {
+    let c_name = CString::new(name).ok()?;
+    let mut buf = vec![0 as libc::c_char; 4096];
+    let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut result: *mut libc::passwd = std::ptr::null_mut();
+    let ret = unsafe { libc::getpwnam_r(c_name.as_ptr(), &mut pwd, buf.as_mut_ptr(), buf.len(), &mut result) };
+    if ret != 0 || result.is_null() {
+        return None;
+    }
+    Some((pwd.pw_uid, pwd.pw_gid))
+}
+
+/// group データベースからグループ名の gid を引く
+pub fn group_by_name(name: &str) -> Option<u32> {
+    let c_name = CString::new(name).ok()?;
+    let mut buf = vec![0 as libc::c_char; 4096];
+    let mut grp: libc::group = unsafe { std::mem::zeroed() };
+    let mut result: *mut libc::group = std::ptr::null_mut();
+    let ret = unsafe { libc::getgrnam_r(c_name.as_ptr(), &mut grp, buf.as_mut_ptr(), buf.len(), &mut result) };
+    if ret != 0 || result.is_null() {
+        return None;
+    }
+    Some(grp.gr_gid)
+}
+
+/// ローカル時刻に分解した日時
+pub struct LocalTime {
+    pub year: i32,
+    /// 1〜12
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+    /// UTC からのオフセット（秒）
+    pub utc_offset: i64,
+}
+
+/// UNIX時刻（秒）をローカル時刻に変換する
+pub fn local_time(secs: i64) -> LocalTime {
+    let t = secs as libc::time_t;
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    unsafe {
+        libc::localtime_r(&t, &mut tm);
+    }
+    LocalTime {
+        year: tm.tm_year + 1900,
+        month: (tm.tm_mon + 1) as u32,
+        day: tm.tm_mday as u32,
+        hour: tm.tm_hour as u32,
+        minute: tm.tm_min as u32,
+        second: tm.tm_sec as u32,
+        utc_offset: tm.tm_gmtoff,
+    }
+}
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// `ls -l` 形式の日時。半年以内なら "Jan  5 12:34"、それより前（や未来）なら "Jan  5  2023"
+pub fn ls_time(secs: i64) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    const SIX_MONTHS: i64 = 31_556_952 / 2;
+
+    let t = local_time(secs);
+    let month = MONTHS[(t.month - 1) as usize];
+    if secs > now - SIX_MONTHS && secs <= now + 60 {
+        format!("{} {:>2} {:02}:{:02}", month, t.day, t.hour, t.minute)
+    } else {
+        format!("{} {:>2}  {}", month, t.day, t.year)
+    }
+}
@@ -0,0 +1,226 @@
+//! よく移動するディレクトリの記録（z・zi 用）
+//!
+//! `cd`（pushd・popd・z も含む）で移動したディレクトリを、移動した回数（rank）と
+//! 最後に移動した日時とともに記録し、その2つから求めた frecency（frequency + recency）の
+//! 高い順に候補を返す。zoxide と同じく:
+//! - スコアは rank に、最後の移動が1時間以内なら 4、1日以内なら 2、1週間以内なら 1/2、
+//!   それより前なら 1/4 を掛けたもの
+//! - rank の合計が上限（設定ファイルの `frecency_max_age`）を超えたら全体を縮め、
+//!   1 未満になったディレクトリは忘れる（aging）
+//! - 除外パターン（`frecency_exclude`、既定はホームディレクトリ）に一致するものは記録しない
+//! - 検索のときに存在しなくなったディレクトリを見つけたら記録から取り除く
+//!
+//! 保存場所は `$XDG_DATA_HOME/taminal/dirs`（未設定なら `~/.local/share/taminal/dirs`）。
+//! 1行に1つ `rank<TAB>最後に移動した日時（UNIX秒）<TAB>パス` の形式で書く。
+
+use crate::config::Config;
+use crate::glob::Glob;
+use std::env;
+use std::ffi::OsStr;
+use std::fs;
+use std::io::{self, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const HOUR: i64 = 60 * 60;
+const DAY: i64 = 24 * HOUR;
+const WEEK: i64 = 7 * DAY;
+
+/// 記録した1つのディレクトリ
+#[derive(Debug, Clone, PartialEq)]
+pub struct DirEntry {
+    pub path: PathBuf,
+    /// 移動した回数（aging で縮む）
+    pub rank: f64,
+    /// 最後に移動した日時（UNIX秒）
+    pub last_access: i64,
+}
+
+impl DirEntry {
+    /// 現在時刻 `now` での frecency
+    pub fn score(&self, now: i64) -> f64 {
+        let age = now - self.last_access;
+        if age < HOUR {
+            self.rank * 4.0
+        } else if age < DAY {
+            self.rank * 2.0
+        } else if age < WEEK {
+            self.rank / 2.0
+        } else {
+            self.rank / 4.0
+        }
+    }
+}
+
+/// 記録の保存場所と設定
+#[derive(Debug, Clone)]
+pub struct Frecency {
+    path: PathBuf,
+    max_age: f64,
+    excludes: Vec<Glob>,
+}
+
+impl Frecency {
+    /// 設定に従って記録を用意する。`frecency = false` なら None
+    pub fn open(config: &Config) -> Option<Frecency> {
+        if !config.frecency {
+            return None;
+        }
+        let home = env::var_os("HOME").map(PathBuf::from);
+        let data_home = match env::var_os("XDG_DATA_HOME").filter(|dir| !dir.is_empty()) {
+            Some(dir) => PathBuf::from(dir),
+            None => home.as_ref()?.join(".local").join("share"),
+        };
+        // 先頭の `~` はホームディレクトリ
+        let excludes = config
+            .frecency_exclude
+            .iter()
+            .filter_map(|pattern| match (pattern.strip_prefix('~'), &home) {
+                (Some(rest), Some(home)) => Glob::new_path(&format!("{}{}", home.display(), rest)).ok(),
+                _ => Glob::new_path(pattern).ok(),
+            })
+            .collect();
+        Some(Frecency {
+            path: data_home.join("taminal").join("dirs"),
+            max_age: config.frecency_max_age as f64,
+            excludes,
+        })
+    }
+
+    /// 記録したディレクトリ（順序は不定）
+    pub fn entries(&self) -> Vec<DirEntry> {
+        match fs::read(&self.path) {
+            Ok(text) => text.split(|&b| b == b'\n').filter_map(parse_line).collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// `dir` に移動したことを記録する。除外パターンに一致すれば何もしない
+    pub fn add(&self, dir: &Path) -> io::Result<()> {
+        if self.excludes.iter().any(|glob| glob.matches(dir.as_os_str())) {
+            return Ok(());
+        }
+        // 改行を含むパスは1行に書けないので記録しない
+        if dir.as_os_str().as_bytes().contains(&b'\n') {
+            return Ok(());
+        }
+        let now = now();
+        let mut entries = self.entries();
+        match entries.iter_mut().find(|entry| entry.path == dir) {
+            Some(entry) => {
+                entry.rank += 1.0;
+                entry.last_access = now;
+            }
+            None => entries.push(DirEntry { path: dir.to_path_buf(), rank: 1.0, last_access: now }),
+        }
+        self.age(&mut entries);
+        self.write(&entries)
+    }
+
+    /// `dir` の記録を取り除く。記録になければ false
+    pub fn remove(&self, dir: &Path) -> io::Result<bool> {
+        let mut entries = self.entries();
+        let count = entries.len();
+        entries.retain(|entry| entry.path != dir);
+        if entries.len() == count {
+            return Ok(false);
+        }
+        self.write(&entries).map(|_| true)
+    }
+
+    /// `keywords` に一致するディレクトリを frecency の高い順に返す（`exclude` は除く）。
+    /// `exists` で存在しないとわかったディレクトリは記録から取り除く
+    pub fn query(&self, keywords: &[String], exclude: Option<&Path>, exists: &dyn Fn(&Path) -> bool) -> Vec<DirEntry> {
+        let mut entries = self.entries();
+        let count = entries.len();
+        entries.retain(|entry| exists(&entry.path));
+        if entries.len() != count {
+            let _ = self.write(&entries);
+        }
+
+        let now = now();
+        let mut matched: Vec<DirEntry> = entries
+            .into_iter()
+            .filter(|entry| Some(entry.path.as_path()) != exclude && matches(&entry.path, keywords))
+            .collect();
+        matched.sort_by(|a, b| b.score(now).total_cmp(&a.score(now)).then_with(|| a.path.cmp(&b.path)));
+        matched
+    }
+
+    /// 存在しないディレクトリをすべて記録から取り除き、取り除いた数を返す
+    pub fn clean(&self, exists: &dyn Fn(&Path) -> bool) -> io::Result<usize> {
+        let mut entries = self.entries();
+        let count = entries.len();
+        entries.retain(|entry| exists(&entry.path));
+        self.write(&entries)?;
+        Ok(count - entries.len())
+    }
+
+    /// rank の合計が上限を超えていれば全体を縮め、1 未満になったものを忘れる
+    fn age(&self, entries: &mut Vec<DirEntry>) {
+        let total: f64 = entries.iter().map(|entry| entry.rank).sum();
+        if total <= self.max_age {
+            return;
+        }
+        let factor = 0.9 * self.max_age / total;
+        for entry in entries.iter_mut() {
+            entry.rank *= factor;
+        }
+        entries.retain(|entry| entry.rank >= 1.0);
+    }
+
+    /// 記録を書き出す（一時ファイルに書いてから置き換える）
+    fn write(&self, entries: &[DirEntry]) -> io::Result<()> {
+        let dir = self.path.parent().unwrap_or(Path::new("."));
+        fs::create_dir_all(dir)?;
+        let temp = dir.join(format!("dirs.tmp-{}", std::process::id()));
+        let mut file = fs::File::create(&temp)?;
+        for entry in entries {
+            file.write_all(format!("{}\t{}\t", entry.rank, entry.last_access).as_bytes())?;
+            file.write_all(entry.path.as_os_str().as_bytes())?;
+            file.write_all(b"\n")?;
+        }
+        file.sync_all()?;
+        fs::rename(&temp, &self.path)
+    }
+}
+
+/// `path` がすべてのキーワードに一致するか。キーワードは大文字・小文字を区別せず、
+/// この順にパスの中に現れ、最後のキーワードは最後の要素（ディレクトリ名）の中にある必要がある
+/// （`z foo bar` は `/x/foo/y/bar` に一致し、`/x/bar/foo` には一致しない）
+pub fn matches(path: &Path, keywords: &[String]) -> bool {
+    let path = path.to_string_lossy().to_lowercase();
+    let mut rest = path.as_str();
+    for keyword in keywords {
+        let keyword = keyword.to_lowercase();
+        match rest.find(&keyword) {
+            Some(pos) => rest = &rest[pos + keyword.len()..],
+            None => return false,
+        }
+    }
+    // 末尾が `/` のキーワードは途中の要素にだけ一致してもよい
+    match keywords.last() {
+        Some(last) if !last.ends_with('/') => {
+            let last = last.to_lowercase();
+            let name = path.rsplit('/').next().unwrap_or("");
+            name.contains(last.rsplit('/').next().unwrap_or(""))
+        }
+        _ => true,
+    }
+}
+
+fn parse_line(line: &[u8]) -> Option<DirEntry> {
+    let mut fields = line.splitn(3, |&b| b == b'\t');
+    let rank = std::str::from_utf8(fields.next()?).ok()?.parse().ok()?;
+    let last_access = std::str::from_utf8(fields.next()?).ok()?.parse().ok()?;
+    let path = PathBuf::from(OsStr::from_bytes(fields.next().filter(|path| !path.is_empty())?));
+    Some(DirEntry { path, rank, last_access })
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
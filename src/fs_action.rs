@@ -0,0 +1,127 @@
+//! ファイルシステムを変更する操作
+//!
+//! ファイルを変更するビルトインは、削除・作成・移動などを直接行わず
+//! `FsAction` として `Context::perform` に渡す。ドライラン（`set -n` または `--dry-run`）では
+//! 実行せずに予定の操作を "would remove 'a.txt'" のように表示する。
+//! 内容のコピーやゴミ箱への移動のように結果を使う操作は、
+//! `Context::preview` でドライランかどうかを確かめてから各コマンドが実行する。
+
+use crate::fs_util::FileTime;
+use crate::quoting;
+use crate::trash::{self, TrashedItem};
+use crate::vfs::Fs;
+use std::io;
+use std::path::Path;
+
+/// 1つの変更。パスはカレントディレクトリ基準に解決済みのもの
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FsAction<'a> {
+    /// ファイル（ディレクトリ以外）を削除する
+    RemoveFile(&'a Path),
+    /// 空のディレクトリを削除する
+    RemoveDir(&'a Path),
+    /// ディレクトリを作る。`mode` の指定があれば umask に関係なくそのモードにする
+    CreateDir { path: &'a Path, mode: Option<u32> },
+    /// 名前を変更する（移動する）
+    Rename { from: &'a Path, to: &'a Path },
+    /// ファイルをコピーする
+    Copy { from: &'a Path, to: &'a Path },
+    /// 空のファイルを作る
+    CreateFile(&'a Path),
+    /// シンボリックリンク `path` を作る
+    Symlink { target: &'a Path, path: &'a Path },
+    /// ハードリンク `path` を作る
+    HardLink { target: &'a Path, path: &'a Path },
+    /// パーミッションを変える
+    SetMode { path: &'a Path, mode: u32 },
+    /// 所有者・グループを変える（`None` の項目は変えない）
+    SetOwner { path: &'a Path, uid: Option<u32>, gid: Option<u32>, follow_symlinks: bool },
+    /// アクセス日時・更新日時を変える（`None` の項目は変えない）
+    SetTimes { path: &'a Path, atime: Option<FileTime>, mtime: Option<FileTime>, follow_symlinks: bool },
+    /// ゴミ箱へ移す
+    Trash(&'a Path),
+    /// ゴミ箱の項目を元の場所へ戻す
+    RestoreTrash(&'a TrashedItem),
+    /// ゴミ箱の項目を完全に削除する
+    PurgeTrash(&'a TrashedItem),
+}
+
+impl FsAction<'_> {
+    /// `fs` 上で操作を実行する。ゴミ箱の操作は実際のファイルシステムでのみ行える
+    pub fn execute(&self, fs: &dyn Fs) -> io::Result<()> {
+        let is_trash = matches!(self, FsAction::Trash(_) | FsAction::RestoreTrash(_) | FsAction::PurgeTrash(_));
+        if is_trash && !fs.is_native() {
+            return Err(io::Error::from_raw_os_error(libc::EOPNOTSUPP));
+        }
+        match *self {
+            FsAction::RemoveFile(path) => fs.remove_file(path),
+            FsAction::RemoveDir(path) => fs.remove_dir(path),
+            FsAction::CreateDir { path, mode } => {
+                fs.create_dir(path, 0o777)?;
+                match mode {
+                    Some(mode) => fs.set_permissions(path, mode),
+                    None => Ok(()),
+                }
+            }
+            FsAction::Rename { from, to } => fs.rename(from, to),
+            FsAction::Copy { from, to } => {
+                let mode = fs.metadata(from)?.mode() & 0o777;
+                io::copy(&mut fs.open(from)?, &mut fs.create(to, mode)?).map(|_| ())
+            }
+            FsAction::CreateFile(path) => fs.create(path, 0o666).map(|_| ()),
+            FsAction::Symlink { target, path } => fs.symlink(target, path),
+            FsAction::HardLink { target, path } => fs.hard_link(target, path),
+            FsAction::SetMode { path, mode } => fs.set_permissions(path, mode),
+            FsAction::SetOwner { path, uid, gid, follow_symlinks } => fs.set_owner(path, uid, gid, follow_symlinks),
+            FsAction::SetTimes { path, atime, mtime, follow_symlinks } => fs.set_times(path, atime, mtime, follow_symlinks),
+            FsAction::Trash(path) => trash::trash(path).map(|_| ()),
+            FsAction::RestoreTrash(item) => trash::restore(item),
+            FsAction::PurgeTrash(item) => trash::purge(item),
+        }
+    }
+
+    /// ドライランで表示する説明（"remove 'a.txt'" など）。
+    /// `cwd` 以下のパスは相対パスで表示する
+    pub fn describe(&self, cwd: &Path) -> String {
+        let name = |path: &Path| {
+            let shown = path.strip_prefix(cwd).ok().filter(|p| !p.as_os_str().is_empty()).unwrap_or(path);
+            quoting::display(shown.as_os_str())
+        };
+        match *self {
+            FsAction::RemoveFile(path) => format!("remove '{}'", name(path)),
+            FsAction::RemoveDir(path) => format!("remove directory '{}'", name(path)),
+            FsAction::CreateDir { path, mode: None } => format!("create directory '{}'", name(path)),
+            FsAction::CreateDir { path, mode: Some(mode) } => {
+                format!("create directory '{}' with mode {:04o}", name(path), mode)
+            }
+            FsAction::Rename { from, to } => format!("rename '{}' -> '{}'", name(from), name(to)),
+            FsAction::Copy { from, to } => format!("copy '{}' -> '{}'", name(from), name(to)),
+            FsAction::CreateFile(path) => format!("create file '{}'", name(path)),
+            FsAction::Symlink { target, path } => {
+                format!("create symbolic link '{}' -> '{}'", name(path), quoting::display(target.as_os_str()))
+            }
+            FsAction::HardLink { target, path } => format!("create hard link '{}' => '{}'", name(path), name(target)),
+            FsAction::SetMode { path, mode } => format!("change mode of '{}' to {:04o}", name(path), mode),
+            FsAction::SetOwner { path, uid, gid, .. } => {
+                let owner = match (uid, gid) {
+                    (Some(uid), Some(gid)) => format!("{}:{}", uid, gid),
+                    (Some(uid), None) => uid.to_string(),
+                    (None, Some(gid)) => format!(":{}", gid),
+                    (None, None) => String::new(),
+                };
+                format!("change ownership of '{}' to {}", name(path), owner)
+            }
+            FsAction::SetTimes { path, atime, mtime, .. } => {
+                let which = match (atime.is_some(), mtime.is_some()) {
+                    (true, false) => "access time",
+                    (false, true) => "modification time",
+                    _ => "timestamps",
+                };
+                format!("set {} of '{}'", which, name(path))
+            }
+            FsAction::Trash(path) => format!("move '{}' to trash", name(path)),
+            FsAction::RestoreTrash(item) => format!("restore '{}' from trash", name(&item.original)),
+            FsAction::PurgeTrash(item) => format!("permanently remove '{}' from trash", name(&item.original)),
+        }
+    }
+}
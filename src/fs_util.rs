@@ -0,0 +1,122 @@
+//! 標準ライブラリにないファイル操作（libc の薄いラッパー）
+
+use std::ffi::{CStr, CString, OsStr};
+use std::io;
+use std::os::fd::RawFd;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+/// パスを C 文字列に変換する
+pub fn c_path(path: &Path) -> io::Result<CString> {
+    CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "path contains a NUL byte"))
+}
+
+/// タイムスタンプの指定（UNIX秒とナノ秒）。`None` の項目は変更しない
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileTime {
+    pub secs: i64,
+    pub nanos: i64,
+}
+
+/// アクセス日時・更新日時を設定する。
+/// `follow_symlinks` が false ならシンボリックリンク自体の日時を変える
+pub fn set_times(
+    path: &Path,
+    atime: Option<FileTime>,
+    mtime: Option<FileTime>,
+    follow_symlinks: bool,
+) -> io::Result<()> {
+    let c_path = c_path(path)?;
+    set_times_at(libc::AT_FDCWD, &c_path, atime, mtime, follow_symlinks)
+}
+
+/// ディレクトリ `dir`（fd）の中の `name` のアクセス日時・更新日時を変える。`set_times` の *at 版
+pub fn set_times_at(
+    dir: RawFd,
+    name: &CStr,
+    atime: Option<FileTime>,
+    mtime: Option<FileTime>,
+    follow_symlinks: bool,
+) -> io::Result<()> {
+    let to_timespec = |t: Option<FileTime>| match t {
+        Some(t) => libc::timespec {
+            tv_sec: t.secs as libc::time_t,
+            tv_nsec: t.nanos as _,
+        },
+        None => libc::timespec {
+            tv_sec: 0,
+            tv_nsec: libc::UTIME_OMIT,
+        },
+    };
+    let times = [to_timespec(atime), to_timespec(mtime)];
+    let flags = if follow_symlinks { 0 } else { libc::AT_SYMLINK_NOFOLLOW };
+
+    let ret = unsafe { libc::utimensat(dir, name.as_ptr(), times.as_ptr(), flags) };
+    if ret == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// 名前付きパイプやデバイスファイルを作る
+pub fn mknod(path: &Path, mode: u32, dev: u64) -> io::Result<()> {
+    let c_path = c_path(path)?;
+    let ret = unsafe { libc::mknod(c_path.as_ptr(), mode as libc::mode_t, dev as libc::dev_t) };
+    if ret == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// プロセスの umask を返す。
+/// 他のスレッドに影響しないよう、可能なら /proc から読む（変更せずに読めるため）
+pub fn umask() -> u32 {
+    let from_proc = std::fs::read_to_string("/proc/self/status").ok().and_then(|status| {
+        status
+            .lines()
+            .find_map(|line| line.strip_prefix("Umask:"))
+            .and_then(|value| u32::from_str_radix(value.trim(), 8).ok())
+    });
+    from_proc.unwrap_or_else(|| {
+        let mask = unsafe { libc::umask(0o022) };
+        unsafe { libc::umask(mask) };
+        mask as u32
+    })
+}
+
+/// パスを URL 形式でエスケープする（英数字と `-_.~/` 以外を %XX に）。
+/// 空白や改行を含まないので、行・空白区切りのファイルにそのまま書ける
+pub fn encode_path(path: &OsStr) -> String {
+    let mut out = String::new();
+    for &b in path.as_bytes() {
+        if b.is_ascii_alphanumeric() || b"-_.~/".contains(&b) {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{:02X}", b));
+        }
+    }
+    out
+}
+
+/// `encode_path` のエスケープを戻す
+pub fn decode_path(text: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(text.len());
+    let mut i = 0;
+    while i < text.len() {
+        let hex = text.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok()).and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (text[i], hex) {
+            (b'%', Some(value)) => {
+                out.push(value);
+                i += 3;
+            }
+            (b, _) => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    out
+}
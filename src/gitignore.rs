@@ -0,0 +1,140 @@
+//! .gitignore の解釈
+//!
+//! ディレクトリをたどるコマンド（grep -r など）が、Git の管理から外されたファイルを
+//! 読み飛ばすのに使う。対応する書式:
+//! - `#` で始まる行はコメント、空行は無視する
+//! - `!` で始まる行は除外の取り消し
+//! - 末尾が `/` のパターンはディレクトリだけに一致する
+//! - 途中に `/` を含むパターンは .gitignore のあるディレクトリからの相対パスと、
+//!   含まないパターンはどの階層の名前とも照合する
+//! - `*` `?` `[...]` と `**`（`crate::glob`）
+//!
+//! リポジトリ直下（`.git` のあるディレクトリ）から調べるディレクトリまでの
+//! 各階層の .gitignore を重ね、深い階層のものを優先する。
+
+use crate::glob::Glob;
+use crate::vfs::Fs;
+use std::ffi::OsStr;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// .gitignore の1行
+struct Rule {
+    glob: Glob,
+    /// `!` で始まる（除外を取り消す）
+    negated: bool,
+    /// 末尾が `/`（ディレクトリだけに一致する）
+    dir_only: bool,
+    /// 途中に `/` を含む（.gitignore のあるディレクトリからの相対パスで照合する）
+    anchored: bool,
+}
+
+/// 1つの .gitignore の内容
+#[derive(Default)]
+pub struct Gitignore {
+    rules: Vec<Rule>,
+}
+
+impl Gitignore {
+    /// .gitignore の内容を解釈する（解釈できない行は無視する）
+    pub fn parse(text: &str) -> Gitignore {
+        let mut rules = Vec::new();
+        for line in text.lines() {
+            // 末尾の空白は `\ ` でエスケープしていなければ無視する
+            let mut line = line.trim_end_matches('\r');
+            while line.ends_with(' ') && !line.ends_with("\\ ") {
+                line = &line[..line.len() - 1];
+            }
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (negated, line) = match line.strip_prefix('!') {
+                Some(rest) => (true, rest),
+                None => (false, line.strip_prefix('\\').filter(|rest| rest.starts_with(['#', '!'])).unwrap_or(line)),
+            };
+            let (dir_only, line) = match line.strip_suffix('/') {
+                Some(rest) => (true, rest),
+                None => (false, line),
+            };
+            let anchored = line.contains('/');
+            let pattern = line.strip_prefix('/').unwrap_or(line);
+            if pattern.is_empty() {
+                continue;
+            }
+            if let Ok(glob) = Glob::new_path(pattern) {
+                rules.push(Rule { glob, negated, dir_only, anchored });
+            }
+        }
+        Gitignore { rules }
+    }
+
+    /// `dir` の .gitignore を読む。なければ空のものを返す
+    pub fn load(fs: &dyn Fs, dir: &Path) -> Gitignore {
+        let mut text = String::new();
+        match fs.open(&dir.join(".gitignore")).and_then(|mut file| file.read_to_string(&mut text)) {
+            Ok(_) => Gitignore::parse(&text),
+            Err(_) => Gitignore::default(),
+        }
+    }
+
+    /// .gitignore のあるディレクトリからの相対パス `rel` が一致するか。
+    /// 除外なら Some(true)、除外の取り消しなら Some(false)、どの行にも一致しなければ None
+    fn matched(&self, rel: &Path, is_dir: bool) -> Option<bool> {
+        let name = rel.file_name().unwrap_or(rel.as_os_str());
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| {
+                (is_dir || !rule.dir_only)
+                    && if rule.anchored { rule.glob.matches(rel.as_os_str()) } else { rule.glob.matches(name) }
+            })
+            .map(|rule| !rule.negated)
+    }
+}
+
+/// たどっている途中のディレクトリごとに積み重ねた .gitignore
+pub struct Ignores {
+    /// (ディレクトリ, その .gitignore)。浅い順
+    levels: Vec<(PathBuf, Gitignore)>,
+}
+
+impl Ignores {
+    /// `dir` より上にあるリポジトリ内の .gitignore を読み込む（`dir` 自身のものは `enter` で読む）。
+    /// リポジトリの外なら何も読まない
+    pub fn above(fs: &dyn Fs, dir: &Path) -> Ignores {
+        let mut levels = Vec::new();
+        if fs.exists(&dir.join(".git")) {
+            return Ignores { levels };
+        }
+        let ancestors: Vec<&Path> = dir.ancestors().skip(1).collect();
+        if let Some(root) = ancestors.iter().position(|dir| fs.exists(&dir.join(".git"))) {
+            for dir in ancestors[..=root].iter().rev() {
+                levels.push((dir.to_path_buf(), Gitignore::load(fs, dir)));
+            }
+        }
+        Ignores { levels }
+    }
+
+    /// ディレクトリに入る（その .gitignore を読み込む）。出るときは `leave` を呼ぶ
+    pub fn enter(&mut self, fs: &dyn Fs, dir: &Path) {
+        self.levels.push((dir.to_path_buf(), Gitignore::load(fs, dir)));
+    }
+
+    /// 直前の `enter` を取り消す
+    pub fn leave(&mut self) {
+        self.levels.pop();
+    }
+
+    /// `path` が除外されているか。`.git` ディレクトリは常に除外する
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        if is_dir && path.file_name() == Some(OsStr::new(".git")) {
+            return true;
+        }
+        self.levels
+            .iter()
+            .rev()
+            .filter_map(|(dir, ignore)| ignore.matched(path.strip_prefix(dir).ok()?, is_dir))
+            .next()
+            .unwrap_or(false)
+    }
+}
@@ -0,0 +1,338 @@
+//! シェル形式のワイルドカード（glob）によるファイル名の照合
+//!
+//! `*`（任意の文字列）、`?`（任意の1文字）、`[abc]` / `[a-z]` / `[!a]` / `[[:alpha:]]`
+//! （文字クラス）と `\` によるエスケープを解釈する。
+//! grep の `--include` や .gitignore、find の `-name` / `-path` などで共通に使う。
+//!
+//! 名前はバイト列として照合する。UTF-8 として正しい部分は1文字ずつ、
+//! 不正なバイトは1バイトを1文字として扱うので、UTF-8 以外の名前も照合できる。
+
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+
+/// 不正な UTF-8 のバイトを表す文字コード（Unicode の範囲外に置く）
+pub(crate) const RAW_BYTE: u32 = 0x11_0000;
+
+/// パターンの構成要素
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Char(u32),
+    /// `?`
+    Any,
+    /// `*`
+    Star,
+    /// `**/`（0個以上のディレクトリ。`/` 区切りを区別するときだけ）
+    AnyDirs,
+    /// 末尾の `/**`（その中のすべて。`/` 区切りを区別するときだけ）
+    AnyPath,
+    Class { negated: bool, items: Vec<ClassItem> },
+}
+
+/// 文字クラス `[...]` の要素
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ClassItem {
+    Range(u32, u32),
+    Named(NamedClass),
+}
+
+/// `[:alpha:]` などの名前付き文字クラス（tr の集合でも使う）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum NamedClass {
+    Alnum,
+    Alpha,
+    Blank,
+    Cntrl,
+    Digit,
+    Graph,
+    Lower,
+    Print,
+    Punct,
+    Space,
+    Upper,
+    Xdigit,
+}
+
+impl NamedClass {
+    pub(crate) fn parse(name: &str) -> Option<NamedClass> {
+        Some(match name {
+            "alnum" => NamedClass::Alnum,
+            "alpha" => NamedClass::Alpha,
+            "blank" => NamedClass::Blank,
+            "cntrl" => NamedClass::Cntrl,
+            "digit" => NamedClass::Digit,
+            "graph" => NamedClass::Graph,
+            "lower" => NamedClass::Lower,
+            "print" => NamedClass::Print,
+            "punct" => NamedClass::Punct,
+            "space" => NamedClass::Space,
+            "upper" => NamedClass::Upper,
+            "xdigit" => NamedClass::Xdigit,
+            _ => return None,
+        })
+    }
+
+    pub(crate) fn contains(self, c: char) -> bool {
+        match self {
+            NamedClass::Alnum => c.is_alphanumeric(),
+            NamedClass::Alpha => c.is_alphabetic(),
+            NamedClass::Blank => c == ' ' || c == '\t',
+            NamedClass::Cntrl => c.is_control(),
+            NamedClass::Digit => c.is_ascii_digit(),
+            NamedClass::Graph => !c.is_control() && !c.is_whitespace(),
+            NamedClass::Lower => c.is_lowercase(),
+            NamedClass::Print => !c.is_control(),
+            NamedClass::Punct => c.is_ascii_punctuation(),
+            NamedClass::Space => c.is_whitespace(),
+            NamedClass::Upper => c.is_uppercase(),
+            NamedClass::Xdigit => c.is_ascii_hexdigit(),
+        }
+    }
+}
+
+/// 解析済みのワイルドカードパターン
+#[derive(Debug, Clone)]
+pub struct Glob {
+    tokens: Vec<Token>,
+    case_insensitive: bool,
+    literal_separator: bool,
+}
+
+impl Glob {
+    /// パターンを解析する。`*` と `?` は `/` にも一致する（find -name / -path と同じ）
+    pub fn new(pattern: &str) -> Result<Glob, String> {
+        Glob::build(pattern, false, false)
+    }
+
+    /// 大文字・小文字を区別しないパターン（find -iname など）
+    pub fn new_case_insensitive(pattern: &str) -> Result<Glob, String> {
+        Glob::build(pattern, true, false)
+    }
+
+    /// パス用のパターン。`*` と `?` は `/` に一致せず、`**` で複数階層に一致する（.gitignore と同じ）
+    pub fn new_path(pattern: &str) -> Result<Glob, String> {
+        Glob::build(pattern, false, true)
+    }
+
+    fn build(pattern: &str, case_insensitive: bool, literal_separator: bool) -> Result<Glob, String> {
+        let chars: Vec<char> = pattern.chars().collect();
+        let mut tokens = Vec::new();
+        let mut i = 0;
+        while i < chars.len() {
+            match chars[i] {
+                '\\' => {
+                    let Some(&c) = chars.get(i + 1) else {
+                        return Err(format!("trailing backslash in '{}'", pattern));
+                    };
+                    tokens.push(Token::Char(c as u32));
+                    i += 2;
+                    continue;
+                }
+                '?' => tokens.push(Token::Any),
+                '*' if literal_separator && chars.get(i + 1) == Some(&'*') => {
+                    // `**` は単独の要素（前後が `/` か端）のときだけ特別な意味を持つ
+                    let starts_component = i == 0 || chars[i - 1] == '/';
+                    match chars.get(i + 2) {
+                        Some('/') if starts_component => {
+                            tokens.push(Token::AnyDirs);
+                            i += 3;
+                            continue;
+                        }
+                        None if starts_component => {
+                            // 末尾の `/**` は手前の `/` を含めて「その中のすべて」
+                            if tokens.last() == Some(&Token::Char('/' as u32)) {
+                                tokens.pop();
+                                tokens.push(Token::AnyPath);
+                            } else {
+                                tokens.push(Token::Star);
+                                tokens.push(Token::AnyDirs);
+                                tokens.push(Token::Star);
+                            }
+                            i += 2;
+                            continue;
+                        }
+                        _ => {
+                            tokens.push(Token::Star);
+                            i += 2;
+                            continue;
+                        }
+                    }
+                }
+                '*' => {
+                    if tokens.last() != Some(&Token::Star) {
+                        tokens.push(Token::Star);
+                    }
+                }
+                '[' => match parse_class(&chars, i) {
+                    Some((token, next)) => {
+                        tokens.push(token);
+                        i = next;
+                        continue;
+                    }
+                    // 閉じていない `[` は文字そのもの
+                    None => tokens.push(Token::Char('[' as u32)),
+                },
+                c => tokens.push(Token::Char(c as u32)),
+            }
+            i += 1;
+        }
+        Ok(Glob { tokens, case_insensitive, literal_separator })
+    }
+
+    /// 名前（またはパス）全体がパターンに一致するか
+    pub fn matches(&self, name: &OsStr) -> bool {
+        let text = units(name.as_bytes());
+        // 失敗した (パターン位置, 名前の位置) を覚えて、`*` が多いときの組み合わせ爆発を防ぐ
+        let mut failed = vec![false; (self.tokens.len() + 1) * (text.len() + 1)];
+        self.match_at(0, &text, 0, &mut failed)
+    }
+
+    fn match_at(&self, p: usize, text: &[u32], t: usize, failed: &mut [bool]) -> bool {
+        let key = p * (text.len() + 1) + t;
+        if failed[key] {
+            return false;
+        }
+        let matched = match self.tokens.get(p) {
+            None => t == text.len(),
+            Some(Token::Star) => {
+                let mut end = t;
+                loop {
+                    if self.match_at(p + 1, text, end, failed) {
+                        break true;
+                    }
+                    if end == text.len() || self.literal_separator && text[end] == '/' as u32 {
+                        break false;
+                    }
+                    end += 1;
+                }
+            }
+            Some(Token::AnyDirs) => {
+                // 0個以上の「名前/」に一致する
+                let mut end = t;
+                loop {
+                    if self.match_at(p + 1, text, end, failed) {
+                        break true;
+                    }
+                    match text[end..].iter().position(|&c| c == '/' as u32) {
+                        Some(slash) => end += slash + 1,
+                        None => break false,
+                    }
+                }
+            }
+            Some(Token::AnyPath) => t < text.len() && text[t] == '/' as u32,
+            Some(token) => {
+                t < text.len()
+                    && !(self.literal_separator && text[t] == '/' as u32 && *token != Token::Char('/' as u32))
+                    && self.token_matches(token, text[t])
+                    && self.match_at(p + 1, text, t + 1, failed)
+            }
+        };
+        if !matched {
+            failed[key] = true;
+        }
+        matched
+    }
+
+    /// 1文字分の要素が文字 `c` に一致するか
+    fn token_matches(&self, token: &Token, c: u32) -> bool {
+        match token {
+            Token::Char(expected) => *expected == c || self.case_insensitive && fold(*expected) == fold(c),
+            Token::Any => true,
+            Token::Class { negated, items } => {
+                let hit = |c: u32| items.iter().any(|item| class_contains(item, c));
+                let found = hit(c) || self.case_insensitive && (hit(fold(c)) || hit(upper(c)));
+                found != *negated
+            }
+            Token::Star | Token::AnyDirs | Token::AnyPath => false,
+        }
+    }
+}
+
+/// `chars[start]` の `[` から始まる文字クラスを解析し、要素と `]` の次の位置を返す
+fn parse_class(chars: &[char], start: usize) -> Option<(Token, usize)> {
+    let mut i = start + 1;
+    let negated = matches!(chars.get(i), Some('!') | Some('^'));
+    if negated {
+        i += 1;
+    }
+    let mut items = Vec::new();
+    let mut first = true;
+    loop {
+        let c = *chars.get(i)?;
+        // 先頭の `]` は文字そのもの
+        if c == ']' && !first {
+            return Some((Token::Class { negated, items }, i + 1));
+        }
+        first = false;
+        if c == '[' && chars.get(i + 1) == Some(&':') {
+            let rest: String = chars[i + 2..].iter().collect();
+            if let Some(end) = rest.find(":]") {
+                if let Some(class) = NamedClass::parse(&rest[..end]) {
+                    items.push(ClassItem::Named(class));
+                    i += 2 + rest[..end].chars().count() + 2;
+                    continue;
+                }
+            }
+        }
+        let (low, next) = match c {
+            '\\' => (*chars.get(i + 1)?, i + 2),
+            _ => (c, i + 1),
+        };
+        i = next;
+        // a-z の範囲（末尾の `-` は文字そのもの）
+        if chars.get(i) == Some(&'-') && chars.get(i + 1).is_some_and(|&c| c != ']') {
+            let (high, next) = match chars[i + 1] {
+                '\\' => (*chars.get(i + 2)?, i + 3),
+                c => (c, i + 2),
+            };
+            items.push(ClassItem::Range(low as u32, high as u32));
+            i = next;
+        } else {
+            items.push(ClassItem::Range(low as u32, low as u32));
+        }
+    }
+}
+
+fn class_contains(item: &ClassItem, c: u32) -> bool {
+    match item {
+        ClassItem::Range(low, high) => (*low..=*high).contains(&c),
+        ClassItem::Named(class) => char::from_u32(c).is_some_and(|c| class.contains(c)),
+    }
+}
+
+/// 小文字にした文字コード（1文字に対応するものだけ）
+fn fold(c: u32) -> u32 {
+    match char::from_u32(c) {
+        Some(ch) => {
+            let mut lower = ch.to_lowercase();
+            match (lower.next(), lower.next()) {
+                (Some(l), None) => l as u32,
+                _ => c,
+            }
+        }
+        None => c,
+    }
+}
+
+/// 大文字にした文字コード（1文字に対応するものだけ）
+fn upper(c: u32) -> u32 {
+    match char::from_u32(c) {
+        Some(ch) => {
+            let mut upper = ch.to_uppercase();
+            match (upper.next(), upper.next()) {
+                (Some(u), None) => u as u32,
+                _ => c,
+            }
+        }
+        None => c,
+    }
+}
+
+/// バイト列を照合用の文字コードの列にする（不正な UTF-8 は1バイトずつ `RAW_BYTE + byte`）
+pub(crate) fn units(bytes: &[u8]) -> Vec<u32> {
+    let mut out = Vec::with_capacity(bytes.len());
+    for chunk in bytes.utf8_chunks() {
+        out.extend(chunk.valid().chars().map(|c| c as u32));
+        out.extend(chunk.invalid().iter().map(|&b| RAW_BYTE + b as u32));
+    }
+    out
+}
@@ -1,18 +1,214 @@
+use simple_shell::ansi;
+use simple_shell::columns;
+use simple_shell::commands::{self, help};
+use simple_shell::file_info;
+use simple_shell::config::Config;
+use simple_shell::frecency::Frecency;
+use simple_shell::journal::Journal;
+use simple_shell::context::{Context, Interaction, Progress};
+use simple_shell::trash::{self, TrashedItem};
+use simple_shell::{dir_stack, outln, pipeline, quoting, shell_words};
+use simple_shell::shell_words::ListItem;
+use simple_shell::table::Table;
+use simple_shell::tree::TreeNode;
 use eframe::egui;
+use eframe::egui::text::{LayoutJob, TextFormat};
 use std::collections::VecDeque;
 use std::env;
-use std::fs;
-use std::path::Path;
+use std::ffi::{OsStr, OsString};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// 出力エリアの1行。ANSIエスケープを含んだまま保持し、描画時に解釈する
+struct OutputLine {
+    text: String,
+    /// 標準エラー（またはエラーメッセージ）由来の行か
+    is_error: bool,
+}
+
+/// 出力エリアの1項目
+enum OutputItem {
+    Line(OutputLine),
+    /// du・df などの表（見出しをクリックすると並べ替える）
+    Table(TableView),
+    /// tree の結果（ディレクトリごとに開閉できる）
+    Tree(TreeNode),
+}
+
+/// 出力エリアに表示する表と、その並べ替えの状態
+struct TableView {
+    table: Table,
+    /// 並べ替えに使っている列と降順か
+    sort: Option<(usize, bool)>,
+}
+
+/// ビルトインコマンドが書き込んだ出力の断片
+enum OutputChunk {
+    Text { is_error: bool, bytes: Vec<u8> },
+    /// 表として表示する結果。テキストと同じ経路で送り、出力の順序を保つ
+    Table(Table),
+    Tree(TreeNode),
+}
+
+/// ビルトインコマンドの出力を出力エリアへ送る書き込み先
+struct OutputWriter {
+    tx: Sender<OutputChunk>,
+    is_error: bool,
+}
+
+impl Write for OutputWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let chunk = OutputChunk::Text { is_error: self.is_error, bytes: buf.to_vec() };
+        self.tx
+            .send(chunk)
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "output closed"))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// 実行中のコマンドから画面への依頼
+enum UiRequest {
+    /// はい/いいえの確認。答えを `reply` に送り返す
+    Confirm { question: String, reply: Sender<bool> },
+    /// 候補からの選択。選ばれた位置（取り消しなら None）を `reply` に送り返す
+    Choose { title: String, items: Vec<String>, reply: Sender<Option<usize>> },
+    /// 進捗の表示（None で消す）
+    Progress(Option<Progress>),
+}
+
+/// GUI版のユーザーとのやりとり。確認はダイアログ、進捗は進捗バー、表と木は出力エリアに表示する
+#[derive(Clone)]
+struct GuiInteraction {
+    tx: Sender<UiRequest>,
+    /// 表や木を出力エリアへ送る送り口
+    output: Sender<OutputChunk>,
+    egui_ctx: egui::Context,
+    /// Stop ボタン（Ctrl+C）で立つ中断の要求
+    interrupt: Arc<AtomicBool>,
+}
+
+impl Interaction for GuiInteraction {
+    fn confirm(&mut self, question: &str) -> bool {
+        let (reply, answer) = mpsc::channel();
+        let request = UiRequest::Confirm { question: question.to_string(), reply };
+        if self.tx.send(request).is_err() {
+            return false;
+        }
+        self.egui_ctx.request_repaint();
+        // 画面側が答えるまで待つ（ウィンドウが閉じられたら「いいえ」）
+        answer.recv().unwrap_or(false)
+    }
+
+    fn choose(&mut self, title: &str, items: &[String]) -> Option<usize> {
+        let (reply, answer) = mpsc::channel();
+        let request = UiRequest::Choose { title: title.to_string(), items: items.to_vec(), reply };
+        if self.tx.send(request).is_err() {
+            return None;
+        }
+        self.egui_ctx.request_repaint();
+        // 画面側で選ぶまで待つ（ウィンドウが閉じられたら取り消し）
+        answer.recv().unwrap_or(None)
+    }
+
+    fn progress(&mut self, progress: Option<&Progress>) {
+        let _ = self.tx.send(UiRequest::Progress(progress.cloned()));
+        self.egui_ctx.request_repaint();
+    }
+
+    fn interrupted(&self) -> bool {
+        self.interrupt.load(Ordering::SeqCst)
+    }
+
+    fn table(&mut self, table: &Table) -> bool {
+        self.output.send(OutputChunk::Table(table.clone())).is_ok()
+    }
+
+    fn tree(&mut self, tree: &TreeNode) -> bool {
+        self.output.send(OutputChunk::Tree(tree.clone())).is_ok()
+    }
+
+    fn fork(&self) -> Box<dyn Interaction> {
+        Box::new(self.clone())
+    }
+}
+
+/// 候補から1つを選ぶウィンドウ（zi）の状態
+struct Picker {
+    title: String,
+    items: Vec<String>,
+    reply: Sender<Option<usize>>,
+    /// 候補を絞り込む文字列（空白区切りのキーワードをすべて含むものだけを表示する）
+    filter: String,
+    /// 絞り込んだ候補の中で選んでいる位置
+    selected: usize,
+}
+
+impl Picker {
+    /// 絞り込んだ候補の元の位置
+    fn visible(&self) -> Vec<usize> {
+        let keywords: Vec<String> = self.filter.split_whitespace().map(str::to_lowercase).collect();
+        (0..self.items.len())
+            .filter(|&i| {
+                let item = self.items[i].to_lowercase();
+                keywords.iter().all(|keyword| item.contains(keyword.as_str()))
+            })
+            .collect()
+    }
+}
+
+/// エラー行の既定の表示色
+const DEFAULT_ERROR_COLOR: egui::Color32 = egui::Color32::from_rgb(0xff, 0x6e, 0x6e);
 
 /// GUIターミナルアプリケーションの状態を管理する構造体
 pub struct TerminalApp {
     /// コマンド入力フィールドの内容
     input: String,
     /// ターミナル出力の履歴（最大1000行保持）
-    output: VecDeque<String>,
-    /// 現在の作業ディレクトリ
-    current_dir: String,
+    output: VecDeque<OutputItem>,
+    /// エラー行の表示色（画面上部のカラーボタンで変更できる）
+    error_color: egui::Color32,
+    /// ビルトインコマンドの実行環境（現在の作業ディレクトリを含む）。
+    /// コマンドの実行中は実行スレッドに渡しているため None
+    ctx: Option<Context>,
+    /// 現在の作業ディレクトリ（実行中も表示できるよう控えておく）
+    cwd: PathBuf,
+    /// 実行中のビルトインコマンド。終了すると実行環境が返ってくる
+    running: Option<JoinHandle<Context>>,
+    /// ビルトインコマンドの出力の送り口（実行環境を作り直すときに使う）
+    output_tx: Sender<OutputChunk>,
+    /// ビルトインコマンドの出力の受け口
+    output_rx: Receiver<OutputChunk>,
+    /// 実行中のコマンドからの確認・進捗の送り口と受け口
+    ui_tx: Sender<UiRequest>,
+    ui_rx: Receiver<UiRequest>,
+    /// 回答待ちの確認（質問と回答の送り先）
+    confirm: Option<(String, Sender<bool>)>,
+    /// 実行中のコマンドが表示を求めている候補の選択（zi）
+    picker: Option<Picker>,
+    /// 表示中の進捗
+    progress: Option<Progress>,
+    /// 実行中のコマンドへの中断の要求（実行環境の GuiInteraction と共有する）
+    interrupt: Arc<AtomicBool>,
+    /// 直前の rm でゴミ箱へ移した項目（「削除を取り消す」で元に戻す）
+    undo_trash: Vec<TrashedItem>,
+    /// 設定ファイルの内容（実行環境を作るたびに渡す）
+    config: Config,
+    /// サンドボックスのルート（起動時の --root または設定ファイルの root）
+    sandbox: Option<PathBuf>,
+    /// 改行待ちの出力（[標準出力, 標準エラー]）
+    pending: [Vec<u8>; 2],
+    /// プロンプトに表示する文字列（`Context::prompt_label`。実行中も表示するので控えておく）
+    prompt: String,
     /// コマンド履歴
     command_history: Vec<String>,
     /// 履歴のインデックス
@@ -23,291 +219,344 @@ pub struct TerminalApp {
 
 impl Default for TerminalApp {
     fn default() -> Self {
-        let current_dir = env::current_dir()
-            .unwrap_or_else(|_| std::path::PathBuf::from("/"))
-            .to_string_lossy()
-            .to_string();
-        
-        let mut output = VecDeque::new();
-        output.push_back("=== Taminal GUI Terminal ===".to_string());
-        output.push_back("Type 'help' for available commands".to_string());
-        output.push_back("".to_string());
+        let current_dir = env::current_dir().unwrap_or_else(|_| PathBuf::from("/"));
+        let (output_tx, output_rx) = mpsc::channel();
+        let (ui_tx, ui_rx) = mpsc::channel();
         
-        Self {
+        let mut app = Self {
             input: String::new(),
-            output,
-            current_dir,
+            output: VecDeque::new(),
+            error_color: DEFAULT_ERROR_COLOR,
+            ctx: None,
+            cwd: current_dir.clone(),
+            running: None,
+            output_tx,
+            output_rx,
+            ui_tx,
+            ui_rx,
+            confirm: None,
+            picker: None,
+            progress: None,
+            interrupt: Arc::new(AtomicBool::new(false)),
+            undo_trash: Vec::new(),
+            config: Config::default(),
+            sandbox: None,
+            pending: [Vec::new(), Vec::new()],
+            prompt: dir_stack::prompt_label(&current_dir, &[]),
             command_history: Vec::new(),
             history_index: 0,
             auto_scroll: true,
-        }
+        };
+        app.push_line("=== Taminal GUI Terminal ===");
+        app.push_line("Type 'help' for available commands");
+        app.push_line("");
+        app
     }
 }
 
 impl TerminalApp {
     /// 新しいアプリケーションインスタンスを作成
-    pub fn new(_cc: &eframe::CreationContext<'_>) -> Self {
+    pub fn new(cc: &eframe::CreationContext<'_>, root: Option<PathBuf>) -> Self {
         // カスタムフォントの設定（オプション）
-        Self::default()
-    }
-    
-    /// コマンドを実行する
-    fn execute_command(&mut self, command: String) {
-        // コマンドを履歴に追加
-        if !command.is_empty() {
-            self.command_history.push(command.clone());
-            self.history_index = self.command_history.len();
+        let mut app = Self::default();
+        let (config, warnings) = Config::load();
+        for warning in warnings {
+            app.push_error(warning);
         }
-        
-        // プロンプトとコマンドを出力に追加
-        let prompt = format!("{}> {}", self.get_dir_name(), command);
-        self.output.push_back(prompt);
-        
-        // 空白で分割してコマンドと引数を取得
-        let parts: Vec<&str> = command.trim().split_whitespace().collect();
-        if parts.is_empty() {
-            return;
-        }
-        
-        let cmd = parts[0];
-        let args = &parts[1..];
-        
-        // ビルトインコマンドの処理
-        match cmd {
-            "exit" | "quit" => {
-                self.output.push_back("Use the window close button to exit".to_string());
-            }
-            "clear" => {
-                self.output.clear();
-                self.output.push_back("=== Terminal Cleared ===".to_string());
-            }
-            "cd" => {
-                self.change_directory(args);
-            }
-            "pwd" => {
-                self.output.push_back(self.current_dir.clone());
-            }
-            "ls" => {
-                self.list_directory(args);
-            }
-            "mkdir" => {
-                self.make_directory(args);
-            }
-            "rmdir" => {
-                self.remove_directory(args);
-            }
-            "rm" => {
-                self.remove_file(args);
-            }
-            "help" => {
-                self.show_help();
+        app.sandbox = root.or_else(|| config.root.clone());
+        app.config = config;
+        // サンドボックスを作れなければ、制限なしでは実行せずコマンドを受け付けない
+        match app.new_context(app.cwd.clone(), &cc.egui_ctx) {
+            Ok(ctx) => {
+                app.cwd = ctx.cwd.clone();
+                app.prompt = ctx.prompt_label();
+                if let Some(root) = &ctx.sandbox {
+                    app.push_line(format!("Sandbox: file access is limited to {}", root.display()));
+                }
+                app.ctx = Some(ctx);
             }
-            _ => {
-                // 外部コマンドの実行
-                self.execute_external_command(cmd, args);
+            Err(e) => {
+                let message = app.sandbox_error(&e);
+                app.push_error(message);
             }
         }
-        
-        // 出力が1000行を超えたら古いものを削除
-        while self.output.len() > 1000 {
-            self.output.pop_front();
-        }
+        app
     }
     
-    /// ディレクトリを変更
-    fn change_directory(&mut self, args: &[&str]) {
-        let new_dir = if args.is_empty() {
-            env::var("HOME").unwrap_or_else(|_| String::from("/"))
-        } else {
-            args[0].to_string()
-        };
-        
-        let path = if Path::new(&new_dir).is_absolute() {
-            Path::new(&new_dir).to_path_buf()
-        } else {
-            Path::new(&self.current_dir).join(&new_dir)
-        };
-        
-        if path.exists() && path.is_dir() {
-            match path.canonicalize() {
-                Ok(canonical_path) => {
-                    self.current_dir = canonical_path.to_string_lossy().to_string();
-                    self.output.push_back(format!("Changed to: {}", self.current_dir));
-                }
-                Err(e) => {
-                    self.output.push_back(format!("cd: {}: {}", new_dir, e));
-                }
-            }
-        } else {
-            self.output.push_back(format!("cd: {}: No such directory", new_dir));
+    /// 出力エリアと確認ダイアログにつながった実行環境を作る。
+    /// サンドボックスのルートを使えなければエラー
+    fn new_context(&self, cwd: PathBuf, egui_ctx: &egui::Context) -> io::Result<Context> {
+        // GUIはANSIカラーを描画できるため、端末出力と同じ扱いにする
+        let stdout = OutputWriter { tx: self.output_tx.clone(), is_error: false };
+        let stderr = OutputWriter { tx: self.output_tx.clone(), is_error: true };
+        let mut ctx = Context::new(cwd, Box::new(stdout), Box::new(stderr), true);
+        ctx.interaction = Box::new(GuiInteraction {
+            tx: self.ui_tx.clone(),
+            output: self.output_tx.clone(),
+            egui_ctx: egui_ctx.clone(),
+            interrupt: self.interrupt.clone(),
+        });
+        ctx.config = self.config.clone();
+        ctx.journal = Journal::open(&self.config);
+        ctx.frecency = Frecency::open(&self.config);
+        if let Some(root) = &self.sandbox {
+            ctx.enter_sandbox(root)?;
         }
+        Ok(ctx)
     }
     
-    /// ディレクトリの内容を表示
-    fn list_directory(&mut self, args: &[&str]) {
-        let dir = if args.is_empty() {
-            &self.current_dir
-        } else {
-            args[0]
-        };
-        
-        let path = if Path::new(dir).is_absolute() {
-            Path::new(dir).to_path_buf()
-        } else {
-            Path::new(&self.current_dir).join(dir)
-        };
-        
-        match fs::read_dir(&path) {
-            Ok(entries) => {
-                let mut files = Vec::new();
-                for entry in entries.flatten() {
-                    if let Some(name) = entry.file_name().to_str() {
-                        if entry.path().is_dir() {
-                            files.push(format!("{}/", name));
-                        } else {
-                            files.push(name.to_string());
-                        }
-                    }
-                }
-                files.sort();
-                
-                // 複数列で表示
-                let mut line = String::new();
-                for (i, file) in files.iter().enumerate() {
-                    line.push_str(&format!("{:<20}", file));
-                    if (i + 1) % 4 == 0 {
-                        self.output.push_back(line.clone());
-                        line.clear();
-                    }
+    /// サンドボックスのルートを使えないときのメッセージ
+    fn sandbox_error(&self, e: &io::Error) -> String {
+        let root = self.sandbox.as_deref().unwrap_or(Path::new(""));
+        format!("taminal: cannot use '{}' as sandbox root: {}", root.display(), commands::error_message(e))
+    }
+    
+    /// 通常の出力行を追加する
+    fn push_line(&mut self, text: impl Into<String>) {
+        self.output.push_back(OutputItem::Line(OutputLine { text: text.into(), is_error: false }));
+    }
+    
+    /// エラー行を追加する（エラー色で描画される）
+    fn push_error(&mut self, text: impl Into<String>) {
+        self.output.push_back(OutputItem::Line(OutputLine { text: text.into(), is_error: true }));
+    }
+    
+    /// 出力をすべて消去する
+    fn clear_output(&mut self) {
+        self.output.clear();
+        self.push_line("=== Terminal Cleared ===");
+    }
+    
+    /// ビルトインコマンドの出力を受け取り、行単位で出力エリアへ追加する。
+    /// `finish` が true なら改行で終わっていない残りも1行として確定する。
+    /// `tail -f` のように終わらないコマンドでも溜まりすぎないよう、受け取るたびに古い行を削除する
+    fn collect_output(&mut self, finish: bool) {
+        while let Ok(chunk) = self.output_rx.try_recv() {
+            let (is_error, bytes) = match chunk {
+                OutputChunk::Text { is_error, bytes } => (is_error, bytes),
+                OutputChunk::Table(table) => {
+                    self.output.push_back(OutputItem::Table(TableView { table, sort: None }));
+                    continue;
                 }
-                if !line.is_empty() {
-                    self.output.push_back(line);
+                OutputChunk::Tree(tree) => {
+                    self.output.push_back(OutputItem::Tree(tree));
+                    continue;
                 }
+            };
+            let stream = is_error as usize;
+            self.pending[stream].extend_from_slice(&bytes);
+            while let Some(pos) = self.pending[stream].iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = self.pending[stream].drain(..=pos).collect();
+                // CRLF の CR は改行の一部（行の途中の CR は ansi::parse_line が上書きとして扱う）
+                let line = &line[..line.len() - 1];
+                self.push_stream_line(is_error, line.strip_suffix(b"\r").unwrap_or(line));
             }
-            Err(e) => {
-                self.output.push_back(format!("ls: {}: {}", dir, e));
+        }
+        if finish {
+            for (stream, is_error) in [(0, false), (1, true)] {
+                if !self.pending[stream].is_empty() {
+                    let line = std::mem::take(&mut self.pending[stream]);
+                    self.push_stream_line(is_error, &line);
+                }
             }
         }
+        self.trim_output();
     }
     
-    /// ディレクトリを作成
-    fn make_directory(&mut self, args: &[&str]) {
-        if args.is_empty() {
-            self.output.push_back("mkdir: missing operand".to_string());
-            return;
+    /// 出力ストリームの1行を追加する
+    fn push_stream_line(&mut self, is_error: bool, bytes: &[u8]) {
+        let text = String::from_utf8_lossy(bytes).into_owned();
+        if is_error {
+            self.push_error(text);
+        } else {
+            self.push_line(text);
         }
-        
-        for dir in args {
-            let path = if Path::new(dir).is_absolute() {
-                Path::new(dir).to_path_buf()
-            } else {
-                Path::new(&self.current_dir).join(dir)
-            };
-            
-            match fs::create_dir(&path) {
-                Ok(_) => {
-                    self.output.push_back(format!("Created directory: {}", dir));
-                }
-                Err(e) => {
-                    self.output.push_back(format!("mkdir: {}: {}", dir, e));
+    }
+    
+    /// 実行中のコマンドからの確認・進捗の依頼を受け取る
+    fn collect_requests(&mut self) {
+        while let Ok(request) = self.ui_rx.try_recv() {
+            match request {
+                UiRequest::Confirm { question, reply } => self.confirm = Some((question, reply)),
+                UiRequest::Choose { title, items, reply } => {
+                    self.picker = Some(Picker { title, items, reply, filter: String::new(), selected: 0 });
                 }
+                UiRequest::Progress(progress) => self.progress = progress,
             }
         }
     }
     
-    /// ディレクトリを削除
-    fn remove_directory(&mut self, args: &[&str]) {
-        if args.is_empty() {
-            self.output.push_back("rmdir: missing operand".to_string());
+    /// 実行中のコマンドが終わっていれば実行環境を取り戻す
+    fn finish_running(&mut self, egui_ctx: &egui::Context) {
+        if !self.running.as_ref().is_some_and(|handle| handle.is_finished()) {
             return;
         }
-        
-        for dir in args {
-            let path = if Path::new(dir).is_absolute() {
-                Path::new(dir).to_path_buf()
-            } else {
-                Path::new(&self.current_dir).join(dir)
-            };
-            
-            match fs::remove_dir(&path) {
-                Ok(_) => {
-                    self.output.push_back(format!("Removed directory: {}", dir));
-                }
-                Err(e) => {
-                    self.output.push_back(format!("rmdir: {}: {}", dir, e));
+        let Some(handle) = self.running.take() else {
+            return;
+        };
+        let mut ctx = match handle.join() {
+            Ok(ctx) => ctx,
+            Err(_) => {
+                self.push_error("taminal: command panicked");
+                match self.new_context(self.cwd.clone(), egui_ctx) {
+                    Ok(ctx) => ctx,
+                    Err(e) => {
+                        let message = self.sandbox_error(&e);
+                        self.push_error(message);
+                        return;
+                    }
                 }
             }
+        };
+        self.cwd = ctx.cwd.clone();
+        self.prompt = ctx.prompt_label();
+        self.undo_trash = std::mem::take(&mut ctx.trashed);
+        self.ctx = Some(ctx);
+        self.collect_requests();
+        self.collect_output(true);
+        self.confirm = None;
+        self.picker = None;
+        self.progress = None;
+    }
+    
+    /// 出力が1000行を超えたら古いものを削除
+    fn trim_output(&mut self) {
+        while self.output.len() > 1000 {
+            self.output.pop_front();
         }
     }
     
-    /// ファイルを削除
-    fn remove_file(&mut self, args: &[&str]) {
-        if args.is_empty() {
-            self.output.push_back("rm: missing operand".to_string());
+    /// コマンドを実行する
+    fn execute_command(&mut self, command: String) {
+        // 前のコマンドの実行中は受け付けない
+        if self.ctx.is_none() {
             return;
         }
+        // 「削除を取り消す」は直前のコマンドに対してだけ使える
+        self.undo_trash.clear();
+        self.interrupt.store(false, Ordering::SeqCst);
         
-        let mut force = false;
-        let mut recursive = false;
-        let mut files = Vec::new();
+        // コマンドを履歴に追加
+        if !command.is_empty() {
+            self.command_history.push(command.clone());
+            self.history_index = self.command_history.len();
+        }
         
-        for arg in args {
-            if arg.starts_with('-') {
-                for ch in arg.chars().skip(1) {
-                    match ch {
-                        'f' => force = true,
-                        'r' | 'R' => recursive = true,
-                        _ => {}
-                    }
-                }
-            } else {
-                files.push(*arg);
+        // プロンプトとコマンドを出力に追加
+        let prompt = format!("{}> {}", self.prompt, command);
+        self.push_line(prompt);
+        
+        // 空白で分割してコマンドと引数を取得（クォートや $'...'、`|` によるパイプライン、
+        // `;` `&&` `||` によるコマンドリスト、`~` `~-` `~1` なども解釈する）
+        let expand = |prefix: &OsStr| self.ctx.as_ref().and_then(|ctx| ctx.expand_tilde(prefix));
+        let list = match shell_words::split_list_expanding(command.as_bytes(), &expand) {
+            Ok(list) => list,
+            Err(e) => {
+                self.push_error(format!("taminal: {}", e));
+                return;
+            }
+        };
+        // パイプラインとコマンドリストはビルトインだけで組める（外部コマンドとはつながない）
+        let [item] = list.as_slice() else {
+            if list.is_empty() {
+                return;
             }
+            self.run_builtin_list(list);
+            return;
+        };
+        let stages = &item.stages;
+        if stages.len() > 1 {
+            self.run_builtin_list(list);
+            return;
         }
+        let parts = &stages[0];
         
-        for file in files {
-            let path = if Path::new(file).is_absolute() {
-                Path::new(file).to_path_buf()
-            } else {
-                Path::new(&self.current_dir).join(file)
-            };
-            
-            if !path.exists() && !force {
-                self.output.push_back(format!("rm: {}: No such file or directory", file));
-                continue;
+        let cmd = parts[0].to_string_lossy().into_owned();
+        let cmd = cmd.as_str();
+        let args = &parts[1..];
+        
+        // GUI固有の処理が必要なもの以外は、CLI版と共通のビルトインを使う
+        match cmd {
+            "exit" | "quit" => {
+                self.push_line("Use the window close button to exit");
             }
-            
-            if path.is_dir() && recursive {
-                match fs::remove_dir_all(&path) {
-                    Ok(_) => {
-                        self.output.push_back(format!("Removed: {}", file));
-                    }
-                    Err(e) if !force => {
-                        self.output.push_back(format!("rm: {}: {}", file, e));
-                    }
-                    _ => {}
+            "clear" => {
+                self.clear_output();
+            }
+            "help" => {
+                self.show_help();
+            }
+            _ => match (commands::find(cmd), self.ctx.take()) {
+                // ビルトインは別スレッドで実行し、その間も画面を更新し続ける
+                (Some(builtin), Some(mut ctx)) => {
+                    let args = args.to_vec();
+                    self.running = Some(thread::spawn(move || {
+                        builtin.call(&mut ctx, &args);
+                        ctx
+                    }));
+                    return;
                 }
-            } else if path.is_file() {
-                match fs::remove_file(&path) {
-                    Ok(_) => {
-                        self.output.push_back(format!("Removed: {}", file));
-                    }
-                    Err(e) if !force => {
-                        self.output.push_back(format!("rm: {}: {}", file, e));
-                    }
-                    _ => {}
+                (_, ctx) => {
+                    self.ctx = ctx;
+                    // ビルトインでなければ外部コマンドとして実行
+                    self.execute_external_command(cmd, args);
                 }
-            } else if path.is_dir() {
-                self.output.push_back(format!("rm: {}: Is a directory (use -r)", file));
+            },
+        }
+        self.collect_output(true);
+    }
+    
+    /// パイプラインやコマンドリストを別スレッドで実行する。ビルトインでないコマンドがあれば実行しない
+    fn run_builtin_list(&mut self, list: Vec<ListItem>) {
+        if let Some(name) = list
+            .iter()
+            .flat_map(|item| &item.stages)
+            .map(|stage| stage[0].to_string_lossy())
+            .find(|name| commands::find(name).is_none())
+        {
+            self.push_error(format!("{}: command not found (only builtin commands can be piped or chained)", name));
+            return;
+        }
+        if let Some(mut ctx) = self.ctx.take() {
+            self.running = Some(thread::spawn(move || {
+                pipeline::run_list(&mut ctx, &list);
+                ctx
+            }));
+        }
+    }
+    
+    /// 直前の rm でゴミ箱へ移した項目を元に戻す
+    fn undo_delete(&mut self) {
+        for item in std::mem::take(&mut self.undo_trash) {
+            let shown = quoting::display(item.original.as_os_str());
+            match trash::restore(&item) {
+                Ok(()) => self.push_line(format!("restored '{}'", shown)),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    self.push_error(format!("cannot restore '{}': File exists", shown));
+                }
+                Err(e) => self.push_error(format!("cannot restore '{}': {}", shown, commands::error_message(&e))),
             }
         }
     }
     
     /// 外部コマンドを実行
-    fn execute_external_command(&mut self, cmd: &str, args: &[&str]) {
-        let output = Command::new(cmd)
+    fn execute_external_command(&mut self, cmd: &str, args: &[OsString]) {
+        if self.sandbox.is_some() {
+            self.push_error(format!("{}: permission denied by sandbox (external commands are disabled)", cmd));
+            return;
+        }
+        let mut command = Command::new(cmd);
+        // パイプ接続でも色付き出力を得られるようにする（ユーザー指定があればそちらを優先）
+        if env::var_os("CARGO_TERM_COLOR").is_none() {
+            command.env("CARGO_TERM_COLOR", "always");
+        }
+        // cd で覚えた直前のディレクトリを $OLDPWD として渡す
+        if let Some(oldpwd) = self.ctx.as_ref().and_then(|ctx| ctx.oldpwd.as_ref()) {
+            command.env("OLDPWD", oldpwd);
+        }
+        let output = command
             .args(args)
-            .current_dir(&self.current_dir)
+            .current_dir(&self.cwd)
             .output();
         
         match output {
@@ -316,64 +565,176 @@ impl TerminalApp {
                 let stderr = String::from_utf8_lossy(&output.stderr);
                 
                 for line in stdout.lines() {
-                    self.output.push_back(line.to_string());
+                    self.push_line(line);
                 }
                 for line in stderr.lines() {
-                    self.output.push_back(format!("[ERROR] {}", line));
+                    self.push_error(line);
                 }
             }
             Err(e) => {
-                self.output.push_back(format!("{}: command not found ({})", cmd, e));
+                self.push_error(format!("{}: command not found ({})", cmd, e));
             }
         }
     }
     
-    /// ヘルプを表示
+    /// ヘルプを表示（コマンド一覧はCLI版と共通）
     fn show_help(&mut self) {
-        let help_text = vec![
-            "=== Available Commands ===",
-            "",
-            "File and Directory Operations:",
-            "  ls [dir]      - List directory contents",
-            "  cd [dir]      - Change directory",
-            "  pwd           - Print working directory",
-            "  mkdir <dir>   - Create directory",
-            "  rmdir <dir>   - Remove empty directory",
-            "  rm <file>     - Remove file",
-            "    -f          - Force removal",
-            "    -r          - Remove directories recursively",
-            "",
-            "Terminal Control:",
-            "  clear         - Clear terminal",
-            "  help          - Show this help",
-            "  exit/quit     - (Use window close button)",
-            "",
-            "Shortcuts:",
-            "  Up/Down       - Navigate command history",
-            "  Ctrl+L        - Clear terminal",
-            "  Enter         - Execute command",
-        ];
-        
-        for line in help_text {
-            self.output.push_back(line.to_string());
+        let Some(ctx) = self.ctx.as_mut() else {
+            return;
+        };
+        outln!(ctx, "=== Available Commands ===");
+        outln!(ctx);
+        help::print_commands(ctx);
+        outln!(ctx, "  exit/quit     - (Use window close button)");
+        outln!(ctx);
+        outln!(ctx, "Shortcuts:");
+        outln!(ctx, "  Up/Down       - Navigate command history");
+        outln!(ctx, "  Ctrl+L        - Clear terminal");
+        outln!(ctx, "  Ctrl+C / Stop - Interrupt running command");
+        outln!(ctx, "  Enter         - Execute command");
+    }
+}
+
+/// ANSIエスケープを含む1行を egui の LayoutJob に変換する
+fn layout_ansi_line(
+    line: &str,
+    style: &mut ansi::Style,
+    font_id: &egui::FontId,
+    default_fg: egui::Color32,
+) -> LayoutJob {
+    let mut job = LayoutJob::default();
+    for span in ansi::parse_line(line, style) {
+        job.append(&span.text, 0.0, text_format(&span.style, font_id, default_fg));
+    }
+    job
+}
+
+/// ANSIのスタイルを egui の TextFormat に変換する
+fn text_format(style: &ansi::Style, font_id: &egui::FontId, default_fg: egui::Color32) -> TextFormat {
+    let to_color32 = |color: ansi::Color| {
+        let (r, g, b) = color.to_rgb();
+        egui::Color32::from_rgb(r, g, b)
+    };
+
+    // 太字は端末と同様に基本8色を明るい色に置き換えて表現する
+    let fg = match style.fg {
+        Some(ansi::Color::Indexed(n)) if style.bold && n < 8 => to_color32(ansi::Color::Indexed(n + 8)),
+        Some(color) => to_color32(color),
+        None if style.bold => {
+            let brighten = |v: u8| v + (255 - v) / 3;
+            egui::Color32::from_rgb(brighten(default_fg.r()), brighten(default_fg.g()), brighten(default_fg.b()))
         }
+        None => default_fg,
+    };
+    let bg = style.bg.map(to_color32).unwrap_or(egui::Color32::TRANSPARENT);
+
+    let (mut fg, bg) = if style.inverse {
+        let inverted_fg = if bg == egui::Color32::TRANSPARENT { egui::Color32::BLACK } else { bg };
+        (inverted_fg, fg)
+    } else {
+        (fg, bg)
+    };
+    if style.dim {
+        fg = fg.gamma_multiply(0.6);
     }
-    
-    /// 現在のディレクトリ名を取得
-    fn get_dir_name(&self) -> String {
-        Path::new(&self.current_dir)
-            .file_name()
-            .and_then(|s| s.to_str())
-            .unwrap_or("?")
-            .to_string()
+
+    let line = |on: bool| if on { egui::Stroke::new(1.0, fg) } else { egui::Stroke::NONE };
+    TextFormat {
+        font_id: font_id.clone(),
+        color: fg,
+        background: bg,
+        italics: style.italic,
+        underline: line(style.underline),
+        strikethrough: line(style.strikethrough),
+        ..Default::default()
+    }
+}
+
+/// 出力エリアに表を描画する。見出しをクリックするとその列で並べ替え、もう一度で逆順にする
+/// （数値の列は最初は大きい順）
+fn show_table(ui: &mut egui::Ui, id: usize, view: &mut TableView) {
+    let table = &view.table;
+    // 等幅フォントで桁をそろえる（数値の列は右寄せ）
+    let mut widths: Vec<usize> = table.columns.iter().map(|c| columns::display_width(&c.title) + 2).collect();
+    for row in &table.rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(columns::display_width(&cell.text));
+        }
+    }
+    let pad = |text: &str, width: usize, right: bool| {
+        let padding = " ".repeat(width.saturating_sub(columns::display_width(text)));
+        if right {
+            format!("{}{}", padding, text)
+        } else {
+            format!("{}{}", text, padding)
+        }
+    };
+
+    let mut clicked = None;
+    egui::Grid::new(("output_table", id)).striped(true).spacing([12.0, 2.0]).show(ui, |ui| {
+        for (i, column) in table.columns.iter().enumerate() {
+            let arrow = match view.sort {
+                Some((sorted, descending)) if sorted == i => if descending { " ▼" } else { " ▲" },
+                _ => "",
+            };
+            let title = pad(&format!("{}{}", column.title, arrow), widths[i], column.numeric);
+            let header = egui::RichText::new(title).monospace().strong();
+            if ui.add(egui::Label::new(header).sense(egui::Sense::click())).on_hover_text("Click to sort").clicked() {
+                clicked = Some(i);
+            }
+        }
+        ui.end_row();
+        for row in &table.rows {
+            for (i, cell) in row.iter().enumerate() {
+                ui.monospace(pad(&cell.text, widths[i], table.columns[i].numeric));
+            }
+            ui.end_row();
+        }
+    });
+
+    if let Some(column) = clicked {
+        let descending = match view.sort {
+            Some((sorted, descending)) if sorted == column => !descending,
+            _ => view.table.columns[column].numeric,
+        };
+        view.sort = Some((column, descending));
+        view.table.sort_by(column, descending);
     }
 }
 
+/// 出力エリアに木を描画する。ディレクトリは開閉できる見出しにし、最初は一番上だけを開く。
+/// `id` は開閉の状態を覚えるのに使う（子は親の id から作る）
+fn show_tree(ui: &mut egui::Ui, id: egui::Id, node: &TreeNode, font_id: &egui::FontId, is_root: bool) {
+    let label = egui::RichText::new(&node.label).font(font_id.clone());
+    if !node.is_dir {
+        ui.label(label);
+        return;
+    }
+    egui::CollapsingHeader::new(label.strong())
+        .id_source(id)
+        .default_open(is_root)
+        .show(ui, |ui| {
+            for (i, child) in node.children.iter().enumerate() {
+                show_tree(ui, id.with(i), child, font_id, false);
+            }
+        });
+}
+
 impl eframe::App for TerminalApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         // ダークテーマを設定
         ctx.set_visuals(egui::Visuals::dark());
         
+        // 実行中のコマンドの出力・依頼を取り込み、終わるまで定期的に再描画する
+        self.collect_output(false);
+        self.collect_requests();
+        self.finish_running(ctx);
+        if self.running.is_some() {
+            ctx.request_repaint_after(Duration::from_millis(50));
+        }
+        self.show_confirm_dialog(ctx);
+        self.show_picker(ctx);
+        
         egui::CentralPanel::default().show(ctx, |ui| {
             // タイトル
             ui.heading("🖥️ Taminal GUI Terminal");
@@ -382,32 +743,93 @@ impl eframe::App for TerminalApp {
             // 現在のディレクトリを表示
             ui.horizontal(|ui| {
                 ui.label("Current Directory:");
-                ui.monospace(&self.current_dir);
+                ui.monospace(self.cwd.display().to_string());
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    ui.color_edit_button_srgba(&mut self.error_color);
+                    ui.label("Error color:");
+                });
             });
             ui.separator();
             
             // ターミナル出力エリア
             let text_height = ui.available_height() - 60.0;
+            
+            // 出力エリアに収まる桁数を ls などの複数列表示の幅として使う
+            // （スクロールバーの分を差し引く）
+            let font_id = egui::TextStyle::Monospace.resolve(ui.style());
+            let char_width = ui.fonts(|fonts| fonts.glyph_width(&font_id, 'M'));
+            let scroll_bar_width = ui.spacing().scroll.bar_width;
+            if let Some(ctx) = self.ctx.as_mut().filter(|_| char_width > 0.0) {
+                let columns = ((ui.available_width() - scroll_bar_width) / char_width).floor();
+                ctx.width = (columns as usize).max(1);
+            }
             egui::ScrollArea::vertical()
                 .max_height(text_height)
                 .auto_shrink([false; 2])
                 .stick_to_bottom(self.auto_scroll)
                 .show(ui, |ui| {
                     ui.vertical(|ui| {
-                        for line in &self.output {
-                            ui.monospace(line);
+                        // SGR の状態は行をまたいで引き継ぐ
+                        let mut style = ansi::Style::default();
+                        let font_id = egui::TextStyle::Monospace.resolve(ui.style());
+                        let text_color = ui.visuals().text_color();
+                        for (i, item) in self.output.iter_mut().enumerate() {
+                            match item {
+                                OutputItem::Line(line) => {
+                                    let default_fg = if line.is_error { self.error_color } else { text_color };
+                                    let job = layout_ansi_line(&line.text, &mut style, &font_id, default_fg);
+                                    ui.label(job);
+                                }
+                                OutputItem::Table(view) => show_table(ui, i, view),
+                                OutputItem::Tree(tree) => show_tree(ui, egui::Id::new(("output_tree", i)), tree, &font_id, true),
+                            }
                         }
                     });
                 });
             
             ui.separator();
             
-            // コマンド入力エリア
-            ui.horizontal(|ui| {
-                ui.label(format!("{}> ", self.get_dir_name()));
+            // 実行中のコマンドの進捗
+            if let Some(progress) = &self.progress {
+                let fraction = if progress.total > 0 {
+                    progress.done as f32 / progress.total as f32
+                } else {
+                    0.0
+                };
+                let text = if progress.total > 0 {
+                    format!(
+                        "{} / {}  {}",
+                        file_info::human_size(progress.done),
+                        file_info::human_size(progress.total),
+                        progress.message
+                    )
+                } else {
+                    progress.message.clone()
+                };
+                ui.add(egui::ProgressBar::new(fraction).text(text));
+            }
+            
+            // 実行中のコマンドは Stop ボタンか Ctrl+C で中断できる
+            if self.running.is_some() {
+                let stop = ui.horizontal(|ui| ui.button("Stop").on_hover_text("Interrupt the running command (Ctrl+C)").clicked()).inner;
+                let ctrl_c = ui.input(|i| {
+                    i.events.iter().any(|event| matches!(event, egui::Event::Copy))
+                        || (i.modifiers.ctrl && i.key_pressed(egui::Key::C))
+                });
+                if stop || ctrl_c {
+                    self.interrupt.store(true, Ordering::SeqCst);
+                }
+            }
+            
+            // コマンド入力エリア（実行中は入力できない）
+            let idle = self.running.is_none();
+            ui.add_enabled_ui(idle, |ui| ui.horizontal(|ui| {
+                ui.label(format!("{}> ", self.prompt));
                 
+                // 右側のボタンの分を空けておく
+                let buttons_width = if self.undo_trash.is_empty() { 100.0 } else { 230.0 };
                 let response = ui.add_sized(
-                    [ui.available_width() - 100.0, 20.0],
+                    [ui.available_width() - buttons_width, 20.0],
                     egui::TextEdit::singleline(&mut self.input)
                 );
                 
@@ -423,20 +845,18 @@ impl eframe::App for TerminalApp {
                 
                 // 履歴ナビゲーション
                 if response.has_focus() {
-                    if ui.input(|i| i.key_pressed(egui::Key::ArrowUp)) {
-                        if self.history_index > 0 {
-                            self.history_index -= 1;
-                            self.input = self.command_history[self.history_index].clone();
-                        }
+                    if ui.input(|i| i.key_pressed(egui::Key::ArrowUp)) && self.history_index > 0 {
+                        self.history_index -= 1;
+                        self.input = self.command_history[self.history_index].clone();
                     }
-                    if ui.input(|i| i.key_pressed(egui::Key::ArrowDown)) {
-                        if self.history_index < self.command_history.len() {
-                            self.history_index += 1;
-                            if self.history_index == self.command_history.len() {
-                                self.input.clear();
-                            } else {
-                                self.input = self.command_history[self.history_index].clone();
-                            }
+                    if ui.input(|i| i.key_pressed(egui::Key::ArrowDown))
+                        && self.history_index < self.command_history.len()
+                    {
+                        self.history_index += 1;
+                        if self.history_index == self.command_history.len() {
+                            self.input.clear();
+                        } else {
+                            self.input = self.command_history[self.history_index].clone();
                         }
                     }
                 }
@@ -448,13 +868,120 @@ impl eframe::App for TerminalApp {
                         self.execute_command(command);
                     }
                 }
-            });
+                
+                if !self.undo_trash.is_empty() {
+                    let label = format!("Undo delete ({})", self.undo_trash.len());
+                    if ui.button(label).on_hover_text("Restore the files moved to the trash by the last rm").clicked() {
+                        self.undo_delete();
+                    }
+                }
+            }));
             
             // ショートカット処理
             if ui.input(|i| i.modifiers.ctrl && i.key_pressed(egui::Key::L)) {
-                self.output.clear();
-                self.output.push_back("=== Terminal Cleared ===".to_string());
+                self.clear_output();
             }
         });
     }
-}
\ No newline at end of file
+}
+
+impl TerminalApp {
+    /// 実行中のコマンドからの候補の選択をウィンドウで表示する。入力欄で絞り込み、
+    /// ↑↓ で選んで Enter（またはクリック）で決定、Esc で取り消す
+    fn show_picker(&mut self, ctx: &egui::Context) {
+        let Some(picker) = &mut self.picker else {
+            return;
+        };
+        let mut answer = None;
+        // キーで選択を動かしたときだけ、選んだ行が見えるようにスクロールする
+        let mut moved = false;
+        let visible = picker.visible();
+        ctx.input(|i| {
+            if i.key_pressed(egui::Key::ArrowDown) {
+                picker.selected += 1;
+                moved = true;
+            } else if i.key_pressed(egui::Key::ArrowUp) {
+                picker.selected = picker.selected.saturating_sub(1);
+                moved = true;
+            } else if i.key_pressed(egui::Key::Enter) {
+                answer = Some(visible.get(picker.selected).copied());
+            } else if i.key_pressed(egui::Key::Escape) {
+                answer = Some(None);
+            }
+        });
+        picker.selected = picker.selected.min(visible.len().saturating_sub(1));
+
+        egui::Window::new(picker.title.as_str())
+            .collapsible(false)
+            .resizable(true)
+            .default_width(500.0)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                let response = ui.add(egui::TextEdit::singleline(&mut picker.filter).hint_text("Filter"));
+                response.request_focus();
+                if response.changed() {
+                    picker.selected = 0;
+                }
+                ui.separator();
+                egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                    for (row, &index) in visible.iter().enumerate() {
+                        let label = ui.selectable_label(row == picker.selected, egui::RichText::new(&picker.items[index]).monospace());
+                        if moved && row == picker.selected {
+                            label.scroll_to_me(None);
+                        }
+                        if label.clicked() {
+                            answer = Some(Some(index));
+                        }
+                    }
+                });
+                if visible.is_empty() {
+                    ui.label("No matches");
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("Cancel").clicked() {
+                        answer = Some(None);
+                    }
+                });
+            });
+        if let Some(answer) = answer {
+            if let Some(picker) = self.picker.take() {
+                let _ = picker.reply.send(answer);
+            }
+        }
+    }
+
+    /// 実行中のコマンドからの確認をダイアログで表示する（Y で「はい」、N/Esc で「いいえ」。Enter では答えない）
+    fn show_confirm_dialog(&mut self, ctx: &egui::Context) {
+        let Some((question, _)) = &self.confirm else {
+            return;
+        };
+        let mut answer = None;
+        egui::Window::new("Confirm")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.label(question.as_str());
+                ui.horizontal(|ui| {
+                    if ui.button("Yes").clicked() {
+                        answer = Some(true);
+                    }
+                    if ui.button("No").clicked() {
+                        answer = Some(false);
+                    }
+                });
+            });
+        ctx.input(|i| {
+            if i.key_pressed(egui::Key::Y) {
+                answer = Some(true);
+            } else if i.key_pressed(egui::Key::N) || i.key_pressed(egui::Key::Escape) {
+                answer = Some(false);
+            }
+        });
+        if let Some(answer) = answer {
+            if let Some((_, reply)) = self.confirm.take() {
+                let _ = reply.send(answer);
+            }
+        }
+    }
+}
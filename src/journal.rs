@@ -0,0 +1,500 @@
+//! ファイル操作の取り消し用ジャーナル
+//!
+//! rm・rmdir・mv・cp（上書き）・mkdir が行った変更を1コマンド1件として記録し、
+//! `undo` で元に戻せるようにする。削除・上書きされる内容はその場で消さず、
+//! 退避場所（stash）へ移しておく。
+//!
+//! 保存場所は `$XDG_STATE_HOME/taminal/undo`（未設定なら `~/.local/state/taminal/undo`）:
+//! - `journal`      操作の記録（古い順）
+//! - `stash/<id>/`  各操作で退避した内容
+//!
+//! 記録の件数と退避した内容の合計サイズには上限（設定ファイルの `undo_entries` と
+//! `undo_stash_size`）があり、超えた分は古い操作から忘れる（退避した内容も削除する）。
+//! 退避場所と別のファイルシステムにあるファイルは rename で退避できないため、
+//! 削除したものは元に戻せない操作として記録する。
+
+use crate::config::Config;
+use crate::fs_util;
+use crate::quoting;
+use crate::trash::TrashedItem;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, Write};
+use std::os::unix::ffi::OsStringExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 操作直後のファイルの状態。undo の前に、その後変更されていないかを確かめるのに使う
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fingerprint {
+    dev: u64,
+    ino: u64,
+    size: u64,
+    mtime_ns: i64,
+    is_dir: bool,
+}
+
+impl Fingerprint {
+    /// `path` の現在の状態（シンボリックリンクはたどらない）
+    pub fn of(path: &Path) -> Option<Fingerprint> {
+        let meta = fs::symlink_metadata(path).ok()?;
+        Some(Fingerprint {
+            dev: meta.dev(),
+            ino: meta.ino(),
+            size: meta.size(),
+            mtime_ns: meta.mtime() * 1_000_000_000 + meta.mtime_nsec(),
+            is_dir: meta.is_dir(),
+        })
+    }
+
+    /// 同じ状態か。ディレクトリは中身の変化で日時が変わるので同一性だけを見る
+    pub fn matches(&self, other: &Fingerprint) -> bool {
+        if self.is_dir || other.is_dir {
+            (self.dev, self.ino, self.is_dir) == (other.dev, other.ino, other.is_dir)
+        } else {
+            self == other
+        }
+    }
+
+    fn encode(&self) -> String {
+        format!("{}:{}:{}:{}:{}", self.dev, self.ino, self.size, self.mtime_ns, self.is_dir as u8)
+    }
+
+    fn decode(text: &str) -> Option<Fingerprint> {
+        let mut fields = text.split(':');
+        let mut next = || fields.next();
+        Some(Fingerprint {
+            dev: next()?.parse().ok()?,
+            ino: next()?.parse().ok()?,
+            size: next()?.parse().ok()?,
+            mtime_ns: next()?.parse().ok()?,
+            is_dir: next()? == "1",
+        })
+    }
+}
+
+/// 退避した内容
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Stash {
+    /// 退避場所のパス
+    Saved(PathBuf),
+    /// 退避できなかった（別のファイルシステム、または大きすぎる）
+    Lost,
+}
+
+/// 1つの変更
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// ファイル・ディレクトリを削除した
+    Removed { path: PathBuf, stash: Stash },
+    /// 空のディレクトリを削除した（元に戻すときは作り直す）
+    RemovedDir { path: PathBuf, mode: u32 },
+    /// ゴミ箱へ移した
+    Trashed { item: TrashedItem },
+    /// ディレクトリを作った（元に戻すときは空なら削除する）
+    Created { path: PathBuf },
+    /// 移動した。`overwritten` は移動先に元々あったもの
+    Moved {
+        from: PathBuf,
+        to: PathBuf,
+        overwritten: Option<Stash>,
+        after: Option<Fingerprint>,
+    },
+    /// 既存のファイルをコピーで上書きした
+    Overwritten { path: PathBuf, stash: Stash, after: Option<Fingerprint> },
+}
+
+/// 1コマンド分の記録
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub id: u64,
+    /// 実行日時（UNIX秒）
+    pub time: i64,
+    /// 実行したコマンドライン（表示用）
+    pub command: String,
+    pub actions: Vec<Action>,
+}
+
+/// ジャーナルの保存場所と上限
+#[derive(Debug, Clone)]
+pub struct Journal {
+    dir: PathBuf,
+    max_entries: usize,
+    max_stash_bytes: u64,
+}
+
+impl Journal {
+    /// 設定に従ってジャーナルを用意する。`undo = false` なら None
+    pub fn open(config: &Config) -> Option<Journal> {
+        if !config.undo {
+            return None;
+        }
+        let state_home = match env::var_os("XDG_STATE_HOME").filter(|dir| !dir.is_empty()) {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(env::var_os("HOME")?).join(".local").join("state"),
+        };
+        Some(Journal {
+            dir: state_home.join("taminal").join("undo"),
+            max_entries: config.undo_entries,
+            max_stash_bytes: config.undo_stash_size,
+        })
+    }
+
+    /// 1コマンド分の記録を始める。`name` と `args` は表示用のコマンドライン
+    pub fn begin(&self, name: &str, args: &[OsString]) -> Recorder {
+        let mut command = name.to_string();
+        for arg in args {
+            command.push(' ');
+            command.push_str(&quoting::display(arg));
+        }
+        Recorder {
+            journal: self.clone(),
+            command,
+            stash_dir: None,
+            stash_count: 0,
+            actions: Vec::new(),
+        }
+    }
+
+    /// 記録済みの操作（古い順）
+    pub fn entries(&self) -> Vec<Entry> {
+        match fs::read(self.journal_path()) {
+            Ok(text) => parse_entries(&text),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// 記録を1件取り除き、退避した内容も削除する（undo の後に使う）
+    pub fn remove_entry(&self, id: u64) -> io::Result<()> {
+        let mut entries = self.entries();
+        entries.retain(|entry| entry.id != id);
+        self.discard_stash(id);
+        self.write_entries(&entries)
+    }
+
+    fn journal_path(&self) -> PathBuf {
+        self.dir.join("journal")
+    }
+
+    fn stash_root(&self) -> PathBuf {
+        self.dir.join("stash")
+    }
+
+    fn discard_stash(&self, id: u64) {
+        let _ = fs::remove_dir_all(self.stash_root().join(id.to_string()));
+    }
+
+    /// 記録を書き出す（一時ファイルに書いてから置き換える）
+    fn write_entries(&self, entries: &[Entry]) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let temp = self.dir.join(format!("journal.tmp-{}", std::process::id()));
+        let mut file = fs::File::create(&temp)?;
+        for entry in entries {
+            file.write_all(format_entry(entry).as_bytes())?;
+        }
+        file.sync_all()?;
+        fs::rename(&temp, self.journal_path())
+    }
+
+    /// 件数と退避した内容の合計サイズが上限を超えないよう、古い操作から忘れる
+    fn prune(&self, entries: &mut Vec<Entry>) {
+        let mut sizes: Vec<u64> = entries.iter().map(|entry| self.stash_size(entry.id)).collect();
+        let mut total: u64 = sizes.iter().sum();
+        while !entries.is_empty() && (entries.len() > self.max_entries || total > self.max_stash_bytes) {
+            let oldest = entries.remove(0);
+            total -= sizes.remove(0);
+            self.discard_stash(oldest.id);
+        }
+    }
+
+    fn stash_size(&self, id: u64) -> u64 {
+        tree_size(&self.stash_root().join(id.to_string()))
+    }
+
+    /// まだ使われていない操作番号
+    fn next_id(&self) -> u64 {
+        let from_entries = self.entries().iter().map(|entry| entry.id).max().unwrap_or(0);
+        let from_stash = fs::read_dir(self.stash_root())
+            .map(|entries| {
+                entries
+                    .filter_map(|e| e.ok())
+                    .filter_map(|e| e.file_name().to_str().and_then(|name| name.parse::<u64>().ok()))
+                    .max()
+                    .unwrap_or(0)
+            })
+            .unwrap_or(0);
+        from_entries.max(from_stash) + 1
+    }
+}
+
+/// 1コマンド分の変更を集め、最後に `commit` でジャーナルへ書き込む
+pub struct Recorder {
+    journal: Journal,
+    command: String,
+    /// この操作の番号と退避場所（最初に退避が必要になったときに決める）
+    stash_dir: Option<(u64, PathBuf)>,
+    stash_count: u64,
+    actions: Vec<Action>,
+}
+
+impl Recorder {
+    /// 削除の代わりに `path` を退避場所へ移して記録する。
+    /// 退避場所と別のファイルシステムにあれば EXDEV のエラーになる（呼び出し側で削除して `lost` を使う）
+    pub fn remove(&mut self, path: &Path) -> io::Result<()> {
+        let stash = self.move_to_stash(path)?;
+        self.actions.push(Action::Removed { path: path.to_path_buf(), stash: Stash::Saved(stash) });
+        Ok(())
+    }
+
+    /// 退避せずに削除したことを記録する（元に戻せない操作になる）
+    pub fn lost(&mut self, path: &Path) {
+        self.actions.push(Action::Removed { path: path.to_path_buf(), stash: Stash::Lost });
+    }
+
+    /// 上書きされる `path` を退避する。`keep` なら元の場所に残したままコピーを取る（cp 用）。
+    /// 退避できなければ `Stash::Lost`
+    pub fn stash_existing(&mut self, path: &Path, keep: bool) -> Stash {
+        if !keep {
+            if let Ok(stash) = self.move_to_stash(path) {
+                return Stash::Saved(stash);
+            }
+        }
+        // 移せなかった場合とコピーを取る場合は、上限以下の通常ファイルだけ複製する
+        let small_file = fs::symlink_metadata(path)
+            .is_ok_and(|meta| meta.is_file() && meta.len() <= self.journal.max_stash_bytes);
+        if !small_file {
+            return Stash::Lost;
+        }
+        match self.new_stash_path().and_then(|stash| fs::copy(path, &stash).map(|_| stash)) {
+            Ok(stash) => Stash::Saved(stash),
+            Err(_) => Stash::Lost,
+        }
+    }
+
+    /// 退避した内容を元の場所へ戻す（操作が失敗したときの後始末）
+    pub fn unstash(&mut self, stash: &Stash, path: &Path) {
+        if let Stash::Saved(stash) = stash {
+            if fs::symlink_metadata(path).is_err() {
+                let _ = fs::rename(stash, path);
+            }
+        }
+    }
+
+    /// 空のディレクトリを削除したことを記録する
+    pub fn removed_dir(&mut self, path: &Path, mode: u32) {
+        self.actions.push(Action::RemovedDir { path: path.to_path_buf(), mode });
+    }
+
+    /// ゴミ箱へ移したことを記録する
+    pub fn trashed(&mut self, item: TrashedItem) {
+        self.actions.push(Action::Trashed { item });
+    }
+
+    /// ディレクトリを作ったことを記録する
+    pub fn created(&mut self, path: &Path) {
+        self.actions.push(Action::Created { path: path.to_path_buf() });
+    }
+
+    /// 移動したことを記録する
+    pub fn moved(&mut self, from: &Path, to: &Path, overwritten: Option<Stash>) {
+        self.actions.push(Action::Moved {
+            from: from.to_path_buf(),
+            to: to.to_path_buf(),
+            overwritten,
+            after: None,
+        });
+    }
+
+    /// コピーで上書きしたことを記録する
+    pub fn overwritten(&mut self, path: &Path, stash: Stash) {
+        self.actions.push(Action::Overwritten { path: path.to_path_buf(), stash, after: None });
+    }
+
+    /// 記録をジャーナルへ書き込む。変更がなければ何もしない
+    pub fn commit(mut self) -> io::Result<()> {
+        if self.actions.is_empty() {
+            if let Some((id, _)) = self.stash_dir {
+                self.journal.discard_stash(id);
+            }
+            return Ok(());
+        }
+
+        // 操作後の状態を記録しておき、undo の前にその後の変更を検出する
+        for action in &mut self.actions {
+            match action {
+                Action::Overwritten { path, after, .. } => *after = Fingerprint::of(path),
+                Action::Moved { to, after, .. } => *after = Fingerprint::of(to),
+                _ => {}
+            }
+        }
+
+        let id = match &self.stash_dir {
+            Some((id, _)) => *id,
+            None => self.journal.next_id(),
+        };
+        let mut entries = self.journal.entries();
+        entries.push(Entry {
+            id,
+            time: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0),
+            command: self.command,
+            actions: self.actions,
+        });
+        self.journal.prune(&mut entries);
+        self.journal.write_entries(&entries)
+    }
+
+    /// `path` を退避場所へ rename する
+    fn move_to_stash(&mut self, path: &Path) -> io::Result<PathBuf> {
+        let stash = self.new_stash_path()?;
+        fs::rename(path, &stash)?;
+        Ok(stash)
+    }
+
+    /// 退避場所の中の新しいパス
+    fn new_stash_path(&mut self) -> io::Result<PathBuf> {
+        if self.stash_dir.is_none() {
+            // 他のインスタンスと番号がぶつかったら次の番号を使う
+            let mut id = self.journal.next_id();
+            let root = self.journal.stash_root();
+            fs::create_dir_all(&root)?;
+            loop {
+                let dir = root.join(id.to_string());
+                match fs::create_dir(&dir) {
+                    Ok(()) => {
+                        self.stash_dir = Some((id, dir));
+                        break;
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::AlreadyExists => id += 1,
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        let (_, dir) = self.stash_dir.as_ref().expect("stash dir is set");
+        self.stash_count += 1;
+        Ok(dir.join(self.stash_count.to_string()))
+    }
+}
+
+/// ディレクトリ以下の合計サイズ（シンボリックリンクはたどらない）
+fn tree_size(path: &Path) -> u64 {
+    let Ok(meta) = fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !meta.is_dir() {
+        return meta.len();
+    }
+    fs::read_dir(path)
+        .map(|entries| entries.filter_map(|e| e.ok()).map(|e| tree_size(&e.path())).sum())
+        .unwrap_or(0)
+}
+
+// ジャーナルファイルの形式:
+//
+//   entry <id> <time> <コマンド>
+//   <操作> <フィールド>...
+//   end
+//
+// パスとコマンドは `fs_util::encode_path` でエスケープするため空白を含まない。
+// 退避できなかった内容は `-`、記録のない状態は `-` で表す。
+
+fn format_entry(entry: &Entry) -> String {
+    let path = |p: &Path| fs_util::encode_path(p.as_os_str());
+    let stash = |s: &Stash| match s {
+        Stash::Saved(p) => path(p),
+        Stash::Lost => "-".to_string(),
+    };
+    let fingerprint = |f: &Option<Fingerprint>| f.map(|f| f.encode()).unwrap_or_else(|| "-".to_string());
+
+    let mut out = format!(
+        "entry {} {} {}\n",
+        entry.id,
+        entry.time,
+        fs_util::encode_path(entry.command.as_ref())
+    );
+    for action in &entry.actions {
+        let line = match action {
+            Action::Removed { path: p, stash: s } => format!("removed {} {}", path(p), stash(s)),
+            Action::RemovedDir { path: p, mode } => format!("rmdir {} {:o}", path(p), mode),
+            Action::Trashed { item } => format!(
+                "trashed {} {} {} {}",
+                path(&item.original),
+                path(&item.trash_dir),
+                fs_util::encode_path(&item.name),
+                item.deletion_date
+            ),
+            Action::Created { path: p } => format!("mkdir {}", path(p)),
+            Action::Moved { from, to, overwritten, after } => format!(
+                "moved {} {} {} {}",
+                path(from),
+                path(to),
+                overwritten.as_ref().map(stash).unwrap_or_else(|| "none".to_string()),
+                fingerprint(after)
+            ),
+            Action::Overwritten { path: p, stash: s, after } => {
+                format!("overwritten {} {} {}", path(p), stash(s), fingerprint(after))
+            }
+        };
+        out.push_str(&line);
+        out.push('\n');
+    }
+    out.push_str("end\n");
+    out
+}
+
+fn parse_entries(text: &[u8]) -> Vec<Entry> {
+    let text = String::from_utf8_lossy(text);
+    let mut entries = Vec::new();
+    let mut current: Option<Entry> = None;
+
+    for line in text.lines() {
+        let fields: Vec<&str> = line.split(' ').collect();
+        match fields.as_slice() {
+            ["entry", id, time, command] => {
+                current = Some(Entry {
+                    id: id.parse().unwrap_or(0),
+                    time: time.parse().unwrap_or(0),
+                    command: String::from_utf8_lossy(&fs_util::decode_path(command.as_bytes())).into_owned(),
+                    actions: Vec::new(),
+                });
+            }
+            ["end"] => entries.extend(current.take()),
+            fields => {
+                // 壊れた行は読み飛ばす
+                if let (Some(entry), Some(action)) = (current.as_mut(), parse_action(fields)) {
+                    entry.actions.push(action);
+                }
+            }
+        }
+    }
+    entries
+}
+
+fn parse_action(fields: &[&str]) -> Option<Action> {
+    let path = |s: &str| PathBuf::from(OsString::from_vec(fs_util::decode_path(s.as_bytes())));
+    let stash = |s: &str| if s == "-" { Stash::Lost } else { Stash::Saved(path(s)) };
+    let fingerprint = |s: &str| Fingerprint::decode(s);
+
+    Some(match *fields {
+        ["removed", p, s] => Action::Removed { path: path(p), stash: stash(s) },
+        ["rmdir", p, mode] => Action::RemovedDir { path: path(p), mode: u32::from_str_radix(mode, 8).ok()? },
+        ["trashed", original, trash_dir, name, date] => Action::Trashed {
+            item: TrashedItem {
+                trash_dir: path(trash_dir),
+                name: OsString::from_vec(fs_util::decode_path(name.as_bytes())),
+                original: path(original),
+                deletion_date: date.to_string(),
+            },
+        },
+        ["mkdir", p] => Action::Created { path: path(p) },
+        ["moved", from, to, overwritten, after] => Action::Moved {
+            from: path(from),
+            to: path(to),
+            overwritten: (overwritten != "none").then(|| stash(overwritten)),
+            after: fingerprint(after),
+        },
+        ["overwritten", p, s, after] => Action::Overwritten { path: path(p), stash: stash(s), after: fingerprint(after) },
+        _ => return None,
+    })
+}
@@ -0,0 +1,50 @@
+//! Taminal のコアライブラリ
+//!
+//! CLI版（main.rs）と GUI版（main_gui.rs）の両方から使う共通部分:
+//! - ansi:      ANSIエスケープ（SGR）の解析
+//! - columns:   端末幅に合わせた複数列レイアウト
+//! - commands:  各ビルトインコマンド（外部コマンドは実行しない）
+//! - config:    設定ファイル（~/.config/taminal/config）
+//! - context:   コマンド実行時の環境（カレントディレクトリ・出力先）
+//! - dir_stack: ディレクトリスタック（pushd・popd・dirs と `~N` の展開）
+//! - frecency:  cd で移動したディレクトリの記録（z・zi で frecency の高い順に候補を出す）
+//! - fs_action: ファイルシステムを変更する操作（ドライランでは表示だけする）
+//! - fs_util:   標準ライブラリにないファイル操作（タイムスタンプ設定など）
+//! - gitignore: .gitignore の解釈（ディレクトリをたどるコマンドが使う）
+//! - glob:      ワイルドカード（`*` `?` `[...]`）によるファイル名の照合
+//! - file_info: パーミッションや所有者などファイル属性の表示用ヘルパー
+//! - journal:   ファイル操作の記録と退避（undo 用）
+//! - ls_colors: LS_COLORS に基づくファイル種別ごとの色分け
+//! - mode:      chmod 形式のモード指定（8進数・記号形式）の解析と適用
+//! - pipeline:  ビルトインコマンドのパイプライン（`cmd1 | cmd2`）とコマンドリスト（`;` `&&` `||`）の実行
+//! - quoting:   ファイル名の表示用クォート（不正な UTF-8 も元に戻せる形で表示）
+//! - sandbox:   ビルトインが扱えるファイルを1つのディレクトリ以下に制限する
+//! - table:     列ごとに並べ替えられる表（du・df の結果。GUI版では表として表示する）
+//! - tree:      折りたためる木構造（tree の結果。GUI版では開閉できる木として表示する）
+//! - trash:     ゴミ箱（freedesktop.org Trash 仕様）への移動・復元
+//! - vfs:       ビルトインが使うファイルシステム（実際のものとメモリ上のもの）
+//! - shell_words: 入力行の引数への分割（クォート・$'...' の解釈）
+
+pub mod ansi;
+pub mod columns;
+pub mod commands;
+pub mod config;
+pub mod context;
+pub mod dir_stack;
+pub mod file_info;
+pub mod frecency;
+pub mod fs_action;
+pub mod fs_util;
+pub mod gitignore;
+pub mod glob;
+pub mod journal;
+pub mod ls_colors;
+pub mod mode;
+pub mod pipeline;
+pub mod quoting;
+pub mod sandbox;
+pub mod shell_words;
+pub mod table;
+pub mod trash;
+pub mod tree;
+pub mod vfs;
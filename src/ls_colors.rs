@@ -0,0 +1,241 @@
+//! LS_COLORS に基づくファイル種別ごとの色分け
+//!
+//! 環境変数 `LS_COLORS`（GNU dircolors 形式, 例: "di=01;34:ln=01;36:*.tar=01;31"）を
+//! 解析し、ファイル名を ANSI エスケープで装飾する。未設定の場合は GNU ls と
+//! 同等の既定配色にアーカイブ拡張子の色を加えたものを使う。
+
+use crate::vfs::{Fs, Stat};
+use std::collections::HashMap;
+use std::env;
+use std::path::Path;
+
+/// `--color=WHEN` の指定
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorMode {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorMode {
+    /// "auto" / "always" / "never"（および GNU ls 互換の別名）を解釈する
+    pub fn parse(when: &str) -> Option<ColorMode> {
+        match when {
+            "auto" | "tty" | "if-tty" => Some(ColorMode::Auto),
+            "always" | "yes" | "force" => Some(ColorMode::Always),
+            "never" | "no" | "none" => Some(ColorMode::Never),
+            _ => None,
+        }
+    }
+
+    /// 出力先が色を表示できるか（`is_tty`）を踏まえて色付けするかを決める
+    pub fn enabled(self, is_tty: bool) -> bool {
+        match self {
+            ColorMode::Auto => is_tty,
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+        }
+    }
+}
+
+/// ファイル種別の既定配色（GNU ls の組み込み値と同じ）
+const DEFAULT_TYPE_COLORS: &[(&str, &str)] = &[
+    ("di", "01;34"),
+    ("ln", "01;36"),
+    ("pi", "33"),
+    ("so", "01;35"),
+    ("do", "01;35"),
+    ("bd", "01;33"),
+    ("cd", "01;33"),
+    ("or", "01;31"),
+    ("mi", "01;31"),
+    ("su", "37;41"),
+    ("sg", "30;43"),
+    ("st", "37;44"),
+    ("ow", "34;42"),
+    ("tw", "30;42"),
+    ("ex", "01;32"),
+];
+
+/// LS_COLORS 未設定時に使うアーカイブ拡張子の配色
+const DEFAULT_ARCHIVE_EXTENSIONS: &[&str] = &[
+    "tar", "tgz", "arc", "arj", "taz", "lha", "lz4", "lzh", "lzma", "tlz", "txz", "tzo", "t7z",
+    "zip", "z", "dz", "gz", "lrz", "lz", "lzo", "xz", "zst", "tzst", "bz2", "bz", "tbz", "tbz2",
+    "tz", "deb", "rpm", "jar", "war", "ear", "sar", "rar", "alz", "ace", "zoo", "cpio", "7z",
+    "rz", "cab", "wim", "swm", "dwm", "esd",
+];
+const DEFAULT_ARCHIVE_COLOR: &str = "01;31";
+
+/// 解析済みの配色表
+#[derive(Debug, Clone)]
+pub struct LsColors {
+    /// "di" や "ex" などファイル種別キーごとの SGR パラメータ
+    types: HashMap<String, String>,
+    /// "*.tar" などの末尾一致パターン（小文字化済み）と SGR パラメータ。後勝ち
+    suffixes: Vec<(String, String)>,
+}
+
+impl Default for LsColors {
+    fn default() -> Self {
+        let mut colors = LsColors::base();
+        for ext in DEFAULT_ARCHIVE_EXTENSIONS {
+            colors.suffixes.push((format!(".{}", ext), DEFAULT_ARCHIVE_COLOR.to_string()));
+        }
+        colors
+    }
+}
+
+impl LsColors {
+    /// 種別の既定配色だけを持つ表
+    fn base() -> Self {
+        LsColors {
+            types: DEFAULT_TYPE_COLORS
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            suffixes: Vec::new(),
+        }
+    }
+
+    /// 環境変数 LS_COLORS から配色表を作る。未設定・空なら既定の配色
+    pub fn from_env() -> Self {
+        match env::var("LS_COLORS") {
+            Ok(value) if !value.is_empty() => LsColors::parse(&value),
+            _ => LsColors::default(),
+        }
+    }
+
+    /// dircolors 形式の文字列を解析する。指定のない種別は既定配色のまま
+    pub fn parse(spec: &str) -> Self {
+        let mut colors = LsColors::base();
+        for entry in spec.split(':') {
+            let Some((key, value)) = entry.split_once('=') else {
+                continue;
+            };
+            if let Some(pattern) = key.strip_prefix('*') {
+                colors.suffixes.push((pattern.to_lowercase(), value.to_string()));
+            } else if value.is_empty() || value == "0" || value == "00" {
+                // 色なしの指定は既定配色を打ち消す
+                colors.types.remove(key);
+            } else {
+                colors.types.insert(key.to_string(), value.to_string());
+            }
+        }
+        colors
+    }
+
+    /// 表示用の文字列 `shown` を `path` のファイル種別に応じた色で装飾する。
+    /// 色の指定がない場合はそのまま返す
+    pub fn paint(&self, fs: &dyn Fs, shown: &str, path: &Path) -> String {
+        match fs.symlink_metadata(path) {
+            Ok(meta) => self.paint_with_metadata(fs, shown, path, &meta),
+            Err(_) => self.wrap(shown, self.types.get("mi")),
+        }
+    }
+
+    /// 取得済みの `symlink_metadata` を使って装飾する
+    pub fn paint_with_metadata(&self, fs: &dyn Fs, shown: &str, path: &Path, meta: &Stat) -> String {
+        // 拡張子の判定は表示用（クォート済み）ではなく実際のファイル名で行う
+        let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+        self.wrap(shown, self.sgr_for(fs, &name, path, meta))
+    }
+
+    /// `paint` のバイト列版（UTF-8 でない名前をそのまま書き出すとき）
+    pub fn paint_bytes(&self, fs: &dyn Fs, shown: &[u8], path: &Path) -> Vec<u8> {
+        match fs.symlink_metadata(path) {
+            Ok(meta) => self.paint_bytes_with_metadata(fs, shown, path, &meta),
+            Err(_) => wrap_bytes(shown, self.types.get("mi")),
+        }
+    }
+
+    /// `paint_with_metadata` のバイト列版
+    pub fn paint_bytes_with_metadata(&self, fs: &dyn Fs, shown: &[u8], path: &Path, meta: &Stat) -> Vec<u8> {
+        let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+        wrap_bytes(shown, self.sgr_for(fs, &name, path, meta))
+    }
+
+    /// SGR パラメータがあればエスケープで囲む
+    fn wrap(&self, name: &str, sgr: Option<&String>) -> String {
+        match sgr {
+            Some(sgr) => format!("\x1b[{}m{}\x1b[0m", sgr, name),
+            None => name.to_string(),
+        }
+    }
+
+    /// ファイル種別（と拡張子）に対応する SGR パラメータを選ぶ
+    fn sgr_for(&self, fs: &dyn Fs, name: &str, path: &Path, meta: &Stat) -> Option<&String> {
+        let file_type = meta.file_type();
+
+        if file_type.is_symlink() {
+            // リンク切れは "or"、"ln=target" ならリンク先の種別で色付けする
+            return match fs.metadata(path) {
+                Err(_) => self
+                    .types
+                    .get("or")
+                    .or_else(|| self.types.get("ln").filter(|sgr| *sgr != "target")),
+                Ok(target) if self.types.get("ln").map(String::as_str) == Some("target") => {
+                    self.sgr_for_target(name, &target)
+                }
+                Ok(_) => self.types.get("ln"),
+            };
+        }
+
+        self.sgr_for_target(name, meta)
+    }
+
+    /// シンボリックリンク以外のファイルの SGR パラメータ
+    fn sgr_for_target(&self, name: &str, meta: &Stat) -> Option<&String> {
+        let file_type = meta.file_type();
+        let mode = meta.mode();
+
+        let key = if file_type.is_dir() {
+            let sticky = mode & 0o1000 != 0;
+            let other_writable = mode & 0o002 != 0;
+            match (sticky, other_writable) {
+                (true, true) => "tw",
+                (false, true) => "ow",
+                (true, false) => "st",
+                (false, false) => "di",
+            }
+        } else if file_type.is_fifo() {
+            "pi"
+        } else if file_type.is_socket() {
+            "so"
+        } else if file_type.is_block_device() {
+            "bd"
+        } else if file_type.is_char_device() {
+            "cd"
+        } else if mode & 0o4000 != 0 {
+            "su"
+        } else if mode & 0o2000 != 0 {
+            "sg"
+        } else if mode & 0o111 != 0 {
+            "ex"
+        } else {
+            "fi"
+        };
+
+        if file_type.is_dir() {
+            // "tw" などの指定がなければ通常のディレクトリ色にする
+            return self.types.get(key).or_else(|| self.types.get("di"));
+        }
+        // 特殊なファイル種別で色があればそれを優先する
+        if let Some(sgr) = self.types.get(key).filter(|_| key != "fi") {
+            return Some(sgr);
+        }
+        // 通常ファイルは拡張子のパターンで判定する（dircolors と同じく後勝ち）
+        let lower = name.to_lowercase();
+        if let Some((_, sgr)) = self.suffixes.iter().rev().find(|(p, _)| lower.ends_with(p.as_str())) {
+            return Some(sgr);
+        }
+        self.types.get("fi")
+    }
+}
+
+/// `LsColors::wrap` のバイト列版
+fn wrap_bytes(name: &[u8], sgr: Option<&String>) -> Vec<u8> {
+    match sgr {
+        Some(sgr) => [format!("\x1b[{}m", sgr).as_bytes(), name, b"\x1b[0m"].concat(),
+        None => name.to_vec(),
+    }
+}
@@ -2,17 +2,62 @@
 //!
 //! 各コマンドの実装はモジュールに分割している:
 //! - shell:    プロンプト表示・入力読み取り
-//! - commands: 各ビルトインコマンド（外部コマンドは実行しない）
+//! - commands: 各ビルトインコマンド（ライブラリ側、GUI版と共通）
 
-mod commands;
 mod shell;
 
+use simple_shell::{columns, commands, pipeline, sandbox, shell_words};
+use simple_shell::config::Config;
+use simple_shell::frecency::Frecency;
+use simple_shell::journal::Journal;
+use simple_shell::context::Context;
+use std::env;
+use std::io::{self, IsTerminal};
+use std::path::PathBuf;
+use std::process;
+
 fn main() {
+    // 起動時の引数は --root DIR（サンドボックス）だけ
+    let root = match sandbox::parse_args(env::args_os().skip(1)) {
+        Ok(root) => root,
+        Err(message) => {
+            eprintln!("taminal: {}", message);
+            eprintln!("Usage: taminal [--root DIR]");
+            process::exit(2);
+        }
+    };
+
+    let cwd = env::current_dir().unwrap_or_else(|_| PathBuf::from("/"));
+    let is_tty = io::stdout().is_terminal();
+    let mut ctx = Context::new(cwd, Box::new(io::stdout()), Box::new(io::stderr()), is_tty);
+    ctx.stdin = Box::new(io::stdin());
+    ctx.interaction = Box::new(shell::TerminalInteraction::new());
+    shell::handle_interrupt();
+
+    let (config, warnings) = Config::load();
+    for warning in warnings {
+        eprintln!("{}", warning);
+    }
+    ctx.journal = Journal::open(&config);
+    ctx.frecency = Frecency::open(&config);
+    let root = root.or_else(|| config.root.clone());
+    ctx.config = config;
+    // サンドボックスを作れなければ制限なしで続けず、終了する
+    if let Some(root) = root {
+        if let Err(e) = ctx.enter_sandbox(&root) {
+            eprintln!("taminal: cannot use '{}' as sandbox root: {}", root.display(), commands::error_message(&e));
+            process::exit(1);
+        }
+    }
+
     println!("Simple Terminal - Type 'exit' or 'quit' to exit");
     println!("Tip: Type 'help' to see available commands");
+    if let Some(root) = &ctx.sandbox {
+        println!("Sandbox: file access is limited to {}", root.display());
+    }
 
     loop {
-        shell::print_prompt();
+        shell::print_prompt(&ctx.prompt_label());
 
         // None は EOF (Ctrl+D)
         let input = match shell::read_input() {
@@ -27,25 +72,40 @@ fn main() {
             continue;
         }
 
-        // "rm -f file.txt" -> ["rm", "-f", "file.txt"]
-        let parts: Vec<&str> = input.split_whitespace().collect();
-        if parts.is_empty() {
-            continue;
-        }
-
-        let command = parts[0];
-        let args = &parts[1..];
+        // "rm -f 'my file.txt' | head; ls" -> [[["rm", "-f", "my file.txt"], ["head"]], [["ls"]]]
+        // `~` `~-` `~1` などはホームや直前のディレクトリ、ディレクトリスタックの項目に展開する
+        let list = match shell_words::split_list_expanding(&input, &|prefix| ctx.expand_tilde(prefix)) {
+            Ok(list) => list,
+            Err(e) => {
+                eprintln!("taminal: {}", e);
+                continue;
+            }
+        };
 
         // 終了だけはループ制御が絡むためここで処理する
-        if command == "exit" || command == "quit" {
-            println!("さようなら!");
-            break;
+        if let [item] = list.as_slice() {
+            if let [stage] = item.stages.as_slice() {
+                if stage[0] == "exit" || stage[0] == "quit" {
+                    println!("さようなら!");
+                    break;
+                }
+            }
         }
 
-        // それ以外はコマンドテーブルから名前で引いて実行する
-        if !commands::dispatch(command, args) {
-            eprintln!("{}: command not found", command);
-            eprintln!("Type 'help' to see available commands");
+        // それ以外はコマンドテーブルから名前で引いて実行する（`|` でつないだものはパイプラインで、
+        // `;` `&&` `||` でつないだものはコマンドリストで）
+        // 端末のサイズは変わりうるので、コマンドごとに取り直す
+        ctx.width = columns::terminal_width();
+        shell::clear_interrupt();
+        match list.as_slice() {
+            [] => {}
+            [item] => {
+                if let Err(command) = pipeline::run(&mut ctx, &item.stages) {
+                    eprintln!("{}: command not found", command);
+                    eprintln!("Type 'help' to see available commands");
+                }
+            }
+            _ => pipeline::run_list(&mut ctx, &list),
         }
     }
 }
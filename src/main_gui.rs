@@ -1,8 +1,21 @@
 mod gui;
 
 use eframe::egui;
+use simple_shell::sandbox;
+use std::env;
+use std::process;
 
 fn main() -> Result<(), eframe::Error> {
+    // 起動時の引数は --root DIR（サンドボックス）だけ
+    let root = match sandbox::parse_args(env::args_os().skip(1)) {
+        Ok(root) => root,
+        Err(message) => {
+            eprintln!("taminal_gui: {}", message);
+            eprintln!("Usage: taminal_gui [--root DIR]");
+            process::exit(2);
+        }
+    };
+
     // アプリケーションの設定
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
@@ -16,6 +29,6 @@ fn main() -> Result<(), eframe::Error> {
     eframe::run_native(
         "Taminal GUI",
         options,
-        Box::new(|cc| Ok(Box::new(gui::TerminalApp::new(cc)))),
+        Box::new(|cc| Ok(Box::new(gui::TerminalApp::new(cc, root)))),
     )
 }
\ No newline at end of file
@@ -0,0 +1,149 @@
+//! パーミッション指定（`chmod` 形式のモード）の解析と適用
+//!
+//! 8進数（`755`, `0644`）と記号形式（`u+x`, `go-w`, `a=rX`, `u=rw,g=u`）に対応する。
+//! `mkdir -m` や `chmod` で共通に使う。
+
+/// 解析済みのモード指定
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mode {
+    /// 8進数による絶対指定
+    Octal(u32),
+    /// 記号形式（カンマ区切りの節）
+    Symbolic(Vec<Clause>),
+}
+
+/// 記号形式の1節（`ug+rw-x` など）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Clause {
+    /// 対象（u/g/o の各ビット）。0 なら「指定なし」（umask を考慮した全員）
+    who: u32,
+    ops: Vec<Op>,
+}
+
+/// 1つの操作（`+rw` など）
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Op {
+    /// '+', '-', '='
+    op: char,
+    perms: Perms,
+}
+
+/// 操作で使うパーミッション
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Perms {
+    /// r/w/x/s/t の組み合わせ。`X` はディレクトリか実行ビットがある場合だけ x になる
+    Bits { bits: u32, conditional_x: bool },
+    /// 他の対象の現在のパーミッションをコピーする（`g=u` など）。値は u/g/o のいずれかのマスク
+    Copy(u32),
+}
+
+const USER: u32 = 0o4700;
+const GROUP: u32 = 0o2070;
+const OTHER: u32 = 0o1007;
+const ALL: u32 = 0o7777;
+
+impl Mode {
+    /// モード指定を解析する。不正なら None
+    pub fn parse(spec: &str) -> Option<Mode> {
+        if !spec.is_empty() && spec.bytes().all(|b| (b'0'..=b'7').contains(&b)) {
+            let value = u32::from_str_radix(spec, 8).ok()?;
+            return (value <= ALL).then_some(Mode::Octal(value));
+        }
+        spec.split(',').map(parse_clause).collect::<Option<Vec<_>>>().map(Mode::Symbolic)
+    }
+
+    /// 現在のモード `mode` に適用した結果を返す。
+    /// `umask` は対象（u/g/o/a）を省略した節にだけ効く
+    pub fn apply(&self, mode: u32, is_dir: bool, umask: u32) -> u32 {
+        let clauses = match self {
+            Mode::Octal(value) => return *value,
+            Mode::Symbolic(clauses) => clauses,
+        };
+
+        let mut mode = mode & ALL;
+        for clause in clauses {
+            let (who, filter) = if clause.who == 0 { (ALL, !umask) } else { (clause.who, ALL) };
+            for op in &clause.ops {
+                let bits = match op.perms {
+                    Perms::Bits { bits, conditional_x } => {
+                        let x = conditional_x && (is_dir || mode & 0o111 != 0);
+                        bits | if x { 0o111 } else { 0 }
+                    }
+                    Perms::Copy(class) => spread(mode, class),
+                };
+                let value = bits & who & filter;
+                mode = match op.op {
+                    '+' => mode | value,
+                    '-' => mode & !value,
+                    _ => (mode & !who) | value,
+                };
+            }
+        }
+        mode
+    }
+}
+
+/// `class`（u/g/o のいずれか）の rwx を全対象の位置に広げる
+fn spread(mode: u32, class: u32) -> u32 {
+    let rwx = match class {
+        USER => (mode >> 6) & 0o7,
+        GROUP => (mode >> 3) & 0o7,
+        _ => mode & 0o7,
+    };
+    rwx * 0o111
+}
+
+/// 1節（`[ugoa]*([-+=]([rwxXst]*|[ugo]))+`）を解析する
+fn parse_clause(clause: &str) -> Option<Clause> {
+    let mut chars = clause.chars().peekable();
+    let mut who = 0;
+    while let Some(&ch) = chars.peek() {
+        who |= match ch {
+            'u' => USER,
+            'g' => GROUP,
+            'o' => OTHER,
+            'a' => ALL,
+            _ => break,
+        };
+        chars.next();
+    }
+
+    let mut ops = Vec::new();
+    while let Some(op) = chars.next() {
+        if !matches!(op, '+' | '-' | '=') {
+            return None;
+        }
+        let perms = match chars.peek() {
+            Some('u') => Some(Perms::Copy(USER)),
+            Some('g') => Some(Perms::Copy(GROUP)),
+            Some('o') => Some(Perms::Copy(OTHER)),
+            _ => None,
+        };
+        let perms = match perms {
+            Some(perms) => {
+                chars.next();
+                perms
+            }
+            None => {
+                let mut bits = 0;
+                let mut conditional_x = false;
+                while let Some(&ch) = chars.peek() {
+                    match ch {
+                        'r' => bits |= 0o444,
+                        'w' => bits |= 0o222,
+                        'x' => bits |= 0o111,
+                        'X' => conditional_x = true,
+                        's' => bits |= 0o6000,
+                        't' => bits |= 0o1000,
+                        _ => break,
+                    }
+                    chars.next();
+                }
+                Perms::Bits { bits, conditional_x }
+            }
+        };
+        ops.push(Op { op, perms });
+    }
+
+    (!ops.is_empty()).then_some(Clause { who, ops })
+}
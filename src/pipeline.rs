@@ -0,0 +1,191 @@
+//! ビルトインコマンドのパイプライン（`cmd1 | cmd2 | ...`）
+//!
+//! 各段を別スレッドで同時に動かし、前の段の標準出力を OS のパイプで次の段の標準入力へつなぐ。
+//! 最後の段は呼び出し元の実行環境でそのまま動かすので、出力先やカレントディレクトリの変更は
+//! 単独で実行したときと同じになる。途中の段は `Context::fork` で作った環境で動き、
+//! cd などの変更は呼び出し元に残らない（シェルのサブシェルと同じ）。
+//!
+//! 後ろの段が入力を読み終える前に終わったら（`head` など）、前の段は書き込みが
+//! 失敗するのに加えて「中断が求められた」ものとして扱われ、`find` や `tail -f` も止まる。
+//! 標準エラーはすべての段で共有する。パイプラインの終了ステータスは最後の段のもの。
+//!
+//! `run_list` は `;` `&&` `||` でつないだコマンドリストを、終了ステータスを見ながら順に実行する。
+
+use crate::commands::{self, Command};
+use crate::context::{Context, Interaction, Progress};
+use crate::errln;
+use crate::shell_words::{Connector, ListItem};
+use std::ffi::OsString;
+use std::io::{self, BufWriter, Write};
+use std::mem;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// コマンドリストを実行する。`&&` `||` の後のパイプラインは直前の終了ステータスで実行するかを決め、
+/// 中断が求められたら残りは実行しない。ビルトインでないコマンドは "command not found" を表示して続ける
+pub fn run_list(ctx: &mut Context, list: &[ListItem]) {
+    for item in list {
+        let runs = match item.connector {
+            Connector::Always => true,
+            Connector::And => ctx.status == 0,
+            Connector::Or => ctx.status != 0,
+        };
+        if ctx.interrupted() {
+            break;
+        }
+        if runs {
+            if let Err(command) = run(ctx, &item.stages) {
+                errln!(ctx, "{}: command not found", command);
+            }
+        }
+    }
+}
+
+/// パイプラインを実行する。ビルトインでないコマンドが含まれていれば、
+/// 何も実行せずに終了ステータスを 127 にし、そのコマンド名を Err で返す
+pub fn run(ctx: &mut Context, stages: &[Vec<OsString>]) -> Result<(), String> {
+    let mut resolved: Vec<(&'static Command, &[OsString])> = Vec::new();
+    for stage in stages {
+        let Some(name) = stage.first() else {
+            continue;
+        };
+        let name = name.to_string_lossy();
+        match commands::find(&name) {
+            Some(cmd) => resolved.push((cmd, &stage[1..])),
+            None => {
+                ctx.status = 127;
+                return Err(name.into_owned());
+            }
+        }
+    }
+    let Some(((last, last_args), upstream)) = resolved.split_last() else {
+        return Ok(());
+    };
+    if upstream.is_empty() {
+        last.call(ctx, last_args);
+        return Ok(());
+    }
+
+    let stderr = SharedWriter(Arc::new(Mutex::new(mem::replace(&mut ctx.stderr, Box::new(io::sink())))));
+    ctx.stderr = Box::new(stderr.clone());
+    let mut input = mem::replace(&mut ctx.stdin, Box::new(io::empty()));
+    thread::scope(|scope| {
+        let mut handles = Vec::new();
+        for (i, (cmd, args)) in upstream.iter().enumerate() {
+            let (reader, writer) = match io::pipe() {
+                Ok(pipe) => pipe,
+                Err(e) => {
+                    errln!(ctx, "taminal: cannot create pipe: {}", commands::error_message(&e));
+                    break;
+                }
+            };
+            let closed = Arc::new(AtomicBool::new(false));
+            let stdout = PipeWriter { writer: BufWriter::new(writer), closed: closed.clone() };
+            let mut stage = ctx.fork(input, Box::new(stdout), Box::new(stderr.clone()));
+            stage.interaction = Box::new(StageInteraction { inner: stage.interaction, closed });
+            input = Box::new(reader);
+            handles.push(scope.spawn(move || {
+                cmd.call(&mut stage, args);
+                let _ = stage.stdout.flush();
+                // 書き込み側と読み込み側をすぐに閉じ、前後の段に終わりを知らせる
+                drop(mem::replace(&mut stage.stdout, Box::new(io::sink())));
+                let stdin = mem::replace(&mut stage.stdin, Box::new(io::empty()));
+                (if i == 0 { Some(stdin) } else { None }, stage.trashed)
+            }));
+        }
+        // パイプを作れなかったときは、途中までの段の出力を読み捨てて終わらせる
+        if handles.len() == upstream.len() {
+            ctx.stdin = input;
+            last.call(ctx, last_args);
+        } else {
+            let _ = io::copy(&mut input, &mut io::sink());
+        }
+        ctx.stdin = Box::new(io::empty());
+        for handle in handles {
+            match handle.join() {
+                Ok((stdin, trashed)) => {
+                    if let Some(stdin) = stdin {
+                        ctx.stdin = stdin;
+                    }
+                    ctx.trashed.extend(trashed);
+                }
+                Err(_) => errln!(ctx, "taminal: a pipeline command panicked"),
+            }
+        }
+    });
+    ctx.stderr = Box::new(io::sink());
+    if let Ok(inner) = Arc::try_unwrap(stderr.0) {
+        ctx.stderr = inner.into_inner().unwrap_or_else(|e| e.into_inner());
+    }
+    Ok(())
+}
+
+/// 次の段へつながるパイプ。行ごとの書き込みで遅くならないようバッファしてまとめて送る
+/// （`tail -f` のように出力を待たせたくないコマンドは flush する）。
+/// 次の段が先に終わったら `closed` を立てる
+struct PipeWriter {
+    writer: BufWriter<io::PipeWriter>,
+    closed: Arc<AtomicBool>,
+}
+
+impl Write for PipeWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let result = self.writer.write(buf);
+        if matches!(&result, Err(e) if e.kind() == io::ErrorKind::BrokenPipe) {
+            self.closed.store(true, Ordering::SeqCst);
+        }
+        result
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let result = self.writer.flush();
+        if matches!(&result, Err(e) if e.kind() == io::ErrorKind::BrokenPipe) {
+            self.closed.store(true, Ordering::SeqCst);
+        }
+        result
+    }
+}
+
+/// 複数の段で共有する書き込み先（標準エラー）
+#[derive(Clone)]
+struct SharedWriter(Arc<Mutex<Box<dyn Write + Send>>>);
+
+impl Write for SharedWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).write(buf)
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        // 1回分の書き込みが他の段の出力と混ざらないようにする
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).write_all(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).flush()
+    }
+}
+
+/// 途中の段のやりとり。次の段が終わったら中断が求められたものとして扱う
+struct StageInteraction {
+    inner: Box<dyn Interaction>,
+    closed: Arc<AtomicBool>,
+}
+
+impl Interaction for StageInteraction {
+    fn confirm(&mut self, question: &str) -> bool {
+        self.inner.confirm(question)
+    }
+
+    fn progress(&mut self, progress: Option<&Progress>) {
+        self.inner.progress(progress)
+    }
+
+    fn interrupted(&self) -> bool {
+        self.closed.load(Ordering::SeqCst) || self.inner.interrupted()
+    }
+
+    fn fork(&self) -> Box<dyn Interaction> {
+        self.inner.fork()
+    }
+}
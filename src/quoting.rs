@@ -0,0 +1,217 @@
+//! ファイル名の表示用クォート（GNU `ls --quoting-style` 相当）
+//!
+//! UTF-8 として不正なバイトや制御文字を含むファイル名も、元のバイト列に
+//! 戻せる形で表示する。`shell-escape` 形式の出力はそのままコマンド引数として
+//! 入力すれば元の名前に戻る（`shell_words::split` が `$'\377'` を解釈する）。
+
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+
+/// クォートの形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuotingStyle {
+    /// そのまま表示する（表示できない文字は `?`）
+    Literal,
+    /// 必要なときだけ '...' で囲む（表示できない文字は `?`）
+    Shell,
+    /// 常に '...' で囲む
+    ShellAlways,
+    /// 必要なときだけ '...' で囲み、表示できない文字は $'\ooo' で表す
+    ShellEscape,
+    /// 常に '...' で囲み、表示できない文字は $'\ooo' で表す
+    ShellEscapeAlways,
+    /// "..." で囲み、C言語のエスケープで表す
+    C,
+    /// C言語のエスケープで表す（囲まない。空白も `\ ` にする）
+    Escape,
+}
+
+impl QuotingStyle {
+    /// `--quoting-style=WORD` の WORD を解釈する
+    pub fn parse(word: &str) -> Option<QuotingStyle> {
+        match word {
+            "literal" => Some(QuotingStyle::Literal),
+            "shell" => Some(QuotingStyle::Shell),
+            "shell-always" => Some(QuotingStyle::ShellAlways),
+            "shell-escape" => Some(QuotingStyle::ShellEscape),
+            "shell-escape-always" => Some(QuotingStyle::ShellEscapeAlways),
+            "c" => Some(QuotingStyle::C),
+            "escape" => Some(QuotingStyle::Escape),
+            _ => None,
+        }
+    }
+}
+
+/// 名前の断片。表示できる文字の並びか、表示できないバイトの並び
+enum Piece<'a> {
+    Text(&'a str),
+    Raw(&'a [u8]),
+}
+
+/// バイト列を「表示できる文字列」と「そうでないバイト列」に分割する
+fn pieces(bytes: &[u8]) -> Vec<Piece<'_>> {
+    let mut result = Vec::new();
+    let mut rest = bytes;
+
+    while !rest.is_empty() {
+        let (valid, invalid_len) = match std::str::from_utf8(rest) {
+            Ok(s) => (s, 0),
+            Err(e) => {
+                let valid = std::str::from_utf8(&rest[..e.valid_up_to()]).unwrap_or_default();
+                (valid, e.error_len().unwrap_or(rest.len() - e.valid_up_to()))
+            }
+        };
+
+        // 正しい UTF-8 の中でも制御文字は表示できないバイトとして扱う
+        let mut start = 0;
+        for (i, ch) in valid.char_indices() {
+            if ch.is_control() {
+                if start < i {
+                    result.push(Piece::Text(&valid[start..i]));
+                }
+                result.push(Piece::Raw(&valid.as_bytes()[i..i + ch.len_utf8()]));
+                start = i + ch.len_utf8();
+            }
+        }
+        if start < valid.len() {
+            result.push(Piece::Text(&valid[start..]));
+        }
+
+        let consumed = valid.len();
+        if invalid_len > 0 {
+            result.push(Piece::Raw(&rest[consumed..consumed + invalid_len]));
+        }
+        rest = &rest[consumed + invalid_len..];
+    }
+
+    result
+}
+
+/// シェルで特別な意味を持つ（クォートが必要な）文字か
+fn is_shell_special(ch: char) -> bool {
+    ch.is_whitespace() || "!\"#$&'()*;<=>?[\\]^`{|}~".contains(ch)
+}
+
+/// 名前を指定の形式でクォートする
+pub fn quote(name: &OsStr, style: QuotingStyle) -> String {
+    let bytes = name.as_bytes();
+    let parts = pieces(bytes);
+    let has_raw = parts.iter().any(|p| matches!(p, Piece::Raw(_)));
+
+    match style {
+        QuotingStyle::Literal => replace_raw(&parts),
+        QuotingStyle::Shell | QuotingStyle::ShellAlways => {
+            let text = replace_raw(&parts);
+            if style == QuotingStyle::ShellAlways || needs_shell_quotes(&text) {
+                single_quote(&text)
+            } else {
+                text
+            }
+        }
+        QuotingStyle::ShellEscape | QuotingStyle::ShellEscapeAlways => {
+            if !has_raw {
+                let text = replace_raw(&parts);
+                return if style == QuotingStyle::ShellEscapeAlways || needs_shell_quotes(&text) {
+                    single_quote(&text)
+                } else {
+                    text
+                };
+            }
+            // 'abc'$'\377''def' のように、表示できない部分だけ $'...' で表す
+            let mut out = String::new();
+            for part in &parts {
+                match part {
+                    Piece::Text(text) => out.push_str(&single_quote(text)),
+                    Piece::Raw(raw) => {
+                        out.push_str("$'");
+                        for &b in raw.iter() {
+                            out.push_str(&c_escape_byte(b));
+                        }
+                        out.push('\'');
+                    }
+                }
+            }
+            out
+        }
+        QuotingStyle::C => format!("\"{}\"", c_escape(&parts, false)),
+        QuotingStyle::Escape => c_escape(&parts, true),
+    }
+}
+
+/// `quote` のバイト列版。`literal` で `hide_control` が false なら名前のバイト列をそのまま返す
+/// （GNU ls と同じく、端末以外へは `?` に置き換えずに元の名前を書き出す）
+pub fn quote_bytes(name: &OsStr, style: QuotingStyle, hide_control: bool) -> Vec<u8> {
+    if style == QuotingStyle::Literal && !hide_control {
+        return name.as_bytes().to_vec();
+    }
+    quote(name, style).into_bytes()
+}
+
+/// エラーメッセージなどに埋め込むための表示。
+/// 表示できる名前はそのまま、そうでなければ shell-escape 形式にする
+pub fn display(name: &OsStr) -> String {
+    match name.to_str() {
+        Some(s) if !s.chars().any(char::is_control) => s.to_string(),
+        _ => quote(name, QuotingStyle::ShellEscape),
+    }
+}
+
+/// クォートが必要か（空文字列やシェルの特殊文字を含む場合）
+fn needs_shell_quotes(text: &str) -> bool {
+    text.is_empty() || text.chars().any(is_shell_special)
+}
+
+/// '...' で囲む。中の ' は '\'' にする
+fn single_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+/// 表示できない部分を ? に置き換えた文字列
+fn replace_raw(parts: &[Piece]) -> String {
+    parts
+        .iter()
+        .map(|part| match part {
+            Piece::Text(text) => text.to_string(),
+            Piece::Raw(raw) => "?".repeat(raw.len()),
+        })
+        .collect()
+}
+
+/// C言語形式でエスケープする。`escape_space` なら空白も `\ ` にする
+fn c_escape(parts: &[Piece], escape_space: bool) -> String {
+    let mut out = String::new();
+    for part in parts {
+        match part {
+            Piece::Text(text) => {
+                for ch in text.chars() {
+                    match ch {
+                        '\\' => out.push_str("\\\\"),
+                        '"' if !escape_space => out.push_str("\\\""),
+                        ' ' if escape_space => out.push_str("\\ "),
+                        _ => out.push(ch),
+                    }
+                }
+            }
+            Piece::Raw(raw) => {
+                for &b in raw.iter() {
+                    out.push_str(&c_escape_byte(b));
+                }
+            }
+        }
+    }
+    out
+}
+
+/// 1バイトを C言語形式のエスケープにする（\n などの短縮形がなければ8進3桁）
+fn c_escape_byte(b: u8) -> String {
+    match b {
+        b'\x07' => "\\a".to_string(),
+        b'\x08' => "\\b".to_string(),
+        b'\x0c' => "\\f".to_string(),
+        b'\n' => "\\n".to_string(),
+        b'\r' => "\\r".to_string(),
+        b'\t' => "\\t".to_string(),
+        b'\x0b' => "\\v".to_string(),
+        _ => format!("\\{:03o}", b),
+    }
+}
@@ -0,0 +1,554 @@
+//! サンドボックス。ビルトインが扱えるファイルを1つのディレクトリ（ルート）以下に制限する
+//!
+//! 起動時に `--root DIR` を指定するか、設定ファイルに `root = DIR` と書くと有効になる。
+//! `SandboxFs` はパスをルートから1要素ずつ openat(2) でたどり、シンボリックリンクも自分で解決する。
+//! `..` やリンクでルートの外へ出ようとすると「permission denied by sandbox」のエラーになる。
+//!
+//! たどった後の操作はすべてディレクトリの fd を基準にした `*at` 系のシステムコールで行い、
+//! 最後の要素もシンボリックリンクをたどらずに開く。確かめた後でパスの一部をリンクに
+//! 差し替えられても、ルートの外には届かない。
+//! ゴミ箱と undo の退避場所はルートの外にあるため、サンドボックスの中では使えない
+//! （`Fs::is_native` が false になる）。
+
+use crate::fs_util::{self, FileTime};
+use crate::vfs::{FileReader, Fs, Stat};
+use std::collections::VecDeque;
+use std::ffi::{CStr, CString, OsStr, OsString};
+use std::fs::File;
+use std::io::{self, Write};
+use std::os::fd::{AsRawFd, FromRawFd, IntoRawFd, OwnedFd, RawFd};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::{Component, Path, PathBuf};
+
+/// シンボリックリンクをたどる回数の上限（Linux の MAXSYMLINKS と同じ）
+const MAX_SYMLINKS: usize = 40;
+
+/// サンドボックスの外へのアクセスを拒んだときのエラー
+pub fn denied() -> io::Error {
+    io::Error::new(io::ErrorKind::PermissionDenied, "permission denied by sandbox")
+}
+
+/// 起動時の引数から `--root DIR`（`--root=DIR`）を取り出す。
+/// 解釈できない引数があればエラーメッセージを返す
+pub fn parse_args(args: impl IntoIterator<Item = OsString>) -> Result<Option<PathBuf>, String> {
+    let mut root = None;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--root" {
+            match args.next() {
+                Some(dir) => root = Some(PathBuf::from(dir)),
+                None => return Err("option '--root' requires an argument".to_string()),
+            }
+        } else if let Some(dir) = arg.as_bytes().strip_prefix(b"--root=") {
+            root = Some(PathBuf::from(OsStr::from_bytes(dir)));
+        } else {
+            return Err(format!("unrecognized argument '{}'", arg.to_string_lossy()));
+        }
+    }
+    Ok(root)
+}
+
+/// ルート以下だけを扱える実際のファイルシステム
+pub struct SandboxFs {
+    /// ルート（シンボリックリンクを含まない絶対パス）
+    root: PathBuf,
+    root_fd: OwnedFd,
+}
+
+/// パスをたどった結果。最後にたどったディレクトリの中の `name` を指す
+struct Resolved<'a> {
+    sandbox: &'a SandboxFs,
+    /// ルートからたどったディレクトリ（fd と名前）
+    dirs: Vec<(OwnedFd, OsString)>,
+    /// 最後の要素の名前。パスがディレクトリ自体を指すなら "."
+    name: CString,
+}
+
+impl Resolved<'_> {
+    /// 最後にたどったディレクトリの fd
+    fn dir(&self) -> RawFd {
+        match self.dirs.last() {
+            Some((fd, _)) => fd.as_raw_fd(),
+            None => self.sandbox.root_fd.as_raw_fd(),
+        }
+    }
+
+    /// シンボリックリンクを含まない絶対パス
+    fn path(&self) -> PathBuf {
+        let mut path = self.sandbox.root.clone();
+        path.extend(self.dirs.iter().map(|(_, name)| name));
+        if self.name.as_bytes() != b"." {
+            path.push(OsStr::from_bytes(self.name.as_bytes()));
+        }
+        path
+    }
+
+    /// 最後の要素を開く（シンボリックリンクはたどらない）
+    fn open(&self, flags: libc::c_int, mode: u32) -> io::Result<OwnedFd> {
+        open_at(self.dir(), &self.name, flags | libc::O_NOFOLLOW, mode)
+    }
+
+    /// 最後の要素の属性
+    fn stat(&self) -> io::Result<Stat> {
+        let file = File::from(self.open(libc::O_PATH, 0)?);
+        file.metadata().map(Stat::from)
+    }
+}
+
+impl SandboxFs {
+    /// `root` 以下に制限する。`root` はディレクトリでなければならない
+    pub fn new(root: &Path) -> io::Result<Self> {
+        let root = root.canonicalize()?;
+        let c_root = fs_util::c_path(&root)?;
+        let root_fd = open_at(libc::AT_FDCWD, &c_root, libc::O_PATH | libc::O_DIRECTORY, 0)?;
+        Ok(SandboxFs { root, root_fd })
+    }
+
+    /// ルートのパス
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// 絶対パス `path` のルートより下の要素。ルートの外ならエラー
+    fn below_root(&self, path: &Path) -> io::Result<VecDeque<OsString>> {
+        let mut components = path.components().filter(|c| *c != Component::CurDir);
+        for expected in self.root.components() {
+            if components.next() != Some(expected) {
+                return Err(denied());
+            }
+        }
+        Ok(components.map(|c| c.as_os_str().to_os_string()).collect())
+    }
+
+    /// `path` をルートからたどる。`follow` なら最後の要素がシンボリックリンクのときもたどる。
+    /// 最後の要素は存在しなくてもよい（作る操作のため）
+    fn resolve(&self, path: &Path, follow: bool) -> io::Result<Resolved<'_>> {
+        let mut rest = self.below_root(path)?;
+        let mut dirs: Vec<(OwnedFd, OsString)> = Vec::new();
+        let mut links = 0;
+        let resolved = |dirs, name: &OsStr| Ok(Resolved { sandbox: self, dirs, name: c_name(name)? });
+
+        while let Some(name) = rest.pop_front() {
+            if name == ".." {
+                if dirs.pop().is_none() {
+                    return Err(denied());
+                }
+                continue;
+            }
+            let is_last = rest.is_empty();
+            if is_last && !follow {
+                return resolved(dirs, &name);
+            }
+
+            let dir = dirs.last().map_or(self.root_fd.as_raw_fd(), |(fd, _)| fd.as_raw_fd());
+            let c_name = c_name(&name)?;
+            let mode = match stat_at(dir, &c_name) {
+                Ok(mode) => mode,
+                Err(e) if is_last && e.raw_os_error() == Some(libc::ENOENT) => return resolved(dirs, &name),
+                Err(e) => return Err(e),
+            };
+            match mode & libc::S_IFMT {
+                libc::S_IFLNK => {
+                    links += 1;
+                    if links > MAX_SYMLINKS {
+                        return Err(io::Error::from_raw_os_error(libc::ELOOP));
+                    }
+                    let target = read_link_at(dir, &c_name)?;
+                    // リンク先の要素を残りの要素の前に置いてたどり直す
+                    let mut parts = if target.is_absolute() {
+                        dirs.clear();
+                        self.below_root(&target)?
+                    } else {
+                        target
+                            .components()
+                            .filter(|c| *c != Component::CurDir)
+                            .map(|c| c.as_os_str().to_os_string())
+                            .collect()
+                    };
+                    parts.append(&mut rest);
+                    rest = parts;
+                }
+                libc::S_IFDIR if !is_last => {
+                    let fd = open_at(dir, &c_name, libc::O_PATH | libc::O_DIRECTORY | libc::O_NOFOLLOW, 0)?;
+                    dirs.push((fd, name));
+                }
+                _ if is_last => return resolved(dirs, &name),
+                _ => return Err(io::Error::from_raw_os_error(libc::ENOTDIR)),
+            }
+        }
+        resolved(dirs, OsStr::new("."))
+    }
+}
+
+impl Fs for SandboxFs {
+    fn metadata(&self, path: &Path) -> io::Result<Stat> {
+        self.resolve(path, true)?.stat()
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<Stat> {
+        self.resolve(path, false)?.stat()
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<OsString>> {
+        let fd = self.resolve(path, true)?.open(libc::O_RDONLY | libc::O_DIRECTORY, 0)?;
+        read_names(fd)
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        let resolved = self.resolve(path, false)?;
+        read_link_at(resolved.dir(), &resolved.name)
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        let resolved = self.resolve(path, true)?;
+        resolved.stat()?;
+        Ok(resolved.path())
+    }
+
+    fn create_dir(&self, path: &Path, mode: u32) -> io::Result<()> {
+        let resolved = self.resolve(path, false)?;
+        check(unsafe { libc::mkdirat(resolved.dir(), resolved.name.as_ptr(), mode as libc::mode_t) })
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        let resolved = self.resolve(path, false)?;
+        check(unsafe { libc::unlinkat(resolved.dir(), resolved.name.as_ptr(), 0) })
+    }
+
+    fn remove_dir(&self, path: &Path) -> io::Result<()> {
+        let resolved = self.resolve(path, false)?;
+        check(unsafe { libc::unlinkat(resolved.dir(), resolved.name.as_ptr(), libc::AT_REMOVEDIR) })
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let (from, to) = (self.resolve(from, false)?, self.resolve(to, false)?);
+        check(unsafe { libc::renameat(from.dir(), from.name.as_ptr(), to.dir(), to.name.as_ptr()) })
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn FileReader>> {
+        let fd = self.resolve(path, true)?.open(libc::O_RDONLY, 0)?;
+        Ok(Box::new(File::from(fd)))
+    }
+
+    fn create(&self, path: &Path, mode: u32) -> io::Result<Box<dyn Write + Send>> {
+        let flags = libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC;
+        let fd = self.resolve(path, true)?.open(flags, mode)?;
+        Ok(Box::new(File::from(fd)))
+    }
+
+    fn create_new(&self, path: &Path, mode: u32) -> io::Result<Box<dyn Write + Send>> {
+        // O_EXCL は最後の要素がシンボリックリンクでもたどらない
+        let flags = libc::O_WRONLY | libc::O_CREAT | libc::O_EXCL;
+        let fd = self.resolve(path, false)?.open(flags, mode)?;
+        Ok(Box::new(File::from(fd)))
+    }
+
+    fn symlink(&self, target: &Path, path: &Path) -> io::Result<()> {
+        // リンク先は作るときには確かめない（たどるときに確かめる）
+        let resolved = self.resolve(path, false)?;
+        let target = fs_util::c_path(target)?;
+        check(unsafe { libc::symlinkat(target.as_ptr(), resolved.dir(), resolved.name.as_ptr()) })
+    }
+
+    fn hard_link(&self, original: &Path, link: &Path) -> io::Result<()> {
+        let (original, link) = (self.resolve(original, false)?, self.resolve(link, false)?);
+        check(unsafe { libc::linkat(original.dir(), original.name.as_ptr(), link.dir(), link.name.as_ptr(), 0) })
+    }
+
+    fn set_permissions(&self, path: &Path, mode: u32) -> io::Result<()> {
+        let resolved = self.resolve(path, true)?;
+        let (dir, name) = (resolved.dir(), resolved.name.as_ptr());
+        match check(unsafe { libc::fchmodat(dir, name, mode as libc::mode_t, libc::AT_SYMLINK_NOFOLLOW) }) {
+            // glibc 2.32 より前や fchmodat2 のないカーネルでは AT_SYMLINK_NOFOLLOW に対応していない
+            Err(e) if matches!(e.raw_os_error(), Some(libc::EOPNOTSUPP | libc::ENOSYS)) => chmod_nofollow(&resolved, mode),
+            result => result,
+        }
+    }
+
+    fn set_times(
+        &self,
+        path: &Path,
+        atime: Option<FileTime>,
+        mtime: Option<FileTime>,
+        follow_symlinks: bool,
+    ) -> io::Result<()> {
+        let resolved = self.resolve(path, follow_symlinks)?;
+        fs_util::set_times_at(resolved.dir(), &resolved.name, atime, mtime, false)
+    }
+
+    fn set_owner(&self, path: &Path, uid: Option<u32>, gid: Option<u32>, follow_symlinks: bool) -> io::Result<()> {
+        let resolved = self.resolve(path, follow_symlinks)?;
+        // -1 は変更しない
+        let uid = uid.map_or(libc::uid_t::MAX, |uid| uid as libc::uid_t);
+        let gid = gid.map_or(libc::gid_t::MAX, |gid| gid as libc::gid_t);
+        let (dir, name) = (resolved.dir(), resolved.name.as_ptr());
+        check(unsafe { libc::fchownat(dir, name, uid, gid, libc::AT_SYMLINK_NOFOLLOW) })
+    }
+}
+
+/// `fchmodat(AT_SYMLINK_NOFOLLOW)` が使えないときの代わり。最後の要素を O_PATH でリンクをたどらずに開き、
+/// シンボリックリンクでないことを確かめてから、その fd が指すファイルのパーミッションを変える。
+/// O_PATH の fd には fchmod できないので `/proc/self/fd/N` を通す（開いた後に差し替えられても同じファイルを指す）
+fn chmod_nofollow(resolved: &Resolved, mode: u32) -> io::Result<()> {
+    let fd = resolved.open(libc::O_PATH, 0)?;
+    let file = File::from(fd);
+    if file.metadata()?.file_type().is_symlink() {
+        return Err(io::Error::from_raw_os_error(libc::EOPNOTSUPP));
+    }
+    let proc_path = CString::new(format!("/proc/self/fd/{}", file.as_raw_fd())).expect("no NUL byte");
+    check(unsafe { libc::chmod(proc_path.as_ptr(), mode as libc::mode_t) })
+}
+
+/// システムコールの戻り値をエラーに変換する
+fn check(ret: libc::c_int) -> io::Result<()> {
+    if ret < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// ファイル名を C 文字列に変換する
+fn c_name(name: &OsStr) -> io::Result<CString> {
+    CString::new(name.as_bytes())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "path contains a NUL byte"))
+}
+
+/// ディレクトリ `dir` の中の `name` を開く
+fn open_at(dir: RawFd, name: &CStr, flags: libc::c_int, mode: u32) -> io::Result<OwnedFd> {
+    let fd = unsafe { libc::openat(dir, name.as_ptr(), flags | libc::O_CLOEXEC, mode as libc::c_uint) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+/// ディレクトリ `dir` の中の `name` の種類とパーミッション（st_mode）。リンクはたどらない
+fn stat_at(dir: RawFd, name: &CStr) -> io::Result<u32> {
+    let mut st: libc::stat = unsafe { std::mem::zeroed() };
+    check(unsafe { libc::fstatat(dir, name.as_ptr(), &mut st, libc::AT_SYMLINK_NOFOLLOW) })?;
+    Ok(st.st_mode)
+}
+
+/// ディレクトリ `dir` の中のシンボリックリンク `name` のリンク先
+fn read_link_at(dir: RawFd, name: &CStr) -> io::Result<PathBuf> {
+    let mut buf = vec![0u8; libc::PATH_MAX as usize];
+    loop {
+        let len = unsafe { libc::readlinkat(dir, name.as_ptr(), buf.as_mut_ptr().cast(), buf.len()) };
+        if len < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let len = len as usize;
+        // 切り詰められた可能性があれば大きなバッファで読み直す
+        if len < buf.len() {
+            buf.truncate(len);
+            return Ok(PathBuf::from(OsString::from_vec(buf)));
+        }
+        buf.resize(buf.len() * 2, 0);
+    }
+}
+
+/// 開いたディレクトリ内の名前（`.` と `..` を除く）
+fn read_names(fd: OwnedFd) -> io::Result<Vec<OsString>> {
+    let raw = fd.into_raw_fd();
+    let dir = unsafe { libc::fdopendir(raw) };
+    if dir.is_null() {
+        let e = io::Error::last_os_error();
+        unsafe { libc::close(raw) };
+        return Err(e);
+    }
+
+    let mut names = Vec::new();
+    let result = loop {
+        // readdir は終端とエラーをどちらも NULL で返すので errno で区別する
+        unsafe { *libc::__errno_location() = 0 };
+        let entry = unsafe { libc::readdir(dir) };
+        if entry.is_null() {
+            let e = io::Error::last_os_error();
+            break if e.raw_os_error() == Some(0) { Ok(()) } else { Err(e) };
+        }
+        let name = unsafe { CStr::from_ptr((*entry).d_name.as_ptr()) }.to_bytes();
+        if name != b"." && name != b".." {
+            names.push(OsStr::from_bytes(name).to_os_string());
+        }
+    };
+    unsafe { libc::closedir(dir) };
+    result.map(|()| names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::{symlink, PermissionsExt};
+
+    /// テストごとの一時ディレクトリ。`jail` をルートにし、その隣の `outside` に外のファイルを置く
+    struct Jail {
+        base: PathBuf,
+        fs: SandboxFs,
+    }
+
+    impl Jail {
+        fn new(name: &str) -> Self {
+            let base = std::env::temp_dir().join(format!("taminal-sandbox-{}-{}", std::process::id(), name));
+            let _ = fs::remove_dir_all(&base);
+            fs::create_dir_all(base.join("jail/dir/sub")).unwrap();
+            fs::create_dir_all(base.join("outside")).unwrap();
+            fs::write(base.join("jail/dir/file"), "inside").unwrap();
+            fs::write(base.join("outside/secret"), "secret").unwrap();
+            let fs = SandboxFs::new(&base.join("jail")).unwrap();
+            Jail { base, fs }
+        }
+
+        /// ルートの中のパス
+        fn path(&self, rel: &str) -> PathBuf {
+            self.fs.root().join(rel)
+        }
+
+        fn link(&self, target: impl AsRef<Path>, rel: &str) {
+            symlink(target, self.path(rel)).unwrap();
+        }
+
+        fn outside(&self) -> PathBuf {
+            self.base.join("outside").canonicalize().unwrap()
+        }
+    }
+
+    impl Drop for Jail {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.base);
+        }
+    }
+
+    fn assert_denied<T>(result: io::Result<T>, what: &str) {
+        match result {
+            Ok(_) => panic!("{}: succeeded", what),
+            Err(e) => assert_eq!(e.to_string(), "permission denied by sandbox", "{}", what),
+        }
+    }
+
+    /// `path` へのあらゆる操作がサンドボックスのエラーになり、外のファイルが変わらないことを確かめる
+    fn assert_all_denied(jail: &Jail, path: &Path) {
+        let fs = &jail.fs;
+        let secret = path.join("secret");
+        assert_denied(fs.metadata(&secret), "metadata");
+        assert_denied(fs.read_dir(path), "read_dir");
+        assert_denied(fs.canonicalize(&secret), "canonicalize");
+        assert_denied(fs.open(&secret), "open");
+        assert_denied(fs.create(&secret, 0o644), "create");
+        assert_denied(fs.create_new(&path.join("new"), 0o644), "create_new");
+        assert_denied(fs.create_dir(&path.join("newdir"), 0o755), "create_dir");
+        assert_denied(fs.remove_file(&secret), "remove_file");
+        assert_denied(fs.rename(&secret, &jail.path("stolen")), "rename");
+        assert_denied(fs.hard_link(&secret, &jail.path("hard")), "hard_link");
+        assert_denied(fs.set_permissions(&secret, 0o777), "set_permissions");
+        assert_denied(fs.set_times(&secret, None, None, true), "set_times");
+        assert_denied(fs.set_owner(&secret, None, None, true), "set_owner");
+
+        let outside = jail.outside();
+        assert_eq!(fs::read_to_string(outside.join("secret")).unwrap(), "secret");
+        assert_ne!(fs::metadata(outside.join("secret")).unwrap().permissions().mode() & 0o777, 0o777);
+        assert!(!outside.join("new").exists() && !outside.join("newdir").exists());
+    }
+
+    #[test]
+    fn paths_inside_the_root_work() {
+        let jail = Jail::new("inside");
+        let fs = &jail.fs;
+        assert!(fs.is_dir(&jail.path("dir/sub")));
+        assert_eq!(fs.canonicalize(&jail.path("dir/sub/../file")).unwrap(), jail.path("dir/file"));
+        assert_eq!(fs.read_dir(&jail.path("dir")).unwrap().len(), 2);
+
+        // ルートの中を指すリンク（絶対パスも相対パスも）はたどれる
+        jail.link(jail.path("dir"), "abs_in");
+        jail.link("dir/sub/..", "rel_in");
+        assert_eq!(fs.canonicalize(&jail.path("abs_in/file")).unwrap(), jail.path("dir/file"));
+        assert_eq!(fs.canonicalize(&jail.path("rel_in/file")).unwrap(), jail.path("dir/file"));
+
+        fs.set_permissions(&jail.path("dir/file"), 0o600).unwrap();
+        assert_eq!(fs.metadata(&jail.path("dir/file")).unwrap().mode() & 0o777, 0o600);
+    }
+
+    #[test]
+    fn dot_dot_above_the_root_is_denied() {
+        let jail = Jail::new("dotdot");
+        assert_all_denied(&jail, &jail.path("../outside"));
+        assert_all_denied(&jail, &jail.path("dir/../../outside"));
+        assert_denied(jail.fs.metadata(&jail.path("..")), "metadata ..");
+        // ルートで始まらない絶対パス
+        assert_all_denied(&jail, &jail.outside());
+    }
+
+    #[test]
+    fn absolute_symlinks_out_of_the_root_are_denied() {
+        let jail = Jail::new("abs");
+        jail.link(jail.outside(), "escape");
+        jail.link(jail.outside(), "dir/escape");
+        assert_all_denied(&jail, &jail.path("escape"));
+        assert_all_denied(&jail, &jail.path("dir/escape"));
+    }
+
+    #[test]
+    fn relative_symlinks_through_dot_dot_are_denied() {
+        let jail = Jail::new("rel");
+        jail.link("../outside", "up");
+        jail.link("../../outside", "dir/up");
+        jail.link("sub/../../..", "dir/around");
+        assert_all_denied(&jail, &jail.path("up"));
+        assert_all_denied(&jail, &jail.path("dir/up"));
+        assert_all_denied(&jail, &jail.path("dir/around/outside"));
+    }
+
+    #[test]
+    fn symlink_loops_fail_with_eloop() {
+        let jail = Jail::new("loop");
+        jail.link("loop2", "loop1");
+        jail.link("loop1", "loop2");
+        jail.link("self/x", "self");
+        for name in ["loop1", "self", "loop1/file"] {
+            let err = jail.fs.metadata(&jail.path(name)).unwrap_err();
+            assert_eq!(err.raw_os_error(), Some(libc::ELOOP), "{}", name);
+            let err = jail.fs.create(&jail.path(name), 0o644).err().and_then(|e| e.raw_os_error());
+            assert_eq!(err, Some(libc::ELOOP), "{}", name);
+        }
+    }
+
+    #[test]
+    fn operations_without_follow_act_on_the_link_itself() {
+        let jail = Jail::new("nofollow");
+        let secret = jail.outside().join("secret");
+        jail.link(&secret, "link");
+        let fs = &jail.fs;
+        let link = jail.path("link");
+
+        // リンク自体は中にあるので調べられるが、たどる操作は拒む
+        assert!(fs.symlink_metadata(&link).unwrap().is_symlink());
+        assert_eq!(fs.read_link(&link).unwrap(), secret);
+        assert_denied(fs.metadata(&link), "metadata");
+        assert_denied(fs.open(&link), "open");
+        assert_denied(fs.create(&link, 0o644), "create");
+        assert_denied(fs.set_permissions(&link, 0o777), "set_permissions");
+        assert_denied(fs.set_times(&link, None, None, true), "set_times");
+
+        // create_new はリンクをたどらずに失敗する
+        assert_eq!(fs.create_new(&link, 0o644).err().and_then(|e| e.raw_os_error()), Some(libc::EEXIST));
+        fs.set_times(&link, None, None, false).unwrap();
+        fs.set_owner(&link, None, None, false).unwrap();
+        fs.rename(&link, &jail.path("renamed")).unwrap();
+        fs.remove_file(&jail.path("renamed")).unwrap();
+        assert_eq!(std::fs::read_to_string(&secret).unwrap(), "secret");
+    }
+
+    #[test]
+    fn chmod_fallback_changes_the_file_but_not_symlinks() {
+        let jail = Jail::new("chmod");
+        jail.link("dir/file", "link");
+        let file = jail.fs.resolve(&jail.path("dir/file"), false).unwrap();
+        chmod_nofollow(&file, 0o640).unwrap();
+        assert_eq!(jail.fs.metadata(&jail.path("dir/file")).unwrap().mode() & 0o777, 0o640);
+
+        let link = jail.fs.resolve(&jail.path("link"), false).unwrap();
+        assert_eq!(chmod_nofollow(&link, 0o600).unwrap_err().raw_os_error(), Some(libc::EOPNOTSUPP));
+        assert_eq!(jail.fs.metadata(&jail.path("dir/file")).unwrap().mode() & 0o777, 0o640);
+    }
+}
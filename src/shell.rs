@@ -1,33 +1,168 @@
 //! 対話シェルの入出力を担当するモジュール
-//! プロンプト表示と標準入力の読み取りを提供する
+//! プロンプト表示と標準入力の読み取り、端末での確認・進捗表示、Ctrl+C による中断を提供する
 
-use std::env;
-use std::io::{self, Write};
+use simple_shell::columns;
+use simple_shell::context::{Interaction, Progress};
+use simple_shell::file_info::human_size;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
-/// "ディレクトリ名> " の形式でプロンプトを表示する
-pub fn print_prompt() {
-    let current_dir = env::current_dir()
-        .unwrap_or_else(|_| std::path::PathBuf::from("?"));
+/// Ctrl+C（SIGINT）を受け取ったか
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
 
-    let dir_name = current_dir
-        .file_name()
-        .and_then(|s| s.to_str())
-        .unwrap_or("?");
+extern "C" fn on_interrupt(_signal: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+/// Ctrl+C でシェルごと終了せず、実行中のコマンドに中断を知らせるようにする。
+/// SA_RESTART を付けないので、入力待ちの read なども EINTR で戻る
+pub fn handle_interrupt() {
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = on_interrupt as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        libc::sigemptyset(&mut action.sa_mask);
+        libc::sigaction(libc::SIGINT, &action, std::ptr::null_mut());
+    }
+}
+
+/// 受け取った Ctrl+C を忘れる（次のコマンドを実行する前に呼ぶ）
+pub fn clear_interrupt() {
+    INTERRUPTED.store(false, Ordering::SeqCst);
+}
 
-    print!("{}> ", dir_name);
+/// "ディレクトリ名> " の形式でプロンプトを表示する（`label` は `Context::prompt_label`）
+pub fn print_prompt(label: &str) {
+    print!("{}> ", label);
     let _ = io::stdout().flush();
 }
 
-/// 標準入力から1行読み取る。EOF (Ctrl+D) の場合は None を返す
-pub fn read_input() -> Option<String> {
-    let mut input = String::new();
+/// 標準入力から1行をバイト列のまま読み取る。EOF (Ctrl+D) の場合は None を返す。
+/// UTF-8 として不正なバイトを含む行も読めるよう、文字列には変換しない
+pub fn read_input() -> Option<Vec<u8>> {
+    let mut input = Vec::new();
 
-    match io::stdin().read_line(&mut input) {
+    match io::stdin().lock().read_until(b'\n', &mut input) {
         Ok(0) => None, // EOF
-        Ok(_) => Some(input.trim().to_string()),
+        Ok(_) => Some(input.trim_ascii().to_vec()),
         Err(e) => {
             eprintln!("Error reading input: {}", e);
             None
         }
     }
 }
+
+/// 端末でのユーザーとのやりとり。質問と進捗は標準エラー出力に表示する
+pub struct TerminalInteraction {
+    /// 標準エラー出力が端末か（端末でなければ進捗は表示しない）
+    stderr_is_tty: bool,
+    /// 最初の進捗を受け取った時刻。短い処理では進捗を表示しないために使う
+    started: Option<Instant>,
+    /// 進捗行を表示中か
+    showing: bool,
+}
+
+/// この時間より早く終わる処理では進捗を表示しない
+const PROGRESS_DELAY: Duration = Duration::from_millis(500);
+
+impl TerminalInteraction {
+    pub fn new() -> Self {
+        TerminalInteraction {
+            stderr_is_tty: io::stderr().is_terminal(),
+            started: None,
+            showing: false,
+        }
+    }
+}
+
+impl Interaction for TerminalInteraction {
+    fn confirm(&mut self, question: &str) -> bool {
+        self.progress(None);
+        eprint!("{}", question);
+        let _ = io::stderr().flush();
+
+        let mut answer = String::new();
+        if io::stdin().lock().read_line(&mut answer).is_err() {
+            return false;
+        }
+        matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes")
+    }
+
+    /// 番号を付けた候補を表示し、番号を入力してもらう（空行や範囲外の番号は取り消し）
+    fn choose(&mut self, title: &str, items: &[String]) -> Option<usize> {
+        self.progress(None);
+        eprintln!("{}", title);
+        for (i, item) in items.iter().enumerate() {
+            eprintln!("{:>3}) {}", i + 1, item);
+        }
+        eprint!("Select [1-{}]: ", items.len());
+        let _ = io::stderr().flush();
+
+        let mut answer = String::new();
+        io::stdin().lock().read_line(&mut answer).ok()?;
+        let number: usize = answer.trim().parse().ok()?;
+        (1..=items.len()).contains(&number).then(|| number - 1)
+    }
+
+    fn interrupted(&self) -> bool {
+        INTERRUPTED.load(Ordering::SeqCst)
+    }
+
+    fn fork(&self) -> Box<dyn Interaction> {
+        Box::new(StageInteraction)
+    }
+
+    fn progress(&mut self, progress: Option<&Progress>) {
+        let Some(progress) = progress else {
+            if self.showing {
+                eprint!("\r\x1b[K");
+                let _ = io::stderr().flush();
+            }
+            self.started = None;
+            self.showing = false;
+            return;
+        };
+
+        let started = *self.started.get_or_insert_with(Instant::now);
+        if !self.stderr_is_tty || started.elapsed() < PROGRESS_DELAY {
+            return;
+        }
+
+        let mut line = progress.message.clone();
+        if let Some(percent) = progress.done.saturating_mul(100).checked_div(progress.total) {
+            line = format!(
+                "{:3}% {}/{} {}",
+                percent.min(100),
+                human_size(progress.done),
+                human_size(progress.total),
+                line
+            );
+        }
+        // 端末幅を超えると折り返して行を消せなくなるので切り詰める
+        let width = columns::terminal_width().saturating_sub(1);
+        let line: String = line.chars().take(width).collect();
+        eprint!("\r\x1b[K{}", line);
+        let _ = io::stderr().flush();
+        self.showing = true;
+    }
+}
+
+/// パイプラインの途中の段用のやりとり。端末の入力はパイプラインが使うので確認には答えず、
+/// 進捗も表示しない。Ctrl+C による中断だけは受け取る
+struct StageInteraction;
+
+impl Interaction for StageInteraction {
+    fn confirm(&mut self, _question: &str) -> bool {
+        false
+    }
+
+    fn progress(&mut self, _progress: Option<&Progress>) {}
+
+    fn interrupted(&self) -> bool {
+        INTERRUPTED.load(Ordering::SeqCst)
+    }
+
+    fn fork(&self) -> Box<dyn Interaction> {
+        Box::new(StageInteraction)
+    }
+}
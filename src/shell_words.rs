@@ -0,0 +1,374 @@
+//! 入力行を引数に分割する
+//!
+//! 空白区切りに加えて、シェルと同じ次のクォートを解釈する:
+//! - `'...'`  中身をそのまま使う
+//! - `"..."`  `\"` `\\` `\$` `` \` `` のみエスケープとして扱う
+//! - `$'...'` C言語形式のエスケープ（`\n`, `\xHH`, `\ooo` など）。
+//!   UTF-8 として不正なバイトも表現でき、`quoting` の出力を元のバイト列に戻せる
+//! - `\c`     クォート外のバックスラッシュは次の1文字をそのまま使う
+//!
+//! `split_list` はさらにクォートされていない `|`（パイプライン）と
+//! `;` `&&` `||`（コマンドリスト）で区切る。`split_list_expanding` は加えて、
+//! 引数の先頭のクォートされていない `~`（最初の `/` まで）を展開する。
+
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+
+/// 分割時のエラー（閉じられていないクォートなど）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError(String);
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// コマンドリストでの前のパイプラインとのつなぎ方
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Connector {
+    /// 行頭か `;` の後。常に実行する
+    Always,
+    /// `&&` の後。直前の終了ステータスが 0 なら実行する
+    And,
+    /// `||` の後。直前の終了ステータスが 0 以外なら実行する
+    Or,
+}
+
+/// コマンドリストの要素（1つのパイプライン）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListItem {
+    pub connector: Connector,
+    /// パイプラインの各段の引数のリスト
+    pub stages: Vec<Vec<OsString>>,
+}
+
+/// 入力行（バイト列）を引数のリストに分割する
+pub fn split(line: &[u8]) -> Result<Vec<OsString>, ParseError> {
+    let mut items = parse(line, false, None)?;
+    Ok(items.pop().and_then(|mut item| item.stages.pop()).unwrap_or_default())
+}
+
+/// 入力行をコマンドリストに分割する。クォートされていない `;` `&&` `||` でパイプラインを、
+/// `|` でパイプラインの段を区切る（`a | | b` や `&& b` のような空のコマンドはエラー）。
+/// `[[ ... ]]` の中の `&&` `||` は区切りではなく、条件式の引数になる
+pub fn split_list(line: &[u8]) -> Result<Vec<ListItem>, ParseError> {
+    parse(line, true, None)
+}
+
+/// `~` の展開に使う関数。`~` の後ろ（`~dir/x` なら `dir`）を受け取り、置き換える文字列を返す
+pub type ExpandTilde<'a> = &'a dyn Fn(&OsStr) -> Option<OsString>;
+
+/// `split_list` に加えて `~` を展開する。`expand` が None を返したものと、
+/// `'~'` `\~` のようにクォートしたものはそのまま残す
+pub fn split_list_expanding(line: &[u8], expand: ExpandTilde) -> Result<Vec<ListItem>, ParseError> {
+    parse(line, true, Some(expand))
+}
+
+/// 引数の先頭の `~` の読み取り状態
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Tilde {
+    /// 先頭が `~` でないか、途中にクォートがあった
+    None,
+    /// `~` の後ろを読んでいる
+    Open,
+    /// `/` で閉じた。値は `/` の位置
+    Closed(usize),
+}
+
+/// 分割の途中の状態
+struct Parser<'a> {
+    items: Vec<ListItem>,
+    connector: Connector,
+    stages: Vec<Vec<OsString>>,
+    words: Vec<OsString>,
+    word: Vec<u8>,
+    /// '' のような空の引数も1つの引数として扱うための印
+    in_word: bool,
+    /// `[[` から `]]` までの間
+    in_test: bool,
+    /// 直前の演算子（後ろにコマンドが続かなければエラー）
+    pending: Option<&'static str>,
+    tilde: Tilde,
+    expand: Option<ExpandTilde<'a>>,
+}
+
+impl Parser<'_> {
+    fn finish_word(&mut self) {
+        if !self.in_word {
+            return;
+        }
+        let mut word = std::mem::take(&mut self.word);
+        let prefix_end = match self.tilde {
+            Tilde::None => None,
+            Tilde::Open => Some(word.len()),
+            Tilde::Closed(end) => Some(end),
+        };
+        self.tilde = Tilde::None;
+        if let (Some(end), Some(expand)) = (prefix_end, self.expand) {
+            if let Some(expanded) = expand(OsStr::from_bytes(&word[1..end])) {
+                word.splice(..end, expanded.into_vec());
+            }
+        }
+        if self.words.is_empty() && word == b"[[" {
+            self.in_test = true;
+        } else if self.in_test && word == b"]]" {
+            self.in_test = false;
+        }
+        self.words.push(OsString::from_vec(word));
+        self.in_word = false;
+        self.pending = None;
+    }
+
+    /// 段を区切る。段が空ならエラー
+    fn end_stage(&mut self, token: &'static str) -> Result<(), ParseError> {
+        self.finish_word();
+        if self.words.is_empty() {
+            return Err(ParseError(format!("syntax error near unexpected token `{}'", token)));
+        }
+        self.stages.push(std::mem::take(&mut self.words));
+        Ok(())
+    }
+
+    /// パイプラインを区切り、次のパイプラインのつなぎ方を `next` にする
+    fn end_pipeline(&mut self, token: &'static str, next: Connector) -> Result<(), ParseError> {
+        self.end_stage(token)?;
+        self.items.push(ListItem { connector: self.connector, stages: std::mem::take(&mut self.stages) });
+        self.connector = next;
+        Ok(())
+    }
+}
+
+/// `operators` が true ならクォートされていない `|` `;` `&&` `||` で区切る
+fn parse(line: &[u8], operators: bool, expand: Option<ExpandTilde>) -> Result<Vec<ListItem>, ParseError> {
+    let mut p = Parser {
+        items: Vec::new(),
+        connector: Connector::Always,
+        stages: Vec::new(),
+        words: Vec::new(),
+        word: Vec::new(),
+        in_word: false,
+        in_test: false,
+        pending: None,
+        tilde: Tilde::None,
+        expand,
+    };
+    let mut i = 0;
+
+    while i < line.len() {
+        let b = line[i];
+        let next = line.get(i + 1).copied();
+        // `~` の後ろにクォートがあれば展開しない（`~"x"` や `~\1`）
+        if p.tilde == Tilde::Open && matches!(b, b'\'' | b'"' | b'\\' | b'$') {
+            p.tilde = Tilde::None;
+        }
+        match b {
+            b' ' | b'\t' | b'\n' | b'\r' => {
+                p.finish_word();
+                i += 1;
+            }
+            b'\'' => {
+                let end = find_closing(line, i + 1, b'\'')?;
+                p.word.extend_from_slice(&line[i + 1..end]);
+                p.in_word = true;
+                i = end + 1;
+            }
+            b'"' => {
+                i = double_quoted(line, i + 1, &mut p.word)?;
+                p.in_word = true;
+            }
+            b'$' if next == Some(b'\'') => {
+                i = ansi_c_quoted(line, i + 2, &mut p.word)?;
+                p.in_word = true;
+            }
+            // [[ ... ]] の中の && と || は条件式の演算子
+            b'&' | b'|' if operators && p.in_test && next == Some(b) => {
+                p.finish_word();
+                p.words.push(OsString::from_vec(vec![b, b]));
+                i += 2;
+            }
+            b'&' if operators && next == Some(b'&') => {
+                p.end_pipeline("&&", Connector::And)?;
+                p.pending = Some("&&");
+                i += 2;
+            }
+            b'|' if operators && next == Some(b'|') => {
+                p.end_pipeline("||", Connector::Or)?;
+                p.pending = Some("||");
+                i += 2;
+            }
+            b'|' if operators => {
+                p.end_stage("|")?;
+                p.pending = Some("|");
+                i += 1;
+            }
+            b';' if operators => {
+                p.end_pipeline(";", Connector::Always)?;
+                i += 1;
+            }
+            b'\\' => {
+                if let Some(next) = next {
+                    p.word.push(next);
+                    i += 2;
+                } else {
+                    i += 1;
+                }
+                p.in_word = true;
+            }
+            _ => {
+                if !p.in_word && b == b'~' {
+                    p.tilde = Tilde::Open;
+                } else if p.tilde == Tilde::Open && b == b'/' {
+                    p.tilde = Tilde::Closed(p.word.len());
+                }
+                p.word.push(b);
+                p.in_word = true;
+                i += 1;
+            }
+        }
+    }
+
+    p.finish_word();
+    if p.words.is_empty() {
+        if let Some(token) = p.pending {
+            return Err(ParseError(format!("syntax error near unexpected token `{}'", token)));
+        }
+    } else {
+        p.stages.push(std::mem::take(&mut p.words));
+    }
+    if !p.stages.is_empty() {
+        p.items.push(ListItem { connector: p.connector, stages: p.stages });
+    }
+    Ok(p.items)
+}
+
+/// `start` 以降で最初の `quote` の位置を探す
+fn find_closing(line: &[u8], start: usize, quote: u8) -> Result<usize, ParseError> {
+    line[start..]
+        .iter()
+        .position(|&b| b == quote)
+        .map(|pos| start + pos)
+        .ok_or_else(|| unexpected_eof(quote))
+}
+
+fn unexpected_eof(quote: u8) -> ParseError {
+    ParseError(format!("unexpected EOF while looking for matching `{}'", quote as char))
+}
+
+/// "..." の中身を読み、閉じクォートの次の位置を返す
+fn double_quoted(line: &[u8], mut i: usize, word: &mut Vec<u8>) -> Result<usize, ParseError> {
+    while i < line.len() {
+        match line[i] {
+            b'"' => return Ok(i + 1),
+            b'\\' if matches!(line.get(i + 1), Some(b'"' | b'\\' | b'$' | b'`')) => {
+                word.push(line[i + 1]);
+                i += 2;
+            }
+            b => {
+                word.push(b);
+                i += 1;
+            }
+        }
+    }
+    Err(unexpected_eof(b'"'))
+}
+
+/// $'...' の中身を読み、閉じクォートの次の位置を返す
+fn ansi_c_quoted(line: &[u8], mut i: usize, word: &mut Vec<u8>) -> Result<usize, ParseError> {
+    while i < line.len() {
+        match line[i] {
+            b'\'' => return Ok(i + 1),
+            b'\\' if i + 1 < line.len() => {
+                i = c_escape(line, i + 1, word);
+            }
+            b => {
+                word.push(b);
+                i += 1;
+            }
+        }
+    }
+    Err(unexpected_eof(b'\''))
+}
+
+/// バックスラッシュの直後（位置 `i`）からエスケープを1つ読み、次の位置を返す
+fn c_escape(line: &[u8], i: usize, word: &mut Vec<u8>) -> usize {
+    let simple = match line[i] {
+        b'a' => Some(0x07),
+        b'b' => Some(0x08),
+        b'e' | b'E' => Some(0x1b),
+        b'f' => Some(0x0c),
+        b'n' => Some(b'\n'),
+        b'r' => Some(b'\r'),
+        b't' => Some(b'\t'),
+        b'v' => Some(0x0b),
+        b'\\' | b'\'' | b'"' | b'?' => Some(line[i]),
+        _ => None,
+    };
+    if let Some(b) = simple {
+        word.push(b);
+        return i + 1;
+    }
+
+    match line[i] {
+        // \ooo: 8進1〜3桁（1バイト）
+        b'0'..=b'7' => {
+            let (value, len) = parse_digits(&line[i..], 8, 3);
+            word.push(value as u8);
+            i + len
+        }
+        // \xHH: 16進1〜2桁（1バイト）
+        b'x' => {
+            let (value, len) = parse_digits(&line[i + 1..], 16, 2);
+            if len == 0 {
+                word.extend_from_slice(b"\\x");
+            } else {
+                word.push(value as u8);
+            }
+            i + 1 + len
+        }
+        // \uHHHH / \UHHHHHHHH: Unicode コードポイント（UTF-8 で格納）
+        b'u' | b'U' => {
+            let max = if line[i] == b'u' { 4 } else { 8 };
+            let (value, len) = parse_digits(&line[i + 1..], 16, max);
+            match char::from_u32(value).filter(|_| len > 0) {
+                Some(ch) => {
+                    let mut buf = [0u8; 4];
+                    word.extend_from_slice(ch.encode_utf8(&mut buf).as_bytes());
+                }
+                None => {
+                    word.push(b'\\');
+                    word.extend_from_slice(&line[i..i + 1 + len]);
+                }
+            }
+            i + 1 + len
+        }
+        // \cX: 制御文字
+        b'c' if i + 1 < line.len() => {
+            word.push(line[i + 1] & 0x1f);
+            i + 2
+        }
+        // 未知のエスケープはそのまま残す
+        other => {
+            word.push(b'\\');
+            word.push(other);
+            i + 1
+        }
+    }
+}
+
+/// 先頭から最大 `max` 桁の数字を読み、(値, 桁数) を返す
+fn parse_digits(bytes: &[u8], radix: u32, max: usize) -> (u32, usize) {
+    let mut value = 0u32;
+    let mut len = 0;
+    for &b in bytes.iter().take(max) {
+        match (b as char).to_digit(radix) {
+            Some(d) => {
+                value = value * radix + d;
+                len += 1;
+            }
+            None => break,
+        }
+    }
+    (value, len)
+}
@@ -0,0 +1,126 @@
+//! 列ごとに並べ替えられる表
+//!
+//! `du` や `df` の結果を、CLI版では桁をそろえたテキストとして、
+//! GUI版では見出しをクリックして並べ替えられる表として表示するためのデータ。
+//! 各セルは表示用の文字列と並べ替え用のキー（数値か文字列）を持つ。
+
+use crate::columns::display_width;
+use std::cmp::Ordering;
+
+/// 表の1列
+#[derive(Debug, Clone, PartialEq)]
+pub struct Column {
+    /// 見出し
+    pub title: String,
+    /// 数値の列か（右寄せで表示する）
+    pub numeric: bool,
+}
+
+/// 並べ替えに使う値
+#[derive(Debug, Clone, PartialEq)]
+pub enum SortKey {
+    Number(f64),
+    Text(String),
+}
+
+/// 表の1セル
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cell {
+    /// 表示する文字列
+    pub text: String,
+    pub key: SortKey,
+}
+
+impl Cell {
+    /// 数値のセル。表示は `text`（`1.5K` など）、並べ替えは `value` で行う
+    pub fn number(text: impl Into<String>, value: f64) -> Cell {
+        Cell { text: text.into(), key: SortKey::Number(value) }
+    }
+
+    /// 文字列のセル
+    pub fn text(text: impl Into<String>) -> Cell {
+        let text = text.into();
+        Cell { key: SortKey::Text(text.clone()), text }
+    }
+}
+
+/// 見出しと行からなる表
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Table {
+    pub columns: Vec<Column>,
+    pub rows: Vec<Vec<Cell>>,
+}
+
+impl Table {
+    /// 見出しを指定して空の表を作る（`(見出し, 数値の列か)` の並び）
+    pub fn new(columns: &[(&str, bool)]) -> Table {
+        Table {
+            columns: columns
+                .iter()
+                .map(|&(title, numeric)| Column { title: title.to_string(), numeric })
+                .collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    /// 行を追加する
+    pub fn push(&mut self, row: Vec<Cell>) {
+        self.rows.push(row);
+    }
+
+    /// `column` 列で並べ替える（安定ソート）。数値は数値として、文字列は辞書順で比べる
+    pub fn sort_by(&mut self, column: usize, descending: bool) {
+        self.rows.sort_by(|a, b| {
+            let ordering = match (a.get(column).map(|c| &c.key), b.get(column).map(|c| &c.key)) {
+                (Some(SortKey::Number(x)), Some(SortKey::Number(y))) => x.partial_cmp(y).unwrap_or(Ordering::Equal),
+                (Some(SortKey::Text(x)), Some(SortKey::Text(y))) => x.cmp(y),
+                // 数値は文字列より前にする
+                (Some(SortKey::Number(_)), Some(SortKey::Text(_))) => Ordering::Less,
+                (Some(SortKey::Text(_)), Some(SortKey::Number(_))) => Ordering::Greater,
+                (x, y) => x.is_some().cmp(&y.is_some()),
+            };
+            if descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+    }
+
+    /// 桁をそろえたテキストの行にする（見出しを含む）。
+    /// 数値の列は右寄せ、最後の列は空白を付け足さない
+    pub fn to_lines(&self) -> Vec<String> {
+        let mut widths: Vec<usize> = self.columns.iter().map(|c| display_width(&c.title)).collect();
+        for row in &self.rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(display_width(&cell.text));
+            }
+        }
+
+        let header: Vec<&str> = self.columns.iter().map(|c| c.title.as_str()).collect();
+        let rows = self.rows.iter().map(|row| row.iter().map(|cell| cell.text.as_str()).collect::<Vec<_>>());
+        std::iter::once(header)
+            .chain(rows)
+            .map(|cells| {
+                let mut line = String::new();
+                for (i, text) in cells.iter().enumerate() {
+                    if i > 0 {
+                        line.push(' ');
+                    }
+                    let padding = " ".repeat(widths[i].saturating_sub(display_width(text)));
+                    let last = i + 1 == cells.len();
+                    if self.columns[i].numeric {
+                        line.push_str(&padding);
+                        line.push_str(text);
+                    } else {
+                        line.push_str(text);
+                        if !last {
+                            line.push_str(&padding);
+                        }
+                    }
+                }
+                line
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,305 @@
+//! ゴミ箱（freedesktop.org Trash 仕様）
+//!
+//! - ホームのゴミ箱: `$XDG_DATA_HOME/Trash`（未設定なら `~/.local/share/Trash`）
+//! - ホームと別のボリューム: 管理者が用意した `$topdir/.Trash/$uid`、なければ `$topdir/.Trash-$uid`
+//!
+//! 各ゴミ箱は `files/` に削除したファイル本体を、`info/<名前>.trashinfo` に
+//! 元のパスと削除日時を持つ。ファイル本体は rename で移すため、大きなディレクトリでもすぐ終わる。
+
+use crate::file_info;
+use crate::fs_util;
+use std::collections::HashSet;
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// ゴミ箱の中の1項目
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrashedItem {
+    /// 項目があるゴミ箱（`files/` と `info/` を含むディレクトリ）
+    pub trash_dir: PathBuf,
+    /// ゴミ箱の中での名前
+    pub name: OsString,
+    /// 元の場所（絶対パス）
+    pub original: PathBuf,
+    /// 削除日時（ローカル時刻の `YYYY-MM-DDThh:mm:ss`）
+    pub deletion_date: String,
+}
+
+impl TrashedItem {
+    /// ゴミ箱の中のファイル本体
+    pub fn file_path(&self) -> PathBuf {
+        self.trash_dir.join("files").join(&self.name)
+    }
+
+    /// 対応する .trashinfo ファイル
+    pub fn info_path(&self) -> PathBuf {
+        let mut name = self.name.clone();
+        name.push(".trashinfo");
+        self.trash_dir.join("info").join(name)
+    }
+}
+
+/// ホームのゴミ箱のパス
+pub fn home_trash() -> Option<PathBuf> {
+    let data_home = match env::var_os("XDG_DATA_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?).join(".local").join("share"),
+    };
+    Some(data_home.join("Trash"))
+}
+
+/// `path` をゴミ箱へ移す
+pub fn trash(path: &Path) -> io::Result<TrashedItem> {
+    let meta = fs::symlink_metadata(path)?;
+    let original = absolute_path(path)?;
+    let (trash_dir, topdir) = trash_dir_for(&original, meta.dev())?;
+    for sub in ["files", "info"] {
+        create_private_dir(&trash_dir.join(sub))?;
+    }
+
+    // ホームのゴミ箱には絶対パス、ボリュームのゴミ箱にはトップディレクトリからの相対パスを記録する
+    let recorded = match &topdir {
+        Some(top) => original.strip_prefix(top).unwrap_or(&original),
+        None => &original,
+    };
+    let deletion_date = format_date(now());
+    let info = format!(
+        "[Trash Info]\nPath={}\nDeletionDate={}\n",
+        fs_util::encode_path(recorded.as_os_str()),
+        deletion_date
+    );
+
+    let base = original.file_name().unwrap_or(OsStr::new("unnamed")).to_os_string();
+    for n in 1.. {
+        let mut name = base.clone();
+        if n > 1 {
+            name.push(format!(".{}", n));
+        }
+        let item = TrashedItem { trash_dir: trash_dir.clone(), name, original: original.clone(), deletion_date: deletion_date.clone() };
+
+        // .trashinfo を排他的に作ることで名前を確保する
+        let mut file = match OpenOptions::new().write(true).create_new(true).open(item.info_path()) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        };
+        if fs::symlink_metadata(item.file_path()).is_ok() {
+            let _ = fs::remove_file(item.info_path());
+            continue;
+        }
+        let moved = file.write_all(info.as_bytes()).and_then(|_| fs::rename(path, item.file_path()));
+        if let Err(e) = moved {
+            let _ = fs::remove_file(item.info_path());
+            return Err(e);
+        }
+        return Ok(item);
+    }
+    unreachable!("ran out of trash names")
+}
+
+/// ゴミ箱の項目を元の場所へ戻す。元の場所に既に何かあれば AlreadyExists
+pub fn restore(item: &TrashedItem) -> io::Result<()> {
+    if fs::symlink_metadata(&item.original).is_ok() {
+        return Err(io::Error::from(io::ErrorKind::AlreadyExists));
+    }
+    if let Some(parent) = item.original.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::rename(item.file_path(), &item.original)?;
+    fs::remove_file(item.info_path())
+}
+
+/// ゴミ箱の項目を完全に削除する
+pub fn purge(item: &TrashedItem) -> io::Result<()> {
+    let file = item.file_path();
+    match fs::symlink_metadata(&file) {
+        Ok(meta) if meta.is_dir() => fs::remove_dir_all(&file)?,
+        Ok(_) => fs::remove_file(&file)?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+    fs::remove_file(item.info_path())
+}
+
+/// すべてのゴミ箱（ホームとマウント済みボリューム）の項目を削除日時の順に返す
+pub fn list() -> Vec<TrashedItem> {
+    let mut items = Vec::new();
+    let home = home_trash();
+    if let Some(home) = &home {
+        items.extend(read_trash_dir(home, None));
+    }
+    let uid = unsafe { libc::getuid() };
+    // 同じボリュームが複数の場所にマウントされていても1度だけ読む
+    let mut seen = HashSet::new();
+    for top in mount_points() {
+        let candidates = [top.join(".Trash").join(uid.to_string()), top.join(format!(".Trash-{}", uid))];
+        for dir in candidates {
+            let Ok(meta) = fs::metadata(dir.join("info")) else {
+                continue;
+            };
+            if Some(&dir) != home.as_ref() && seen.insert((meta.dev(), meta.ino())) {
+                items.extend(read_trash_dir(&dir, Some(&top)));
+            }
+        }
+    }
+    items.sort_by(|a, b| a.deletion_date.cmp(&b.deletion_date).then_with(|| a.original.cmp(&b.original)));
+    items
+}
+
+/// `days` 日より前に削除された項目か（`days` が 0 ならすべて）
+pub fn is_older_than(item: &TrashedItem, days: u64) -> bool {
+    let cutoff = format_date(now() - (days as i64) * 86400);
+    item.deletion_date < cutoff
+}
+
+/// 1つのゴミ箱の項目を読む。`topdir` はボリュームのゴミ箱の場合のトップディレクトリ
+fn read_trash_dir(trash_dir: &Path, topdir: Option<&Path>) -> Vec<TrashedItem> {
+    let Ok(entries) = fs::read_dir(trash_dir.join("info")) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let file_name = entry.file_name();
+            let name = file_name.as_bytes().strip_suffix(b".trashinfo")?;
+            let text = fs::read(entry.path()).ok()?;
+            let (path, date) = parse_info(&text)?;
+            let original = match topdir {
+                Some(top) if path.is_relative() => top.join(path),
+                _ => path,
+            };
+            Some(TrashedItem {
+                trash_dir: trash_dir.to_path_buf(),
+                name: OsString::from_vec(name.to_vec()),
+                original,
+                deletion_date: date,
+            })
+        })
+        .collect()
+}
+
+/// .trashinfo の内容から (元のパス, 削除日時) を取り出す
+fn parse_info(text: &[u8]) -> Option<(PathBuf, String)> {
+    let mut path = None;
+    let mut date = String::new();
+    let mut in_section = false;
+    for line in text.split(|&b| b == b'\n') {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if line.starts_with(b"[") {
+            in_section = line == b"[Trash Info]";
+        } else if !in_section {
+            continue;
+        } else if let Some(value) = line.strip_prefix(b"Path=") {
+            path = Some(PathBuf::from(OsString::from_vec(fs_util::decode_path(value))));
+        } else if let Some(value) = line.strip_prefix(b"DeletionDate=") {
+            date = String::from_utf8_lossy(value).into_owned();
+        }
+    }
+    Some((path?, date))
+}
+
+/// `path` と同じボリュームのゴミ箱を返す。
+/// ホームと別のボリュームなら (ゴミ箱, Some(トップディレクトリ))
+fn trash_dir_for(path: &Path, device: u64) -> io::Result<(PathBuf, Option<PathBuf>)> {
+    let home = home_trash().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "HOME is not set"))?;
+    if device_of(&home) == Some(device) {
+        return Ok((home, None));
+    }
+
+    // 同じデバイスのうち最も上のディレクトリ（マウントポイント）を探す
+    let mut top = path.parent().unwrap_or(Path::new("/")).to_path_buf();
+    while let Some(parent) = top.parent() {
+        if fs::metadata(parent).map(|m| m.dev()).ok() != Some(device) {
+            break;
+        }
+        top = parent.to_path_buf();
+    }
+
+    let uid = unsafe { libc::getuid() };
+    // 管理者が用意した .Trash は、シンボリックリンクでない sticky なディレクトリのときだけ使う
+    let admin = top.join(".Trash");
+    if let Ok(meta) = fs::symlink_metadata(&admin) {
+        if meta.is_dir() && meta.permissions().mode() & 0o1000 != 0 {
+            let dir = admin.join(uid.to_string());
+            if create_private_dir(&dir).is_ok() {
+                return Ok((dir, Some(top)));
+            }
+        }
+    }
+    let dir = top.join(format!(".Trash-{}", uid));
+    create_private_dir(&dir)?;
+    Ok((dir, Some(top)))
+}
+
+/// 所有者だけが読み書きできるディレクトリを作る（既にあれば何もしない）
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+    match fs::DirBuilder::new().recursive(true).mode(0o700).create(dir) {
+        Err(_) if dir.is_dir() => Ok(()),
+        result => result,
+    }
+}
+
+/// 存在する最も近い祖先のデバイス番号
+fn device_of(path: &Path) -> Option<u64> {
+    path.ancestors().find_map(|p| fs::metadata(p).ok()).map(|m| m.dev())
+}
+
+/// 末尾のシンボリックリンクはたどらずに絶対パスにする
+fn absolute_path(path: &Path) -> io::Result<PathBuf> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid file name"))?;
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.canonicalize()?,
+        _ => env::current_dir()?,
+    };
+    Ok(parent.join(name))
+}
+
+/// /proc/self/mountinfo のマウントポイント一覧
+fn mount_points() -> Vec<PathBuf> {
+    let Ok(text) = fs::read("/proc/self/mountinfo") else {
+        return Vec::new();
+    };
+    text.split(|&b| b == b'\n')
+        .filter_map(|line| line.split(|&b| b == b' ').nth(4))
+        .map(|field| PathBuf::from(OsString::from_vec(unescape_mountinfo(field))))
+        .collect()
+}
+
+/// mountinfo のパス中の `\040` などの8進エスケープを戻す
+fn unescape_mountinfo(field: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(field.len());
+    let mut i = 0;
+    while i < field.len() {
+        if field[i] == b'\\' && i + 3 < field.len() && field[i + 1..i + 4].iter().all(|b| (b'0'..=b'7').contains(b)) {
+            let value = field[i + 1..i + 4].iter().fold(0u32, |acc, &b| acc * 8 + (b - b'0') as u32);
+            out.push(value as u8);
+            i += 4;
+        } else {
+            out.push(field[i]);
+            i += 1;
+        }
+    }
+    out
+}
+
+/// 現在の UNIX 時刻（秒）
+fn now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0)
+}
+
+/// .trashinfo の DeletionDate 形式（ローカル時刻）
+fn format_date(secs: i64) -> String {
+    let t = file_info::local_time(secs);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        t.year, t.month, t.day, t.hour, t.minute, t.second
+    )
+}
@@ -0,0 +1,67 @@
+//! 折りたためる木構造
+//!
+//! `tree` の結果を、CLI版では罫線で枝を描いたテキストとして、
+//! GUI版ではディレクトリごとに開閉できる木として表示するためのデータ。
+
+/// 枝を描く文字
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Charset {
+    /// `├── ` `└── ` `│   `
+    Unicode,
+    /// `|-- ` `` `-- `` `|   `
+    Ascii,
+}
+
+impl Charset {
+    /// `--charset` の値を解釈する
+    pub fn parse(name: &str) -> Option<Charset> {
+        match name.to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" | "unicode" => Some(Charset::Unicode),
+            "ascii" => Some(Charset::Ascii),
+            _ => None,
+        }
+    }
+
+    /// (途中の子の枝, 最後の子の枝, 続きがある縦線, 続きがない空白)
+    fn branches(self) -> [&'static str; 4] {
+        match self {
+            Charset::Unicode => ["├── ", "└── ", "│   ", "    "],
+            Charset::Ascii => ["|-- ", "`-- ", "|   ", "    "],
+        }
+    }
+}
+
+/// 木の1節
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TreeNode {
+    /// 表示する文字列（名前とサイズなどの注記）
+    pub label: String,
+    /// ディレクトリか（GUI版では開閉できる見出しにする）
+    pub is_dir: bool,
+    pub children: Vec<TreeNode>,
+}
+
+impl TreeNode {
+    /// 子のない節を作る
+    pub fn new(label: impl Into<String>, is_dir: bool) -> TreeNode {
+        TreeNode { label: label.into(), is_dir, children: Vec::new() }
+    }
+
+    /// 枝を罫線で描いたテキストの行にする（自分自身を含む）
+    pub fn to_lines(&self, charset: Charset) -> Vec<String> {
+        let mut lines = vec![self.label.clone()];
+        self.push_children(&mut lines, &mut String::new(), charset.branches());
+        lines
+    }
+
+    fn push_children(&self, lines: &mut Vec<String>, indent: &mut String, branches: [&str; 4]) {
+        for (i, child) in self.children.iter().enumerate() {
+            let last = i + 1 == self.children.len();
+            lines.push(format!("{}{}{}", indent, branches[last as usize], child.label));
+            let len = indent.len();
+            indent.push_str(branches[2 + last as usize]);
+            child.push_children(lines, indent, branches);
+            indent.truncate(len);
+        }
+    }
+}
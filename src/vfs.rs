@@ -0,0 +1,1001 @@
+//! ビルトインが使うファイルシステム
+//!
+//! ビルトインは `std::fs` を直接呼ばず、`Context::fs` の `Fs` を通してファイルを扱う。
+//! 実装は2つ:
+//! - `OsFs`     実際のファイルシステム（既定）
+//! - `MemoryFs` メモリ上のファイルシステム。ディスクに触れずに決まった結果が得られる
+//!
+//! パスはすべて絶対パス（`Context::resolve` で解決済みのもの）で渡す。
+//! reflink・ゴミ箱・undo の退避のように OS の機能に依存する処理は、
+//! `Fs::is_native` が true の場合だけ行う。
+
+use crate::fs_util::{self, FileTime};
+use std::collections::{HashMap, VecDeque};
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io::{self, Cursor, Read, Seek, Write};
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// ファイルの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    File,
+    Dir,
+    Symlink,
+    Fifo,
+    Socket,
+    BlockDevice,
+    CharDevice,
+}
+
+impl FileKind {
+    pub fn is_file(self) -> bool {
+        self == FileKind::File
+    }
+
+    pub fn is_dir(self) -> bool {
+        self == FileKind::Dir
+    }
+
+    pub fn is_symlink(self) -> bool {
+        self == FileKind::Symlink
+    }
+
+    pub fn is_fifo(self) -> bool {
+        self == FileKind::Fifo
+    }
+
+    pub fn is_socket(self) -> bool {
+        self == FileKind::Socket
+    }
+
+    pub fn is_block_device(self) -> bool {
+        self == FileKind::BlockDevice
+    }
+
+    pub fn is_char_device(self) -> bool {
+        self == FileKind::CharDevice
+    }
+
+    /// st_mode の種類のビット
+    fn mode_bits(self) -> u32 {
+        match self {
+            FileKind::File => libc::S_IFREG,
+            FileKind::Dir => libc::S_IFDIR,
+            FileKind::Symlink => libc::S_IFLNK,
+            FileKind::Fifo => libc::S_IFIFO,
+            FileKind::Socket => libc::S_IFSOCK,
+            FileKind::BlockDevice => libc::S_IFBLK,
+            FileKind::CharDevice => libc::S_IFCHR,
+        }
+    }
+}
+
+/// ファイルの属性（`std::fs::Metadata` 相当）。
+/// メソッド名は `MetadataExt` に合わせてある
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stat {
+    kind: FileKind,
+    /// 種類のビットを含む st_mode
+    mode: u32,
+    len: u64,
+    dev: u64,
+    ino: u64,
+    nlink: u64,
+    uid: u32,
+    gid: u32,
+    rdev: u64,
+    blocks: u64,
+    atime: FileTime,
+    mtime: FileTime,
+    ctime: FileTime,
+    blksize: u64,
+}
+
+impl Stat {
+    pub fn file_type(&self) -> FileKind {
+        self.kind
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.kind.is_dir()
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.kind.is_file()
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.kind.is_symlink()
+    }
+
+    /// 種類のビットを含む st_mode（パーミッションは `mode() & 0o7777`）
+    pub fn mode(&self) -> u32 {
+        self.mode
+    }
+
+    /// サイズ（バイト）
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn dev(&self) -> u64 {
+        self.dev
+    }
+
+    pub fn ino(&self) -> u64 {
+        self.ino
+    }
+
+    pub fn nlink(&self) -> u64 {
+        self.nlink
+    }
+
+    pub fn uid(&self) -> u32 {
+        self.uid
+    }
+
+    pub fn gid(&self) -> u32 {
+        self.gid
+    }
+
+    pub fn rdev(&self) -> u64 {
+        self.rdev
+    }
+
+    /// 割り当て済みのブロック数（512バイト単位）
+    pub fn blocks(&self) -> u64 {
+        self.blocks
+    }
+
+    pub fn atime(&self) -> FileTime {
+        self.atime
+    }
+
+    pub fn mtime(&self) -> FileTime {
+        self.mtime
+    }
+
+    /// 属性の変更日時
+    pub fn ctime(&self) -> FileTime {
+        self.ctime
+    }
+
+    /// 読み書きに適したブロックの大きさ（バイト）
+    pub fn blksize(&self) -> u64 {
+        self.blksize
+    }
+}
+
+impl From<fs::Metadata> for Stat {
+    fn from(meta: fs::Metadata) -> Self {
+        let file_type = meta.file_type();
+        let kind = if file_type.is_dir() {
+            FileKind::Dir
+        } else if file_type.is_symlink() {
+            FileKind::Symlink
+        } else if file_type.is_fifo() {
+            FileKind::Fifo
+        } else if file_type.is_socket() {
+            FileKind::Socket
+        } else if file_type.is_block_device() {
+            FileKind::BlockDevice
+        } else if file_type.is_char_device() {
+            FileKind::CharDevice
+        } else {
+            FileKind::File
+        };
+        Stat {
+            kind,
+            mode: meta.mode(),
+            len: meta.size(),
+            dev: meta.dev(),
+            ino: meta.ino(),
+            nlink: meta.nlink(),
+            uid: meta.uid(),
+            gid: meta.gid(),
+            rdev: meta.rdev(),
+            blocks: meta.blocks(),
+            atime: FileTime { secs: meta.atime(), nanos: meta.atime_nsec() },
+            mtime: FileTime { secs: meta.mtime(), nanos: meta.mtime_nsec() },
+            ctime: FileTime { secs: meta.ctime(), nanos: meta.ctime_nsec() },
+            blksize: meta.blksize(),
+        }
+    }
+}
+
+/// 読み込み用に開いたファイル（読む位置を移動できる）
+pub trait FileReader: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> FileReader for T {}
+
+/// ファイルシステムの操作
+pub trait Fs: Send + Sync {
+    /// 属性（シンボリックリンクはたどる）
+    fn metadata(&self, path: &Path) -> io::Result<Stat>;
+
+    /// 属性（シンボリックリンク自体）
+    fn symlink_metadata(&self, path: &Path) -> io::Result<Stat>;
+
+    /// ディレクトリ内の名前（`.` と `..` を除く。順序は不定）
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<OsString>>;
+
+    /// シンボリックリンクのリンク先
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf>;
+
+    /// シンボリックリンクをすべてたどった絶対パス
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf>;
+
+    /// ディレクトリを作る（`mode` には umask が適用される）
+    fn create_dir(&self, path: &Path, mode: u32) -> io::Result<()>;
+
+    /// ファイル（ディレクトリ以外）を削除する
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+
+    /// 空のディレクトリを削除する
+    fn remove_dir(&self, path: &Path) -> io::Result<()>;
+
+    /// 名前を変更する（rename(2) と同じく、既存の移動先は置き換える）
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+
+    /// 読み込み用に開く
+    fn open(&self, path: &Path) -> io::Result<Box<dyn FileReader>>;
+
+    /// 書き込み用に開く。なければ `mode`（umask 適用）で作り、あれば空にする
+    fn create(&self, path: &Path, mode: u32) -> io::Result<Box<dyn Write + Send>>;
+
+    /// 新しいファイルを `mode`（umask 適用）で作り、書き込み用に開く。既にあればエラー（O_EXCL）
+    fn create_new(&self, path: &Path, mode: u32) -> io::Result<Box<dyn Write + Send>>;
+
+    /// シンボリックリンク `path` を作る
+    fn symlink(&self, target: &Path, path: &Path) -> io::Result<()>;
+
+    /// ハードリンク `link` を作る（`original` がシンボリックリンクならリンク自体を指す）
+    fn hard_link(&self, original: &Path, link: &Path) -> io::Result<()>;
+
+    /// パーミッションを変える（シンボリックリンクはたどる）
+    fn set_permissions(&self, path: &Path, mode: u32) -> io::Result<()>;
+
+    /// 所有者・グループを変える。`None` の項目は変更しない
+    fn set_owner(&self, path: &Path, uid: Option<u32>, gid: Option<u32>, follow_symlinks: bool) -> io::Result<()>;
+
+    /// アクセス日時・更新日時を変える。`None` の項目は変更しない
+    fn set_times(
+        &self,
+        path: &Path,
+        atime: Option<FileTime>,
+        mtime: Option<FileTime>,
+        follow_symlinks: bool,
+    ) -> io::Result<()>;
+
+    /// 実際のファイルシステムか（OS の機能に依存する処理を行ってよいか）
+    fn is_native(&self) -> bool {
+        false
+    }
+
+    /// 存在するか（壊れたシンボリックリンクも存在するとみなす）
+    fn exists(&self, path: &Path) -> bool {
+        self.symlink_metadata(path).is_ok()
+    }
+
+    /// ディレクトリか（シンボリックリンクはたどる）
+    fn is_dir(&self, path: &Path) -> bool {
+        self.metadata(path).is_ok_and(|meta| meta.is_dir())
+    }
+
+    /// ファイルまたはディレクトリを中身ごと削除する（シンボリックリンクはたどらない）
+    fn remove_all(&self, path: &Path) -> io::Result<()> {
+        if !self.symlink_metadata(path)?.is_dir() {
+            return self.remove_file(path);
+        }
+        for name in self.read_dir(path)? {
+            self.remove_all(&path.join(name))?;
+        }
+        self.remove_dir(path)
+    }
+}
+
+/// 実際のファイルシステム
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OsFs;
+
+impl Fs for OsFs {
+    fn metadata(&self, path: &Path) -> io::Result<Stat> {
+        fs::metadata(path).map(Stat::from)
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<Stat> {
+        fs::symlink_metadata(path).map(Stat::from)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<OsString>> {
+        fs::read_dir(path)?.map(|entry| entry.map(|e| e.file_name())).collect()
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        fs::read_link(path)
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        path.canonicalize()
+    }
+
+    fn create_dir(&self, path: &Path, mode: u32) -> io::Result<()> {
+        fs::DirBuilder::new().mode(mode).create(path)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn remove_dir(&self, path: &Path) -> io::Result<()> {
+        fs::remove_dir(path)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn FileReader>> {
+        Ok(Box::new(fs::File::open(path)?))
+    }
+
+    fn create(&self, path: &Path, mode: u32) -> io::Result<Box<dyn Write + Send>> {
+        let file = fs::OpenOptions::new().write(true).create(true).truncate(true).mode(mode).open(path)?;
+        Ok(Box::new(file))
+    }
+
+    fn create_new(&self, path: &Path, mode: u32) -> io::Result<Box<dyn Write + Send>> {
+        let file = fs::OpenOptions::new().write(true).create_new(true).mode(mode).open(path)?;
+        Ok(Box::new(file))
+    }
+
+    fn symlink(&self, target: &Path, path: &Path) -> io::Result<()> {
+        std::os::unix::fs::symlink(target, path)
+    }
+
+    fn hard_link(&self, original: &Path, link: &Path) -> io::Result<()> {
+        fs::hard_link(original, link)
+    }
+
+    fn set_permissions(&self, path: &Path, mode: u32) -> io::Result<()> {
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))
+    }
+
+    fn set_owner(&self, path: &Path, uid: Option<u32>, gid: Option<u32>, follow_symlinks: bool) -> io::Result<()> {
+        if follow_symlinks {
+            std::os::unix::fs::chown(path, uid, gid)
+        } else {
+            std::os::unix::fs::lchown(path, uid, gid)
+        }
+    }
+
+    fn set_times(
+        &self,
+        path: &Path,
+        atime: Option<FileTime>,
+        mtime: Option<FileTime>,
+        follow_symlinks: bool,
+    ) -> io::Result<()> {
+        fs_util::set_times(path, atime, mtime, follow_symlinks)
+    }
+
+    fn is_native(&self) -> bool {
+        true
+    }
+
+    fn remove_all(&self, path: &Path) -> io::Result<()> {
+        if fs::symlink_metadata(path)?.is_dir() {
+            fs::remove_dir_all(path)
+        } else {
+            fs::remove_file(path)
+        }
+    }
+}
+
+/// メモリ上のファイルシステム。最初は空のルートディレクトリだけがある。
+/// 所有者は現在のユーザー、デバイス番号はすべて同じになる
+#[derive(Clone)]
+pub struct MemoryFs {
+    tree: Arc<Mutex<Tree>>,
+}
+
+/// メモリ上の全ノード（正規化した絶対パス → ノード）
+struct Tree {
+    nodes: HashMap<PathBuf, Node>,
+    next_ino: u64,
+    umask: u32,
+}
+
+struct Node {
+    data: NodeData,
+    /// パーミッション（0o7777）
+    mode: u32,
+    ino: u64,
+    uid: u32,
+    gid: u32,
+    atime: FileTime,
+    mtime: FileTime,
+    ctime: FileTime,
+}
+
+enum NodeData {
+    File(Vec<u8>),
+    Dir,
+    Symlink(PathBuf),
+}
+
+/// MemoryFs のデバイス番号
+const MEMORY_DEV: u64 = 1;
+/// シンボリックリンクをたどる回数の上限
+const MAX_SYMLINKS: usize = 40;
+
+fn errno(code: i32) -> io::Error {
+    io::Error::from_raw_os_error(code)
+}
+
+fn now() -> FileTime {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    FileTime { secs: now.as_secs() as i64, nanos: now.subsec_nanos() as i64 }
+}
+
+impl Default for MemoryFs {
+    fn default() -> Self {
+        MemoryFs::new()
+    }
+}
+
+impl MemoryFs {
+    pub fn new() -> Self {
+        let mut tree = Tree { nodes: HashMap::new(), next_ino: 1, umask: 0o022 };
+        tree.insert(PathBuf::from("/"), NodeData::Dir, 0o755);
+        MemoryFs { tree: Arc::new(Mutex::new(tree)) }
+    }
+
+    /// ファイルを作る（親ディレクトリも作る）。既にあれば内容を置き換える
+    pub fn add_file(&self, path: impl AsRef<Path>, contents: impl Into<Vec<u8>>) {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            self.add_dir(parent);
+        }
+        let mut tree = self.lock();
+        let mode = 0o666 & !tree.umask;
+        tree.insert(normalize(path), NodeData::File(contents.into()), mode);
+    }
+
+    /// ディレクトリを親も含めて作る
+    pub fn add_dir(&self, path: impl AsRef<Path>) {
+        let mut tree = self.lock();
+        let mode = 0o777 & !tree.umask;
+        let mut current = PathBuf::from("/");
+        for component in normalize(path.as_ref()).components().skip(1) {
+            current.push(component);
+            if !tree.nodes.contains_key(&current) {
+                tree.insert(current.clone(), NodeData::Dir, mode);
+            }
+        }
+    }
+
+    /// ファイルの内容
+    pub fn contents(&self, path: impl AsRef<Path>) -> Option<Vec<u8>> {
+        let tree = self.lock();
+        let key = tree.resolve(path.as_ref(), true).ok()?;
+        match &tree.nodes.get(&key)?.data {
+            NodeData::File(data) => Some(data.clone()),
+            _ => None,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Tree> {
+        // 他のスレッドが操作中にパニックしても内容は壊れていないので使い続ける
+        self.tree.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Tree {
+    fn insert(&mut self, path: PathBuf, data: NodeData, mode: u32) {
+        let ino = self.next_ino;
+        self.next_ino += 1;
+        let time = now();
+        let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+        self.nodes.insert(path, Node { data, mode: mode & 0o7777, ino, uid, gid, atime: time, mtime: time, ctime: time });
+    }
+
+    /// パスを正規化したキーにする。途中のシンボリックリンクはたどり、
+    /// 最後の要素は `follow_last` のときだけたどる
+    fn resolve(&self, path: &Path, follow_last: bool) -> io::Result<PathBuf> {
+        let mut pending: VecDeque<OsString> = VecDeque::new();
+        push_components(&mut pending, path);
+        let mut current = PathBuf::from("/");
+        let mut hops = 0;
+
+        while let Some(name) = pending.pop_front() {
+            if name == ".." {
+                current.pop();
+                continue;
+            }
+            // 途中の要素はディレクトリでなければならない
+            match self.nodes.get(&current).map(|node| &node.data) {
+                Some(NodeData::Dir) => {}
+                Some(_) => return Err(errno(libc::ENOTDIR)),
+                None => return Err(errno(libc::ENOENT)),
+            }
+            let next = current.join(&name);
+            let is_last = pending.is_empty();
+            match self.nodes.get(&next).map(|node| &node.data) {
+                Some(NodeData::Symlink(target)) if !is_last || follow_last => {
+                    hops += 1;
+                    if hops > MAX_SYMLINKS {
+                        return Err(errno(libc::ELOOP));
+                    }
+                    // リンク先の要素を残りの要素の前に差し込む
+                    let mut rest: VecDeque<OsString> = VecDeque::new();
+                    push_components(&mut rest, target);
+                    rest.extend(pending.drain(..));
+                    pending = rest;
+                    if target.is_absolute() {
+                        current = PathBuf::from("/");
+                    }
+                }
+                _ => current = next,
+            }
+        }
+        Ok(current)
+    }
+
+    /// 既存のノードのキー
+    fn existing(&self, path: &Path, follow_last: bool) -> io::Result<PathBuf> {
+        let key = self.resolve(path, follow_last)?;
+        if self.nodes.contains_key(&key) {
+            Ok(key)
+        } else {
+            Err(errno(libc::ENOENT))
+        }
+    }
+
+    /// 新しく作るノードのキー（親ディレクトリは存在しなければならない）
+    fn new_entry(&self, path: &Path) -> io::Result<PathBuf> {
+        let key = self.resolve(path, false)?;
+        if key.parent().is_some_and(|parent| !matches!(self.nodes.get(parent).map(|n| &n.data), Some(NodeData::Dir))) {
+            return Err(errno(libc::ENOENT));
+        }
+        if self.nodes.contains_key(&key) {
+            return Err(errno(libc::EEXIST));
+        }
+        Ok(key)
+    }
+
+    fn children<'a>(&'a self, dir: &'a Path) -> impl Iterator<Item = &'a PathBuf> + 'a {
+        self.nodes.keys().filter(move |key| key.parent() == Some(dir))
+    }
+
+    fn stat(&self, key: &Path) -> Stat {
+        let node = &self.nodes[key];
+        let (kind, len) = match &node.data {
+            NodeData::File(data) => (FileKind::File, data.len() as u64),
+            NodeData::Dir => (FileKind::Dir, 4096),
+            NodeData::Symlink(target) => (FileKind::Symlink, target.as_os_str().len() as u64),
+        };
+        let nlink = match node.data {
+            NodeData::Dir => 2 + self.children(key).filter(|c| matches!(self.nodes[*c].data, NodeData::Dir)).count() as u64,
+            _ => 1,
+        };
+        Stat {
+            kind,
+            mode: kind.mode_bits() | node.mode,
+            len,
+            dev: MEMORY_DEV,
+            ino: node.ino,
+            nlink,
+            uid: node.uid,
+            gid: node.gid,
+            rdev: 0,
+            blocks: len.div_ceil(512),
+            atime: node.atime,
+            mtime: node.mtime,
+            ctime: node.ctime,
+            blksize: 4096,
+        }
+    }
+
+    /// 親ディレクトリの更新日時を進める
+    fn touch_parent(&mut self, key: &Path) {
+        if let Some(parent) = key.parent().and_then(|parent| self.nodes.get_mut(parent)) {
+            parent.mtime = now();
+            parent.ctime = parent.mtime;
+        }
+    }
+}
+
+/// パスの要素を正規化前の順で積む（`.` は除く）
+fn push_components(out: &mut VecDeque<OsString>, path: &Path) {
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => out.push_back(name.to_os_string()),
+            Component::ParentDir => out.push_back(OsString::from("..")),
+            Component::RootDir | Component::CurDir | Component::Prefix(_) => {}
+        }
+    }
+}
+
+/// シンボリックリンクをたどらずに `.` と `..` を取り除いた絶対パス
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::from("/");
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => out.push(name),
+            Component::ParentDir => {
+                out.pop();
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+impl Fs for MemoryFs {
+    fn metadata(&self, path: &Path) -> io::Result<Stat> {
+        let tree = self.lock();
+        let key = tree.existing(path, true)?;
+        Ok(tree.stat(&key))
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<Stat> {
+        let tree = self.lock();
+        let key = tree.existing(path, false)?;
+        Ok(tree.stat(&key))
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<OsString>> {
+        let tree = self.lock();
+        let key = tree.existing(path, true)?;
+        if !matches!(tree.nodes[&key].data, NodeData::Dir) {
+            return Err(errno(libc::ENOTDIR));
+        }
+        Ok(tree.children(&key).filter_map(|child| child.file_name()).map(OsStr::to_os_string).collect())
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        let tree = self.lock();
+        let key = tree.existing(path, false)?;
+        match &tree.nodes[&key].data {
+            NodeData::Symlink(target) => Ok(target.clone()),
+            _ => Err(errno(libc::EINVAL)),
+        }
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        self.lock().existing(path, true)
+    }
+
+    fn create_dir(&self, path: &Path, mode: u32) -> io::Result<()> {
+        let mut tree = self.lock();
+        let key = tree.new_entry(path)?;
+        let mode = mode & !tree.umask;
+        tree.insert(key.clone(), NodeData::Dir, mode);
+        tree.touch_parent(&key);
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        let mut tree = self.lock();
+        let key = tree.existing(path, false)?;
+        if matches!(tree.nodes[&key].data, NodeData::Dir) {
+            return Err(errno(libc::EISDIR));
+        }
+        tree.nodes.remove(&key);
+        tree.touch_parent(&key);
+        Ok(())
+    }
+
+    fn remove_dir(&self, path: &Path) -> io::Result<()> {
+        let mut tree = self.lock();
+        let key = tree.existing(path, false)?;
+        if !matches!(tree.nodes[&key].data, NodeData::Dir) {
+            return Err(errno(libc::ENOTDIR));
+        }
+        if key == Path::new("/") {
+            return Err(errno(libc::EBUSY));
+        }
+        if tree.children(&key).next().is_some() {
+            return Err(errno(libc::ENOTEMPTY));
+        }
+        tree.nodes.remove(&key);
+        tree.touch_parent(&key);
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let mut tree = self.lock();
+        let from = tree.existing(from, false)?;
+        let to = match tree.new_entry(to) {
+            Ok(key) => key,
+            Err(e) if e.raw_os_error() == Some(libc::EEXIST) => tree.resolve(to, false)?,
+            Err(e) => return Err(e),
+        };
+        if from == to {
+            return Ok(());
+        }
+        let from_is_dir = matches!(tree.nodes[&from].data, NodeData::Dir);
+        if from_is_dir && to.starts_with(&from) {
+            return Err(errno(libc::EINVAL));
+        }
+        if let Some(existing) = tree.nodes.get(&to) {
+            match (from_is_dir, matches!(existing.data, NodeData::Dir)) {
+                (true, false) => return Err(errno(libc::ENOTDIR)),
+                (false, true) => return Err(errno(libc::EISDIR)),
+                (true, true) if tree.children(&to).next().is_some() => return Err(errno(libc::ENOTEMPTY)),
+                _ => {}
+            }
+            tree.nodes.remove(&to);
+        }
+
+        // 移動元以下のノードをすべて付け替える
+        let moved: Vec<PathBuf> = tree.nodes.keys().filter(|key| key.starts_with(&from)).cloned().collect();
+        for key in moved {
+            let node = tree.nodes.remove(&key).expect("key exists");
+            let suffix = key.strip_prefix(&from).expect("key is under from");
+            let new_key = if suffix.as_os_str().is_empty() { to.clone() } else { to.join(suffix) };
+            tree.nodes.insert(new_key, node);
+        }
+        tree.touch_parent(&from);
+        tree.touch_parent(&to);
+        Ok(())
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn FileReader>> {
+        let tree = self.lock();
+        let key = tree.existing(path, true)?;
+        match &tree.nodes[&key].data {
+            NodeData::File(data) => Ok(Box::new(Cursor::new(data.clone()))),
+            NodeData::Dir => Err(errno(libc::EISDIR)),
+            NodeData::Symlink(_) => Err(errno(libc::ELOOP)),
+        }
+    }
+
+    fn create(&self, path: &Path, mode: u32) -> io::Result<Box<dyn Write + Send>> {
+        let mut tree = self.lock();
+        let key = match tree.new_entry(path) {
+            Ok(key) => {
+                let mode = mode & !tree.umask;
+                tree.insert(key.clone(), NodeData::File(Vec::new()), mode);
+                tree.touch_parent(&key);
+                key
+            }
+            Err(e) if e.raw_os_error() == Some(libc::EEXIST) => {
+                let key = tree.existing(path, true)?;
+                let node = tree.nodes.get_mut(&key).expect("key exists");
+                match &mut node.data {
+                    NodeData::File(data) => data.clear(),
+                    _ => return Err(errno(libc::EISDIR)),
+                }
+                node.mtime = now();
+                node.ctime = node.mtime;
+                key
+            }
+            Err(e) => return Err(e),
+        };
+        let ino = tree.nodes[&key].ino;
+        Ok(Box::new(MemoryWriter { tree: Arc::clone(&self.tree), key, ino }))
+    }
+
+    fn create_new(&self, path: &Path, mode: u32) -> io::Result<Box<dyn Write + Send>> {
+        let mut tree = self.lock();
+        let key = tree.new_entry(path)?;
+        let mode = mode & !tree.umask;
+        tree.insert(key.clone(), NodeData::File(Vec::new()), mode);
+        tree.touch_parent(&key);
+        let ino = tree.nodes[&key].ino;
+        Ok(Box::new(MemoryWriter { tree: Arc::clone(&self.tree), key, ino }))
+    }
+
+    fn symlink(&self, target: &Path, path: &Path) -> io::Result<()> {
+        let mut tree = self.lock();
+        let key = tree.new_entry(path)?;
+        tree.insert(key.clone(), NodeData::Symlink(target.to_path_buf()), 0o777);
+        tree.touch_parent(&key);
+        Ok(())
+    }
+
+    /// ノードは1つのパスにだけ結び付くので、ハードリンクは作れない
+    fn hard_link(&self, original: &Path, link: &Path) -> io::Result<()> {
+        let tree = self.lock();
+        tree.existing(original, false)?;
+        tree.new_entry(link)?;
+        Err(errno(libc::EPERM))
+    }
+
+    fn set_permissions(&self, path: &Path, mode: u32) -> io::Result<()> {
+        let mut tree = self.lock();
+        let key = tree.existing(path, true)?;
+        let node = tree.nodes.get_mut(&key).expect("key exists");
+        node.mode = mode & 0o7777;
+        node.ctime = now();
+        Ok(())
+    }
+
+    fn set_owner(&self, path: &Path, uid: Option<u32>, gid: Option<u32>, follow_symlinks: bool) -> io::Result<()> {
+        let mut tree = self.lock();
+        let key = tree.existing(path, follow_symlinks)?;
+        let node = tree.nodes.get_mut(&key).expect("key exists");
+        node.uid = uid.unwrap_or(node.uid);
+        node.gid = gid.unwrap_or(node.gid);
+        node.ctime = now();
+        Ok(())
+    }
+
+    fn set_times(
+        &self,
+        path: &Path,
+        atime: Option<FileTime>,
+        mtime: Option<FileTime>,
+        follow_symlinks: bool,
+    ) -> io::Result<()> {
+        let mut tree = self.lock();
+        let key = tree.existing(path, follow_symlinks)?;
+        let node = tree.nodes.get_mut(&key).expect("key exists");
+        if let Some(atime) = atime {
+            node.atime = atime;
+        }
+        if let Some(mtime) = mtime {
+            node.mtime = mtime;
+        }
+        node.ctime = now();
+        Ok(())
+    }
+}
+
+/// `MemoryFs::create` で開いたファイルへの書き込み（書いた分はすぐに反映する）
+struct MemoryWriter {
+    tree: Arc<Mutex<Tree>>,
+    key: PathBuf,
+    /// 開いたときのファイル。削除・置き換えられた後の書き込みは捨てる
+    ino: u64,
+}
+
+impl Write for MemoryWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut tree = self.tree.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(node) = tree.nodes.get_mut(&self.key).filter(|node| node.ino == self.ino) {
+            if let NodeData::File(data) = &mut node.data {
+                data.extend_from_slice(buf);
+                node.mtime = now();
+                node.ctime = node.mtime;
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands;
+    use crate::context::Context;
+
+    /// 書いた内容を後から読める出力先
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Buffer {
+        fn text(&self) -> String {
+            String::from_utf8_lossy(&self.0.lock().unwrap()).into_owned()
+        }
+    }
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// `/work` をカレントディレクトリにして MemoryFs の上でコマンドを動かす
+    struct Shell {
+        fs: MemoryFs,
+        ctx: Context,
+        out: Buffer,
+        err: Buffer,
+    }
+
+    impl Shell {
+        fn new() -> Self {
+            let fs = MemoryFs::new();
+            fs.add_dir("/work");
+            let (out, err) = (Buffer::default(), Buffer::default());
+            let mut ctx = Context::new(PathBuf::from("/work"), Box::new(out.clone()), Box::new(err.clone()), false);
+            ctx.fs = Arc::new(fs.clone());
+            Shell { fs, ctx, out, err }
+        }
+
+        fn run(&mut self, name: &str, args: &[&str]) -> i32 {
+            let command = commands::find(name).expect("builtin exists");
+            let args: Vec<OsString> = args.iter().map(OsString::from).collect();
+            self.ctx.status = 0;
+            (command.run)(&mut self.ctx, &args);
+            self.ctx.status
+        }
+
+        fn exists(&self, path: &str) -> bool {
+            self.fs.exists(Path::new(path))
+        }
+    }
+
+    #[test]
+    fn mkdir_p_creates_missing_parents() {
+        let mut sh = Shell::new();
+        sh.fs.add_dir("/work/a");
+        assert_eq!(sh.run("mkdir", &["-p", "a/b/c", "x/y"]), 0, "{}", sh.err.text());
+        assert!(sh.fs.is_dir(Path::new("/work/a/b/c")));
+        assert!(sh.fs.is_dir(Path::new("/work/x/y")));
+
+        // 既にあってもエラーにしない。-p なしなら親がないとエラー
+        assert_eq!(sh.run("mkdir", &["-p", "a/b"]), 0);
+        assert_eq!(sh.run("mkdir", &["p/q"]), 1);
+        assert!(!sh.exists("/work/p"));
+    }
+
+    #[test]
+    fn ls_lists_directory_contents() {
+        let mut sh = Shell::new();
+        sh.fs.add_file("/work/b.txt", "b");
+        sh.fs.add_file("/work/a.txt", "a");
+        sh.fs.add_file("/work/.hidden", "");
+        sh.fs.add_dir("/work/dir");
+        assert_eq!(sh.run("ls", &["-1"]), 0, "{}", sh.err.text());
+        assert_eq!(sh.out.text(), "a.txt\nb.txt\ndir/\n");
+
+        assert_eq!(sh.run("ls", &["-1", "missing"]), 1);
+        assert!(sh.err.text().contains("missing"));
+    }
+
+    #[test]
+    fn rm_r_removes_whole_tree() {
+        let mut sh = Shell::new();
+        sh.fs.add_file("/work/d/x", "x");
+        sh.fs.add_file("/work/d/e/y", "y");
+        sh.fs.add_dir("/work/d/e/empty");
+        sh.fs.add_file("/work/keep", "k");
+
+        // -r がなければディレクトリは消さない
+        assert_eq!(sh.run("rm", &["d"]), 1);
+        assert!(sh.exists("/work/d/e/y"));
+
+        assert_eq!(sh.run("rm", &["-r", "d"]), 0, "{}", sh.err.text());
+        assert!(!sh.exists("/work/d"));
+        assert!(!sh.exists("/work/d/e/y"));
+        assert_eq!(sh.fs.contents("/work/keep"), Some(b"k".to_vec()));
+    }
+
+    #[test]
+    fn mv_renames_and_moves_into_directory() {
+        let mut sh = Shell::new();
+        sh.fs.add_file("/work/a.txt", "hello");
+        sh.fs.add_file("/work/src/one", "1");
+        sh.fs.add_dir("/work/dest");
+
+        assert_eq!(sh.run("mv", &["a.txt", "b.txt"]), 0, "{}", sh.err.text());
+        assert!(!sh.exists("/work/a.txt"));
+        assert_eq!(sh.fs.contents("/work/b.txt"), Some(b"hello".to_vec()));
+
+        assert_eq!(sh.run("mv", &["b.txt", "src", "dest"]), 0, "{}", sh.err.text());
+        assert_eq!(sh.fs.contents("/work/dest/b.txt"), Some(b"hello".to_vec()));
+        assert_eq!(sh.fs.contents("/work/dest/src/one"), Some(b"1".to_vec()));
+        assert!(!sh.exists("/work/src"));
+    }
+}